@@ -0,0 +1,97 @@
+//! Procedural macros for [diesel-async](https://docs.rs/diesel-async)
+//!
+//! Use the re-exports of this crate in `diesel_async` instead of
+//! depending on this crate directly.
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::quote;
+use syn::{FnArg, ItemFn, LitStr, Type};
+
+/// See the documentation of `diesel_async::test`
+#[proc_macro_attribute]
+pub fn test(args: TokenStream, item: TokenStream) -> TokenStream {
+    let mut env = LitStr::new("DATABASE_URL", Span::call_site());
+    let parser = syn::meta::parser(|meta| {
+        if meta.path.is_ident("env") {
+            env = meta.value()?.parse()?;
+            Ok(())
+        } else {
+            Err(meta.error("unsupported argument, expected `env = \"...\"`"))
+        }
+    });
+    syn::parse_macro_input!(args with parser);
+    let item = syn::parse_macro_input!(item as ItemFn);
+
+    expand_test(env, item)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn expand_test(env: LitStr, item: ItemFn) -> syn::Result<proc_macro2::TokenStream> {
+    let ItemFn {
+        attrs,
+        vis,
+        sig,
+        block,
+    } = item;
+
+    if sig.asyncness.is_none() {
+        return Err(syn::Error::new_spanned(
+            sig.fn_token,
+            "the `async` keyword is missing from the function declaration",
+        ));
+    }
+    if !sig.generics.params.is_empty() || sig.generics.where_clause.is_some() {
+        return Err(syn::Error::new_spanned(
+            &sig.generics,
+            "test functions cannot be generic",
+        ));
+    }
+    let connection = match sig.inputs.iter().collect::<Vec<_>>().as_slice() {
+        [FnArg::Typed(arg)] => match *arg.ty {
+            Type::Reference(ref reference) if reference.mutability.is_some() => {
+                reference.elem.clone()
+            }
+            ref ty => {
+                return Err(syn::Error::new_spanned(
+                    ty,
+                    "expected a mutable reference to a connection",
+                ))
+            }
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &sig.inputs,
+                "expected exactly one argument, like `conn: &mut AsyncPgConnection`",
+            ))
+        }
+    };
+
+    let name = &sig.ident;
+    let inputs = &sig.inputs;
+    let output = &sig.output;
+
+    Ok(quote! {
+        #(#attrs)*
+        #[::core::prelude::v1::test]
+        #vis fn #name() #output {
+            async fn __diesel_async_test(#inputs) #output #block
+
+            ::diesel_async::test_support::runtime().block_on(async {
+                let mut conn =
+                    ::diesel_async::test_support::test_connection::<#connection>(#env).await;
+                ::diesel_async::AsyncConnection::test_transaction::<
+                    _,
+                    ::std::convert::Infallible,
+                    _,
+                >(&mut *conn, |conn| {
+                    ::diesel_async::scoped_futures::ScopedFutureExt::scope_boxed(async move {
+                        ::std::result::Result::Ok(__diesel_async_test(conn).await)
+                    })
+                })
+                .await
+            })
+        }
+    })
+}
@@ -34,13 +34,24 @@ pub trait BlockOn {
 /// provide a sync [`diesel::Connection`] implementation.
 ///
 /// Internally this wrapper type will use `block_on` to wait for
-/// the execution of futures from the inner connection. This implies you
-/// cannot use functions of this type in a scope with an already existing
-/// tokio runtime. If you are in a situation where you want to use this
-/// connection wrapper in the scope of an existing tokio runtime (for example
+/// the execution of futures from the inner connection. If the wrapper
+/// is constructed in the scope of an existing tokio runtime it reuses
+/// that runtime instead of starting a new one. For multi-threaded runtimes
+/// the blocking calls are wrapped into
+/// [`tokio::task::block_in_place`](https://docs.rs/tokio/latest/tokio/task/fn.block_in_place.html),
+/// so that the wrapper can be used directly from async code running on such a runtime.
+/// This is not possible for the current thread runtime. If you are in a situation where you
+/// want to use this connection wrapper in the scope of a current thread runtime (for example
 /// for running migrations via `diesel_migration`) you need to wrap
 /// the relevant code block into a `tokio::task::spawn_blocking` task.
 ///
+/// Use [`AsyncConnectionWrapper::with_runtime`] together with [`Tokio::from`] to
+/// construct a wrapper that uses a specific runtime handle.
+///
+/// Loading data via `diesel::RunQueryDsl::load_iter` does not buffer the
+/// whole result set. The rows are fetched one by one from the
+/// stream returned by the inner connection while iterating.
+///
 /// # Examples
 ///
 /// ```rust
@@ -59,7 +70,7 @@ pub trait BlockOn {
 /// # }
 /// ```
 ///
-/// If you are in the scope of an existing tokio runtime you need to use
+/// If you are in the scope of an existing current thread tokio runtime you need to use
 /// `tokio::task::spawn_blocking` to encapsulate the blocking tasks
 /// ```rust
 /// # include!("doctest_setup.rs");
@@ -91,6 +102,9 @@ pub trait BlockOn {
 pub type AsyncConnectionWrapper<C, B = self::implementation::Tokio> =
     self::implementation::AsyncConnectionWrapper<C, B>;
 
+#[cfg(feature = "tokio")]
+pub use self::implementation::Tokio;
+
 /// A helper type that wraps an [`crate::AsyncConnectionWrapper`] to
 /// provide a sync [`diesel::Connection`] implementation.
 ///
@@ -124,6 +138,27 @@ mod implementation {
         }
     }
 
+    impl<C, B> AsyncConnectionWrapper<C, B> {
+        /// Wrap the given connection and use the provided runtime
+        /// to execute futures
+        pub fn with_runtime(inner: C, runtime: B) -> Self {
+            Self {
+                inner,
+                runtime,
+                instrumentation: None,
+            }
+        }
+
+        /// Consumes the wrapper and returns the wrapped connection
+        ///
+        /// This allows to continue to use the connection asynchronously
+        /// after the sync part of the application is done, for example after
+        /// running migrations via `diesel_migrations`.
+        pub fn into_inner(self) -> C {
+            self.inner
+        }
+    }
+
     impl<C, B> diesel::connection::SimpleConnection for AsyncConnectionWrapper<C, B>
     where
         C: crate::SimpleAsyncConnection,
@@ -317,12 +352,26 @@ mod implementation {
         }
     }
 
+    /// The default [`BlockOn`] implementation based on tokio
+    ///
+    /// It either reuses the runtime it was created in or
+    /// starts a new current thread runtime.
     #[cfg(feature = "tokio")]
     pub struct Tokio {
         handle: Option<tokio::runtime::Handle>,
         runtime: Option<tokio::runtime::Runtime>,
     }
 
+    #[cfg(feature = "tokio")]
+    impl From<tokio::runtime::Handle> for Tokio {
+        fn from(handle: tokio::runtime::Handle) -> Self {
+            Self {
+                handle: Some(handle),
+                runtime: None,
+            }
+        }
+    }
+
     #[cfg(feature = "tokio")]
     impl BlockOn for Tokio {
         fn block_on<F>(&self, f: F) -> F::Output
@@ -330,7 +379,15 @@ mod implementation {
             F: Future,
         {
             if let Some(handle) = &self.handle {
-                handle.block_on(f)
+                match handle.runtime_flavor() {
+                    // blocking a worker thread of a multi threaded runtime
+                    // is only allowed via `block_in_place`, otherwise
+                    // tokio panics. This also works outside of worker threads
+                    tokio::runtime::RuntimeFlavor::MultiThread => {
+                        tokio::task::block_in_place(|| handle.block_on(f))
+                    }
+                    _ => handle.block_on(f),
+                }
             } else if let Some(runtime) = &self.runtime {
                 runtime.block_on(f)
             } else {
@@ -340,10 +397,7 @@ mod implementation {
 
         fn get_runtime() -> Self {
             if let Ok(handle) = tokio::runtime::Handle::try_current() {
-                Self {
-                    handle: Some(handle),
-                    runtime: None,
-                }
+                Self::from(handle)
             } else {
                 let runtime = tokio::runtime::Builder::new_current_thread()
                     .enable_io()
@@ -0,0 +1,129 @@
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::time::Duration;
+
+/// A policy for the delays between repeated attempts of an operation,
+/// like reconnecting to the database via `ManagerConfig::reconnect_backoff`
+/// or retrying a deadlocked transaction via `RetryPolicy::backoff`
+///
+/// The delay after `n` failed attempts (starting at 1) is
+/// `min(initial_delay * multiplier^(n - 1), max_delay)`. With jitter enabled,
+/// the delay is chosen randomly between half and the full value, so that many
+/// clients losing their connections at the same time do not reconnect in
+/// lockstep.
+///
+/// By default the delay starts at 100 milliseconds and doubles after each
+/// failed attempt up to 30 seconds, with jitter enabled and without a limit
+/// on the number of attempts.
+///
+/// ```rust
+/// use diesel_async::pooled_connection::BackoffPolicy;
+/// use std::time::Duration;
+///
+/// let policy = BackoffPolicy::default()
+///     .initial_delay(Duration::from_millis(50))
+///     .multiplier(3.0)
+///     .max_delay(Duration::from_secs(5))
+///     .max_attempts(4)
+///     .jitter(false);
+///
+/// assert_eq!(Duration::from_millis(50), policy.delay(1));
+/// assert_eq!(Duration::from_millis(150), policy.delay(2));
+/// assert_eq!(Duration::from_secs(5), policy.delay(10));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct BackoffPolicy {
+    initial_delay: Duration,
+    multiplier: f64,
+    max_delay: Duration,
+    max_attempts: Option<u32>,
+    jitter: bool,
+}
+
+impl Default for BackoffPolicy {
+    fn default() -> Self {
+        Self {
+            initial_delay: Duration::from_millis(100),
+            multiplier: 2.0,
+            max_delay: Duration::from_secs(30),
+            max_attempts: None,
+            jitter: true,
+        }
+    }
+}
+
+impl BackoffPolicy {
+    /// A policy waiting the same time after each failed attempt,
+    /// without jitter and without a limit on the number of attempts
+    pub fn constant(delay: Duration) -> Self {
+        Self {
+            initial_delay: delay,
+            multiplier: 1.0,
+            max_delay: delay,
+            max_attempts: None,
+            jitter: false,
+        }
+    }
+
+    /// Set the delay after the first failed attempt
+    pub fn initial_delay(mut self, initial_delay: Duration) -> Self {
+        self.initial_delay = initial_delay;
+        self
+    }
+
+    /// Set the factor by which the delay grows after each failed attempt
+    ///
+    /// # Panics
+    ///
+    /// If `multiplier` is less than 1 or not finite.
+    pub fn multiplier(mut self, multiplier: f64) -> Self {
+        assert!(
+            multiplier.is_finite() && multiplier >= 1.0,
+            "The multiplier of a backoff policy must be a finite number of at least 1"
+        );
+        self.multiplier = multiplier;
+        self
+    }
+
+    /// Set the upper limit of the delay between attempts
+    pub fn max_delay(mut self, max_delay: Duration) -> Self {
+        self.max_delay = max_delay;
+        self
+    }
+
+    /// Set the maximal number of attempts, including the first one
+    ///
+    /// There is no limit by default.
+    pub fn max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = Some(max_attempts.max(1));
+        self
+    }
+
+    /// Set whether the delays are randomized, which is the default
+    pub fn jitter(mut self, jitter: bool) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// The delay after the given number of failed attempts, starting at 1
+    pub fn delay(&self, failed_attempts: u32) -> Duration {
+        let exponent = failed_attempts.saturating_sub(1).min(i32::MAX as u32) as i32;
+        let delay = (self.initial_delay.as_secs_f64()
+            * self.multiplier.powi(exponent).min(f64::MAX))
+        .min(self.max_delay.as_secs_f64());
+        let delay = Duration::from_secs_f64(delay);
+        if self.jitter {
+            let jitter = RandomState::new().hash_one(failed_attempts) % 1024;
+            delay / 2 + (delay / 2).mul_f64(jitter as f64 / 1023.0)
+        } else {
+            delay
+        }
+    }
+
+    /// Whether another attempt is allowed after the given
+    /// number of failed attempts
+    pub fn allows_retry(&self, failed_attempts: u32) -> bool {
+        self.max_attempts
+            .map_or(true, |max_attempts| failed_attempts < max_attempts)
+    }
+}
@@ -0,0 +1,140 @@
+//! Helpers to write large batches of records in chunks
+//!
+//! Backends limit the number of bind parameters a single statement
+//! can contain, so inserting a large number of records at once requires
+//! splitting them into several statements. The helpers in this module take
+//! care of that and combine the results of the individual statements.
+
+use crate::methods::{ExecuteDsl, LoadQuery};
+use crate::{AsyncConnection, RunQueryDsl};
+use diesel::QueryResult;
+use futures_util::future::{self, BoxFuture, Either};
+use futures_util::stream::FuturesUnordered;
+use futures_util::{FutureExt, Stream, StreamExt};
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/// Runs an upsert (`INSERT … ON CONFLICT … DO UPDATE … RETURNING …`) for a
+/// large number of records in chunks of `chunk_size` records
+///
+/// For each chunk `build_query` is called to construct the actual statement.
+/// The rows returned by each statement are matched back to the input records
+/// by comparing `record_key` with `row_key`, so that the returned rows are in
+/// the same order as `records`, independently of the order the database returned
+/// them in. Records without a matching returned row (for example because they were
+/// skipped by a `WHERE` clause on the `DO UPDATE` part) are omitted from the result.
+///
+/// The chunks are executed one after another. Wrap the call into a transaction
+/// if either all or none of the records should be written.
+///
+/// # Panics
+///
+/// This function panics if `chunk_size` is 0.
+pub async fn upsert_returning_in_chunks<'a, Conn, T, Q, U, K>(
+    conn: &mut Conn,
+    records: &'a [T],
+    chunk_size: usize,
+    build_query: impl Fn(&'a [T]) -> Q,
+    record_key: impl Fn(&T) -> K,
+    row_key: impl Fn(&U) -> K,
+) -> QueryResult<Vec<U>>
+where
+    Conn: AsyncConnection,
+    Q: LoadQuery<'a, Conn, U> + 'a,
+    U: Send,
+    K: Eq + Hash,
+{
+    assert!(chunk_size > 0, "The chunk size must be greater than 0");
+
+    let mut result = Vec::with_capacity(records.len());
+    for chunk in records.chunks(chunk_size) {
+        let rows = build_query(chunk).load::<U>(conn).await?;
+        let mut rows_by_key = rows
+            .into_iter()
+            .map(|row| (row_key(&row), row))
+            .collect::<HashMap<_, _>>();
+        result.extend(
+            chunk
+                .iter()
+                .filter_map(|record| rows_by_key.remove(&record_key(record))),
+        );
+    }
+    Ok(result)
+}
+
+/// Writes all records produced by a stream in batches of `batch_size` records
+/// and returns the total number of affected rows
+///
+/// For each batch `build_query` is called to construct the actual statement, usually
+/// an `INSERT` statement inserting the given records. Up to `max_in_flight` statements
+/// are executed concurrently on the same connection, which allows connection implementations
+/// supporting pipelining like [`AsyncPgConnection`](crate::AsyncPgConnection) to send
+/// the next batch before the previous one finished. The stream is only polled for new records
+/// as long as less than `max_in_flight` statements are pending, so a fast producer is slowed down
+/// to the speed the database accepts the records.
+///
+/// This function requires that the futures returned by the connection do not borrow
+/// the connection itself, as otherwise no second statement could be started while the
+/// first one is still pending. This is the case for
+/// [`AsyncPgConnection`](crate::AsyncPgConnection) and
+/// [`SyncConnectionWrapper`](crate::sync_connection_wrapper::SyncConnectionWrapper).
+///
+/// If any statement fails the error is returned and no further batches are sent.
+/// Batches sent before might already be written at that point, so wrap the call into a
+/// transaction if either all or none of the records should be written.
+///
+/// # Panics
+///
+/// This function panics if `batch_size` or `max_in_flight` is 0.
+pub async fn insert_stream<Conn, S, Q>(
+    conn: &mut Conn,
+    records: S,
+    batch_size: usize,
+    max_in_flight: usize,
+    mut build_query: impl FnMut(Vec<S::Item>) -> Q,
+) -> QueryResult<usize>
+where
+    Conn: AsyncConnection,
+    S: Stream,
+    Q: ExecuteDsl<Conn> + 'static,
+    for<'conn> Conn::ExecuteFuture<'conn, 'static>: 'static,
+{
+    assert!(batch_size > 0, "The batch size must be greater than 0");
+    assert!(
+        max_in_flight > 0,
+        "The number of concurrent statements must be greater than 0"
+    );
+
+    let mut batches = std::pin::pin!(records.chunks(batch_size));
+    let mut in_flight = FuturesUnordered::<BoxFuture<'static, QueryResult<usize>>>::new();
+    let mut records_exhausted = false;
+    let mut total = 0;
+
+    loop {
+        let next = if records_exhausted || in_flight.len() >= max_in_flight {
+            match in_flight.next().await {
+                Some(res) => Either::Right(res),
+                None => break,
+            }
+        } else if in_flight.is_empty() {
+            Either::Left(batches.next().await)
+        } else {
+            match future::select(batches.next(), in_flight.next()).await {
+                Either::Left((batch, _)) => Either::Left(batch),
+                Either::Right((res, _)) => {
+                    Either::Right(res.expect("There is at least one pending statement"))
+                }
+            }
+        };
+
+        match next {
+            Either::Left(Some(batch)) => {
+                let statement: Conn::ExecuteFuture<'_, 'static> = build_query(batch).execute(conn);
+                in_flight.push(statement.boxed());
+            }
+            Either::Left(None) => records_exhausted = true,
+            Either::Right(res) => total += res?,
+        }
+    }
+    Ok(total)
+}
@@ -81,8 +81,20 @@ use scoped_futures::{ScopedBoxFuture, ScopedFutureExt};
 
 #[cfg(feature = "async-connection-wrapper")]
 pub mod async_connection_wrapper;
+#[cfg(any(
+    feature = "postgres",
+    feature = "mysql",
+    feature = "deadpool",
+    feature = "bb8",
+    feature = "mobc",
+    feature = "r2d2"
+))]
+mod backoff;
+pub mod batch;
+pub mod migrations;
 #[cfg(feature = "mysql")]
-mod mysql;
+pub mod mysql;
+pub mod pagination;
 #[cfg(feature = "postgres")]
 pub mod pg;
 #[cfg(any(
@@ -92,12 +104,22 @@ pub mod pg;
     feature = "r2d2"
 ))]
 pub mod pooled_connection;
+mod read_only;
+#[cfg(any(feature = "postgres", feature = "mysql"))]
+pub mod retry;
 mod run_query_dsl;
 #[cfg(any(feature = "postgres", feature = "mysql"))]
 mod stmt_cache;
 #[cfg(feature = "sync-connection-wrapper")]
 pub mod sync_connection_wrapper;
+#[cfg(feature = "testcontainers")]
+pub mod test_containers;
+#[cfg(feature = "macros")]
+#[doc(hidden)]
+pub mod test_support;
+mod transaction_connection;
 mod transaction_manager;
+mod with_transaction_manager;
 
 #[cfg(feature = "mysql")]
 #[doc(inline)]
@@ -106,10 +128,106 @@ pub use self::mysql::AsyncMysqlConnection;
 #[doc(inline)]
 pub use self::pg::AsyncPgConnection;
 #[doc(inline)]
+pub use self::read_only::ReadOnly;
+#[doc(inline)]
 pub use self::run_query_dsl::*;
 
 #[doc(inline)]
-pub use self::transaction_manager::{AnsiTransactionManager, TransactionManager};
+pub use self::transaction_connection::{InTransaction, TransactionConnection};
+#[doc(inline)]
+pub use self::transaction_manager::{
+    AnsiTransactionManager, TransactionEvent, TransactionEventCallback, TransactionEventKind,
+    TransactionManager,
+};
+#[doc(inline)]
+pub use self::with_transaction_manager::WithTransactionManager;
+
+/// Run an async test against a database inside of a test transaction
+///
+/// The attribute turns an `async fn` taking a mutable reference to a
+/// connection into a regular `#[test]`. The test builds a Tokio runtime,
+/// checks out a connection from a pool configured via the `DATABASE_URL`
+/// environment variable and runs the test body inside of a transaction that
+/// is rolled back once the body finished, like
+/// [`AsyncConnection::test_transaction`]. The environment variable can be
+/// changed via `#[diesel_async::test(env = "OTHER_DATABASE_URL")]`.
+///
+/// The test may return a [`Result`] like other tests. The future of the
+/// test body must be `Send`.
+///
+/// ```rust,no_run
+/// # include!("doctest_setup.rs");
+/// use diesel_async::RunQueryDsl;
+///
+/// #[diesel_async::test]
+/// async fn inserts_a_user(conn: &mut DbConnection) -> QueryResult<()> {
+///     use schema::users;
+///
+///     diesel::insert_into(users::table)
+///         .values(users::name.eq("Ruby"))
+///         .execute(conn)
+///         .await?;
+///     let count = users::table.count().get_result::<i64>(conn).await?;
+///     assert_eq!(1, count);
+///     Ok(())
+/// }
+/// # fn main() {}
+/// ```
+#[cfg(feature = "macros")]
+pub use diesel_async_macros::test;
+
+/// Executes the given async block inside of a database transaction
+///
+/// This is a shorthand for [`AsyncConnection::transaction`], which boxes the
+/// future returned by the closure. It removes the need to import
+/// [`ScopedFutureExt`](scoped_futures::ScopedFutureExt) and to call
+/// `.scope_boxed()` on every transaction body. The macro expects a connection
+/// and a closure with a single argument, which returns a future.
+///
+/// ```rust
+/// # include!("doctest_setup.rs");
+/// use diesel::result::Error;
+/// use diesel_async::RunQueryDsl;
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// #     run_test().await.unwrap();
+/// # }
+/// #
+/// # async fn run_test() -> QueryResult<()> {
+/// #     use schema::users::dsl::*;
+/// #     let conn = &mut establish_connection().await;
+/// let all_names = diesel_async::transaction!(conn, |conn| async move {
+///     diesel::insert_into(users)
+///         .values(name.eq("Ruby"))
+///         .execute(conn)
+///         .await?;
+///
+///     users.select(name).load::<String>(conn).await
+/// })
+/// .await?;
+/// assert_eq!(vec!["Sean", "Tess", "Ruby"], all_names);
+///
+/// let res = diesel_async::transaction!(conn, |conn| async move {
+///     diesel::insert_into(users)
+///         .values(name.eq("Pascal"))
+///         .execute(conn)
+///         .await?;
+///     Err::<(), _>(Error::RollbackTransaction)
+/// })
+/// .await;
+/// assert_eq!(Err(Error::RollbackTransaction), res);
+/// #     Ok(())
+/// # }
+/// ```
+#[macro_export]
+macro_rules! transaction {
+    ($conn:expr, |$arg:pat_param| $body:expr $(,)?) => {
+        $crate::AsyncConnection::transaction($conn, |$arg| {
+            $crate::scoped_futures::ScopedFutureExt::scope_boxed($body)
+        })
+    };
+}
 
 /// Perform simple operations on a backend.
 ///
@@ -142,7 +260,10 @@ pub trait AsyncConnection: SimpleAsyncConnection + Sized + Send {
     /// The backend this type connects to
     type Backend: Backend;
 
-    #[doc(hidden)]
+    /// The transaction manager used by this connection
+    ///
+    /// See [`WithTransactionManager`] to use a custom transaction manager
+    /// with an existing connection type.
     type TransactionManager: TransactionManager<Self>;
 
     /// Establishes a new connection to the database
@@ -178,9 +299,14 @@ pub trait AsyncConnection: SimpleAsyncConnection + Sized + Send {
     /// If a nested transaction fails to release the corresponding savepoint
     /// the error will be returned directly.
     ///
-    /// **WARNING:** Canceling the returned future does currently **not**
-    /// close an already open transaction. You may end up with a connection
-    /// containing a dangling transaction.
+    /// Canceling the returned future does not immediately close an already
+    /// open transaction. For connections using the [`AnsiTransactionManager`]
+    /// the dangling transaction is rolled back before the next statement or
+    /// transaction is executed on that connection. Until then the connection is considered
+    /// to be broken, so connection pools discard it instead of handing it out again.
+    ///
+    /// The [`transaction!`] macro wraps this function, so that the returned future
+    /// does not need to be boxed via `.scope_boxed()` manually.
     ///
     /// # Example
     ///
@@ -238,6 +364,25 @@ pub trait AsyncConnection: SimpleAsyncConnection + Sized + Send {
         Self::TransactionManager::transaction(self, callback).await
     }
 
+    /// Executes the given function inside of a database transaction, passing
+    /// a [`TransactionConnection`] to it
+    ///
+    /// This behaves like [`AsyncConnection::transaction`]. As a
+    /// [`TransactionConnection`] cannot be obtained otherwise, functions
+    /// accepting it can only be called within a transaction.
+    /// See [`TransactionConnection`] for an example.
+    async fn typed_transaction<'a, R, E, F>(&mut self, callback: F) -> Result<R, E>
+    where
+        F: for<'r> FnOnce(TransactionConnection<'r, Self>) -> ScopedBoxFuture<'a, 'r, Result<R, E>>
+            + Send
+            + 'a,
+        E: From<diesel::result::Error> + Send + 'a,
+        R: Send + 'a,
+    {
+        self.transaction(|conn| callback(TransactionConnection::new(conn)))
+            .await
+    }
+
     /// Creates a transaction that will never be committed. This is useful for
     /// tests. Panics if called while inside of a transaction or
     /// if called with a connection containing a broken transaction
@@ -319,6 +464,34 @@ pub trait AsyncConnection: SimpleAsyncConnection + Sized + Send {
         user_result.expect("Transaction did not succeed")
     }
 
+    /// Checks that the connection is still alive with a cheap round trip
+    /// to the database
+    ///
+    /// The default implementation executes `SELECT 1`. The PostgreSQL backend
+    /// sends an empty query instead, the MySQL backend sends a `COM_PING`
+    /// packet, so neither needs to parse or plan a statement.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # include!("doctest_setup.rs");
+    /// use diesel_async::AsyncConnection;
+    /// #
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// #     run_test().await.unwrap();
+    /// # }
+    /// #
+    /// # async fn run_test() -> QueryResult<()> {
+    /// #     let conn = &mut establish_connection().await;
+    /// conn.ping_connection().await?;
+    /// #     Ok(())
+    /// # }
+    /// ```
+    async fn ping_connection(&mut self) -> QueryResult<()> {
+        self.batch_execute("SELECT 1").await
+    }
+
     #[doc(hidden)]
     fn load<'conn, 'query, T>(&'conn mut self, source: T) -> Self::LoadFuture<'conn, 'query>
     where
@@ -0,0 +1,428 @@
+//! Run diesel migrations directly on an [`AsyncConnection`]
+//!
+//! This module provides the [`AsyncMigrationHarness`] trait, an async
+//! counterpart of `diesel_migrations::MigrationHarness`. It can be used to execute
+//! migrations embedded via `diesel_migrations::embed_migrations!` without going through
+//! [`AsyncConnectionWrapper`](crate::async_connection_wrapper::AsyncConnectionWrapper)
+//! and `spawn_blocking`.
+//!
+//! ```rust
+//! # include!("doctest_setup.rs");
+//! use diesel_async::migrations::AsyncMigrationHarness;
+//! use diesel_migrations::{embed_migrations, EmbeddedMigrations};
+//!
+//! pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("tests/migrations");
+//!
+//! # #[tokio::main(flavor = "current_thread")]
+//! # async fn main() {
+//! #     run_test().await.unwrap();
+//! # }
+//! #
+//! # async fn run_test() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+//! #     let mut conn = establish_connection().await;
+//! conn.run_pending_migrations(MIGRATIONS).await?;
+//! assert!(!conn.has_pending_migration(MIGRATIONS).await?);
+//! #     Ok(())
+//! # }
+//! ```
+
+use crate::methods::{ExecuteDsl, LoadQuery};
+use crate::{AsyncConnection, RunQueryDsl};
+use diesel::associations::HasTable;
+use diesel::backend::Backend;
+use diesel::connection::{
+    AnsiTransactionManager, Connection, ConnectionSealed, Instrumentation, SimpleConnection,
+};
+use diesel::migration::{Migration, MigrationSource, MigrationVersion, Result};
+use diesel::prelude::*;
+use diesel::query_builder::{DeleteStatement, InsertStatement, IntoUpdateTarget};
+use diesel::{dsl, ConnectionResult};
+use scoped_futures::ScopedFutureExt;
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::time::{Duration, Instant};
+
+mod schema {
+    diesel::table! {
+        __diesel_schema_migrations (version) {
+            version -> VarChar,
+        }
+    }
+}
+
+use self::schema::__diesel_schema_migrations;
+
+/// A migration harness for async connections
+///
+/// This trait mirrors the `MigrationHarness` trait provided by
+/// `diesel_migrations`. It is implemented for all [`AsyncConnection`]
+/// types provided by diesel-async.
+///
+/// Migrations from any [`MigrationSource`] can be executed, as long as they only
+/// consist of SQL that is passed to the connection via `batch_execute`. This is the
+/// case for all migrations provided by `diesel_migrations`.
+#[async_trait::async_trait]
+pub trait AsyncMigrationHarness<DB: Backend> {
+    /// Checks if the database represented by the current harness has unapplied migrations
+    async fn has_pending_migration<S>(&mut self, source: S) -> Result<bool>
+    where
+        S: MigrationSource<DB> + Send;
+
+    /// Execute all unapplied migrations for a given migration source
+    ///
+    /// Returns the versions of the applied migrations
+    async fn run_pending_migrations<S>(
+        &mut self,
+        source: S,
+    ) -> Result<Vec<MigrationVersion<'static>>>
+    where
+        S: MigrationSource<DB> + Send;
+
+    /// Execute all unapplied migrations for a given migration source and
+    /// report the outcome of each migration to `on_progress`
+    ///
+    /// `on_progress` is called once after each executed migration, including
+    /// the migration that failed if any. Execution stops at the first failing
+    /// migration.
+    ///
+    /// Returns the versions of the applied migrations
+    async fn run_pending_migrations_with_progress<S, F>(
+        &mut self,
+        source: S,
+        on_progress: F,
+    ) -> Result<Vec<MigrationVersion<'static>>>
+    where
+        S: MigrationSource<DB> + Send,
+        F: FnMut(MigrationProgress<'_>) + Send;
+
+    /// Get the names of all unapplied migrations for a given migration source
+    /// in the order they would be executed by
+    /// [`run_pending_migrations`](AsyncMigrationHarness::run_pending_migrations)
+    async fn pending_migrations<S>(&mut self, source: S) -> Result<Vec<String>>
+    where
+        S: MigrationSource<DB> + Send;
+
+    /// Revert the last applied migration from a given migration source
+    ///
+    /// Returns the version of the reverted migration
+    async fn revert_last_migration<S>(&mut self, source: S) -> Result<MigrationVersion<'static>>
+    where
+        S: MigrationSource<DB> + Send;
+
+    /// Get a list of all applied migrations, ordered from the
+    /// most recently applied one to the oldest one
+    async fn applied_migrations(&mut self) -> Result<Vec<MigrationVersion<'static>>>;
+}
+
+/// The outcome of a single migration executed by
+/// [`AsyncMigrationHarness::run_pending_migrations_with_progress`]
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct MigrationProgress<'a> {
+    /// The name of the migration
+    pub name: &'a str,
+    /// The version of the migration
+    pub version: &'a MigrationVersion<'static>,
+    /// The time it took to execute the migration
+    pub duration: Duration,
+    /// The result of the migration
+    pub result: std::result::Result<(), &'a diesel::result::Error>,
+}
+
+type AppliedMigrationsQuery = dsl::Order<
+    dsl::Select<__diesel_schema_migrations::table, __diesel_schema_migrations::version>,
+    dsl::Desc<__diesel_schema_migrations::version>,
+>;
+
+type InsertMigrationStatement = InsertStatement<
+    __diesel_schema_migrations::table,
+    <dsl::Eq<__diesel_schema_migrations::version, MigrationVersion<'static>> as Insertable<
+        __diesel_schema_migrations::table,
+    >>::Values,
+>;
+
+type DeleteMigrationStatement = DeleteStatement<
+    <dsl::Find<__diesel_schema_migrations::table, MigrationVersion<'static>> as HasTable>::Table,
+    <dsl::Find<__diesel_schema_migrations::table, MigrationVersion<'static>> as IntoUpdateTarget>::WhereClause,
+>;
+
+#[async_trait::async_trait]
+impl<C, DB> AsyncMigrationHarness<DB> for C
+where
+    C: AsyncConnection<Backend = DB>,
+    DB: Backend + 'static,
+    AppliedMigrationsQuery: LoadQuery<'static, C, MigrationVersion<'static>>,
+    InsertMigrationStatement: ExecuteDsl<C>,
+    DeleteMigrationStatement: ExecuteDsl<C>,
+{
+    async fn has_pending_migration<S>(&mut self, source: S) -> Result<bool>
+    where
+        S: MigrationSource<DB> + Send,
+    {
+        let applied = self.applied_migrations().await?;
+        Ok(!collect_pending(&source, applied)?.is_empty())
+    }
+
+    async fn run_pending_migrations<S>(
+        &mut self,
+        source: S,
+    ) -> Result<Vec<MigrationVersion<'static>>>
+    where
+        S: MigrationSource<DB> + Send,
+    {
+        self.run_pending_migrations_with_progress(source, |_| {})
+            .await
+    }
+
+    async fn run_pending_migrations_with_progress<S, F>(
+        &mut self,
+        source: S,
+        mut on_progress: F,
+    ) -> Result<Vec<MigrationVersion<'static>>>
+    where
+        S: MigrationSource<DB> + Send,
+        F: FnMut(MigrationProgress<'_>) + Send,
+    {
+        let applied = self.applied_migrations().await?;
+        let pending = collect_pending(&source, applied)?;
+        let mut versions = Vec::with_capacity(pending.len());
+        for migration in pending {
+            let name = migration.name.clone();
+            let version = migration.version.as_owned();
+            let start = Instant::now();
+            let result = migration.apply(self).await;
+            on_progress(MigrationProgress {
+                name: &name,
+                version: &version,
+                duration: start.elapsed(),
+                result: result.as_ref().map(|_| ()),
+            });
+            result?;
+            versions.push(version);
+        }
+        Ok(versions)
+    }
+
+    async fn pending_migrations<S>(&mut self, source: S) -> Result<Vec<String>>
+    where
+        S: MigrationSource<DB> + Send,
+    {
+        let applied = self.applied_migrations().await?;
+        Ok(collect_pending(&source, applied)?
+            .into_iter()
+            .map(|m| m.name)
+            .collect())
+    }
+
+    async fn revert_last_migration<S>(&mut self, source: S) -> Result<MigrationVersion<'static>>
+    where
+        S: MigrationSource<DB> + Send,
+    {
+        let applied = self.applied_migrations().await?;
+        let last_version = applied
+            .into_iter()
+            .next()
+            .ok_or("No migration has been run")?;
+        let migration = source
+            .migrations()?
+            .into_iter()
+            .find(|m| m.name().version() == last_version)
+            .map(|m| CollectedMigration::revert(&*m))
+            .ok_or_else(|| {
+                format!("Unable to find migration version {last_version} to revert")
+            })??;
+        migration.apply(self).await?;
+        Ok(last_version)
+    }
+
+    async fn applied_migrations(&mut self) -> Result<Vec<MigrationVersion<'static>>> {
+        self.batch_execute(diesel::migration::CREATE_MIGRATIONS_TABLE)
+            .await?;
+        Ok(__diesel_schema_migrations::table
+            .select(__diesel_schema_migrations::version)
+            .order(__diesel_schema_migrations::version.desc())
+            .load(self)
+            .await?)
+    }
+}
+
+fn collect_pending<DB, S>(
+    source: &S,
+    applied: Vec<MigrationVersion<'static>>,
+) -> Result<Vec<CollectedMigration>>
+where
+    DB: Backend + 'static,
+    S: MigrationSource<DB>,
+{
+    let mut migrations = source
+        .migrations()?
+        .into_iter()
+        .map(|m| (m.name().version().as_owned(), m))
+        .collect::<HashMap<_, _>>();
+    for version in applied {
+        migrations.remove(&version);
+    }
+    let mut migrations = migrations.into_values().collect::<Vec<_>>();
+    migrations.sort_unstable_by(|a, b| a.name().version().cmp(&b.name().version()));
+    migrations
+        .iter()
+        .map(|m| CollectedMigration::run(&**m))
+        .collect()
+}
+
+enum Direction {
+    Run,
+    Revert,
+}
+
+/// The SQL of a single migration
+///
+/// `Migration` trait objects are neither `Send` nor async, so
+/// we collect the SQL they would execute up front and execute
+/// it afterwards on the async connection
+struct CollectedMigration {
+    name: String,
+    version: MigrationVersion<'static>,
+    statements: Vec<String>,
+    run_in_transaction: bool,
+    direction: Direction,
+}
+
+impl CollectedMigration {
+    fn run<DB>(migration: &dyn Migration<DB>) -> Result<Self>
+    where
+        DB: Backend + 'static,
+    {
+        let mut collector = SqlCollector::<DB>::new();
+        migration.run(&mut collector)?;
+        Ok(Self::new(migration, collector, Direction::Run))
+    }
+
+    fn revert<DB>(migration: &dyn Migration<DB>) -> Result<Self>
+    where
+        DB: Backend + 'static,
+    {
+        let mut collector = SqlCollector::<DB>::new();
+        migration.revert(&mut collector)?;
+        Ok(Self::new(migration, collector, Direction::Revert))
+    }
+
+    fn new<DB>(
+        migration: &dyn Migration<DB>,
+        collector: SqlCollector<DB>,
+        direction: Direction,
+    ) -> Self
+    where
+        DB: Backend,
+    {
+        Self {
+            name: migration.name().to_string(),
+            version: migration.name().version().as_owned(),
+            statements: collector.statements,
+            run_in_transaction: migration.metadata().run_in_transaction(),
+            direction,
+        }
+    }
+
+    async fn apply<C>(self, conn: &mut C) -> QueryResult<()>
+    where
+        C: AsyncConnection,
+        InsertMigrationStatement: ExecuteDsl<C>,
+        DeleteMigrationStatement: ExecuteDsl<C>,
+    {
+        if self.run_in_transaction {
+            conn.transaction(|conn| self.apply_inner(conn).scope_boxed())
+                .await
+        } else {
+            self.apply_inner(conn).await
+        }
+    }
+
+    async fn apply_inner<C>(self, conn: &mut C) -> QueryResult<()>
+    where
+        C: AsyncConnection,
+        InsertMigrationStatement: ExecuteDsl<C>,
+        DeleteMigrationStatement: ExecuteDsl<C>,
+    {
+        for statement in &self.statements {
+            conn.batch_execute(statement).await?;
+        }
+        match self.direction {
+            Direction::Run => {
+                diesel::insert_into(__diesel_schema_migrations::table)
+                    .values(__diesel_schema_migrations::version.eq(self.version))
+                    .execute(conn)
+                    .await?;
+            }
+            Direction::Revert => {
+                diesel::delete(__diesel_schema_migrations::table.find(self.version))
+                    .execute(conn)
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A connection that only records the SQL passed to `batch_execute`
+struct SqlCollector<DB> {
+    statements: Vec<String>,
+    transaction_manager: AnsiTransactionManager,
+    instrumentation: Option<Box<dyn Instrumentation>>,
+    backend: PhantomData<fn() -> DB>,
+}
+
+impl<DB> SqlCollector<DB> {
+    fn new() -> Self {
+        Self {
+            statements: Vec::new(),
+            transaction_manager: AnsiTransactionManager::default(),
+            instrumentation: None,
+            backend: PhantomData,
+        }
+    }
+}
+
+impl<DB> SimpleConnection for SqlCollector<DB> {
+    fn batch_execute(&mut self, query: &str) -> QueryResult<()> {
+        self.statements.push(query.to_owned());
+        Ok(())
+    }
+}
+
+impl<DB> ConnectionSealed for SqlCollector<DB> {}
+
+impl<DB> Connection for SqlCollector<DB>
+where
+    DB: Backend + 'static,
+{
+    type Backend = DB;
+    type TransactionManager = AnsiTransactionManager;
+
+    fn establish(_database_url: &str) -> ConnectionResult<Self> {
+        Ok(Self::new())
+    }
+
+    fn execute_returning_count<T>(&mut self, _source: &T) -> QueryResult<usize>
+    where
+        T: diesel::query_builder::QueryFragment<Self::Backend> + diesel::query_builder::QueryId,
+    {
+        Err(diesel::result::Error::QueryBuilderError(
+            "Migrations executed via `AsyncMigrationHarness` \
+             can only use `batch_execute` to execute SQL"
+                .into(),
+        ))
+    }
+
+    fn transaction_state(&mut self) -> &mut AnsiTransactionManager {
+        &mut self.transaction_manager
+    }
+
+    fn instrumentation(&mut self) -> &mut dyn Instrumentation {
+        &mut self.instrumentation
+    }
+
+    fn set_instrumentation(&mut self, instrumentation: impl Instrumentation) {
+        self.instrumentation = Some(Box::new(instrumentation));
+    }
+}
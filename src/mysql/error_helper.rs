@@ -1,5 +1,5 @@
 use diesel::{result::DatabaseErrorKind, ConnectionError};
-use mysql_async::Error;
+use mysql_async::{DriverError, Error};
 
 pub(super) struct ErrorHelper(pub(super) Error);
 
@@ -8,10 +8,14 @@ impl From<ErrorHelper> for diesel::result::Error {
         match e {
             Error::Server(e) => {
                 let kind = match e.code {
+                    // the server closed the connection due to inactivity
+                    4031 => DatabaseErrorKind::ClosedConnection,
                     1062 | 1586 | 1859 => DatabaseErrorKind::UniqueViolation,
                     1216 | 1217 | 1451 | 1452 | 1830 | 1834 => {
                         DatabaseErrorKind::ForeignKeyViolation
                     }
+                    // the server rolled back the transaction due to a deadlock
+                    1213 => DatabaseErrorKind::SerializationFailure,
                     1792 => DatabaseErrorKind::ReadOnlyTransaction,
                     1048 | 1364 => DatabaseErrorKind::NotNullViolation,
                     3819 => DatabaseErrorKind::CheckViolation,
@@ -19,6 +23,12 @@ impl From<ErrorHelper> for diesel::result::Error {
                 };
                 diesel::result::Error::DatabaseError(kind, Box::new(e.message) as _)
             }
+            e @ (Error::Io(_) | Error::Driver(DriverError::ConnectionClosed)) => {
+                diesel::result::Error::DatabaseError(
+                    DatabaseErrorKind::ClosedConnection,
+                    Box::new(e.to_string()) as _,
+                )
+            }
             e => diesel::result::Error::DatabaseError(
                 DatabaseErrorKind::Unknown,
                 Box::new(e.to_string()) as _,
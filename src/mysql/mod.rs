@@ -1,3 +1,9 @@
+//! Provides types and functions related to working with MySQL
+//!
+//! Much of this module is re-exported from database agnostic locations.
+//! However, if you are writing code specifically to extend diesel-async on
+//! MySQL, you may need to work with this module directly.
+
 use crate::stmt_cache::{PrepareCallback, StmtCache};
 use crate::{AnsiTransactionManager, AsyncConnection, SimpleAsyncConnection};
 use diesel::connection::statement_cache::{MaybeCached, StatementCacheKey};
@@ -10,28 +16,54 @@ use futures_util::future::BoxFuture;
 use futures_util::stream::{self, BoxStream};
 use futures_util::{Future, FutureExt, StreamExt, TryStreamExt};
 use mysql_async::prelude::Queryable;
-use mysql_async::{Opts, OptsBuilder, Statement};
+use mysql_async::Statement;
+use scoped_futures::ScopedBoxFuture;
 
+mod connection_builder;
 mod error_helper;
+mod procedure;
 mod row;
 mod serialize;
+mod warnings;
 
+pub use self::connection_builder::{AsyncMysqlConnectionBuilder, SslMode};
 use self::error_helper::ErrorHelper;
+pub use self::procedure::ProcedureResults;
 use self::row::MysqlRow;
 use self::serialize::ToSqlHelper;
+pub use self::warnings::MysqlWarning;
 
 /// A connection to a MySQL database. Connection URLs should be in the form
-/// `mysql://[user[:password]@]host/database_name`
+/// `mysql://[user[:password]@]host/database_name`. Append
+/// `?unix_socket=/path/to/mysqld.sock` to connect via a unix socket.
+///
+/// See [`AsyncMysqlConnectionBuilder`] for how to establish encrypted connections.
+///
+/// Query results are streamed from the server as they are consumed. Use
+/// [`RunQueryDsl::load_stream`](crate::RunQueryDsl::load_stream) to process large
+/// result sets with bounded memory: the next row is only read from the socket once
+/// the previous one was taken from the stream. Server side cursors
+/// (`COM_STMT_FETCH`) are not supported by `mysql_async`. Keep in mind that the
+/// connection cannot execute other statements until the stream is dropped and
+/// that the server keeps the statement open until all rows were read.
 pub struct AsyncMysqlConnection {
     conn: mysql_async::Conn,
     stmt_cache: StmtCache<Mysql, Statement>,
     transaction_manager: AnsiTransactionManager,
+    // set as soon as an operation reported that the server closed the connection
+    is_closed: bool,
 }
 
 #[async_trait::async_trait]
 impl SimpleAsyncConnection for AsyncMysqlConnection {
     async fn batch_execute(&mut self, query: &str) -> diesel::QueryResult<()> {
-        Ok(self.conn.query_drop(query).await.map_err(ErrorHelper)?)
+        AnsiTransactionManager::rollback_abandoned_transaction(self).await?;
+        let res = self
+            .conn
+            .query_drop(query)
+            .await
+            .map_err(|e| ErrorHelper(e).into());
+        update_connection_status(res, &mut self.is_closed)
     }
 }
 
@@ -53,20 +85,16 @@ impl AsyncConnection for AsyncMysqlConnection {
     type TransactionManager = AnsiTransactionManager;
 
     async fn establish(database_url: &str) -> diesel::ConnectionResult<Self> {
-        let opts = Opts::from_url(database_url)
-            .map_err(|e| diesel::result::ConnectionError::InvalidConnectionUrl(e.to_string()))?;
-        let builder = OptsBuilder::from_opts(opts)
-            .init(CONNECTION_SETUP_QUERIES.to_vec())
-            .stmt_cache_size(0) // We have our own cache
-            .client_found_rows(true); // This allows a consistent behavior between MariaDB/MySQL and PostgreSQL (and is already set in `diesel`)
-
-        let conn = mysql_async::Conn::new(builder).await.map_err(ErrorHelper)?;
+        AsyncMysqlConnectionBuilder::new(database_url)
+            .establish()
+            .await
+    }
 
-        Ok(AsyncMysqlConnection {
-            conn,
-            stmt_cache: StmtCache::new(),
-            transaction_manager: AnsiTransactionManager::default(),
-        })
+    async fn ping_connection(&mut self) -> QueryResult<()> {
+        // `COM_PING` detects connections closed by the server,
+        // for example due to `wait_timeout`, without executing a query
+        let res = self.conn.ping().await.map_err(|e| ErrorHelper(e).into());
+        update_connection_status(res, &mut self.is_closed)
     }
 
     fn load<'conn, 'query, T>(&'conn mut self, source: T) -> Self::LoadFuture<'conn, 'query>
@@ -154,6 +182,21 @@ impl AsyncConnection for AsyncMysqlConnection {
     }
 }
 
+#[inline(always)]
+fn update_connection_status<T>(
+    query_result: QueryResult<T>,
+    is_closed: &mut bool,
+) -> QueryResult<T> {
+    if let Err(diesel::result::Error::DatabaseError(
+        diesel::result::DatabaseErrorKind::ClosedConnection,
+        _,
+    )) = query_result
+    {
+        *is_closed = true;
+    }
+    query_result
+}
+
 #[inline(always)]
 fn update_transaction_manager_status<T>(
     query_result: QueryResult<T>,
@@ -188,13 +231,18 @@ impl AsyncMysqlConnection {
     /// Wrap an existing [`mysql_async::Conn`] into a async diesel mysql connection
     ///
     /// This function constructs a new `AsyncMysqlConnection` based on an existing
-    /// [`mysql_async::Conn]`.
+    /// [`mysql_async::Conn`]. This allows to use connections established with custom
+    /// logic, for example over an SSH tunnel or with TLS settings not covered by
+    /// [`AsyncMysqlConnectionBuilder`]. The session is configured with the same
+    /// settings (time zone and character set) as connections established by
+    /// diesel-async itself.
     pub async fn try_from(conn: mysql_async::Conn) -> ConnectionResult<Self> {
         use crate::run_query_dsl::RunQueryDsl;
         let mut conn = AsyncMysqlConnection {
             conn,
             stmt_cache: StmtCache::new(),
             transaction_manager: AnsiTransactionManager::default(),
+            is_closed: false,
         };
 
         for stmt in CONNECTION_SETUP_QUERIES {
@@ -207,6 +255,240 @@ impl AsyncMysqlConnection {
         Ok(conn)
     }
 
+    /// Roll back all open transactions and savepoints to bring this connection
+    /// back into a usable state
+    ///
+    /// Connection pools discard connections with an open transaction or a
+    /// broken transaction manager. Call this function to recover such a
+    /// connection instead. See [`AnsiTransactionManager::rollback_to_clean_state`]
+    /// for details.
+    pub async fn rollback_to_clean_state(&mut self) -> QueryResult<()> {
+        AnsiTransactionManager::rollback_to_clean_state(self).await
+    }
+
+    /// Checks whether this connection is usable, waiting at most `timeout`
+    /// for the database to respond
+    ///
+    /// This returns `false` if the connection was closed, if the database
+    /// responded with an error or if it did not respond in time, see
+    /// [`AsyncConnection::ping_connection`]. Connections that are not valid should be
+    /// discarded.
+    ///
+    /// ```rust
+    /// # include!("../doctest_setup.rs");
+    /// use std::time::Duration;
+    /// #
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// #     let conn = &mut establish_connection().await;
+    /// assert!(conn.is_valid(Duration::from_secs(1)).await);
+    /// # }
+    /// ```
+    pub async fn is_valid(&mut self, timeout: std::time::Duration) -> bool {
+        if self.is_closed {
+            return false;
+        }
+        match tokio::time::timeout(timeout, AsyncConnection::ping_connection(self)).await {
+            Ok(res) => res.is_ok(),
+            Err(_) => {
+                // the response to the cancelled ping might still arrive
+                // and would be mistaken for the response to the next command
+                self.is_closed = true;
+                false
+            }
+        }
+    }
+
+    /// Returns the value generated for an `AUTO_INCREMENT` column
+    /// by the last statement executed on this connection
+    ///
+    /// Returns `None` if the last statement did not generate such a value.
+    ///
+    /// ```rust
+    /// # include!("../doctest_setup.rs");
+    /// #
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// #     run_test().await.unwrap();
+    /// # }
+    /// #
+    /// # async fn run_test() -> QueryResult<()> {
+    /// #     use diesel_async::RunQueryDsl;
+    /// #     use schema::users;
+    /// #     let conn = &mut establish_connection().await;
+    /// diesel::insert_into(users::table)
+    ///     .values(users::name.eq("Ruby"))
+    ///     .execute(conn)
+    ///     .await?;
+    /// assert_eq!(Some(3), conn.last_insert_id());
+    /// #     Ok(())
+    /// # }
+    /// ```
+    pub fn last_insert_id(&self) -> Option<u64> {
+        self.conn.last_insert_id()
+    }
+
+    /// Run `f` with access to the underlying [`mysql_async::Conn`]
+    ///
+    /// This allows to use features of `mysql_async` which are not exposed
+    /// by diesel-async. Errors returned by `f` are converted the same way as errors
+    /// of other operations, so connections closed by the server are detected by
+    /// connection pools.
+    ///
+    /// `f` must leave the session in a state diesel-async can continue to work with.
+    /// In particular it must not reset the connection (`Conn::reset`,
+    /// `Conn::change_user`), as this invalidates the prepared statements cached
+    /// by diesel-async, and must not start or end transactions.
+    ///
+    /// ```rust
+    /// # include!("../doctest_setup.rs");
+    /// use mysql_async::prelude::Queryable as _;
+    /// use scoped_futures::ScopedFutureExt;
+    /// #
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// #     run_test().await.unwrap();
+    /// # }
+    /// #
+    /// # async fn run_test() -> QueryResult<()> {
+    /// #     let conn = &mut establish_connection().await;
+    /// let names = conn
+    ///     .with_raw_connection(|conn| {
+    ///         async move {
+    ///             conn.query::<String, _>("SELECT name FROM users ORDER BY id")
+    ///                 .await
+    ///         }
+    ///         .scope_boxed()
+    ///     })
+    ///     .await?;
+    /// assert_eq!(vec!["Sean", "Tess"], names);
+    /// #     Ok(())
+    /// # }
+    /// ```
+    pub async fn with_raw_connection<'a, F, R>(&'a mut self, f: F) -> QueryResult<R>
+    where
+        F: for<'r> FnOnce(
+                &'r mut mysql_async::Conn,
+            ) -> ScopedBoxFuture<'a, 'r, mysql_async::Result<R>>
+            + Send
+            + 'a,
+        R: Send + 'a,
+    {
+        let res = f(&mut self.conn).await.map_err(|e| ErrorHelper(e).into());
+        update_connection_status(res, &mut self.is_closed)
+    }
+
+    /// Execute multiple `;` separated SQL statements and report the result
+    /// of each statement
+    ///
+    /// Unlike [`SimpleAsyncConnection::batch_execute`] this returns one entry
+    /// per executed statement: the number of affected rows for data modifying
+    /// statements or the number of returned rows for queries. MySQL stops executing
+    /// the batch at the first failing statement, so if any statement fails the
+    /// last entry contains its error and the following statements are not
+    /// executed. Statements executed before the error are not rolled back unless
+    /// the batch is executed inside of a transaction.
+    ///
+    /// ```rust
+    /// # include!("../doctest_setup.rs");
+    /// #
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// #     run_test().await.unwrap();
+    /// # }
+    /// #
+    /// # async fn run_test() -> QueryResult<()> {
+    /// #     let conn = &mut establish_connection().await;
+    /// let results = conn
+    ///     .batch_query(
+    ///         "UPDATE users SET name = 'Jim' WHERE id = 1;
+    ///          SELECT * FROM users;
+    ///          INSERT INTO no_such_table VALUES (1);
+    ///          DELETE FROM users",
+    ///     )
+    ///     .await;
+    /// assert_eq!(3, results.len());
+    /// assert_eq!(Some(&1), results[0].as_ref().ok());
+    /// assert_eq!(Some(&2), results[1].as_ref().ok());
+    /// assert!(results[2].is_err());
+    /// #     Ok(())
+    /// # }
+    /// ```
+    pub async fn batch_query(&mut self, sql: &str) -> Vec<QueryResult<usize>> {
+        let mut results = Vec::new();
+        let res = async {
+            let mut result = self.conn.query_iter(sql).await.map_err(ErrorHelper)?;
+            while !result.is_empty() {
+                let returns_rows = !result.columns_ref().is_empty();
+                let affected_rows = result.affected_rows() as usize;
+                let mut row_count = 0;
+                // Reading the end of a result set also reads the response to the
+                // next statement, so an error returned here belongs to the next statement
+                let res = result
+                    .reduce((), |(), _: mysql_async::Row| row_count += 1)
+                    .await;
+                results.push(Ok(if returns_rows {
+                    row_count
+                } else {
+                    affected_rows
+                }));
+                res.map_err(ErrorHelper)?;
+            }
+            QueryResult::Ok(())
+        }
+        .await;
+        if let Err(e) = update_connection_status(res, &mut self.is_closed) {
+            results.push(Err(e));
+        }
+        results
+    }
+
+    /// Prepare the statement for the given query and store it in the
+    /// statement cache of this connection, without executing the query
+    ///
+    /// Later executions of queries of the same type reuse the cached statement.
+    /// This is mostly useful to warm up new connections, see
+    /// [`ManagerConfig::warm_up`](crate::pooled_connection::ManagerConfig::warm_up).
+    /// Queries that are not safe to cache are prepared and closed again.
+    ///
+    /// ```rust
+    /// # include!("../doctest_setup.rs");
+    /// #
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// #     run_test().await.unwrap();
+    /// # }
+    /// #
+    /// # async fn run_test() -> QueryResult<()> {
+    /// #     use diesel_async::RunQueryDsl;
+    /// #     use schema::users;
+    /// #     let conn = &mut establish_connection().await;
+    /// conn.prepare_cached(users::table.select(users::name).filter(users::id.eq(0)))
+    ///     .await?;
+    ///
+    /// // uses the already prepared statement
+    /// let name = users::table
+    ///     .select(users::name)
+    ///     .filter(users::id.eq(1))
+    ///     .get_result::<String>(conn)
+    ///     .await?;
+    /// assert_eq!("Sean", name);
+    /// #     Ok(())
+    /// # }
+    /// ```
+    pub async fn prepare_cached<T>(&mut self, query: T) -> QueryResult<()>
+    where
+        T: QueryFragment<Mysql> + QueryId + Send,
+    {
+        self.with_prepared_statement(query, |conn, stmt, _| async move {
+            if let MaybeCached::CannotCache(stmt) = stmt {
+                conn.close(stmt).await.map_err(ErrorHelper)?;
+            }
+            Ok(())
+        })
+        .await
+    }
+
     fn with_prepared_statement<'conn, T, F, R>(
         &'conn mut self,
         query: T,
@@ -228,6 +510,7 @@ impl AsyncMysqlConnection {
             ref mut conn,
             ref mut stmt_cache,
             ref mut transaction_manager,
+            ref mut is_closed,
             ..
         } = self;
 
@@ -235,8 +518,13 @@ impl AsyncMysqlConnection {
         let mut qb = MysqlQueryBuilder::new();
         let sql = query.to_sql(&mut qb, &Mysql).map(|()| qb.finish());
         let query_id = T::query_id();
+        let abandoned = transaction_manager.take_abandoned();
 
         async move {
+            if let Some(abandoned) = abandoned {
+                conn.query_drop("ROLLBACK").await.map_err(ErrorHelper)?;
+                abandoned.finish();
+            }
             let RawBytesBindCollector {
                 metadata, binds, ..
             } = bind_collector?;
@@ -260,10 +548,9 @@ impl AsyncMysqlConnection {
                     conn,
                 )
                 .await?;
-            update_transaction_manager_status(
-                callback(conn, stmt, ToSqlHelper { metadata, binds }).await,
-                transaction_manager,
-            )
+            let res = callback(conn, stmt, ToSqlHelper { metadata, binds }).await;
+            let res = update_connection_status(res, is_closed);
+            update_transaction_manager_status(res, transaction_manager)
         }
         .boxed()
     }
@@ -308,7 +595,87 @@ impl AsyncMysqlConnection {
     feature = "mobc",
     feature = "r2d2"
 ))]
-impl crate::pooled_connection::PoolableConnection for AsyncMysqlConnection {}
+#[async_trait::async_trait]
+impl crate::pooled_connection::PoolableConnection for AsyncMysqlConnection {
+    async fn ping(
+        &mut self,
+        config: &crate::pooled_connection::RecyclingMethod<Self>,
+    ) -> diesel::QueryResult<()> {
+        use crate::pooled_connection::RecyclingMethod;
+        use crate::run_query_dsl::RunQueryDsl;
+
+        match config {
+            RecyclingMethod::Fast => Ok(()),
+            RecyclingMethod::Verified => AsyncConnection::ping_connection(self).await,
+            RecyclingMethod::CustomQuery(query) => diesel::sql_query(query.as_ref())
+                .execute(self)
+                .await
+                .map(|_| ()),
+            RecyclingMethod::CustomFunction(c) => c(self).await,
+        }
+    }
+
+    async fn rollback_open_transactions(&mut self) -> diesel::QueryResult<()> {
+        // also recovers from dropped transaction futures and failed rollbacks
+        AnsiTransactionManager::rollback_to_clean_state(self).await
+    }
+
+    fn apply_tcp_config<'a>(
+        database_url: &'a str,
+        config: &crate::pooled_connection::TcpConfig,
+    ) -> std::borrow::Cow<'a, str> {
+        let mut params = Vec::new();
+        if let Some(idle) = config.keepalive_idle {
+            let millis = u32::try_from(idle.as_millis()).unwrap_or(u32::MAX);
+            params.push(("tcp_keepalive", millis.to_string()));
+        }
+        if let Some(nodelay) = config.nodelay {
+            params.push(("tcp_nodelay", nodelay.to_string()));
+        }
+        if params.is_empty() {
+            std::borrow::Cow::Borrowed(database_url)
+        } else {
+            crate::pooled_connection::append_url_params(database_url, &params).into()
+        }
+    }
+
+    async fn configure_statement_cache(
+        &mut self,
+        config: &crate::pooled_connection::StatementCacheConfig,
+    ) {
+        self.stmt_cache
+            .configure(config.capacity(), config.query_id_only);
+    }
+
+    async fn reset_session(&mut self) -> diesel::QueryResult<()> {
+        use crate::run_query_dsl::RunQueryDsl;
+
+        let supported = self.conn.reset().await.map_err(ErrorHelper)?;
+        if !supported {
+            return Err(diesel::result::Error::QueryBuilderError(
+                "The server does not support `COM_RESET_CONNECTION`".into(),
+            ));
+        }
+        // the server closed all prepared statements
+        self.stmt_cache.clear();
+        // `Conn::reset` runs the setup commands of the connection options again,
+        // which only contain the setup queries for connections established via
+        // `AsyncMysqlConnectionBuilder`, not for those passed to `try_from`
+        let setup = self.conn.opts().setup().to_vec();
+        for stmt in CONNECTION_SETUP_QUERIES {
+            if !setup.iter().any(|s| s == stmt) {
+                diesel::sql_query(*stmt).execute(self).await?;
+            }
+        }
+        Ok(())
+    }
+
+    fn is_broken(&mut self) -> bool {
+        use crate::TransactionManager;
+
+        self.is_closed || Self::TransactionManager::is_broken_transaction_manager(self)
+    }
+}
 
 #[cfg(test)]
 mod tests {
@@ -0,0 +1,193 @@
+use super::error_helper::ErrorHelper;
+use super::row::MysqlRow;
+use super::AsyncMysqlConnection;
+use diesel::connection::statement_cache::MaybeCached;
+use diesel::deserialize::QueryableByName;
+use diesel::mysql::Mysql;
+use diesel::query_builder::{QueryFragment, QueryId};
+use diesel::QueryResult;
+use mysql_async::prelude::Queryable;
+
+/// The results of a stored procedure called via
+/// [`AsyncMysqlConnection::call_procedure`]
+pub struct ProcedureResults {
+    result_sets: Vec<Vec<MysqlRow>>,
+    out_params: Option<MysqlRow>,
+}
+
+impl ProcedureResults {
+    /// The number of result sets returned by the procedure
+    ///
+    /// This does not include the final status result MySQL sends
+    /// for each `CALL` statement.
+    pub fn result_set_count(&self) -> usize {
+        self.result_sets.len()
+    }
+
+    /// Deserialize the rows of the result set at `index`
+    ///
+    /// Result sets are numbered in the order the procedure returned them,
+    /// starting at 0. Columns are matched by name, like for
+    /// [`diesel::sql_query`].
+    pub fn load<U>(&self, index: usize) -> QueryResult<Vec<U>>
+    where
+        U: QueryableByName<Mysql>,
+    {
+        let rows = self.result_sets.get(index).ok_or_else(|| {
+            diesel::result::Error::DeserializationError(
+                format!("The procedure did not return a result set with index {index}").into(),
+            )
+        })?;
+        rows.iter()
+            .map(|row| U::build(row).map_err(diesel::result::Error::DeserializationError))
+            .collect()
+    }
+
+    /// Deserialize the values of the OUT parameters requested in
+    /// [`AsyncMysqlConnection::call_procedure`]
+    ///
+    /// Each parameter is returned as column named after the user variable,
+    /// without the leading `@`.
+    pub fn out_params<U>(&self) -> QueryResult<U>
+    where
+        U: QueryableByName<Mysql>,
+    {
+        let row = self.out_params.as_ref().ok_or_else(|| {
+            diesel::result::Error::DeserializationError(
+                "No OUT parameters were requested for this procedure call".into(),
+            )
+        })?;
+        U::build(row).map_err(diesel::result::Error::DeserializationError)
+    }
+}
+
+impl std::fmt::Debug for ProcedureResults {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ProcedureResults")
+            .field(
+                "result_sets",
+                &self.result_sets.iter().map(Vec::len).collect::<Vec<_>>(),
+            )
+            .field("out_params", &self.out_params.is_some())
+            .finish()
+    }
+}
+
+impl AsyncMysqlConnection {
+    /// Call a stored procedure and collect all result sets it returns
+    ///
+    /// `call` is usually a [`diesel::sql_query`] containing a `CALL` statement.
+    /// Procedures can return any number of result sets, which cannot be expressed
+    /// by [`RunQueryDsl::load`](crate::RunQueryDsl::load).
+    ///
+    /// MySQL does not return OUT parameters of prepared `CALL` statements
+    /// directly. Instead pass user variables (e.g. `@total`) as OUT parameters
+    /// and list their names (without the `@`) in `out_params`. Their values are
+    /// selected after the call and are available via
+    /// [`ProcedureResults::out_params`].
+    ///
+    /// ```rust
+    /// # include!("../doctest_setup.rs");
+    /// use diesel::sql_types::{BigInt, Integer, Text};
+    /// use diesel_async::SimpleAsyncConnection;
+    /// #
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// #     run_test().await.unwrap();
+    /// # }
+    /// #
+    /// #[derive(QueryableByName)]
+    /// struct Name {
+    ///     #[diesel(sql_type = Text)]
+    ///     name: String,
+    /// }
+    ///
+    /// #[derive(QueryableByName)]
+    /// struct Total {
+    ///     #[diesel(sql_type = BigInt)]
+    ///     total: i64,
+    /// }
+    ///
+    /// # async fn run_test() -> QueryResult<()> {
+    /// #     let conn = &mut connection_no_data().await;
+    /// conn.batch_execute(
+    ///     "CREATE PROCEDURE IF NOT EXISTS diesel_async_doctest_names(IN n INTEGER, OUT total BIGINT)
+    ///      BEGIN
+    ///          SELECT 'Sean' AS name;
+    ///          SELECT 'Tess' AS name UNION ALL SELECT 'Jim';
+    ///          SET total = n * 3;
+    ///      END",
+    /// )
+    /// .await?;
+    ///
+    /// let results = conn
+    ///     .call_procedure(
+    ///         diesel::sql_query("CALL diesel_async_doctest_names(?, @total)").bind::<Integer, _>(1),
+    ///         &["total"],
+    ///     )
+    ///     .await?;
+    /// assert_eq!(2, results.result_set_count());
+    /// assert_eq!("Sean", results.load::<Name>(0)?[0].name);
+    /// assert_eq!(2, results.load::<Name>(1)?.len());
+    /// assert_eq!(3, results.out_params::<Total>()?.total);
+    /// #     Ok(())
+    /// # }
+    /// ```
+    pub async fn call_procedure<T>(
+        &mut self,
+        call: T,
+        out_params: &[&str],
+    ) -> QueryResult<ProcedureResults>
+    where
+        T: QueryFragment<Mysql> + QueryId + Send,
+    {
+        let result_sets = self.load_result_sets(call).await?;
+        let out_params = if out_params.is_empty() {
+            None
+        } else {
+            let select = out_params
+                .iter()
+                .map(|name| {
+                    let name = format!("`{}`", name.replace('`', "``"));
+                    format!("@{name} AS {name}")
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+            self.load_result_sets(diesel::sql_query(format!("SELECT {select}")))
+                .await?
+                .into_iter()
+                .flatten()
+                .next()
+        };
+        Ok(ProcedureResults {
+            result_sets,
+            out_params,
+        })
+    }
+
+    async fn load_result_sets<T>(&mut self, query: T) -> QueryResult<Vec<Vec<MysqlRow>>>
+    where
+        T: QueryFragment<Mysql> + QueryId + Send,
+    {
+        self.with_prepared_statement(query, |conn, stmt, binds| async move {
+            let mut result = conn.exec_iter(&*stmt, binds).await.map_err(ErrorHelper)?;
+            let mut result_sets = Vec::new();
+            while !result.is_empty() {
+                // The status result terminating a `CALL` does not contain any columns
+                let has_columns = !result.columns_ref().is_empty();
+                let rows = result.collect::<MysqlRow>().await.map_err(ErrorHelper)?;
+                if has_columns {
+                    result_sets.push(rows);
+                }
+            }
+            drop(result);
+            // Non-cached statements need to be closed explicitly,
+            // see `AsyncConnection::execute_returning_count`
+            if let MaybeCached::CannotCache(stmt) = stmt {
+                conn.close(stmt).await.map_err(ErrorHelper)?;
+            }
+            Ok(result_sets)
+        })
+        .await
+    }
+}
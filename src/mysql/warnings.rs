@@ -0,0 +1,74 @@
+use super::error_helper::ErrorHelper;
+use super::{update_connection_status, AsyncMysqlConnection};
+use diesel::QueryResult;
+use mysql_async::prelude::Queryable;
+
+/// A warning reported by the server for a statement, as returned by `SHOW WARNINGS`
+///
+/// See [`AsyncMysqlConnection::take_warnings`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MysqlWarning {
+    /// The severity of the warning, one of `Note`, `Warning` or `Error`
+    pub level: String,
+    /// The MySQL error code of the warning, for example `1265` for truncated data
+    pub code: u32,
+    /// The message describing the warning
+    pub message: String,
+}
+
+impl AsyncMysqlConnection {
+    /// The number of warnings the server reported for the last statement
+    /// executed on this connection
+    pub fn warning_count(&self) -> u16 {
+        self.conn.get_warnings()
+    }
+
+    /// Fetch the warnings the server reported for the last statement
+    /// executed on this connection
+    ///
+    /// MySQL only keeps the warnings of the most recent statement, so this needs
+    /// to be called directly after the statement of interest. No query is sent
+    /// to the server if [`AsyncMysqlConnection::warning_count`] is 0. Note that
+    /// the server limits the number of stored warnings via the `max_error_count`
+    /// system variable.
+    ///
+    /// ```rust
+    /// # include!("../doctest_setup.rs");
+    /// #
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// #     run_test().await.unwrap();
+    /// # }
+    /// #
+    /// # async fn run_test() -> QueryResult<()> {
+    /// #     use diesel_async::RunQueryDsl;
+    /// #     let conn = &mut establish_connection().await;
+    /// diesel::sql_query("SELECT CAST('12abc' AS SIGNED)")
+    ///     .execute(conn)
+    ///     .await?;
+    /// let warnings = conn.take_warnings().await?;
+    /// assert_eq!(1, warnings.len());
+    /// assert_eq!(1292, warnings[0].code);
+    /// #     Ok(())
+    /// # }
+    /// ```
+    pub async fn take_warnings(&mut self) -> QueryResult<Vec<MysqlWarning>> {
+        if self.conn.get_warnings() == 0 {
+            return Ok(Vec::new());
+        }
+        let res = self
+            .conn
+            .query::<(String, u32, String), _>("SHOW WARNINGS")
+            .await
+            .map_err(|e| ErrorHelper(e).into());
+        let warnings = update_connection_status(res, &mut self.is_closed)?;
+        Ok(warnings
+            .into_iter()
+            .map(|(level, code, message)| MysqlWarning {
+                level,
+                code,
+                message,
+            })
+            .collect())
+    }
+}
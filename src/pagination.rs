@@ -0,0 +1,261 @@
+//! Helpers to load large result sets page by page
+
+use crate::methods::LoadQuery;
+use crate::{AsyncConnection, RunQueryDsl};
+use diesel::backend::Backend;
+use diesel::query_builder::{AstPass, Query, QueryFragment, QueryId};
+use diesel::serialize::ToSql;
+use diesel::sql_types::{BigInt, HasSqlType};
+use diesel::QueryResult;
+use futures_util::{stream, Stream, TryStreamExt};
+
+const DEFAULT_PER_PAGE: i64 = 10;
+
+/// Extension trait to split the result of a query into pages
+///
+/// This trait is implemented for all queries.
+pub trait Paginate: Sized {
+    /// Select the given page of the query result
+    ///
+    /// Pages are numbered starting at 1. By default each page contains
+    /// 10 rows, use [`Paginated::per_page`] to change that. Executing the
+    /// query fails with [`diesel::result::Error::QueryBuilderError`] if the
+    /// page or the number of rows per page is less than 1, or if the offset
+    /// of the page does not fit into an `i64`.
+    ///
+    /// Use [`Paginated::load_and_count_total`] to load the page together
+    /// with the total number of rows returned by the underlying query.
+    fn paginate(self, page: i64) -> Paginated<Self>;
+}
+
+impl<T: Query> Paginate for T {
+    fn paginate(self, page: i64) -> Paginated<Self> {
+        Paginated {
+            query: self,
+            page,
+            per_page: DEFAULT_PER_PAGE,
+            offset: offset(page, DEFAULT_PER_PAGE),
+        }
+    }
+}
+
+/// A query limited to a single page of results
+///
+/// This type is returned by [`Paginate::paginate`]. The generated SQL
+/// wraps the underlying query into a subquery and selects the
+/// requested page via `LIMIT` and `OFFSET`. Additionally each row contains
+/// the total number of rows of the underlying query, which is computed via
+/// a windowed `COUNT(*) OVER ()` in the same query.
+#[derive(Debug, Clone, Copy, QueryId)]
+pub struct Paginated<T> {
+    query: T,
+    page: i64,
+    per_page: i64,
+    // `None` for invalid pages, which are reported once the query is built
+    offset: Option<i64>,
+}
+
+fn offset(page: i64, per_page: i64) -> Option<i64> {
+    if page < 1 || per_page < 1 {
+        return None;
+    }
+    (page - 1).checked_mul(per_page)
+}
+
+impl<T> Paginated<T> {
+    /// Set the number of rows per page
+    pub fn per_page(self, per_page: i64) -> Self {
+        Paginated {
+            per_page,
+            offset: offset(self.page, per_page),
+            ..self
+        }
+    }
+
+    /// Load the selected page and the total number of rows of the
+    /// underlying query in one round trip
+    ///
+    /// The total count is part of each returned row, so it is reported as 0
+    /// if the selected page does not contain any rows. This includes pages
+    /// past the last page, even though the underlying query returns rows.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # include!("doctest_setup.rs");
+    /// use diesel_async::pagination::Paginate;
+    ///
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// #     run_test().await.unwrap();
+    /// # }
+    /// #
+    /// # async fn run_test() -> QueryResult<()> {
+    /// #     use schema::users;
+    /// #     let conn = &mut establish_connection().await;
+    /// let (names, total) = users::table
+    ///     .select(users::name)
+    ///     .order_by(users::id)
+    ///     .paginate(2)
+    ///     .per_page(1)
+    ///     .load_and_count_total::<String, _>(conn)
+    ///     .await?;
+    ///
+    /// assert_eq!(vec![String::from("Tess")], names);
+    /// assert_eq!(2, total);
+    /// #     Ok(())
+    /// # }
+    /// ```
+    pub async fn load_and_count_total<'query, U, Conn>(
+        self,
+        conn: &mut Conn,
+    ) -> QueryResult<(Vec<U>, i64)>
+    where
+        Self: LoadQuery<'query, Conn, (U, i64)> + 'query,
+        Conn: AsyncConnection,
+        U: Send,
+    {
+        let results = self.load::<(U, i64)>(conn).await?;
+        let total = results.iter().map(|(_, total)| *total).next().unwrap_or(0);
+        let records = results.into_iter().map(|(record, _)| record).collect();
+        Ok((records, total))
+    }
+}
+
+impl<T: Query> Query for Paginated<T> {
+    type SqlType = (T::SqlType, BigInt);
+}
+
+impl<T, DB> QueryFragment<DB> for Paginated<T>
+where
+    DB: Backend + HasSqlType<BigInt>,
+    T: QueryFragment<DB>,
+    i64: ToSql<BigInt, DB>,
+{
+    fn walk_ast<'b>(&'b self, mut out: AstPass<'_, 'b, DB>) -> QueryResult<()> {
+        let Some(ref offset) = self.offset else {
+            let message = if self.page < 1 || self.per_page < 1 {
+                format!(
+                    "Invalid page {} with {} rows per page, both need to be at least 1",
+                    self.page, self.per_page
+                )
+            } else {
+                format!(
+                    "The offset of page {} with {} rows per page overflows",
+                    self.page, self.per_page
+                )
+            };
+            return Err(diesel::result::Error::QueryBuilderError(message.into()));
+        };
+        out.push_sql("SELECT *, COUNT(*) OVER () FROM (");
+        self.query.walk_ast(out.reborrow())?;
+        out.push_sql(") AS paginated_query LIMIT ");
+        out.push_bind_param::<BigInt, _>(&self.per_page)?;
+        out.push_sql(" OFFSET ");
+        out.push_bind_param::<BigInt, _>(offset)?;
+        Ok(())
+    }
+}
+
+/// Loads all rows of a query as continuous stream by issuing a series
+/// of keyset paginated queries
+///
+/// Keyset pagination (sometimes called "seek method") selects the next page
+/// by filtering for rows with a key larger than the key of the last row of
+/// the previous page instead of using an `OFFSET`. This allows to scan through
+/// large tables without keeping a cursor open and without the cost of skipping
+/// already seen rows.
+///
+/// `build_page` is called for each page with the key of the last row of the previous
+/// page (or `None` for the first page) and with `page_size`. It is expected to return
+/// a query that
+///
+/// * filters for rows with a key greater than the given key,
+/// * is ordered by that key and
+/// * is limited to `page_size` rows.
+///
+/// `key_of` extracts the key from a loaded row. Composite keys can be expressed
+/// by returning a tuple here and by using a row value comparison in the query.
+///
+/// The stream ends as soon as a page contains less than `page_size` rows.
+///
+/// # Example
+///
+/// ```rust
+/// # include!("doctest_setup.rs");
+/// use diesel_async::pagination::keyset_stream;
+/// use futures_util::TryStreamExt;
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// #     run_test().await.unwrap();
+/// # }
+/// #
+/// # async fn run_test() -> QueryResult<()> {
+/// #     use schema::users;
+/// #     let conn = &mut establish_connection().await;
+/// let all_users = keyset_stream(
+///     conn,
+///     1,
+///     |last_id, page_size| {
+///         users::table
+///             .filter(users::id.gt(last_id.unwrap_or(0)))
+///             .order_by(users::id)
+///             .limit(page_size)
+///     },
+///     |(id, _): &(i32, String)| *id,
+/// )
+/// .try_collect::<Vec<_>>()
+/// .await?;
+///
+/// assert_eq!(
+///     vec![(1, String::from("Sean")), (2, String::from("Tess"))],
+///     all_users
+/// );
+/// #     Ok(())
+/// # }
+/// ```
+pub fn keyset_stream<'conn, Conn, Q, U, K>(
+    conn: &'conn mut Conn,
+    page_size: i64,
+    build_page: impl FnMut(Option<K>, i64) -> Q + Send + 'conn,
+    key_of: impl Fn(&U) -> K + Send + 'conn,
+) -> impl Stream<Item = QueryResult<U>> + Send + 'conn
+where
+    Conn: AsyncConnection,
+    Q: LoadQuery<'static, Conn, U> + 'static,
+    U: Send + 'conn,
+    K: Send + 'conn,
+{
+    struct State<'conn, Conn, F, KF, K> {
+        conn: &'conn mut Conn,
+        build_page: F,
+        key_of: KF,
+        last_key: Option<K>,
+        done: bool,
+    }
+
+    let state = State {
+        conn,
+        build_page,
+        key_of,
+        last_key: None,
+        done: false,
+    };
+
+    stream::try_unfold(state, move |mut state| async move {
+        if state.done {
+            return QueryResult::Ok(None);
+        }
+        let page = (state.build_page)(state.last_key.take(), page_size)
+            .load::<U>(state.conn)
+            .await?;
+        state.done = (page.len() as i64) < page_size;
+        match page.last() {
+            Some(last) => state.last_key = Some((state.key_of)(last)),
+            None => return Ok(None),
+        }
+        Ok(Some((stream::iter(page.into_iter().map(Ok)), state)))
+    })
+    .try_flatten()
+}
@@ -0,0 +1,183 @@
+use super::AsyncPgConnection;
+use crate::RunQueryDsl;
+use diesel::sql_types::{BigInt, Bool};
+use diesel::QueryResult;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex};
+
+/// A session level PostgreSQL advisory lock
+///
+/// This guard is returned by [`AsyncPgConnection::advisory_lock`] and
+/// [`AsyncPgConnection::try_advisory_lock`]. The lock is held as long as the
+/// guard is alive. The guard dereferences to the underlying connection,
+/// so it can be used to execute queries while holding the lock.
+///
+/// Use [`AdvisoryLock::release`] to release the lock and to wait until
+/// the database confirmed that. If the guard is dropped instead, the lock
+/// is released right before the next statement executed on the connection.
+/// Inside of an aborted transaction the lock is released once the
+/// transaction was rolled back.
+///
+/// See [the PostgreSQL documentation][pg-docs] for details about advisory locks.
+///
+/// [pg-docs]: https://www.postgresql.org/docs/current/explicit-locking.html#ADVISORY-LOCKS
+#[must_use = "The advisory lock is released as soon as the guard is dropped"]
+pub struct AdvisoryLock<'a> {
+    connection: &'a mut AsyncPgConnection,
+    key: i64,
+    released: bool,
+}
+
+impl<'a> AdvisoryLock<'a> {
+    /// The key of the held lock
+    pub fn key(&self) -> i64 {
+        self.key
+    }
+
+    /// Release the lock
+    ///
+    /// Fails with [`diesel::result::Error::QueryBuilderError`] wrapping an
+    /// [`AdvisoryLockNotHeld`] if the session did not hold the lock anymore,
+    /// for example because it was released via `pg_advisory_unlock` already.
+    pub async fn release(mut self) -> QueryResult<()> {
+        self.released = true;
+        let released = diesel::select(
+            diesel::dsl::sql::<Bool>("pg_advisory_unlock(")
+                .bind::<BigInt, _>(self.key)
+                .sql(")"),
+        )
+        .get_result::<bool>(&mut *self.connection)
+        .await?;
+        if !released {
+            return Err(diesel::result::Error::QueryBuilderError(Box::new(
+                AdvisoryLockNotHeld { key: self.key },
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl Deref for AdvisoryLock<'_> {
+    type Target = AsyncPgConnection;
+
+    fn deref(&self) -> &Self::Target {
+        self.connection
+    }
+}
+
+impl DerefMut for AdvisoryLock<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.connection
+    }
+}
+
+impl Drop for AdvisoryLock<'_> {
+    fn drop(&mut self) {
+        if !self.released {
+            self.connection
+                .advisory_unlocks
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .push(self.key);
+        }
+    }
+}
+
+/// The error of [`AdvisoryLock::release`] if the session
+/// did not hold the lock anymore
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AdvisoryLockNotHeld {
+    key: i64,
+}
+
+impl AdvisoryLockNotHeld {
+    /// The key of the advisory lock which was not held
+    pub fn key(&self) -> i64 {
+        self.key
+    }
+}
+
+impl std::fmt::Display for AdvisoryLockNotHeld {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "The advisory lock {} was not held", self.key)
+    }
+}
+
+impl std::error::Error for AdvisoryLockNotHeld {}
+
+// The keys of advisory locks whose guard was dropped. Query futures may
+// outlive the borrow of the connection, so failed unlocks are queued
+// again via this shared queue instead of the connection itself
+pub(super) type UnlockQueue = Arc<Mutex<Vec<i64>>>;
+
+// Releases the advisory locks of dropped guards
+pub(super) async fn unlock_dropped_locks(client: &tokio_postgres::Client, queue: &UnlockQueue) {
+    let keys = std::mem::take(&mut *queue.lock().unwrap_or_else(|e| e.into_inner()));
+    for key in keys {
+        // Fails inside of an aborted transaction, so the unlock
+        // is retried before the next statement
+        if client
+            .execute("SELECT pg_advisory_unlock($1)", &[&key])
+            .await
+            .is_err()
+        {
+            queue.lock().unwrap_or_else(|e| e.into_inner()).push(key);
+        }
+    }
+}
+
+impl AsyncPgConnection {
+    /// Acquire the session level advisory lock identified by `key`
+    ///
+    /// This waits until the lock becomes available. The lock is released
+    /// once the returned guard is dropped or [`AdvisoryLock::release`] is called.
+    ///
+    /// ```rust
+    /// # include!("../doctest_setup.rs");
+    /// #
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// #     run_test().await.unwrap();
+    /// # }
+    /// #
+    /// # async fn run_test() -> QueryResult<()> {
+    /// #     use diesel_async::RunQueryDsl;
+    /// #     use schema::users;
+    /// #     let conn = &mut establish_connection().await;
+    /// let mut lock = conn.advisory_lock(42).await?;
+    /// let count = users::table.count().get_result::<i64>(&mut *lock).await?;
+    /// assert_eq!(2, count);
+    /// lock.release().await?;
+    /// #     Ok(())
+    /// # }
+    /// ```
+    pub async fn advisory_lock(&mut self, key: i64) -> QueryResult<AdvisoryLock<'_>> {
+        diesel::sql_query("SELECT pg_advisory_lock($1)")
+            .bind::<BigInt, _>(key)
+            .execute(self)
+            .await?;
+        Ok(AdvisoryLock {
+            connection: self,
+            key,
+            released: false,
+        })
+    }
+
+    /// Try to acquire the session level advisory lock identified by `key`
+    ///
+    /// Returns `None` if the lock is currently held by another session.
+    pub async fn try_advisory_lock(&mut self, key: i64) -> QueryResult<Option<AdvisoryLock<'_>>> {
+        let acquired = diesel::select(
+            diesel::dsl::sql::<Bool>("pg_try_advisory_lock(")
+                .bind::<BigInt, _>(key)
+                .sql(")"),
+        )
+        .get_result::<bool>(self)
+        .await?;
+        Ok(acquired.then(|| AdvisoryLock {
+            connection: self,
+            key,
+            released: false,
+        }))
+    }
+}
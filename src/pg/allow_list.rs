@@ -0,0 +1,215 @@
+use super::interceptor::Fnv1aHasher;
+use super::sql_scanner::{self, Token};
+use super::{InterceptedQuery, QueryInterceptor};
+use diesel::pg::{Pg, PgQueryBuilder};
+use diesel::query_builder::{QueryBuilder, QueryFragment, QueryId};
+use diesel::QueryResult;
+use std::any::TypeId;
+use std::collections::HashSet;
+use std::hash::Hasher;
+
+/// A [`QueryInterceptor`] rejecting all queries that were not
+/// explicitly allowed
+///
+/// Queries are allowed either by their [`QueryId`] or by the fingerprint of
+/// their SQL, see [`StatementAllowList::fingerprint`]. Rejected queries fail
+/// with a [`diesel::result::Error::QueryBuilderError`] wrapping a
+/// [`PolicyViolation`].
+///
+/// The statements controlling transactions issued by the transaction manager
+/// and the [`TransactionBuilder`](super::TransactionBuilder), like `BEGIN`,
+/// `COMMIT`, `ROLLBACK` or `SAVEPOINT diesel_savepoint_1`, as well as empty
+/// statements are always allowed, so that transactions and connection checks
+/// keep working. Other statements starting with these keywords, like
+/// `COMMIT PREPARED`, need to be allowed explicitly.
+///
+/// ```rust
+/// # include!("../doctest_setup.rs");
+/// use diesel_async::pg::{PolicyViolation, StatementAllowList};
+/// use std::sync::Arc;
+/// #
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// #     run_test().await.unwrap();
+/// # }
+/// #
+/// # async fn run_test() -> QueryResult<()> {
+/// # use diesel_async::RunQueryDsl;
+/// #     use schema::users;
+/// #     let conn = &mut establish_connection().await;
+/// let allow_list = StatementAllowList::new().allow_query(&users::table.select(users::name))?;
+/// conn.add_interceptor(Arc::new(allow_list));
+///
+/// let names = users::table.select(users::name).load::<String>(conn).await?;
+/// # assert_eq!(2, names.len());
+/// let Err(diesel::result::Error::QueryBuilderError(e)) =
+///     diesel::delete(users::table).execute(conn).await
+/// else {
+///     panic!("The query is not allowed");
+/// };
+/// assert!(e.is::<PolicyViolation>());
+/// #     Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct StatementAllowList {
+    query_ids: HashSet<TypeId>,
+    fingerprints: HashSet<u64>,
+}
+
+impl StatementAllowList {
+    /// Construct an allow-list that rejects all queries
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allow queries of the same type as `query`, as well as all
+    /// queries with the same SQL
+    pub fn allow_query<T>(mut self, query: &T) -> QueryResult<Self>
+    where
+        T: QueryFragment<Pg> + QueryId,
+    {
+        let mut query_builder = PgQueryBuilder::default();
+        query.to_sql(&mut query_builder, &Pg)?;
+        self.fingerprints
+            .insert(Self::fingerprint(&query_builder.finish()));
+        if let Some(query_id) = T::query_id() {
+            self.query_ids.insert(query_id);
+        }
+        Ok(self)
+    }
+
+    /// Allow queries with the given SQL
+    pub fn allow_sql(self, sql: &str) -> Self {
+        self.allow_fingerprint(Self::fingerprint(sql))
+    }
+
+    /// Allow queries with the given fingerprint, see
+    /// [`StatementAllowList::fingerprint`]
+    pub fn allow_fingerprint(mut self, fingerprint: u64) -> Self {
+        self.fingerprints.insert(fingerprint);
+        self
+    }
+
+    /// The fingerprint of the given SQL
+    ///
+    /// The fingerprint is a 64 bit FNV-1a hash of the SQL, which is stable
+    /// across versions of this crate, so it can be stored in configuration
+    /// files. Queries built by diesel use the SQL as rendered for PostgreSQL,
+    /// with bind parameters as `$1`, `$2` and so on.
+    pub fn fingerprint(sql: &str) -> u64 {
+        let mut hasher = Fnv1aHasher::default();
+        hasher.write(sql.as_bytes());
+        hasher.finish()
+    }
+
+    fn is_allowed(&self, query: &InterceptedQuery) -> bool {
+        if query
+            .query_id()
+            .is_some_and(|query_id| self.query_ids.contains(&query_id))
+        {
+            return true;
+        }
+        is_transaction_statement(query)
+            || self.fingerprints.contains(&Self::fingerprint(query.sql()))
+    }
+}
+
+impl QueryInterceptor for StatementAllowList {
+    fn before_query(&self, query: &mut InterceptedQuery) -> QueryResult<()> {
+        if self.is_allowed(query) {
+            return Ok(());
+        }
+        Err(diesel::result::Error::QueryBuilderError(Box::new(
+            PolicyViolation {
+                sql: query.sql().to_owned(),
+                fingerprint: Self::fingerprint(query.sql()),
+            },
+        )))
+    }
+}
+
+// Whether the query is one of the statements controlling a transaction
+// that are issued by the transaction manager and the `TransactionBuilder`
+fn is_transaction_statement(query: &InterceptedQuery) -> bool {
+    const BEGIN_OPTIONS: [&str; 10] = [
+        "ISOLATION",
+        "LEVEL",
+        "READ",
+        "COMMITTED",
+        "REPEATABLE",
+        "SERIALIZABLE",
+        "ONLY",
+        "WRITE",
+        "NOT",
+        "DEFERRABLE",
+    ];
+
+    if !query.binds().is_empty() {
+        return false;
+    }
+    // transaction statements only consist of keywords and savepoint names,
+    // which also rejects multiple statements
+    let Some(words) = sql_scanner::tokens(query.sql())
+        .into_iter()
+        .map(|token| match token {
+            Token::Word(word) => Some(word.to_ascii_uppercase()),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>()
+    else {
+        return false;
+    };
+    // two phase commits operate on transactions of other sessions
+    if words.iter().any(|word| word == "PREPARED") {
+        return false;
+    }
+    match words.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        [] | ["BEGIN"] | ["COMMIT"] | ["ROLLBACK"] => true,
+        ["BEGIN", "TRANSACTION", ref options @ ..] => {
+            options.iter().all(|option| BEGIN_OPTIONS.contains(option))
+        }
+        ["SAVEPOINT", name]
+        | ["RELEASE", "SAVEPOINT", name]
+        | ["ROLLBACK", "TO", "SAVEPOINT", name] => is_savepoint_name(name),
+        _ => false,
+    }
+}
+
+// Savepoint names are validated by the transaction manager in the same way
+fn is_savepoint_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// The error of queries rejected by a [`StatementAllowList`]
+#[derive(Debug, Clone)]
+pub struct PolicyViolation {
+    sql: String,
+    fingerprint: u64,
+}
+
+impl PolicyViolation {
+    /// The SQL of the rejected query
+    pub fn sql(&self) -> &str {
+        &self.sql
+    }
+
+    /// The fingerprint of the rejected query, see
+    /// [`StatementAllowList::fingerprint`]
+    pub fn fingerprint(&self) -> u64 {
+        self.fingerprint
+    }
+}
+
+impl std::fmt::Display for PolicyViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "The query `{}` (fingerprint {:#018x}) is not allowed",
+            self.sql, self.fingerprint
+        )
+    }
+}
+
+impl std::error::Error for PolicyViolation {}
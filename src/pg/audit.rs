@@ -0,0 +1,191 @@
+use super::interceptor::Fnv1aHasher;
+use super::{InterceptedQuery, QueryInterceptor, QueryOutcome};
+use std::future::Future;
+use std::hash::Hasher;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+tokio::task_local! {
+    static AUDIT_CONTEXT: AuditContext;
+}
+
+/// The user and tenant on whose behalf queries are executed,
+/// reported to the [`AuditSink`] of an [`AuditLogger`]
+///
+/// The context applies to all queries executed within
+/// [`AuditContext::scope`], independent of the connection they
+/// are executed on.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AuditContext {
+    user: Option<String>,
+    tenant: Option<String>,
+}
+
+impl AuditContext {
+    /// Construct an empty context
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the user executing the queries
+    pub fn with_user(mut self, user: impl Into<String>) -> Self {
+        self.user = Some(user.into());
+        self
+    }
+
+    /// Set the tenant the queries are executed for
+    pub fn with_tenant(mut self, tenant: impl Into<String>) -> Self {
+        self.tenant = Some(tenant.into());
+        self
+    }
+
+    /// The user executing the queries
+    pub fn user(&self) -> Option<&str> {
+        self.user.as_deref()
+    }
+
+    /// The tenant the queries are executed for
+    pub fn tenant(&self) -> Option<&str> {
+        self.tenant.as_deref()
+    }
+
+    /// Run `future` with this context, all queries executed by
+    /// the future are audited with this context
+    pub async fn scope<F: Future>(self, future: F) -> F::Output {
+        AUDIT_CONTEXT.scope(self, future).await
+    }
+}
+
+/// A single audited query, see [`AuditSink`]
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct AuditRecord<'a> {
+    /// The SQL of the query as it was executed
+    pub sql: &'a str,
+    /// A fingerprint of the bind values of the query
+    ///
+    /// This allows to correlate queries with the same bind values without
+    /// storing the values themselves. The fingerprint is a 64 bit FNV-1a hash,
+    /// which is stable across versions of this crate.
+    pub bind_fingerprint: u64,
+    /// The context set via [`AuditContext::scope`], if any
+    pub context: Option<&'a AuditContext>,
+    /// When the execution of the query started
+    pub started_at: SystemTime,
+    /// The time the execution took, see [`QueryOutcome::duration`]
+    pub duration: Duration,
+    /// The number of affected rows if known, or the error the query failed with
+    pub result: Result<Option<usize>, &'a diesel::result::Error>,
+}
+
+/// The destination of the records of an [`AuditLogger`]
+///
+/// This is implemented for closures taking an [`AuditRecord`]. Records are
+/// passed to the sink inline while executing queries, so sinks writing to
+/// slow destinations should buffer the records, for example via a channel.
+pub trait AuditSink: Send + Sync {
+    /// Store the given record
+    fn record(&self, record: &AuditRecord<'_>);
+}
+
+impl<F> AuditSink for F
+where
+    F: Fn(&AuditRecord<'_>) + Send + Sync,
+{
+    fn record(&self, record: &AuditRecord<'_>) {
+        self(record)
+    }
+}
+
+/// A [`QueryInterceptor`] recording all executed queries
+/// to an [`AuditSink`]
+///
+/// Add the logger before other interceptors to also record
+/// queries rejected by them.
+///
+/// ```rust
+/// # include!("../doctest_setup.rs");
+/// use diesel_async::pg::{AuditContext, AuditLogger, AuditRecord};
+/// use std::sync::Arc;
+/// #
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// #     run_test().await.unwrap();
+/// # }
+/// #
+/// # async fn run_test() -> QueryResult<()> {
+/// # use diesel_async::RunQueryDsl;
+/// #     use schema::users;
+/// #     let conn = &mut establish_connection().await;
+/// conn.add_interceptor(Arc::new(AuditLogger::new(|record: &AuditRecord<'_>| {
+///     let user = record.context.and_then(|context| context.user());
+///     println!("{user:?} executed `{}` in {:?}", record.sql, record.duration);
+/// })));
+///
+/// let context = AuditContext::new().with_user("alice").with_tenant("acme");
+/// let count = context
+///     .scope(users::table.count().get_result::<i64>(conn))
+///     .await?;
+/// #     assert_eq!(2, count);
+/// #     Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct AuditLogger {
+    sink: Arc<dyn AuditSink>,
+}
+
+impl std::fmt::Debug for AuditLogger {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AuditLogger").finish_non_exhaustive()
+    }
+}
+
+impl AuditLogger {
+    /// Construct a logger writing to the given sink
+    pub fn new(sink: impl AuditSink + 'static) -> Self {
+        Self {
+            sink: Arc::new(sink),
+        }
+    }
+}
+
+impl QueryInterceptor for AuditLogger {
+    fn after_query(&self, query: &InterceptedQuery, outcome: &QueryOutcome<'_>) {
+        let bind_fingerprint = bind_fingerprint(query.binds());
+        let started_at = SystemTime::now() - outcome.duration;
+        let record = |context: Option<&AuditContext>| {
+            self.sink.record(&AuditRecord {
+                sql: query.sql(),
+                bind_fingerprint,
+                context,
+                started_at,
+                duration: outcome.duration,
+                result: outcome.result,
+            })
+        };
+        if AUDIT_CONTEXT
+            .try_with(|context| record(Some(context)))
+            .is_err()
+        {
+            record(None);
+        }
+    }
+}
+
+fn bind_fingerprint(binds: &[Option<Vec<u8>>]) -> u64 {
+    let mut hasher = Fnv1aHasher::default();
+    for bind in binds {
+        // prefix each value with its length, so that different
+        // splits of the same bytes result in different fingerprints
+        match bind {
+            Some(bind) => {
+                hasher.write_u8(1);
+                hasher.write(&(bind.len() as u64).to_le_bytes());
+                hasher.write(bind);
+            }
+            None => hasher.write_u8(0),
+        }
+    }
+    hasher.finish()
+}
@@ -0,0 +1,272 @@
+use super::interceptor::Fnv1aHasher;
+use super::{quote_identifier, AsyncPgConnection};
+use crate::SimpleAsyncConnection;
+use diesel::QueryResult;
+use futures_util::{future, Stream, StreamExt};
+use std::hash::Hasher;
+
+const NOTIFY_FUNCTION: &str = "diesel_async_change_feed_notify";
+
+/// The kind of a change reported by a change feed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TableChangeOperation {
+    /// A row was inserted
+    Insert,
+    /// A row was updated
+    Update,
+    /// A row was deleted
+    Delete,
+}
+
+/// A single row change reported by a change feed
+///
+/// See [`AsyncPgConnection::install_change_feed`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TableChange {
+    /// The kind of change
+    pub operation: TableChangeOperation,
+    /// The schema of the changed table
+    pub schema: String,
+    /// The name of the changed table
+    pub table: String,
+    /// The new row for inserts and updates, the old row for deletes,
+    /// as JSON object as produced by `row_to_json`
+    ///
+    /// Only contains the primary key columns if [`TableChange::key_only`] is set.
+    pub row: serde_json::Value,
+    /// Whether [`TableChange::row`] only contains the primary key columns of
+    /// the row, as the full row exceeded the payload limit of notifications
+    ///
+    /// The object is empty for tables without a primary key.
+    pub key_only: bool,
+}
+
+impl TableChange {
+    fn parse(payload: &str) -> QueryResult<Self> {
+        let mut parts = payload.splitn(5, '\t');
+        let (Some(operation), Some(schema), Some(table), Some(kind), Some(row)) = (
+            parts.next(),
+            parts.next(),
+            parts.next(),
+            parts.next(),
+            parts.next(),
+        ) else {
+            return Err(invalid_payload(payload));
+        };
+        let operation = match operation {
+            "INSERT" => TableChangeOperation::Insert,
+            "UPDATE" => TableChangeOperation::Update,
+            "DELETE" => TableChangeOperation::Delete,
+            _ => return Err(invalid_payload(payload)),
+        };
+        let key_only = match kind {
+            "row" => false,
+            "key" => true,
+            _ => return Err(invalid_payload(payload)),
+        };
+        let row = serde_json::from_str(row).map_err(|e| {
+            diesel::result::Error::DeserializationError(
+                format!("Invalid row in change feed payload: {e}").into(),
+            )
+        })?;
+        Ok(Self {
+            operation,
+            schema: schema.to_owned(),
+            table: table.to_owned(),
+            row,
+            key_only,
+        })
+    }
+}
+
+fn invalid_payload(payload: &str) -> diesel::result::Error {
+    diesel::result::Error::DeserializationError(
+        format!("Invalid change feed payload: `{payload}`").into(),
+    )
+}
+
+fn quote_literal(literal: &str) -> String {
+    format!("'{}'", literal.replace('\'', "''"))
+}
+
+// Quotes each part of a table name given as `table` or `schema.table`
+fn quote_table(table: &str) -> String {
+    table
+        .split('.')
+        .map(quote_identifier)
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+// PostgreSQL truncates identifiers to 63 bytes, so the names of long
+// channels are shortened and suffixed with a hash of the full channel
+// to keep the triggers of different channels apart
+fn trigger_name(channel: &str) -> String {
+    const MAX_IDENTIFIER_LENGTH: usize = 63;
+
+    let name = format!("diesel_async_change_feed_{channel}");
+    if name.len() <= MAX_IDENTIFIER_LENGTH {
+        return quote_identifier(&name);
+    }
+    // the name needs to be stable between processes
+    let mut hasher = Fnv1aHasher::default();
+    hasher.write(channel.as_bytes());
+    let suffix = format!("_{:016x}", hasher.finish());
+    let mut prefix_length = MAX_IDENTIFIER_LENGTH - suffix.len();
+    while !name.is_char_boundary(prefix_length) {
+        prefix_length -= 1;
+    }
+    quote_identifier(&format!("{}{suffix}", &name[..prefix_length]))
+}
+
+impl AsyncPgConnection {
+    /// Install triggers reporting all row changes of the given tables
+    /// as notifications on `channel`
+    ///
+    /// This creates a trigger function and a row level trigger for each table,
+    /// which emits a notification via `pg_notify` for each inserted, updated or
+    /// deleted row. Use [`AsyncPgConnection::listen_change_feed`] and
+    /// [`AsyncPgConnection::change_feed_stream`] to receive them.
+    /// Calling this function again for the same channel and table replaces the
+    /// existing trigger.
+    ///
+    /// Table names are quoted and therefore case sensitive. Use `schema.table`
+    /// to refer to a table outside of the search path. Notifications are only
+    /// sent once the modifying transaction commits. Their payload is limited
+    /// to 8000 bytes by PostgreSQL, so changes of larger rows only report the
+    /// primary key of the row, which is flagged via [`TableChange::key_only`].
+    /// For a full change data capture solution consider logical decoding via
+    /// [`AsyncPgConnection::logical_changes_stream`] instead.
+    pub async fn install_change_feed(&mut self, channel: &str, tables: &[&str]) -> QueryResult<()> {
+        let mut sql = format!(
+            "CREATE OR REPLACE FUNCTION {NOTIFY_FUNCTION}() RETURNS trigger AS $$
+             DECLARE
+                 changed_row RECORD;
+                 row_json json;
+                 payload text;
+             BEGIN
+                 IF TG_OP = 'DELETE' THEN
+                     changed_row := OLD;
+                 ELSE
+                     changed_row := NEW;
+                 END IF;
+                 row_json := row_to_json(changed_row);
+                 payload := concat_ws(
+                     E'\\t', TG_OP, TG_TABLE_SCHEMA, TG_TABLE_NAME, 'row', row_json::text
+                 );
+                 -- payloads of notifications are limited to 8000 bytes,
+                 -- larger rows are reported via their primary key
+                 IF octet_length(payload) >= 8000 THEN
+                     SELECT coalesce(json_object_agg(a.attname, row_json -> a.attname), '{{}}')
+                     INTO row_json
+                     FROM pg_index i
+                     JOIN pg_attribute a ON a.attrelid = i.indrelid AND a.attnum = ANY(i.indkey)
+                     WHERE i.indrelid = TG_RELID AND i.indisprimary;
+                     payload := concat_ws(
+                         E'\\t', TG_OP, TG_TABLE_SCHEMA, TG_TABLE_NAME, 'key', row_json::text
+                     );
+                     IF octet_length(payload) >= 8000 THEN
+                         payload := concat_ws(
+                             E'\\t', TG_OP, TG_TABLE_SCHEMA, TG_TABLE_NAME, 'key', '{{}}'
+                         );
+                     END IF;
+                 END IF;
+                 PERFORM pg_notify(TG_ARGV[0], payload);
+                 RETURN NULL;
+             END;
+             $$ LANGUAGE plpgsql;"
+        );
+        for table in tables {
+            sql += &format!(
+                "DROP TRIGGER IF EXISTS {trigger} ON {table};
+                 CREATE TRIGGER {trigger} AFTER INSERT OR UPDATE OR DELETE ON {table}
+                 FOR EACH ROW EXECUTE PROCEDURE {NOTIFY_FUNCTION}({channel});",
+                trigger = trigger_name(channel),
+                table = quote_table(table),
+                channel = quote_literal(channel),
+            );
+        }
+        self.batch_execute(&sql).await
+    }
+
+    /// Remove the triggers installed by [`AsyncPgConnection::install_change_feed`]
+    /// for the given channel from the given tables
+    pub async fn remove_change_feed(&mut self, channel: &str, tables: &[&str]) -> QueryResult<()> {
+        let sql = tables
+            .iter()
+            .map(|table| {
+                format!(
+                    "DROP TRIGGER IF EXISTS {trigger} ON {table};",
+                    trigger = trigger_name(channel),
+                    table = quote_table(table),
+                )
+            })
+            .collect::<String>();
+        self.batch_execute(&sql).await
+    }
+
+    /// Subscribe to the change feed reported on `channel`
+    ///
+    /// See [`AsyncPgConnection::install_change_feed`] for how to set up a change feed.
+    pub async fn listen_change_feed(&mut self, channel: &str) -> QueryResult<()> {
+        self.batch_execute(&format!("LISTEN {}", quote_identifier(channel)))
+            .await
+    }
+
+    /// Returns a stream of the changes received on `channel`
+    ///
+    /// This requires subscribing to the change feed first via
+    /// [`AsyncPgConnection::listen_change_feed`]. Notifications on other
+    /// channels this connection listens on are skipped.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # include!("../doctest_setup.rs");
+    /// use diesel_async::pg::TableChangeOperation;
+    /// use futures_util::StreamExt;
+    /// #
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// #     run_test().await.unwrap();
+    /// # }
+    /// #
+    /// # async fn run_test() -> QueryResult<()> {
+    /// #     use diesel_async::{RunQueryDsl, SimpleAsyncConnection};
+    /// #     use schema::users;
+    /// #     let conn = &mut connection_no_transaction().await;
+    /// #     conn.batch_execute(
+    /// #         "CREATE TEMPORARY TABLE users (id SERIAL PRIMARY KEY, name TEXT NOT NULL)"
+    /// #     ).await?;
+    /// conn.install_change_feed("user_changes", &["users"]).await?;
+    /// conn.listen_change_feed("user_changes").await?;
+    ///
+    /// diesel::insert_into(users::table)
+    ///     .values(users::name.eq("Ruby"))
+    ///     .execute(conn)
+    ///     .await?;
+    ///
+    /// let mut changes = conn.change_feed_stream("user_changes");
+    /// let change = changes.next().await.unwrap()?;
+    /// assert_eq!(TableChangeOperation::Insert, change.operation);
+    /// assert_eq!("users", change.table);
+    /// assert_eq!(serde_json::json!({"id": 1, "name": "Ruby"}), change.row);
+    /// #     Ok(())
+    /// # }
+    /// ```
+    pub fn change_feed_stream(
+        &mut self,
+        channel: &str,
+    ) -> impl Stream<Item = QueryResult<TableChange>> + Send + '_ {
+        let channel = channel.to_owned();
+        self.notifications_stream().filter_map(move |notification| {
+            future::ready(match notification {
+                Ok(notification) if notification.channel() == channel => {
+                    Some(TableChange::parse(notification.payload()))
+                }
+                Ok(_) => None,
+                Err(e) => Some(Err(e)),
+            })
+        })
+    }
+}
@@ -0,0 +1,170 @@
+use super::error_helper::ErrorHelper;
+use super::interceptor::QueryOutput;
+use super::row::PgRowStream;
+use super::serialize::PooledBinds;
+use super::{AsyncPgConnection, QueryStatement};
+use diesel::deserialize::FromSqlRow;
+use diesel::pg::Pg;
+use diesel::query_builder::{AsQuery, QueryFragment, QueryId};
+use diesel::QueryResult;
+use futures_util::TryStreamExt;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// The description of a single column of a query result
+///
+/// Returned by [`AsyncPgConnection::load_with_column_metadata`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResultColumn {
+    /// The name of the column
+    pub name: String,
+    /// The OID of the type of the column
+    pub type_oid: u32,
+    /// The name of the type of the column, for example `int4`
+    pub type_name: String,
+    /// The OID of the table the column stems from, if any
+    pub table_oid: Option<u32>,
+    /// The attribute number of the column within its table, if any
+    pub column_id: Option<i16>,
+    /// Whether the column may contain `NULL` values
+    ///
+    /// Only known for columns referring directly to a table column,
+    /// based on its `NOT NULL` constraint.
+    pub nullable: Option<bool>,
+}
+
+pub(super) struct RowsWithColumns {
+    rows: PgRowStream,
+    columns: Vec<ResultColumn>,
+}
+
+impl QueryOutput for RowsWithColumns {
+    fn affected_rows(&self) -> Option<usize> {
+        None
+    }
+}
+
+async fn load_prepared_with_columns(
+    conn: Arc<tokio_postgres::Client>,
+    stmt: QueryStatement,
+    binds: PooledBinds,
+) -> QueryResult<RowsWithColumns> {
+    // the description of the result is only known for prepared statements
+    let stmt = match stmt {
+        QueryStatement::Prepared(stmt) => stmt,
+        QueryStatement::Unprepared { sql, types } => conn
+            .prepare_typed(&sql, &types)
+            .await
+            .map_err(ErrorHelper)?,
+    };
+    let mut columns = stmt
+        .columns()
+        .iter()
+        .map(|column| ResultColumn {
+            name: column.name().to_owned(),
+            type_oid: column.type_().oid(),
+            type_name: column.type_().name().to_owned(),
+            table_oid: column.table_oid(),
+            column_id: column.column_id(),
+            nullable: None,
+        })
+        .collect::<Vec<_>>();
+    resolve_nullability(&conn, &mut columns).await?;
+    let rows = conn
+        .query_raw(&stmt, binds.iter())
+        .await
+        .map_err(ErrorHelper)?;
+    Ok(RowsWithColumns {
+        rows: PgRowStream::new(rows),
+        columns,
+    })
+}
+
+// Looks up the `NOT NULL` constraints of the table columns of the result
+async fn resolve_nullability(
+    conn: &tokio_postgres::Client,
+    columns: &mut [ResultColumn],
+) -> QueryResult<()> {
+    let mut tables = columns
+        .iter()
+        .filter_map(|column| column.table_oid)
+        .collect::<Vec<_>>();
+    if tables.is_empty() {
+        return Ok(());
+    }
+    tables.sort_unstable();
+    tables.dedup();
+    let not_null = conn
+        .query(
+            "SELECT attrelid, attnum, attnotnull FROM pg_catalog.pg_attribute \
+             WHERE attrelid = ANY($1) AND attnum > 0",
+            &[&tables],
+        )
+        .await
+        .map_err(ErrorHelper)?
+        .into_iter()
+        .map(|row| ((row.get::<_, u32>(0), row.get::<_, i16>(1)), row.get(2)))
+        .collect::<HashMap<_, bool>>();
+    for column in columns {
+        if let (Some(table_oid), Some(column_id)) = (column.table_oid, column.column_id) {
+            column.nullable = not_null
+                .get(&(table_oid, column_id))
+                .map(|not_null| !not_null);
+        }
+    }
+    Ok(())
+}
+
+impl AsyncPgConnection {
+    /// Load the results of the given query together with a description
+    /// of the result columns
+    ///
+    /// The description is taken from the prepared statement, so it is
+    /// available even if the query returns no rows. This allows generic tools
+    /// like admin interfaces or exporters to display or serialize results of
+    /// arbitrary queries. Whether a column may contain `NULL` values is looked
+    /// up in the system catalog for columns referring to table columns, which
+    /// requires an additional round trip for such queries.
+    ///
+    /// ```rust
+    /// # include!("../doctest_setup.rs");
+    /// #
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// #     run_test().await.unwrap();
+    /// # }
+    /// #
+    /// # async fn run_test() -> QueryResult<()> {
+    /// #     use schema::users;
+    /// #     let conn = &mut establish_connection().await;
+    /// let (users, columns) = conn
+    ///     .load_with_column_metadata::<_, (i32, String)>(users::table.order(users::id))
+    ///     .await?;
+    /// assert_eq!(vec![(1, "Sean".to_owned()), (2, "Tess".to_owned())], users);
+    ///
+    /// let names = columns.iter().map(|c| c.name.as_str()).collect::<Vec<_>>();
+    /// assert_eq!(vec!["id", "name"], names);
+    /// assert_eq!("int4", columns[0].type_name);
+    /// assert_eq!(Some(false), columns[0].nullable);
+    /// #     Ok(())
+    /// # }
+    /// ```
+    pub async fn load_with_column_metadata<T, U>(
+        &mut self,
+        query: T,
+    ) -> QueryResult<(Vec<U>, Vec<ResultColumn>)>
+    where
+        T: AsQuery,
+        T::Query: QueryFragment<Pg> + QueryId,
+        U: FromSqlRow<T::SqlType, Pg>,
+    {
+        let load = self.with_prepared_statement(query.as_query(), load_prepared_with_columns);
+        let RowsWithColumns { rows, columns } = self.run_with_connection_future(load).await?;
+        let rows = rows.try_collect::<Vec<_>>().await?;
+        let rows = rows
+            .iter()
+            .map(|row| U::build_from_row(row).map_err(diesel::result::Error::DeserializationError))
+            .collect::<QueryResult<Vec<_>>>()?;
+        Ok((rows, columns))
+    }
+}
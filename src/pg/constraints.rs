@@ -0,0 +1,122 @@
+use super::{quote_identifier, AsyncPgConnection};
+use crate::{AnsiTransactionManager, SimpleAsyncConnection, TransactionManager};
+use diesel::QueryResult;
+
+/// When deferrable constraints are checked, see
+/// [`AsyncPgConnection::set_constraints`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConstraintCheck {
+    /// Check the constraints at the end of each statement
+    Immediate,
+    /// Check the constraints when the transaction commits
+    Deferred,
+}
+
+impl ConstraintCheck {
+    fn as_sql(self) -> &'static str {
+        match self {
+            ConstraintCheck::Immediate => "IMMEDIATE",
+            ConstraintCheck::Deferred => "DEFERRED",
+        }
+    }
+}
+
+impl AsyncPgConnection {
+    /// Change when the given constraints are checked for the
+    /// rest of the current transaction
+    ///
+    /// This executes `SET CONSTRAINTS`, which only affects constraints declared
+    /// as `DEFERRABLE`. Deferring foreign key constraints allows to insert rows
+    /// in any order, for example during bulk loads. Switching constraints back
+    /// to [`ConstraintCheck::Immediate`] checks all changes made so far.
+    ///
+    /// Constraint names are quoted, use `schema.constraint` to refer to a
+    /// constraint outside of the search path. Returns an error if called
+    /// outside of a transaction or without constraints, use
+    /// [`AsyncPgConnection::set_all_constraints`] to change all constraints.
+    ///
+    /// See [the PostgreSQL documentation for `SET CONSTRAINTS`][pg-docs]
+    /// for details.
+    ///
+    /// [pg-docs]: https://www.postgresql.org/docs/current/sql-set-constraints.html
+    ///
+    /// ```rust
+    /// # include!("../doctest_setup.rs");
+    /// use diesel_async::pg::ConstraintCheck;
+    /// use diesel_async::SimpleAsyncConnection;
+    /// #
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// #     run_test().await.unwrap();
+    /// # }
+    /// #
+    /// # async fn run_test() -> QueryResult<()> {
+    /// #     let conn = &mut establish_connection().await;
+    /// conn.batch_execute(
+    ///     "CREATE TEMPORARY TABLE parents (id INTEGER PRIMARY KEY);
+    ///      CREATE TEMPORARY TABLE children (
+    ///          parent_id INTEGER CONSTRAINT children_parent_fkey REFERENCES parents
+    ///              DEFERRABLE INITIALLY IMMEDIATE
+    ///      );",
+    /// )
+    /// .await?;
+    ///
+    /// conn.set_constraints(&["children_parent_fkey"], ConstraintCheck::Deferred)
+    ///     .await?;
+    /// // the parent is only required to exist once the constraint is checked
+    /// conn.batch_execute("INSERT INTO children VALUES (1); INSERT INTO parents VALUES (1);")
+    ///     .await?;
+    /// conn.set_constraints(&["children_parent_fkey"], ConstraintCheck::Immediate)
+    ///     .await?;
+    /// #     Ok(())
+    /// # }
+    /// ```
+    pub async fn set_constraints(
+        &mut self,
+        constraints: &[&str],
+        check: ConstraintCheck,
+    ) -> QueryResult<()> {
+        if constraints.is_empty() {
+            return Err(diesel::result::Error::QueryBuilderError(
+                "At least one constraint is required to set constraints".into(),
+            ));
+        }
+        let constraints = constraints
+            .iter()
+            .map(|constraint| {
+                constraint
+                    .split('.')
+                    .map(quote_identifier)
+                    .collect::<Vec<_>>()
+                    .join(".")
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        self.execute_set_constraints(&constraints, check).await
+    }
+
+    /// Change when all deferrable constraints are checked for
+    /// the rest of the current transaction
+    ///
+    /// See [`AsyncPgConnection::set_constraints`] for details.
+    pub async fn set_all_constraints(&mut self, check: ConstraintCheck) -> QueryResult<()> {
+        self.execute_set_constraints("ALL", check).await
+    }
+
+    async fn execute_set_constraints(
+        &mut self,
+        constraints: &str,
+        check: ConstraintCheck,
+    ) -> QueryResult<()> {
+        // outside of a transaction PostgreSQL only emits a warning,
+        // which would silently leave the constraints unchanged
+        let in_transaction = AnsiTransactionManager::transaction_manager_status_mut(self)
+            .transaction_depth()?
+            .is_some();
+        if !in_transaction {
+            return Err(diesel::result::Error::NotInTransaction);
+        }
+        self.batch_execute(&format!("SET CONSTRAINTS {constraints} {}", check.as_sql()))
+            .await
+    }
+}
@@ -0,0 +1,224 @@
+use super::{load_prepared, AsyncPgConnection};
+use diesel::pg::Pg;
+use diesel::query_builder::{QueryFragment, QueryId};
+use diesel::QueryResult;
+use futures_util::TryStreamExt;
+use std::error::Error;
+use std::sync::Arc;
+use tokio_postgres::types::{FromSql, Type};
+
+/// A single value of a [`DynamicRow`]
+///
+/// Values of types without a dedicated variant are returned
+/// as [`Value::Raw`] in the binary representation of PostgreSQL.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum Value {
+    /// A `NULL` value
+    Null,
+    /// A `boolean` value
+    Bool(bool),
+    /// A `smallint`, `integer`, `bigint` or `oid` value
+    Int(i64),
+    /// A `real` or `double precision` value
+    Float(f64),
+    /// A `text`, `varchar`, `char`, `name`, `json` or `jsonb` value
+    Text(String),
+    /// A `bytea` value
+    Bytes(Vec<u8>),
+    /// A value of any other type
+    Raw {
+        /// The OID of the type of the value
+        type_oid: u32,
+        /// The value in its binary representation
+        bytes: Vec<u8>,
+    },
+}
+
+impl Value {
+    /// Whether this value is `NULL`
+    pub fn is_null(&self) -> bool {
+        matches!(self, Value::Null)
+    }
+
+    /// Returns the value if it is a [`Value::Bool`]
+    pub fn as_bool(&self) -> Option<bool> {
+        match *self {
+            Value::Bool(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    /// Returns the value if it is a [`Value::Int`]
+    pub fn as_i64(&self) -> Option<i64> {
+        match *self {
+            Value::Int(i) => Some(i),
+            _ => None,
+        }
+    }
+
+    /// Returns the value if it is a [`Value::Float`]
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self {
+            Value::Float(f) => Some(f),
+            _ => None,
+        }
+    }
+
+    /// Returns the value if it is a [`Value::Text`]
+    pub fn as_str(&self) -> Option<&str> {
+        match *self {
+            Value::Text(ref s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Returns the value if it is a [`Value::Bytes`]
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match *self {
+            Value::Bytes(ref b) => Some(b),
+            _ => None,
+        }
+    }
+}
+
+impl<'a> FromSql<'a> for Value {
+    fn from_sql(ty: &Type, raw: &'a [u8]) -> Result<Self, Box<dyn Error + Sync + Send>> {
+        let value = match *ty {
+            Type::BOOL => Value::Bool(bool::from_sql(ty, raw)?),
+            Type::INT2 => Value::Int(i16::from_sql(ty, raw)?.into()),
+            Type::INT4 => Value::Int(i32::from_sql(ty, raw)?.into()),
+            Type::INT8 => Value::Int(i64::from_sql(ty, raw)?),
+            Type::OID => Value::Int(u32::from_sql(ty, raw)?.into()),
+            Type::FLOAT4 => Value::Float(f32::from_sql(ty, raw)?.into()),
+            Type::FLOAT8 => Value::Float(f64::from_sql(ty, raw)?),
+            Type::TEXT | Type::VARCHAR | Type::BPCHAR | Type::NAME | Type::UNKNOWN => {
+                Value::Text(String::from_sql(ty, raw)?)
+            }
+            Type::JSON => Value::Text(std::str::from_utf8(raw)?.to_owned()),
+            // the binary representation of `jsonb` is prefixed with a version byte
+            Type::JSONB => match raw.split_first() {
+                Some((1, json)) => Value::Text(std::str::from_utf8(json)?.to_owned()),
+                _ => return Err("Unsupported jsonb encoding version".into()),
+            },
+            Type::BYTEA => Value::Bytes(raw.to_vec()),
+            _ => Value::Raw {
+                type_oid: ty.oid(),
+                bytes: raw.to_vec(),
+            },
+        };
+        Ok(value)
+    }
+
+    fn from_sql_null(_ty: &Type) -> Result<Self, Box<dyn Error + Sync + Send>> {
+        Ok(Value::Null)
+    }
+
+    fn accepts(_ty: &Type) -> bool {
+        true
+    }
+}
+
+/// A row of a query whose shape is only known at runtime
+///
+/// Returned by [`AsyncPgConnection::load_dynamic`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct DynamicRow {
+    // shared by all rows of a result
+    columns: Arc<[String]>,
+    values: Vec<Value>,
+}
+
+impl DynamicRow {
+    /// The names of the columns of this row
+    pub fn columns(&self) -> &[String] {
+        &self.columns
+    }
+
+    /// The values of this row, in the order of the columns
+    pub fn values(&self) -> &[Value] {
+        &self.values
+    }
+
+    /// Returns the value of the first column with the given name
+    pub fn get(&self, column: &str) -> Option<&Value> {
+        let idx = self.columns.iter().position(|c| c == column)?;
+        self.values.get(idx)
+    }
+
+    /// Iterate over the column names and values of this row
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Value)> {
+        self.columns.iter().map(String::as_str).zip(&self.values)
+    }
+
+    /// Consume this row, returning its values
+    pub fn into_values(self) -> Vec<Value> {
+        self.values
+    }
+}
+
+impl AsyncPgConnection {
+    /// Load the results of the given query as [`DynamicRow`]s
+    ///
+    /// This allows to load the results of ad-hoc statements whose shape is only
+    /// known at runtime, like queries built via [`diesel::sql_query`], without
+    /// defining a type implementing `QueryableByName`. Values of common types
+    /// are decoded into the matching [`Value`] variant.
+    ///
+    /// ```rust
+    /// # include!("../doctest_setup.rs");
+    /// use diesel_async::pg::Value;
+    /// #
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// #     run_test().await.unwrap();
+    /// # }
+    /// #
+    /// # async fn run_test() -> QueryResult<()> {
+    /// #     let conn = &mut establish_connection().await;
+    /// let rows = conn
+    ///     .load_dynamic(
+    ///         diesel::sql_query("SELECT id, name, NULL AS nickname FROM users WHERE id = $1")
+    ///             .bind::<diesel::sql_types::Integer, _>(1),
+    ///     )
+    ///     .await?;
+    /// assert_eq!(vec!["id", "name", "nickname"], rows[0].columns());
+    /// assert_eq!(Some(&Value::Int(1)), rows[0].get("id"));
+    /// assert_eq!(Some("Sean"), rows[0].get("name").and_then(Value::as_str));
+    /// assert!(rows[0].get("nickname").is_some_and(Value::is_null));
+    /// #     Ok(())
+    /// # }
+    /// ```
+    pub async fn load_dynamic<T>(&mut self, query: T) -> QueryResult<Vec<DynamicRow>>
+    where
+        T: QueryFragment<Pg> + QueryId,
+    {
+        let load = self.with_prepared_statement(query, load_prepared);
+        let rows = self
+            .run_with_connection_future(load)
+            .await?
+            .try_collect::<Vec<_>>()
+            .await?;
+        let Some(first) = rows.first() else {
+            return Ok(Vec::new());
+        };
+        let columns = first
+            .row()
+            .columns()
+            .iter()
+            .map(|c| c.name().to_owned())
+            .collect::<Arc<[_]>>();
+        rows.iter()
+            .map(|row| {
+                let values = (0..columns.len())
+                    .map(|idx| row.row().try_get(idx))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|e| diesel::result::Error::DeserializationError(Box::new(e)))?;
+                Ok(DynamicRow {
+                    columns: columns.clone(),
+                    values,
+                })
+            })
+            .collect()
+    }
+}
@@ -28,7 +28,10 @@ pub(super) fn from_tokio_postgres_error(
             let kind = match *code {
                 SqlState::UNIQUE_VIOLATION => UniqueViolation,
                 SqlState::FOREIGN_KEY_VIOLATION => ForeignKeyViolation,
-                SqlState::T_R_SERIALIZATION_FAILURE => SerializationFailure,
+                // deadlocks abort the transaction like serialization failures
+                SqlState::T_R_SERIALIZATION_FAILURE | SqlState::T_R_DEADLOCK_DETECTED => {
+                    SerializationFailure
+                }
                 SqlState::READ_ONLY_SQL_TRANSACTION => ReadOnlyTransaction,
                 SqlState::NOT_NULL_VIOLATION => NotNullViolation,
                 SqlState::CHECK_VIOLATION => CheckViolation,
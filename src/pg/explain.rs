@@ -0,0 +1,213 @@
+use super::AsyncPgConnection;
+use crate::RunQueryDsl;
+use diesel::pg::Pg;
+use diesel::query_builder::{AsQuery, AstPass, Query, QueryFragment, QueryId};
+use diesel::sql_types::Text;
+use diesel::QueryResult;
+use serde_json::{Map, Value};
+
+/// The execution plan of a query as reported by `EXPLAIN`
+///
+/// This type is returned by [`AsyncPgConnection::explain`] and
+/// [`AsyncPgConnection::explain_analyze`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryPlan {
+    /// The root node of the plan
+    pub plan: PlanNode,
+    /// The time spent planning the query in milliseconds
+    ///
+    /// Only reported by `EXPLAIN ANALYZE`
+    pub planning_time: Option<f64>,
+    /// The time spent executing the query in milliseconds
+    ///
+    /// Only reported by `EXPLAIN ANALYZE`
+    pub execution_time: Option<f64>,
+}
+
+/// A single node of a [`QueryPlan`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlanNode {
+    /// The kind of node, for example `Seq Scan` or `Index Scan`
+    pub node_type: String,
+    /// The table scanned by this node, if any
+    pub relation_name: Option<String>,
+    /// The index used by this node, if any
+    pub index_name: Option<String>,
+    /// The estimated cost to return the first row
+    pub startup_cost: f64,
+    /// The estimated cost to return all rows
+    pub total_cost: f64,
+    /// The estimated number of rows returned by this node
+    pub plan_rows: f64,
+    /// The actual number of rows returned by this node per loop
+    ///
+    /// Only reported by `EXPLAIN ANALYZE`
+    pub actual_rows: Option<f64>,
+    /// The actual time in milliseconds spent in this node per loop
+    ///
+    /// Only reported by `EXPLAIN ANALYZE`
+    pub actual_total_time: Option<f64>,
+    /// The child nodes of this node
+    pub plans: Vec<PlanNode>,
+    /// All properties of this node as reported by PostgreSQL,
+    /// except for the child nodes
+    pub properties: Map<String, Value>,
+}
+
+impl PlanNode {
+    /// Iterate over this node and all of its descendants in depth first order
+    pub fn nodes(&self) -> impl Iterator<Item = &PlanNode> {
+        let mut stack = vec![self];
+        std::iter::from_fn(move || {
+            let node = stack.pop()?;
+            stack.extend(node.plans.iter().rev());
+            Some(node)
+        })
+    }
+
+    /// Check whether this node or any of its descendants uses the given index
+    pub fn uses_index(&self, index_name: &str) -> bool {
+        self.nodes()
+            .any(|node| node.index_name.as_deref() == Some(index_name))
+    }
+
+    fn from_json(mut properties: Map<String, Value>) -> QueryResult<Self> {
+        let plans = match properties.remove("Plans") {
+            Some(Value::Array(plans)) => plans
+                .into_iter()
+                .map(|plan| match plan {
+                    Value::Object(plan) => PlanNode::from_json(plan),
+                    _ => Err(invalid_plan()),
+                })
+                .collect::<QueryResult<_>>()?,
+            Some(_) => return Err(invalid_plan()),
+            None => Vec::new(),
+        };
+        let string = |key| properties.get(key).and_then(Value::as_str).map(Into::into);
+        let number = |key| properties.get(key).and_then(Value::as_f64);
+        Ok(Self {
+            node_type: string("Node Type").ok_or_else(invalid_plan)?,
+            relation_name: string("Relation Name"),
+            index_name: string("Index Name"),
+            startup_cost: number("Startup Cost").ok_or_else(invalid_plan)?,
+            total_cost: number("Total Cost").ok_or_else(invalid_plan)?,
+            plan_rows: number("Plan Rows").ok_or_else(invalid_plan)?,
+            actual_rows: number("Actual Rows"),
+            actual_total_time: number("Actual Total Time"),
+            plans,
+            properties,
+        })
+    }
+}
+
+impl QueryPlan {
+    fn parse(explain_output: &str) -> QueryResult<Self> {
+        let output = serde_json::from_str::<Value>(explain_output)
+            .map_err(|e| diesel::result::Error::DeserializationError(Box::new(e)))?;
+        let Value::Array(mut output) = output else {
+            return Err(invalid_plan());
+        };
+        let Some(Value::Object(mut output)) = output.pop() else {
+            return Err(invalid_plan());
+        };
+        let Some(Value::Object(plan)) = output.remove("Plan") else {
+            return Err(invalid_plan());
+        };
+        Ok(Self {
+            plan: PlanNode::from_json(plan)?,
+            planning_time: output.get("Planning Time").and_then(Value::as_f64),
+            execution_time: output.get("Execution Time").and_then(Value::as_f64),
+        })
+    }
+}
+
+fn invalid_plan() -> diesel::result::Error {
+    diesel::result::Error::DeserializationError("Unexpected `EXPLAIN` output".into())
+}
+
+struct Explain<T> {
+    query: T,
+    analyze: bool,
+}
+
+impl<T> QueryId for Explain<T> {
+    type QueryId = ();
+
+    const HAS_STATIC_QUERY_ID: bool = false;
+}
+
+impl<T> Query for Explain<T> {
+    type SqlType = Text;
+}
+
+impl<T> QueryFragment<Pg> for Explain<T>
+where
+    T: QueryFragment<Pg>,
+{
+    fn walk_ast<'b>(&'b self, mut out: AstPass<'_, 'b, Pg>) -> QueryResult<()> {
+        out.push_sql("EXPLAIN (FORMAT JSON");
+        if self.analyze {
+            out.push_sql(", ANALYZE");
+        }
+        out.push_sql(") ");
+        self.query.walk_ast(out.reborrow())
+    }
+}
+
+impl AsyncPgConnection {
+    /// Returns the execution plan PostgreSQL chooses for the given query
+    /// without executing it
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # include!("../doctest_setup.rs");
+    /// #
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// #     run_test().await.unwrap();
+    /// # }
+    /// #
+    /// # async fn run_test() -> QueryResult<()> {
+    /// #     use schema::users;
+    /// #     let conn = &mut establish_connection().await;
+    /// let plan = conn.explain(users::table.find(1)).await?;
+    /// assert!(plan
+    ///     .plan
+    ///     .nodes()
+    ///     .any(|node| node.relation_name.as_deref() == Some("users")));
+    /// #     Ok(())
+    /// # }
+    /// ```
+    pub async fn explain<T>(&mut self, query: T) -> QueryResult<QueryPlan>
+    where
+        T: AsQuery,
+        T::Query: QueryFragment<Pg> + Send,
+    {
+        self.run_explain(query.as_query(), false).await
+    }
+
+    /// Executes the given query and returns the execution plan including
+    /// the actual run times and row counts
+    ///
+    /// The query is actually executed, including all side effects
+    /// of data modifying statements. Wrap the call into a transaction
+    /// that is rolled back afterwards if this is not desired.
+    pub async fn explain_analyze<T>(&mut self, query: T) -> QueryResult<QueryPlan>
+    where
+        T: AsQuery,
+        T::Query: QueryFragment<Pg> + Send,
+    {
+        self.run_explain(query.as_query(), true).await
+    }
+
+    async fn run_explain<T>(&mut self, query: T, analyze: bool) -> QueryResult<QueryPlan>
+    where
+        T: QueryFragment<Pg> + Send,
+    {
+        let output = Explain { query, analyze }
+            .get_result::<String>(self)
+            .await?;
+        QueryPlan::parse(&output)
+    }
+}
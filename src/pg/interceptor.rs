@@ -0,0 +1,283 @@
+use super::row::PgRowStream;
+use super::AsyncPgConnection;
+use diesel::pg::PgTypeMetadata;
+use diesel::QueryResult;
+use std::any::TypeId;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Inspects, rewrites or rejects the queries executed on an
+/// [`AsyncPgConnection`], see [`AsyncPgConnection::add_interceptor`]
+///
+/// Interceptors form a chain: [`QueryInterceptor::before_query`] is called in
+/// the order the interceptors were added, [`QueryInterceptor::after_query`] in
+/// the reverse order. If an interceptor rejects a query, the query is not
+/// executed and only the interceptors that already saw the query observe the
+/// error.
+///
+/// Both methods are called inline while executing the query,
+/// so they should return quickly.
+pub trait QueryInterceptor: Send + Sync {
+    /// Called before a query is executed
+    ///
+    /// The SQL and the bind values of the query can be modified via
+    /// `query`. Returning an error rejects the query, the error is
+    /// returned to the caller instead of executing the query.
+    fn before_query(&self, query: &mut InterceptedQuery) -> QueryResult<()> {
+        let _ = query;
+        Ok(())
+    }
+
+    /// Called once a query finished, with the query as it was executed
+    fn after_query(&self, query: &InterceptedQuery, outcome: &QueryOutcome<'_>) {
+        let _ = (query, outcome);
+    }
+}
+
+/// A query passed to a [`QueryInterceptor`]
+///
+/// Statements executed via
+/// [`SimpleAsyncConnection::batch_execute`](crate::SimpleAsyncConnection::batch_execute),
+/// including the statements controlling transactions, are intercepted as
+/// queries without bind values.
+#[derive(Debug, Clone)]
+pub struct InterceptedQuery {
+    sql: String,
+    binds: Vec<Option<Vec<u8>>>,
+    bind_types: Vec<PgTypeMetadata>,
+    query_id: Option<TypeId>,
+}
+
+impl InterceptedQuery {
+    pub(super) fn new(
+        sql: String,
+        binds: Vec<Option<Vec<u8>>>,
+        bind_types: Vec<PgTypeMetadata>,
+        query_id: Option<TypeId>,
+    ) -> Self {
+        Self {
+            sql,
+            binds,
+            bind_types,
+            query_id,
+        }
+    }
+
+    /// The SQL of the query
+    pub fn sql(&self) -> &str {
+        &self.sql
+    }
+
+    /// Replace the SQL of the query
+    ///
+    /// The new SQL needs to use the same bind parameters. This clears the
+    /// [`InterceptedQuery::query_id`], as it no longer identifies the SQL.
+    pub fn set_sql(&mut self, sql: impl Into<String>) {
+        self.sql = sql.into();
+        self.query_id = None;
+    }
+
+    /// The serialized bind values of the query, `None` for `NULL`
+    pub fn binds(&self) -> &[Option<Vec<u8>>] {
+        &self.binds
+    }
+
+    /// Mutable access to the serialized bind values of the query
+    ///
+    /// New values need to be serialized in the binary format of
+    /// the type given by [`InterceptedQuery::bind_types`].
+    pub fn binds_mut(&mut self) -> &mut [Option<Vec<u8>>] {
+        &mut self.binds
+    }
+
+    /// The types of the bind values of the query
+    pub fn bind_types(&self) -> &[PgTypeMetadata] {
+        &self.bind_types
+    }
+
+    /// The [`QueryId`](diesel::query_builder::QueryId) of the query,
+    /// if the query has a static query id
+    pub fn query_id(&self) -> Option<TypeId> {
+        self.query_id
+    }
+}
+
+/// The outcome of an intercepted query, see [`QueryInterceptor::after_query`]
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct QueryOutcome<'a> {
+    /// The time the execution took
+    ///
+    /// For queries returning rows this covers the time until the first
+    /// response was received, but not the time to receive all rows.
+    pub duration: Duration,
+    /// The number of affected rows if known, or the error the query failed with
+    pub result: Result<Option<usize>, &'a diesel::result::Error>,
+}
+
+#[derive(Clone, Default)]
+pub(super) struct InterceptorChain(Arc<Vec<Arc<dyn QueryInterceptor>>>);
+
+impl InterceptorChain {
+    pub(super) fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Run the `before_query` hooks of all interceptors, the returned
+    /// value needs to be finished with the result of the query
+    pub(super) fn before_query(&self, mut query: InterceptedQuery) -> QueryResult<PendingQuery> {
+        for (idx, interceptor) in self.0.iter().enumerate() {
+            if let Err(e) = interceptor.before_query(&mut query) {
+                let outcome = QueryOutcome {
+                    duration: Duration::ZERO,
+                    result: Err(&e),
+                };
+                for interceptor in self.0[..=idx].iter().rev() {
+                    interceptor.after_query(&query, &outcome);
+                }
+                return Err(e);
+            }
+        }
+        Ok(PendingQuery {
+            chain: self.clone(),
+            query,
+            start: Instant::now(),
+        })
+    }
+}
+
+pub(super) struct PendingQuery {
+    chain: InterceptorChain,
+    query: InterceptedQuery,
+    start: Instant,
+}
+
+impl PendingQuery {
+    pub(super) fn query(&self) -> &InterceptedQuery {
+        &self.query
+    }
+
+    /// Run the `after_query` hooks of all interceptors
+    pub(super) fn finish<R: QueryOutput>(self, result: &QueryResult<R>) {
+        let outcome = QueryOutcome {
+            duration: self.start.elapsed(),
+            result: result.as_ref().map(QueryOutput::affected_rows),
+        };
+        for interceptor in self.chain.0.iter().rev() {
+            interceptor.after_query(&self.query, &outcome);
+        }
+    }
+}
+
+/// A 64 bit FNV-1a hasher, used for fingerprints that need
+/// to be stable across versions and processes
+pub(super) struct Fnv1aHasher(u64);
+
+impl Default for Fnv1aHasher {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl std::hash::Hasher for Fnv1aHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+}
+
+/// The results of the different kinds of queries
+pub(super) trait QueryOutput {
+    fn affected_rows(&self) -> Option<usize>;
+}
+
+impl QueryOutput for usize {
+    fn affected_rows(&self) -> Option<usize> {
+        Some(*self)
+    }
+}
+
+impl QueryOutput for () {
+    fn affected_rows(&self) -> Option<usize> {
+        None
+    }
+}
+
+impl QueryOutput for Vec<usize> {
+    fn affected_rows(&self) -> Option<usize> {
+        Some(self.iter().sum())
+    }
+}
+
+impl QueryOutput for Vec<tokio_postgres::SimpleQueryMessage> {
+    fn affected_rows(&self) -> Option<usize> {
+        None
+    }
+}
+
+impl QueryOutput for PgRowStream {
+    fn affected_rows(&self) -> Option<usize> {
+        None
+    }
+}
+
+impl AsyncPgConnection {
+    /// Add an interceptor to the queries executed on this connection
+    ///
+    /// This allows to audit queries, to rewrite them or to enforce policies
+    /// about which queries may be executed, see [`QueryInterceptor`].
+    /// This includes the queries executed via a
+    /// [`PreparedQuery`](super::PreparedQuery).
+    ///
+    /// To intercept the queries of all connections of a pool, add the
+    /// interceptor via
+    /// [`ManagerConfig::warm_up`](crate::pooled_connection::ManagerConfig::warm_up).
+    ///
+    /// ```rust
+    /// # include!("../doctest_setup.rs");
+    /// use diesel_async::pg::{InterceptedQuery, QueryInterceptor};
+    /// use std::sync::Arc;
+    /// #
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// #     run_test().await.unwrap();
+    /// # }
+    /// #
+    /// # async fn run_test() -> QueryResult<()> {
+    /// # use diesel_async::RunQueryDsl;
+    /// #     use schema::users;
+    /// #     let conn = &mut establish_connection().await;
+    /// struct ReadOnly;
+    ///
+    /// impl QueryInterceptor for ReadOnly {
+    ///     fn before_query(&self, query: &mut InterceptedQuery) -> QueryResult<()> {
+    ///         if query.sql().starts_with("DELETE") {
+    ///             return Err(diesel::result::Error::QueryBuilderError(
+    ///                 "Deleting is not allowed".into(),
+    ///             ));
+    ///         }
+    ///         Ok(())
+    ///     }
+    /// }
+    ///
+    /// conn.add_interceptor(Arc::new(ReadOnly));
+    /// assert!(diesel::delete(users::table).execute(conn).await.is_err());
+    /// assert_eq!(2, users::table.count().get_result::<i64>(conn).await?);
+    /// #     Ok(())
+    /// # }
+    /// ```
+    pub fn add_interceptor(&mut self, interceptor: Arc<dyn QueryInterceptor>) {
+        Arc::make_mut(&mut self.interceptors.0).push(interceptor);
+    }
+
+    /// Remove all interceptors added to this connection
+    pub fn clear_interceptors(&mut self) {
+        self.interceptors = InterceptorChain::default();
+    }
+}
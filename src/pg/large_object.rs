@@ -0,0 +1,262 @@
+use super::error_helper::ErrorHelper;
+use super::AsyncPgConnection;
+use crate::RunQueryDsl;
+use diesel::sql_types::{Integer, Oid};
+use diesel::QueryResult;
+use futures_util::future::BoxFuture;
+use futures_util::FutureExt;
+use std::io;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{ready, Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio_postgres::Statement;
+
+// see `libpq/libpq-fs.h`
+const INV_WRITE: i32 = 0x0002_0000;
+const INV_READ: i32 = 0x0004_0000;
+
+/// The mode a large object is opened with
+///
+/// See [`AsyncPgConnection::lo_open`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LargeObjectMode {
+    /// Open the large object for reading
+    Read,
+    /// Open the large object for writing
+    Write,
+    /// Open the large object for reading and writing
+    ReadWrite,
+}
+
+impl LargeObjectMode {
+    fn flags(self) -> i32 {
+        match self {
+            LargeObjectMode::Read => INV_READ,
+            LargeObjectMode::Write => INV_WRITE,
+            LargeObjectMode::ReadWrite => INV_READ | INV_WRITE,
+        }
+    }
+}
+
+/// An open PostgreSQL large object
+///
+/// This type is returned by [`AsyncPgConnection::lo_open`]. It implements
+/// [`AsyncRead`] and [`AsyncWrite`], so the content of the large object can
+/// be streamed from or to any other source, for example via [`tokio::io::copy`].
+/// Each read or write call is translated into a single `loread` or `lowrite` call,
+/// so wrap this type into a buffered reader or writer to control the size of the
+/// transferred chunks.
+///
+/// Large object descriptors are only valid inside of the transaction they were
+/// opened in, they are closed automatically at the end of the transaction.
+///
+/// See [the PostgreSQL documentation][pg-docs] for details about large objects.
+///
+/// [pg-docs]: https://www.postgresql.org/docs/current/largeobjects.html
+pub struct LargeObject<'a> {
+    client: Arc<tokio_postgres::Client>,
+    fd: i32,
+    read_statement: Statement,
+    write_statement: Statement,
+    pending_read: Option<BoxFuture<'static, io::Result<Vec<u8>>>>,
+    // Data read from the server that did not fit into the buffer of the
+    // caller, as the buffer can shrink while a read is pending
+    read_buffer: Vec<u8>,
+    read_position: usize,
+    pending_write: Option<BoxFuture<'static, io::Result<usize>>>,
+    connection: PhantomData<&'a mut AsyncPgConnection>,
+}
+
+impl LargeObject<'_> {
+    /// Close the large object descriptor
+    ///
+    /// This is optional, as the descriptor is closed at the
+    /// end of the transaction anyway.
+    pub async fn close(self) -> QueryResult<()> {
+        tokio_postgres::Client::execute(&self.client, "SELECT lo_close($1)", &[&self.fd])
+            .await
+            .map_err(ErrorHelper)?;
+        Ok(())
+    }
+}
+
+fn to_io_error(error: tokio_postgres::Error) -> io::Error {
+    io::Error::other(error)
+}
+
+impl AsyncRead for LargeObject<'_> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if this.read_position < this.read_buffer.len() {
+            let buffered = &this.read_buffer[this.read_position..];
+            let len = buffered.len().min(buf.remaining());
+            buf.put_slice(&buffered[..len]);
+            this.read_position += len;
+            return Poll::Ready(Ok(()));
+        }
+        let pending = this.pending_read.get_or_insert_with(|| {
+            let client = this.client.clone();
+            let statement = this.read_statement.clone();
+            let fd = this.fd;
+            let len = i32::try_from(buf.remaining()).unwrap_or(i32::MAX);
+            async move {
+                let row = client
+                    .query_one(&statement, &[&fd, &len])
+                    .await
+                    .map_err(to_io_error)?;
+                row.try_get::<_, Vec<u8>>(0).map_err(to_io_error)
+            }
+            .boxed()
+        });
+        let res = ready!(pending.as_mut().poll(cx));
+        this.pending_read = None;
+        let data = res?;
+        let len = data.len().min(buf.remaining());
+        buf.put_slice(&data[..len]);
+        this.read_buffer = data;
+        this.read_position = len;
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncWrite for LargeObject<'_> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let pending = this.pending_write.get_or_insert_with(|| {
+            let client = this.client.clone();
+            let statement = this.write_statement.clone();
+            let fd = this.fd;
+            let data = buf.to_vec();
+            async move {
+                let row = client
+                    .query_one(&statement, &[&fd, &data])
+                    .await
+                    .map_err(to_io_error)?;
+                let written = row.try_get::<_, i32>(0).map_err(to_io_error)?;
+                Ok(written as usize)
+            }
+            .boxed()
+        });
+        let res = ready!(pending.as_mut().poll(cx));
+        this.pending_write = None;
+        Poll::Ready(res)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        // writes are finished as soon as `poll_write` returns,
+        // only an interrupted write might still be pending
+        let this = self.get_mut();
+        if let Some(pending) = this.pending_write.as_mut() {
+            let res = ready!(pending.as_mut().poll(cx));
+            this.pending_write = None;
+            res?;
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.poll_flush(cx)
+    }
+}
+
+impl AsyncPgConnection {
+    /// Create a new empty large object and return its OID
+    pub async fn lo_create(&mut self) -> QueryResult<u32> {
+        diesel::select(diesel::dsl::sql::<Oid>("lo_create(0)"))
+            .get_result(self)
+            .await
+    }
+
+    /// Open the large object identified by `oid`
+    ///
+    /// This needs to be called inside of a transaction, as the returned
+    /// descriptor is only valid until the end of the current transaction.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # include!("../doctest_setup.rs");
+    /// use diesel_async::pg::LargeObjectMode;
+    /// use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    /// #
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// #     run_test().await.unwrap();
+    /// # }
+    /// #
+    /// # async fn run_test() -> Result<(), Box<dyn std::error::Error>> {
+    /// #     let conn = &mut establish_connection().await;
+    /// let oid = conn.lo_create().await?;
+    ///
+    /// let mut large_object = conn.lo_open(oid, LargeObjectMode::Write).await?;
+    /// large_object.write_all(b"Hello large object").await?;
+    /// large_object.close().await?;
+    ///
+    /// let mut content = String::new();
+    /// let mut large_object = conn.lo_open(oid, LargeObjectMode::Read).await?;
+    /// large_object.read_to_string(&mut content).await?;
+    /// assert_eq!("Hello large object", content);
+    ///
+    /// conn.lo_unlink(oid).await?;
+    /// #     Ok(())
+    /// # }
+    /// ```
+    pub async fn lo_open(
+        &mut self,
+        oid: u32,
+        mode: LargeObjectMode,
+    ) -> QueryResult<LargeObject<'_>> {
+        let fd = diesel::select(
+            diesel::dsl::sql::<Integer>("lo_open(")
+                .bind::<Oid, _>(oid)
+                .sql(", ")
+                .bind::<Integer, _>(mode.flags())
+                .sql(")"),
+        )
+        .get_result::<i32>(self)
+        .await?;
+        let read_statement = self
+            .conn
+            .prepare("SELECT loread($1, $2)")
+            .await
+            .map_err(ErrorHelper)?;
+        let write_statement = self
+            .conn
+            .prepare("SELECT lowrite($1, $2)")
+            .await
+            .map_err(ErrorHelper)?;
+        Ok(LargeObject {
+            client: self.conn.clone(),
+            fd,
+            read_statement,
+            write_statement,
+            pending_read: None,
+            read_buffer: Vec::new(),
+            read_position: 0,
+            pending_write: None,
+            connection: PhantomData,
+        })
+    }
+
+    /// Delete the large object identified by `oid`
+    pub async fn lo_unlink(&mut self, oid: u32) -> QueryResult<()> {
+        diesel::select(
+            diesel::dsl::sql::<Integer>("lo_unlink(")
+                .bind::<Oid, _>(oid)
+                .sql(")"),
+        )
+        .get_result::<i32>(self)
+        .await?;
+        Ok(())
+    }
+}
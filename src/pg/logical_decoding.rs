@@ -0,0 +1,143 @@
+use super::AsyncPgConnection;
+use crate::RunQueryDsl;
+use diesel::sql_types::{Array, BigInt, Binary, Integer, Text};
+use diesel::QueryResult;
+use futures_util::{stream, Stream, TryStreamExt};
+
+/// A single change decoded from a logical replication slot
+///
+/// The content of [`LogicalChange::data`] depends on the output plugin
+/// used by the slot. Text based plugins like `test_decoding` or `wal2json`
+/// produce UTF-8 text, while `pgoutput` produces binary messages in the format
+/// of the logical replication protocol.
+#[derive(Debug, Clone, PartialEq, Eq, diesel::QueryableByName)]
+pub struct LogicalChange {
+    /// The LSN of the change in its textual representation (e.g. `0/16B3748`)
+    #[diesel(sql_type = Text)]
+    pub lsn: String,
+    /// The id of the transaction the change belongs to
+    #[diesel(sql_type = BigInt)]
+    pub xid: i64,
+    /// The change as emitted by the output plugin
+    #[diesel(sql_type = Binary)]
+    pub data: Vec<u8>,
+}
+
+impl LogicalChange {
+    /// The change as text, if the output plugin produced valid UTF-8
+    pub fn data_as_str(&self) -> Option<&str> {
+        std::str::from_utf8(&self.data).ok()
+    }
+}
+
+impl AsyncPgConnection {
+    /// Create a logical replication slot using the given output plugin
+    ///
+    /// This requires the server to run with `wal_level = logical`.
+    pub async fn create_logical_replication_slot(
+        &mut self,
+        slot_name: &str,
+        plugin: &str,
+    ) -> QueryResult<()> {
+        diesel::sql_query("SELECT 1 FROM pg_create_logical_replication_slot($1, $2)")
+            .bind::<Text, _>(slot_name)
+            .bind::<Text, _>(plugin)
+            .execute(self)
+            .await?;
+        Ok(())
+    }
+
+    /// Drop the replication slot with the given name
+    pub async fn drop_replication_slot(&mut self, slot_name: &str) -> QueryResult<()> {
+        diesel::sql_query("SELECT 1 FROM pg_drop_replication_slot($1)")
+            .bind::<Text, _>(slot_name)
+            .execute(self)
+            .await?;
+        Ok(())
+    }
+
+    /// Mark the changes of a logical replication slot up to the given LSN
+    /// as consumed
+    ///
+    /// This advances the slot via `pg_replication_slot_advance`, so that the
+    /// server can release the WAL retained for it. Changes are only skipped by
+    /// later calls of [`AsyncPgConnection::logical_changes_stream`] once they
+    /// were acknowledged, either via this method or by the stream itself.
+    pub async fn acknowledge_logical_changes(
+        &mut self,
+        slot_name: &str,
+        lsn: &str,
+    ) -> QueryResult<()> {
+        diesel::sql_query("SELECT 1 FROM pg_replication_slot_advance($1, $2::pg_lsn)")
+            .bind::<Text, _>(slot_name)
+            .bind::<Text, _>(lsn)
+            .execute(self)
+            .await?;
+        Ok(())
+    }
+
+    /// Read the pending changes of a logical replication slot as stream
+    ///
+    /// The changes are fetched in batches of roughly `batch_size` changes via
+    /// `pg_logical_slot_peek_binary_changes`. PostgreSQL only stops decoding at
+    /// transaction boundaries, so a single batch might contain more changes.
+    /// `options` are passed to the output plugin, for example
+    /// `[("proto_version", "1"), ("publication_names", "my_publication")]`
+    /// for `pgoutput`.
+    ///
+    /// Reading changes does not consume them. A batch is acknowledged via
+    /// [`AsyncPgConnection::acknowledge_logical_changes`] once the stream is
+    /// polled after the last change of the batch was yielded, which means the
+    /// consumer processed all of them. Changes of a batch that was not fully
+    /// processed, because the consumer failed or dropped the stream, are
+    /// returned again by the next call of this method, so changes are delivered
+    /// at least once. Consumers that stop reading early should acknowledge the
+    /// changes they processed themselves.
+    ///
+    /// This uses the SQL interface for logical decoding, not the streaming
+    /// replication protocol. The stream ends as soon as the slot has no further
+    /// pending changes, so consumers that want to follow the slot continuously
+    /// need to call this method again periodically.
+    pub fn logical_changes_stream(
+        &mut self,
+        slot_name: &str,
+        batch_size: i32,
+        options: &[(&str, &str)],
+    ) -> impl Stream<Item = QueryResult<LogicalChange>> + Send + '_ {
+        let slot_name = slot_name.to_owned();
+        let options = options
+            .iter()
+            .flat_map(|(key, value)| [key.to_string(), value.to_string()])
+            .collect::<Vec<_>>();
+
+        // the state holds the LSN of the last change of the previous batch,
+        // which is acknowledged once the next batch is requested
+        stream::try_unfold((self, None::<String>), move |(conn, processed)| {
+            let slot_name = slot_name.clone();
+            let options = options.clone();
+            async move {
+                if let Some(lsn) = processed {
+                    conn.acknowledge_logical_changes(&slot_name, &lsn).await?;
+                }
+                let changes = diesel::sql_query(
+                    "SELECT lsn::text AS lsn, xid::text::bigint AS xid, data \
+                     FROM pg_logical_slot_peek_binary_changes($1, NULL, $2, VARIADIC $3)",
+                )
+                .bind::<Text, _>(slot_name)
+                .bind::<Integer, _>(batch_size)
+                .bind::<Array<Text>, _>(options)
+                .load::<LogicalChange>(conn)
+                .await?;
+                let Some(last) = changes.last() else {
+                    return QueryResult::Ok(None);
+                };
+                let processed = Some(last.lsn.clone());
+                Ok(Some((
+                    stream::iter(changes.into_iter().map(Ok)),
+                    (conn, processed),
+                )))
+            }
+        })
+        .try_flatten()
+    }
+}
@@ -5,8 +5,10 @@
 //! PostgreSQL, you may need to work with this module directly.
 
 use self::error_helper::ErrorHelper;
+use self::interceptor::{InterceptorChain, QueryOutput};
+use self::pipeline::{PipelineCounters, PipelinePermits};
 use self::row::PgRow;
-use self::serialize::ToSqlHelper;
+use self::serialize::{BindBufferPool, PooledBinds};
 use crate::stmt_cache::{PrepareCallback, StmtCache};
 use crate::{AnsiTransactionManager, AsyncConnection, SimpleAsyncConnection};
 use diesel::connection::statement_cache::{PrepareForCache, StatementCacheKey};
@@ -20,24 +22,84 @@ use diesel::result::DatabaseErrorKind;
 use diesel::{ConnectionError, ConnectionResult, QueryResult};
 use futures_util::future::BoxFuture;
 use futures_util::future::Either;
-use futures_util::stream::{BoxStream, TryStreamExt};
+use futures_util::stream::TryStreamExt;
 use futures_util::TryFutureExt;
-use futures_util::{Future, FutureExt, StreamExt};
+use futures_util::{Future, FutureExt, Stream, StreamExt};
 use std::borrow::Cow;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::sync::broadcast;
+use tokio::sync::mpsc;
 use tokio::sync::oneshot;
+use tokio::sync::watch;
 use tokio::sync::Mutex;
-use tokio_postgres::types::ToSql;
+use tokio::sync::RwLock;
 use tokio_postgres::types::Type;
 use tokio_postgres::Statement;
 
-pub use self::transaction_builder::TransactionBuilder;
+pub use self::advisory_lock::{AdvisoryLock, AdvisoryLockNotHeld};
+pub use self::allow_list::{PolicyViolation, StatementAllowList};
+pub use self::audit::{AuditContext, AuditLogger, AuditRecord, AuditSink};
+#[cfg(feature = "serde_json")]
+pub use self::change_feed::{TableChange, TableChangeOperation};
+pub use self::column_metadata::ResultColumn;
+pub use self::constraints::ConstraintCheck;
+pub use self::dynamic_row::{DynamicRow, Value};
+#[cfg(feature = "serde_json")]
+pub use self::explain::{PlanNode, QueryPlan};
+pub use self::interceptor::{InterceptedQuery, QueryInterceptor, QueryOutcome};
+pub use self::large_object::{LargeObject, LargeObjectMode};
+pub use self::logical_decoding::LogicalChange;
+pub use self::pipeline::{PipelineLimit, PipelineLimitExceeded, PipelineMetrics};
+pub use self::prepared_query::PreparedQuery;
+pub use self::query_cache::{
+    MemoryQueryCacheStore, QueryCache, QueryCacheEntry, QueryCacheKey, QueryCacheStore,
+};
+pub use self::replica::{ReplicaGuard, ReplicaWriteError};
+pub use self::row::PgRowStream;
+pub use self::serialized_query::SerializedQuery;
+pub use self::session_config::{ConfigGuard, ConfigScope, SessionContext};
+pub use self::shared::SharedAsyncPgConnection;
+#[cfg(feature = "explain-slow-queries")]
+pub use self::slow_query::{SlowQuery, SlowQueryCallback};
+pub use self::statement_registry::StatementRegistry;
+#[cfg(feature = "postgres-rustls")]
+pub use self::tls::establish_with_rustls;
+pub use self::transaction_builder::{AdvisoryLockNotAcquired, IsolationLevel, TransactionBuilder};
+pub use self::work_queue::Claim;
 
+mod advisory_lock;
+mod allow_list;
+mod audit;
+#[cfg(feature = "serde_json")]
+mod change_feed;
+mod column_metadata;
+mod constraints;
+mod dynamic_row;
 mod error_helper;
+#[cfg(feature = "serde_json")]
+mod explain;
+mod interceptor;
+mod large_object;
+mod logical_decoding;
+mod pipeline;
+mod prepared_query;
+mod query_cache;
+mod replica;
 mod row;
 mod serialize;
+mod serialized_query;
+mod session_config;
+mod shared;
+#[cfg(feature = "explain-slow-queries")]
+mod slow_query;
+mod sql_scanner;
+mod statement_registry;
+#[cfg(feature = "postgres-rustls")]
+mod tls;
 mod transaction_builder;
+mod work_queue;
 
 /// A connection to a PostgreSQL database.
 ///
@@ -49,6 +111,16 @@ mod transaction_builder;
 ///
 /// [tokio_postgres]: https://docs.rs/tokio-postgres/0.7.6/tokio_postgres/config/struct.Config.html#url
 ///
+/// On Unix, connections to a local server can be established via its Unix domain
+/// socket by passing the directory containing the socket as host, which avoids the
+/// overhead of TCP and TLS. Both the key-value format
+/// `host=/var/run/postgresql user=postgres dbname=database_name` and URLs like
+/// `postgres://postgres@%2Fvar%2Frun%2Fpostgresql/database_name` are supported.
+/// The `port` parameter selects the socket file, like `.s.PGSQL.5432`. Like for
+/// `libpq` the `sslmode` parameter is ignored for connections that only use Unix
+/// domain sockets, so a URL requiring TLS for remote connections can be pointed to
+/// a local socket.
+///
 /// This connection supports *pipelined* requests. Pipelining can improve performance in use cases in which multiple,
 /// independent queries need to be executed. In a traditional workflow, each query is sent to the server after the
 /// previous query completes. In contrast, pipelining allows the client to send all of the queries to the server up
@@ -73,7 +145,9 @@ mod transaction_builder;
 /// the connection to work concurrently when possible.
 ///
 /// Pipelining happens automatically when futures are polled concurrently (for example, by using the futures `join`
-/// combinator):
+/// combinator). Starting a transaction or a savepoint fails with a
+/// [`diesel::result::Error::QueryBuilderError`] as long as futures of queries created from the connection did not
+/// finish or were dropped, as these queries would be executed inside of the transaction otherwise:
 ///
 /// ```rust
 /// # include!("../doctest_setup.rs");
@@ -104,23 +178,55 @@ mod transaction_builder;
 /// # }
 pub struct AsyncPgConnection {
     conn: Arc<tokio_postgres::Client>,
-    stmt_cache: Arc<Mutex<StmtCache<diesel::pg::Pg, Statement>>>,
-    transaction_state: Arc<Mutex<AnsiTransactionManager>>,
+    stmt_cache: Arc<RwLock<StmtCache<diesel::pg::Pg, Statement>>>,
+    bind_buffers: BindBufferPool,
+    statement_registry: Option<StatementRegistry>,
+    transaction_state: AnsiTransactionManager,
+    // Set by query futures that failed with a serialization failure.
+    // Query futures may outlive the borrow of the connection they were created from,
+    // so they cannot update `transaction_state` directly. The flag is applied to
+    // `transaction_state` as soon as it is accessed the next time.
+    serialization_failure: Arc<AtomicBool>,
     metadata_cache: Arc<Mutex<PgMetadataCache>>,
-    connection_future: Option<broadcast::Receiver<Arc<tokio_postgres::Error>>>,
-    shutdown_channel: Option<oneshot::Sender<()>>,
+    connection_error: Option<ConnectionErrorReceiver>,
+    shutdown_channel: ShutdownSender,
+    notifications: Option<mpsc::UnboundedReceiver<tokio_postgres::Notification>>,
+    connected_at: Instant,
+    pipeline_permits: Option<Arc<PipelinePermits>>,
+    pipeline_counters: Arc<PipelineCounters>,
+    // Set once the connection was reclaimed from the user holding it
+    reclaimed: Arc<AtomicBool>,
+    interceptors: InterceptorChain,
+    // Resets queued by dropped `ConfigGuard`s, applied before the next statement
+    config_resets: Vec<session_config::ConfigReset>,
+    // Advisory locks of dropped guards, released before the next statement
+    advisory_unlocks: advisory_lock::UnlockQueue,
+    // Slow queries explained before the next statement outside of a transaction
+    #[cfg(feature = "explain-slow-queries")]
+    slow_queries: slow_query::ExplainQueue,
 }
 
+// Stops the background task driving the connection once dropped
+struct ShutdownSender(Option<oneshot::Sender<()>>);
+
 #[async_trait::async_trait]
 impl SimpleAsyncConnection for AsyncPgConnection {
     async fn batch_execute(&mut self, query: &str) -> QueryResult<()> {
-        let connection_future = self.connection_future.as_ref().map(|rx| rx.resubscribe());
-        let batch_execute = self
-            .conn
-            .batch_execute(query)
-            .map_err(ErrorHelper)
-            .map_err(Into::into);
-        drive_future(connection_future, batch_execute).await
+        AnsiTransactionManager::rollback_abandoned_transaction(self).await?;
+        self.run_queued_statements().await?;
+        if self.interceptors.is_empty() {
+            let batch_execute = self
+                .conn
+                .batch_execute(query)
+                .map_err(ErrorHelper)
+                .map_err(Into::into);
+            return self.run_with_connection_future(batch_execute).await;
+        }
+        self.run_simple_query(
+            query,
+            |conn, sql| async move { conn.batch_execute(&sql).await },
+        )
+        .await
     }
 }
 
@@ -128,27 +234,19 @@ impl SimpleAsyncConnection for AsyncPgConnection {
 impl AsyncConnection for AsyncPgConnection {
     type LoadFuture<'conn, 'query> = BoxFuture<'query, QueryResult<Self::Stream<'conn, 'query>>>;
     type ExecuteFuture<'conn, 'query> = BoxFuture<'query, QueryResult<usize>>;
-    type Stream<'conn, 'query> = BoxStream<'static, QueryResult<PgRow>>;
+    type Stream<'conn, 'query> = PgRowStream;
     type Row<'conn, 'query> = PgRow;
     type Backend = diesel::pg::Pg;
     type TransactionManager = AnsiTransactionManager;
 
     async fn establish(database_url: &str) -> ConnectionResult<Self> {
-        let (client, connection) = tokio_postgres::connect(database_url, tokio_postgres::NoTls)
-            .await
-            .map_err(ErrorHelper)?;
-        let (tx, rx) = tokio::sync::broadcast::channel(1);
-        let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel();
-        tokio::spawn(async move {
-            match futures_util::future::select(shutdown_rx, connection).await {
-                Either::Left(_) | Either::Right((Ok(_), _)) => {}
-                Either::Right((Err(e), _)) => {
-                    let _ = tx.send(Arc::new(e));
-                }
-            }
-        });
+        Self::establish_with_tls(database_url, tokio_postgres::NoTls).await
+    }
 
-        Self::setup(client, Some(rx), Some(shutdown_tx)).await
+    async fn ping_connection(&mut self) -> QueryResult<()> {
+        // an empty query is the cheapest round trip
+        // the protocol offers, as there is nothing to parse or plan
+        self.batch_execute("").await
     }
 
     fn load<'conn, 'query, T>(&'conn mut self, source: T) -> Self::LoadFuture<'conn, 'query>
@@ -174,65 +272,86 @@ impl AsyncConnection for AsyncPgConnection {
     }
 
     fn transaction_state(&mut self) -> &mut AnsiTransactionManager {
-        // there should be no other pending future when this is called
-        // that means there is only one instance of this arc and
-        // we can simply access the inner data
-        if let Some(tm) = Arc::get_mut(&mut self.transaction_state) {
-            tm.get_mut()
-        } else {
-            panic!("Cannot access shared transaction state")
+        if self.serialization_failure.swap(false, Ordering::AcqRel) {
+            self.transaction_state
+                .status
+                .set_requires_rollback_maybe_up_to_top_level(true);
         }
+        &mut self.transaction_state
     }
 }
 
-impl Drop for AsyncPgConnection {
+impl Drop for ShutdownSender {
     fn drop(&mut self) {
-        if let Some(tx) = self.shutdown_channel.take() {
+        if let Some(tx) = self.0.take() {
             let _ = tx.send(());
         }
     }
 }
 
+// The statement a query is executed with
+enum QueryStatement {
+    Prepared(Statement),
+    // Parsed by the server as part of the execution, using the unnamed statement
+    Unprepared { sql: String, types: Vec<Type> },
+}
+
+impl QueryStatement {
+    fn unprepared(sql: String, metadata: &[PgTypeMetadata]) -> QueryResult<Self> {
+        let types = metadata
+            .iter()
+            .map(type_from_oid)
+            .collect::<QueryResult<Vec<_>>>()?;
+        Ok(Self::Unprepared { sql, types })
+    }
+}
+
 async fn load_prepared(
     conn: Arc<tokio_postgres::Client>,
-    stmt: Statement,
-    binds: Vec<ToSqlHelper>,
-) -> QueryResult<BoxStream<'static, QueryResult<PgRow>>> {
-    let res = conn.query_raw(&stmt, binds).await.map_err(ErrorHelper)?;
-
-    Ok(res
-        .map_err(|e| diesel::result::Error::from(ErrorHelper(e)))
-        .map_ok(PgRow::new)
-        .boxed())
+    stmt: QueryStatement,
+    binds: PooledBinds,
+) -> QueryResult<PgRowStream> {
+    let res = match stmt {
+        QueryStatement::Prepared(stmt) => conn.query_raw(&stmt, binds.iter()).await,
+        QueryStatement::Unprepared { sql, types } => {
+            conn.query_typed_raw(&sql, binds.iter().zip(types)).await
+        }
+    };
+    Ok(PgRowStream::new(res.map_err(ErrorHelper)?))
 }
 
 async fn execute_prepared(
     conn: Arc<tokio_postgres::Client>,
-    stmt: Statement,
-    binds: Vec<ToSqlHelper>,
+    stmt: QueryStatement,
+    binds: PooledBinds,
 ) -> QueryResult<usize> {
-    let binds = binds
-        .iter()
-        .map(|b| b as &(dyn ToSql + Sync))
-        .collect::<Vec<_>>();
-
-    let res = tokio_postgres::Client::execute(&conn, &stmt, &binds as &[_])
-        .await
-        .map_err(ErrorHelper)?;
-    Ok(res as usize)
+    let res = match stmt {
+        QueryStatement::Prepared(stmt) => conn.execute_raw(&stmt, binds.iter()).await,
+        QueryStatement::Unprepared { sql, types } => {
+            let rows = conn.query_typed_raw(&sql, binds.iter().zip(types)).await;
+            match rows {
+                Ok(rows) => {
+                    let mut rows = std::pin::pin!(rows);
+                    // the number of affected rows is only known once all rows were received
+                    while rows.try_next().await.map_err(ErrorHelper)?.is_some() {}
+                    Ok(rows.rows_affected().unwrap_or_default())
+                }
+                Err(e) => Err(e),
+            }
+        }
+    };
+    Ok(res.map_err(ErrorHelper)? as usize)
 }
 
 #[inline(always)]
 fn update_transaction_manager_status<T>(
     query_result: QueryResult<T>,
-    transaction_manager: &mut AnsiTransactionManager,
+    serialization_failure: &AtomicBool,
 ) -> QueryResult<T> {
     if let Err(diesel::result::Error::DatabaseError(DatabaseErrorKind::SerializationFailure, _)) =
         query_result
     {
-        transaction_manager
-            .status
-            .set_requires_rollback_maybe_up_to_top_level(true)
+        serialization_failure.store(true, Ordering::Release);
     }
     query_result
 }
@@ -258,6 +377,54 @@ impl PrepareCallback<Statement, PgTypeMetadata> for Arc<tokio_postgres::Client>
     }
 }
 
+const CONNECTION_TASK_NAME: &str = "diesel_async::pg connection";
+
+fn connection_task_name(config: &tokio_postgres::Config) -> String {
+    let host = match config.get_hosts().first() {
+        Some(tokio_postgres::config::Host::Tcp(host)) => host.clone(),
+        #[cfg(unix)]
+        Some(tokio_postgres::config::Host::Unix(path)) => path.display().to_string(),
+        None => String::from("localhost"),
+    };
+    let port = config.get_ports().first().copied().unwrap_or(5432);
+    let dbname = config
+        .get_dbname()
+        .or(config.get_user())
+        .unwrap_or_default();
+    format!("{CONNECTION_TASK_NAME} {host}:{port}/{dbname}")
+}
+
+fn is_unix_socket_only(config: &tokio_postgres::Config) -> bool {
+    let hosts = config.get_hosts();
+    #[cfg(unix)]
+    let is_unix =
+        |host: &tokio_postgres::config::Host| matches!(host, tokio_postgres::config::Host::Unix(_));
+    #[cfg(not(unix))]
+    let is_unix = |_: &tokio_postgres::config::Host| false;
+    // `hostaddr` takes precedence over the host and always uses TCP
+    config.get_hostaddrs().is_empty() && !hosts.is_empty() && hosts.iter().all(is_unix)
+}
+
+fn spawn_connection_task<F>(name: &str, task: F)
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    #[cfg(all(feature = "tokio-console", tokio_unstable))]
+    tokio::task::Builder::new()
+        .name(name)
+        .spawn(task)
+        .expect("Failed to spawn the connection task");
+    #[cfg(not(all(feature = "tokio-console", tokio_unstable)))]
+    {
+        let _ = name;
+        tokio::spawn(task);
+    }
+}
+
+pub(crate) fn quote_identifier(identifier: &str) -> String {
+    format!("\"{}\"", identifier.replace('"', "\"\""))
+}
+
 fn type_from_oid(t: &PgTypeMetadata) -> QueryResult<Type> {
     let oid = t
         .oid()
@@ -308,26 +475,171 @@ impl AsyncPgConnection {
 
     /// Construct a new `AsyncPgConnection` instance from an existing [`tokio_postgres::Client`]
     /// with additional arguments that are hidden from [`Self::setup`].
+    ///
+    /// The first error received via `connection_future` is latched and reported
+    /// by all pending and future operations on the returned connection,
+    /// see [`AsyncPgConnection::connection_error`]. The capacity of the broadcast
+    /// channel is up to the caller, if the receiver lagged behind the oldest
+    /// error still retained by the channel is reported instead.
     pub async fn try_from(
         conn: tokio_postgres::Client,
         connection_future: Option<broadcast::Receiver<Arc<tokio_postgres::Error>>>,
         shutdown_channel: Option<oneshot::Sender<()>>,
     ) -> ConnectionResult<Self> {
-        Self::setup(conn, connection_future, shutdown_channel).await
+        let connection_error = connection_future.map(|mut connection_future| {
+            let (error_tx, error_rx) = watch::channel(None);
+            tokio::spawn(async move {
+                loop {
+                    match connection_future.recv().await {
+                        Ok(e) => {
+                            let _ = error_tx.send(Some(e));
+                            break;
+                        }
+                        Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                        Err(broadcast::error::RecvError::Closed) => break,
+                    }
+                }
+            });
+            error_rx
+        });
+        Self::setup(conn, connection_error, shutdown_channel, None).await
+    }
+
+    /// Constructs a new `AsyncPgConnection` from an existing [`tokio_postgres::Client`] and
+    /// [`tokio_postgres::Connection`]
+    ///
+    /// This spawns a background task driving the connection, which also
+    /// forwards notifications received from the database. These are available
+    /// via [`AsyncPgConnection::notifications_stream`].
+    ///
+    /// With the `tokio-console` feature enabled and the `tokio_unstable` cfg
+    /// set, the background task is named, so tools like `tokio-console` can
+    /// attribute its work to database connections. Connections established via
+    /// [`AsyncConnection::establish`] include the host and database in this name.
+    pub async fn try_from_client_and_connection<S>(
+        client: tokio_postgres::Client,
+        connection: tokio_postgres::Connection<tokio_postgres::Socket, S>,
+    ) -> ConnectionResult<Self>
+    where
+        S: tokio_postgres::tls::TlsStream + Unpin + Send + 'static,
+    {
+        Self::from_client_and_connection(client, connection, String::from(CONNECTION_TASK_NAME))
+            .await
+    }
+
+    /// Establish a connection to the given database URL, using the given
+    /// TLS connector to encrypt the connection
+    ///
+    /// The TLS connector is usually provided by a crate like
+    /// `tokio-postgres-rustls` or `postgres-native-tls`. Whether TLS is
+    /// required is controlled by the `sslmode` parameter of the URL. See
+    /// [`TlsConfig`](crate::pooled_connection::TlsConfig) to use a TLS
+    /// connector for pooled connections.
+    pub async fn establish_with_tls<T>(database_url: &str, tls: T) -> ConnectionResult<Self>
+    where
+        T: tokio_postgres::tls::MakeTlsConnect<tokio_postgres::Socket>,
+        T::Stream: Send + 'static,
+        T::TlsConnect: Send,
+        <T::TlsConnect as tokio_postgres::tls::TlsConnect<tokio_postgres::Socket>>::Future: Send,
+    {
+        let config = database_url
+            .parse::<tokio_postgres::Config>()
+            .map_err(ErrorHelper)?;
+        Self::establish_with_config(config, tls).await
+    }
+
+    // Establishes a connection with an already parsed configuration
+    pub(crate) async fn establish_with_config<T>(
+        mut config: tokio_postgres::Config,
+        tls: T,
+    ) -> ConnectionResult<Self>
+    where
+        T: tokio_postgres::tls::MakeTlsConnect<tokio_postgres::Socket>,
+        T::Stream: Send + 'static,
+        T::TlsConnect: Send,
+        <T::TlsConnect as tokio_postgres::tls::TlsConnect<tokio_postgres::Socket>>::Future: Send,
+    {
+        if is_unix_socket_only(&config) {
+            // `libpq` never negotiates TLS over unix domain sockets, which
+            // would fail for servers without TLS or with connectors that
+            // require a host name
+            config.ssl_mode(tokio_postgres::config::SslMode::Disable);
+        }
+        let (client, connection) = config.connect(tls).await.map_err(ErrorHelper)?;
+        Self::from_client_and_connection(client, connection, connection_task_name(&config)).await
+    }
+
+    async fn from_client_and_connection<S>(
+        client: tokio_postgres::Client,
+        mut connection: tokio_postgres::Connection<tokio_postgres::Socket, S>,
+        task_name: String,
+    ) -> ConnectionResult<Self>
+    where
+        S: tokio_postgres::tls::TlsStream + Unpin + Send + 'static,
+    {
+        let (error_tx, error_rx) = watch::channel(None);
+        let (notification_tx, notification_rx) = tokio::sync::mpsc::unbounded_channel();
+        let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel();
+        let connection = futures_util::stream::poll_fn(move |cx| connection.poll_message(cx))
+            .try_for_each(move |message| {
+                if let tokio_postgres::AsyncMessage::Notification(notification) = message {
+                    // the receiver might be gone, but that's
+                    // no reason to stop driving the connection
+                    let _ = notification_tx.send(notification);
+                }
+                futures_util::future::ready(Ok(()))
+            });
+        let shutdown = Box::pin(async move {
+            if shutdown_rx.await.is_err() {
+                // The sender was dropped without sending by `AsyncPgConnection::into_client`,
+                // so the returned client still relies on this task
+                futures_util::future::pending::<()>().await;
+            }
+        });
+        spawn_connection_task(&task_name, async move {
+            match futures_util::future::select(shutdown, connection).await {
+                Either::Left(_) | Either::Right((Ok(_), _)) => {}
+                Either::Right((Err(e), _)) => {
+                    let _ = error_tx.send(Some(Arc::new(e)));
+                }
+            }
+        });
+
+        Self::setup(
+            client,
+            Some(error_rx),
+            Some(shutdown_tx),
+            Some(notification_rx),
+        )
+        .await
     }
 
     async fn setup(
         conn: tokio_postgres::Client,
-        connection_future: Option<broadcast::Receiver<Arc<tokio_postgres::Error>>>,
+        connection_error: Option<ConnectionErrorReceiver>,
         shutdown_channel: Option<oneshot::Sender<()>>,
+        notifications: Option<mpsc::UnboundedReceiver<tokio_postgres::Notification>>,
     ) -> ConnectionResult<Self> {
         let mut conn = Self {
             conn: Arc::new(conn),
-            stmt_cache: Arc::new(Mutex::new(StmtCache::new())),
-            transaction_state: Arc::new(Mutex::new(AnsiTransactionManager::default())),
+            stmt_cache: Arc::new(RwLock::new(StmtCache::new())),
+            bind_buffers: BindBufferPool::default(),
+            statement_registry: None,
+            transaction_state: AnsiTransactionManager::default(),
+            serialization_failure: Arc::new(AtomicBool::new(false)),
             metadata_cache: Arc::new(Mutex::new(PgMetadataCache::new())),
-            connection_future,
-            shutdown_channel,
+            connection_error,
+            shutdown_channel: ShutdownSender(shutdown_channel),
+            notifications,
+            connected_at: Instant::now(),
+            pipeline_permits: None,
+            pipeline_counters: Arc::default(),
+            reclaimed: Arc::new(AtomicBool::new(false)),
+            interceptors: InterceptorChain::default(),
+            config_resets: Vec::new(),
+            advisory_unlocks: Arc::default(),
+            #[cfg(feature = "explain-slow-queries")]
+            slow_queries: Arc::default(),
         };
         conn.set_config_options()
             .await
@@ -335,11 +647,387 @@ impl AsyncPgConnection {
         Ok(conn)
     }
 
+    /// Roll back all open transactions and savepoints to bring this connection
+    /// back into a usable state
+    ///
+    /// Connection pools discard connections with an open transaction or a
+    /// broken transaction manager. Call this function to recover such a
+    /// connection instead. See [`AnsiTransactionManager::rollback_to_clean_state`]
+    /// for details.
+    pub async fn rollback_to_clean_state(&mut self) -> QueryResult<()> {
+        AnsiTransactionManager::rollback_to_clean_state(self).await
+    }
+
+    /// Checks whether this connection is usable, waiting at most `timeout`
+    /// for the database to respond
+    ///
+    /// This returns `false` if the connection was closed, if the database
+    /// responded with an error or if it did not respond in time, see
+    /// [`AsyncConnection::ping_connection`]. Connections that are not valid should be
+    /// discarded.
+    ///
+    /// ```rust
+    /// # include!("../doctest_setup.rs");
+    /// use std::time::Duration;
+    /// #
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// #     let conn = &mut establish_connection().await;
+    /// assert!(conn.is_valid(Duration::from_secs(1)).await);
+    /// # }
+    /// ```
+    pub async fn is_valid(&mut self, timeout: Duration) -> bool {
+        if self.conn.is_closed() {
+            return false;
+        }
+        matches!(
+            tokio::time::timeout(timeout, AsyncConnection::ping_connection(self)).await,
+            Ok(Ok(()))
+        )
+    }
+
+    /// Returns the error that terminated the background task driving this connection
+    ///
+    /// Once the connection failed, all pending and future operations
+    /// on this connection return this error. Returns `None` as long as the
+    /// connection is healthy or if the connection was constructed via
+    /// [`AsyncPgConnection::try_from`] without a `connection_future`.
+    pub fn connection_error(&self) -> Option<Arc<tokio_postgres::Error>> {
+        self.connection_error
+            .as_ref()
+            .and_then(|connection_error| connection_error.borrow().clone())
+    }
+
+    /// Returns a future that resolves once the background task driving this
+    /// connection terminated
+    ///
+    /// The returned future does not borrow the connection, so it can be spawned
+    /// to observe the lifecycle of the connection, for example to log
+    /// connections closed by the server. It resolves as well once the
+    /// connection itself is dropped. For connections constructed via
+    /// [`AsyncPgConnection::try_from`] without a `connection_future` it
+    /// never resolves.
+    ///
+    /// ```rust
+    /// # include!("../doctest_setup.rs");
+    /// #
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// #     run_test().await.unwrap();
+    /// # }
+    /// #
+    /// # async fn run_test() -> QueryResult<()> {
+    /// #     let conn = connection_no_transaction().await;
+    /// let closed = conn.closed();
+    /// drop(conn);
+    /// let closed = closed.await;
+    /// assert!(closed.error.is_none());
+    /// println!("Connection closed after {:?}", closed.connected_for);
+    /// #     Ok(())
+    /// # }
+    /// ```
+    pub fn closed(&self) -> impl Future<Output = ConnectionClosed> + Send + 'static {
+        let connection_error = self.connection_error.clone();
+        let connected_at = self.connected_at;
+        async move {
+            let error = match connection_error {
+                Some(connection_error) => wait_for_connection_close(connection_error).await,
+                None => futures_util::future::pending().await,
+            };
+            ConnectionClosed {
+                connected_for: connected_at.elapsed(),
+                error,
+            }
+        }
+    }
+
+    /// Decompose this connection into the underlying [`tokio_postgres::Client`]
+    ///
+    /// This fails and returns the connection again as long as futures of pipelined
+    /// queries created from this connection are still alive. The background task
+    /// driving a connection created via [`AsyncConnection::establish`] or
+    /// [`AsyncPgConnection::try_from_client_and_connection`] keeps running until
+    /// the returned client is dropped. Notifications are no longer forwarded. For
+    /// connections created via [`AsyncPgConnection::try_from`] the shutdown channel
+    /// is dropped without sending a message.
+    ///
+    /// Open transactions are not rolled back and session settings applied by
+    /// diesel-async (like `DateStyle`) remain in place.
+    ///
+    /// ```rust
+    /// # include!("../doctest_setup.rs");
+    /// #
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// #     run_test().await.unwrap();
+    /// # }
+    /// #
+    /// # async fn run_test() -> QueryResult<()> {
+    /// #     let conn = connection_no_transaction().await;
+    /// let client = conn.into_client().unwrap_or_else(|_| panic!("Queries still in flight"));
+    /// let row = client.query_one("SELECT 1 + 1", &[]).await.unwrap();
+    /// assert_eq!(2, row.get::<_, i32>(0));
+    /// #     Ok(())
+    /// # }
+    /// ```
+    // returning the connection itself is the point of the error variant
+    #[allow(clippy::result_large_err)]
+    pub fn into_client(self) -> Result<tokio_postgres::Client, Self> {
+        // Query futures hold the only other references to the client and
+        // new references can only be created via `self`
+        if Arc::strong_count(&self.conn) != 1 {
+            return Err(self);
+        }
+        let Self {
+            conn,
+            mut shutdown_channel,
+            ..
+        } = self;
+        drop(shutdown_channel.0.take());
+        match Arc::try_unwrap(conn) {
+            Ok(client) => Ok(client),
+            Err(_) => unreachable!("The reference count was checked above"),
+        }
+    }
+
+    /// Close this connection gracefully
+    ///
+    /// Dropping a connection stops the background task driving it right away,
+    /// which abandons queries still in flight and closes the socket without
+    /// telling the server. This function instead waits until the futures of
+    /// all pipelined queries created from this connection completed or were
+    /// dropped, sends a `Terminate` message to the server and waits until the
+    /// background task finished. Open transactions are rolled back by the server.
+    ///
+    /// Wrap the returned future into a timeout to bound the time spent waiting
+    /// for queries in flight, dropping the future closes the connection the same
+    /// way as dropping the connection itself. To abort running queries instead
+    /// of waiting for them, cancel them via [`AsyncPgConnection::cancel_token`]
+    /// first.
+    ///
+    /// Returns the error the connection failed with, if any. For connections
+    /// created via [`AsyncPgConnection::try_from`] the shutdown channel is dropped
+    /// without sending a message, and the function only waits for the background
+    /// task if a `connection_future` was passed.
+    ///
+    /// ```rust
+    /// # include!("../doctest_setup.rs");
+    /// #
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// #     run_test().await.unwrap();
+    /// # }
+    /// #
+    /// # async fn run_test() -> QueryResult<()> {
+    /// #     let conn = connection_no_transaction().await;
+    /// conn.close().await?;
+    /// #     Ok(())
+    /// # }
+    /// ```
+    pub async fn close(self) -> QueryResult<()> {
+        let Self {
+            conn,
+            mut shutdown_channel,
+            connection_error,
+            ..
+        } = self;
+        // Dropping the sender without sending keeps the background task running,
+        // until all references to the client are gone. The connection then
+        // sends `Terminate` and completes the background task
+        drop(shutdown_channel.0.take());
+        drop(conn);
+        let error = match connection_error {
+            Some(connection_error) => wait_for_connection_close(connection_error).await,
+            None => None,
+        };
+        match error {
+            Some(e) => Err(self::error_helper::from_tokio_postgres_error(e)),
+            None => Ok(()),
+        }
+    }
+
+    /// Execute the given SQL via the simple query protocol, returning
+    /// the rows and row counts reported for each statement
+    ///
+    /// Unlike [`SimpleAsyncConnection::batch_execute`] this keeps the rows
+    /// returned by the statements. This is useful for scripts consisting of
+    /// multiple statements and for commands that cannot be prepared. The
+    /// simple query protocol does not support bind parameters and returns all
+    /// values in their text representation.
+    ///
+    /// ```rust
+    /// # include!("../doctest_setup.rs");
+    /// use tokio_postgres::SimpleQueryMessage;
+    /// #
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// #     run_test().await.unwrap();
+    /// # }
+    /// #
+    /// # async fn run_test() -> QueryResult<()> {
+    /// #     let conn = &mut establish_connection().await;
+    /// let messages = conn
+    ///     .simple_query("UPDATE users SET name = upper(name); SELECT name FROM users ORDER BY id")
+    ///     .await?;
+    /// let names = messages
+    ///     .iter()
+    ///     .filter_map(|message| match message {
+    ///         SimpleQueryMessage::Row(row) => row.get(0),
+    ///         _ => None,
+    ///     })
+    ///     .collect::<Vec<_>>();
+    /// assert_eq!(vec!["SEAN", "TESS"], names);
+    /// assert!(matches!(messages[0], SimpleQueryMessage::CommandComplete(2)));
+    /// #     Ok(())
+    /// # }
+    /// ```
+    pub async fn simple_query(
+        &mut self,
+        sql: &str,
+    ) -> QueryResult<Vec<tokio_postgres::SimpleQueryMessage>> {
+        self.run_simple_query(
+            sql,
+            |conn, sql| async move { conn.simple_query(&sql).await },
+        )
+        .await
+    }
+
+    /// Execute the given SQL like [`SimpleAsyncConnection::batch_execute`],
+    /// returning the number of rows affected by each statement
+    ///
+    /// The counts are returned in the order of the statements. Statements
+    /// returning rows, like `SELECT`, report the number of returned rows, while
+    /// statements without a row count, like `CREATE TABLE`, report `0`. This is
+    /// useful for migration-style scripts that need to verify their effects.
+    ///
+    /// ```rust
+    /// # include!("../doctest_setup.rs");
+    /// #
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// #     run_test().await.unwrap();
+    /// # }
+    /// #
+    /// # async fn run_test() -> QueryResult<()> {
+    /// #     let conn = &mut establish_connection().await;
+    /// let counts = conn
+    ///     .batch_execute_with_row_counts(
+    ///         "INSERT INTO users (name) VALUES ('Ruby'), ('Jim');
+    ///          UPDATE users SET name = 'Tom' WHERE name = 'Jim';
+    ///          DELETE FROM users WHERE name = 'Nobody'",
+    ///     )
+    ///     .await?;
+    /// assert_eq!(vec![2, 1, 0], counts);
+    /// #     Ok(())
+    /// # }
+    /// ```
+    pub async fn batch_execute_with_row_counts(&mut self, sql: &str) -> QueryResult<Vec<usize>> {
+        self.run_simple_query(sql, |conn, sql| async move {
+            let messages = conn.simple_query(&sql).await?;
+            Ok(messages
+                .into_iter()
+                .filter_map(|message| match message {
+                    tokio_postgres::SimpleQueryMessage::CommandComplete(count) => {
+                        Some(count as usize)
+                    }
+                    _ => None,
+                })
+                .collect())
+        })
+        .await
+    }
+
     /// Constructs a cancellation token that can later be used to request cancellation of a query running on the connection associated with this client.
     pub fn cancel_token(&self) -> tokio_postgres::CancelToken {
         self.conn.cancel_token()
     }
 
+    /// Returns a stream of the notifications received by this connection
+    ///
+    /// Use `LISTEN` to subscribe to a notification channel first.
+    /// Notifications are only forwarded for connections created via
+    /// [`AsyncConnection::establish`] or
+    /// [`AsyncPgConnection::try_from_client_and_connection`]. For other
+    /// connections the returned stream is empty. Notifications received
+    /// while the stream is not polled are buffered.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # include!("../doctest_setup.rs");
+    /// use diesel_async::SimpleAsyncConnection;
+    /// use futures_util::StreamExt;
+    /// #
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// #     run_test().await.unwrap();
+    /// # }
+    /// #
+    /// # async fn run_test() -> QueryResult<()> {
+    /// #     let conn = &mut connection_no_transaction().await;
+    /// conn.batch_execute("LISTEN example_channel").await?;
+    /// conn.batch_execute("NOTIFY example_channel, 'Hello'").await?;
+    ///
+    /// let notification = conn.notifications_stream().next().await.unwrap()?;
+    /// assert_eq!("example_channel", notification.channel());
+    /// assert_eq!("Hello", notification.payload());
+    /// #     Ok(())
+    /// # }
+    /// ```
+    pub fn notifications_stream(
+        &mut self,
+    ) -> impl Stream<Item = QueryResult<tokio_postgres::Notification>> + Send + '_ {
+        match self.notifications {
+            Some(ref mut notifications) => {
+                futures_util::stream::poll_fn(move |cx| notifications.poll_recv(cx))
+                    .map(Ok)
+                    .left_stream()
+            }
+            None => futures_util::stream::empty().right_stream(),
+        }
+    }
+
+    /// Prepare the statement for the given query and store it in the
+    /// statement cache of this connection, without executing the query
+    ///
+    /// Later executions of queries of the same type reuse the cached statement.
+    /// This is mostly useful to warm up new connections, see
+    /// [`ManagerConfig::warm_up`](crate::pooled_connection::ManagerConfig::warm_up).
+    /// Queries that are not safe to cache are not prepared at all.
+    ///
+    /// ```rust
+    /// # include!("../doctest_setup.rs");
+    /// #
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// #     run_test().await.unwrap();
+    /// # }
+    /// #
+    /// # async fn run_test() -> QueryResult<()> {
+    /// # use diesel_async::RunQueryDsl;
+    /// #     use schema::users;
+    /// #     let conn = &mut establish_connection().await;
+    /// conn.prepare_cached(users::table.select(users::name).filter(users::id.eq(0)))
+    ///     .await?;
+    ///
+    /// // uses the already prepared statement
+    /// let name = users::table
+    ///     .select(users::name)
+    ///     .filter(users::id.eq(1))
+    ///     .get_result::<String>(conn)
+    ///     .await?;
+    /// assert_eq!("Sean", name);
+    /// #     Ok(())
+    /// # }
+    /// ```
+    pub async fn prepare_cached<T>(&mut self, query: T) -> QueryResult<()>
+    where
+        T: QueryFragment<diesel::pg::Pg> + QueryId + Send,
+    {
+        let prepare = self.with_prepared_statement(query, |_, _, _| async { Ok(()) });
+        self.run_with_connection_future(prepare).await
+    }
+
     async fn set_config_options(&mut self) -> QueryResult<()> {
         use crate::run_query_dsl::RunQueryDsl;
 
@@ -350,23 +1038,102 @@ impl AsyncPgConnection {
         Ok(())
     }
 
+    // Runs the statements queued on this connection before the next statement
+    async fn run_queued_statements(&mut self) -> QueryResult<()> {
+        advisory_lock::unlock_dropped_locks(&self.conn, &self.advisory_unlocks).await;
+        #[cfg(feature = "explain-slow-queries")]
+        {
+            let pending = self.take_pending_explains();
+            slow_query::explain_pending(&self.conn, pending).await;
+        }
+        if self.config_resets.is_empty() {
+            return Ok(());
+        }
+        let resets = std::mem::take(&mut self.config_resets);
+        session_config::apply_config_resets(&self.conn, resets).await
+    }
+
+    // Slow queries are only explained outside of a transaction, so that
+    // a failing `EXPLAIN` does not abort a transaction of the application
+    #[cfg(feature = "explain-slow-queries")]
+    fn take_pending_explains(&mut self) -> Vec<slow_query::PendingExplain> {
+        if !matches!(self.transaction_state.status.transaction_depth(), Ok(None)) {
+            return Vec::new();
+        }
+        std::mem::take(&mut *self.slow_queries.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+
     fn run_with_connection_future<'a, R: 'a>(
         &self,
         future: impl Future<Output = QueryResult<R>> + Send + 'a,
     ) -> BoxFuture<'a, QueryResult<R>> {
-        let connection_future = self.connection_future.as_ref().map(|rx| rx.resubscribe());
-        drive_future(connection_future, future).boxed()
+        let connection_error = self.connection_error.clone();
+        let permits = self.pipeline_permits.clone();
+        let counters = self.pipeline_counters.clone();
+        let reclaimed = self.reclaimed.clone();
+        let pending = pipeline::PendingQuery::new(&self.transaction_state.pending_queries);
+        async move {
+            let _pending = pending;
+            if reclaimed.load(Ordering::Acquire) {
+                return Err(diesel::result::Error::DatabaseError(
+                    DatabaseErrorKind::UnableToSendCommand,
+                    Box::new(String::from(
+                        "The connection was reclaimed after exceeding the maximum checkout duration",
+                    )),
+                ));
+            }
+            // the operation is only sent to the database once `future` is polled,
+            // so holding the permit until then bounds the operations in flight
+            let _permit = match permits {
+                Some(ref permits) => Some(permits.acquire(&counters).await?),
+                None => None,
+            };
+            let _in_flight = counters.start_operation();
+            drive_future(connection_error, future).await
+        }
+        .boxed()
+    }
+
+    // Executes a statement via the simple query protocol,
+    // passing it through the interceptors of this connection
+    async fn run_simple_query<R, F, Fut>(&mut self, sql: &str, execute: F) -> QueryResult<R>
+    where
+        F: FnOnce(Arc<tokio_postgres::Client>, String) -> Fut,
+        Fut: Future<Output = Result<R, tokio_postgres::Error>> + Send + 'static,
+        R: QueryOutput + Send + 'static,
+    {
+        let pending = if self.interceptors.is_empty() {
+            None
+        } else {
+            Some(self.interceptors.before_query(InterceptedQuery::new(
+                sql.to_owned(),
+                Vec::new(),
+                Vec::new(),
+                None,
+            ))?)
+        };
+        let sql = pending
+            .as_ref()
+            .map_or(sql, |pending| pending.query().sql());
+        let execute = execute(self.conn.clone(), sql.to_owned())
+            .map_err(ErrorHelper)
+            .map_err(Into::into);
+        let res = self.run_with_connection_future(execute).await;
+        if let Some(pending) = pending {
+            pending.finish(&res);
+        }
+        res
     }
 
     fn with_prepared_statement<'a, T, F, R>(
         &mut self,
         query: T,
-        callback: fn(Arc<tokio_postgres::Client>, Statement, Vec<ToSqlHelper>) -> F,
-    ) -> BoxFuture<'a, QueryResult<R>>
+        callback: fn(Arc<tokio_postgres::Client>, QueryStatement, PooledBinds) -> F,
+    ) -> impl Future<Output = QueryResult<R>> + Send + 'a
     where
         T: QueryFragment<diesel::pg::Pg> + QueryId,
         F: Future<Output = QueryResult<R>> + Send + 'a,
-        R: Send,
+        R: QueryOutput + Send,
     {
         // we explicilty descruct the query here before going into the async block
         //
@@ -377,7 +1144,7 @@ impl AsyncPgConnection {
         // so there is no need to even access the query in the async block below
         let mut query_builder = PgQueryBuilder::default();
 
-        let mut bind_collector = RawBytesBindCollector::<diesel::pg::Pg>::new();
+        let mut bind_collector = self.bind_buffers.bind_collector();
 
         // we don't resolve custom types here yet, we do that later
         // in the async block below as we might need to perform lookup
@@ -402,7 +1169,7 @@ impl AsyncPgConnection {
 
     fn with_prepared_statement_after_sql_built<'a, F, R>(
         &mut self,
-        callback: fn(Arc<tokio_postgres::Client>, Statement, Vec<ToSqlHelper>) -> F,
+        callback: fn(Arc<tokio_postgres::Client>, QueryStatement, PooledBinds) -> F,
         is_safe_to_cache_prepared: QueryResult<bool>,
         query_id: Option<std::any::TypeId>,
         to_sql_result: QueryResult<()>,
@@ -410,90 +1177,190 @@ impl AsyncPgConnection {
         query_builder: PgQueryBuilder,
         mut bind_collector: RawBytesBindCollector<Pg>,
         metadata_lookup: PgAsyncMetadataLookup,
-    ) -> BoxFuture<'a, QueryResult<R>>
+    ) -> impl Future<Output = QueryResult<R>> + Send + 'a
     where
         F: Future<Output = QueryResult<R>> + Send + 'a,
-        R: Send,
+        R: QueryOutput + Send,
     {
         let raw_connection = self.conn.clone();
         let stmt_cache = self.stmt_cache.clone();
         let metadata_cache = self.metadata_cache.clone();
-        let tm = self.transaction_state.clone();
+        let serialization_failure = self.serialization_failure.clone();
+        let bind_buffers = self.bind_buffers.clone();
+        let statement_registry = self.statement_registry.clone();
+        let interceptors = self.interceptors.clone();
+        let abandoned = self.transaction_state.take_abandoned();
+        let config_resets = std::mem::take(&mut self.config_resets);
+        let advisory_unlocks = self.advisory_unlocks.clone();
+        #[cfg(feature = "explain-slow-queries")]
+        let pending_explains = self.take_pending_explains();
 
         async move {
-            let sql = to_sql_result.map(|_| query_builder.finish())?;
+            if let Some(abandoned) = abandoned {
+                raw_connection
+                    .batch_execute("ROLLBACK")
+                    .await
+                    .map_err(ErrorHelper)?;
+                abandoned.finish();
+            }
+            advisory_lock::unlock_dropped_locks(&raw_connection, &advisory_unlocks).await;
+            #[cfg(feature = "explain-slow-queries")]
+            slow_query::explain_pending(&raw_connection, pending_explains).await;
+            session_config::apply_config_resets(&raw_connection, config_resets).await?;
+            let mut sql = to_sql_result.map(|_| query_builder.finish())?;
             let is_safe_to_cache_prepared = is_safe_to_cache_prepared?;
             collect_bind_result?;
-            // Check whether we need to resolve some types at all
-            //
-            // If the user doesn't use custom types there is no need
-            // to borther with that at all
-            if !metadata_lookup.unresolved_types.is_empty() {
-                let metadata_cache = &mut *metadata_cache.lock().await;
-                let mut next_unresolved = metadata_lookup.unresolved_types.into_iter();
-                for m in &mut bind_collector.metadata {
-                    // for each unresolved item
-                    // we check whether it's arleady in the cache
-                    // or perform a lookup and insert it into the cache
-                    if m.oid().is_err() {
-                        if let Some((ref schema, ref lookup_type_name)) = next_unresolved.next() {
-                            let cache_key = PgMetadataCacheKey::new(
-                                schema.as_ref().map(Into::into),
-                                lookup_type_name.into(),
-                            );
-                            if let Some(entry) = metadata_cache.lookup_type(&cache_key) {
-                                *m = entry;
-                            } else {
-                                let type_metadata = lookup_type(
-                                    schema.clone(),
-                                    lookup_type_name.clone(),
-                                    &raw_connection,
-                                )
+            resolve_types(
+                metadata_lookup.unresolved_types,
+                &mut bind_collector.metadata,
+                &metadata_cache,
+                &raw_connection,
+            )
+            .await?;
+            let mut query_id = query_id;
+            let pending = if interceptors.is_empty() {
+                None
+            } else {
+                let query = InterceptedQuery::new(
+                    sql,
+                    std::mem::take(&mut bind_collector.binds),
+                    bind_collector.metadata.clone(),
+                    query_id,
+                );
+                let pending = interceptors.before_query(query)?;
+                sql = pending.query().sql().to_owned();
+                bind_collector.binds = pending.query().binds().to_vec();
+                query_id = pending.query().query_id();
+                Some(pending)
+            };
+            let binds = bind_buffers.binds(bind_collector);
+            let res = if is_safe_to_cache_prepared {
+                execute_cached(
+                    callback,
+                    raw_connection,
+                    &stmt_cache,
+                    statement_registry.as_ref(),
+                    sql,
+                    query_id,
+                    binds,
+                )
+                .await
+            } else {
+                // Statements that are not cached are parsed as part of their execution,
+                // which saves the round trips to prepare and to close them
+                match QueryStatement::unprepared(sql, binds.metadata()) {
+                    Ok(statement) => callback(raw_connection, statement, binds).await,
+                    Err(e) => Err(e),
+                }
+            };
+            let res = update_transaction_manager_status(res, &serialization_failure);
+            if let Some(pending) = pending {
+                pending.finish(&res);
+            }
+            res
+        }
+    }
+}
+
+// Executes a query via the statement cache, preparing
+// and caching the statement if it is not cached yet
+async fn execute_cached<F, R>(
+    callback: fn(Arc<tokio_postgres::Client>, QueryStatement, PooledBinds) -> F,
+    raw_connection: Arc<tokio_postgres::Client>,
+    stmt_cache: &RwLock<StmtCache<diesel::pg::Pg, Statement>>,
+    statement_registry: Option<&StatementRegistry>,
+    sql: String,
+    query_id: Option<std::any::TypeId>,
+    binds: PooledBinds,
+) -> QueryResult<R>
+where
+    F: Future<Output = QueryResult<R>>,
+{
+    let key = match query_id {
+        Some(id) => StatementCacheKey::Type(id),
+        None => StatementCacheKey::Sql {
+            sql: sql.clone(),
+            bind_types: binds.metadata().to_vec(),
+        },
+    };
+    // Cache hits only need shared access, so that pipelined queries
+    // do not wait on each other for already prepared statements
+    let (cached, accepted) = {
+        let stmt_cache = stmt_cache.read().await;
+        (stmt_cache.get(&key).cloned(), stmt_cache.accepts(&key))
+    };
+    if let Some(stmt) = cached {
+        return callback(raw_connection, QueryStatement::Prepared(stmt), binds).await;
+    }
+    if !accepted {
+        let statement = QueryStatement::unprepared(sql, binds.metadata())?;
+        return callback(raw_connection, statement, binds).await;
+    }
+    if let Some(statement_registry) = statement_registry {
+        statement_registry.record(query_id, &sql, binds.metadata());
+    }
+    // Prepare the statement for later executions while this query is
+    // executed via the unnamed statement. Both requests are pipelined,
+    // so a cache miss costs a single round trip
+    let statement = QueryStatement::unprepared(sql.clone(), binds.metadata())?;
+    let metadata = binds.metadata().to_vec();
+    let prepare = async {
+        let (stmt, _) = raw_connection
+            .clone()
+            .prepare(&sql, &metadata, PrepareForCache::Yes)
+            .await?;
+        stmt_cache.write().await.insert(key, stmt);
+        QueryResult::Ok(())
+    };
+    let execute = callback(raw_connection.clone(), statement, binds);
+    match futures_util::future::join(prepare, execute).await {
+        // A failed preparation aborts a surrounding transaction,
+        // so its error is the more relevant one
+        (Err(e), Err(_)) => Err(e),
+        (_, res) => res,
+    }
+}
+
+async fn resolve_types(
+    unresolved_types: Vec<(Option<String>, String)>,
+    metadata: &mut [PgTypeMetadata],
+    metadata_cache: &Mutex<PgMetadataCache>,
+    raw_connection: &tokio_postgres::Client,
+) -> QueryResult<()> {
+    // Check whether we need to resolve some types at all
+    //
+    // If the user doesn't use custom types there is no need
+    // to borther with that at all
+    if !unresolved_types.is_empty() {
+        let metadata_cache = &mut *metadata_cache.lock().await;
+        let mut next_unresolved = unresolved_types.into_iter();
+        for m in metadata {
+            // for each unresolved item
+            // we check whether it's arleady in the cache
+            // or perform a lookup and insert it into the cache
+            if m.oid().is_err() {
+                if let Some((ref schema, ref lookup_type_name)) = next_unresolved.next() {
+                    let cache_key = PgMetadataCacheKey::new(
+                        schema.as_ref().map(Into::into),
+                        lookup_type_name.into(),
+                    );
+                    if let Some(entry) = metadata_cache.lookup_type(&cache_key) {
+                        *m = entry;
+                    } else {
+                        let type_metadata =
+                            lookup_type(schema.clone(), lookup_type_name.clone(), raw_connection)
                                 .await?;
-                                *m = PgTypeMetadata::from_result(Ok(type_metadata));
+                        *m = PgTypeMetadata::from_result(Ok(type_metadata));
 
-                                metadata_cache.store_type(cache_key, type_metadata);
-                            }
-                        } else {
-                            break;
-                        }
+                        metadata_cache.store_type(cache_key, type_metadata);
                     }
+                } else {
+                    break;
                 }
             }
-            let key = match query_id {
-                Some(id) => StatementCacheKey::Type(id),
-                None => StatementCacheKey::Sql {
-                    sql: sql.clone(),
-                    bind_types: bind_collector.metadata.clone(),
-                },
-            };
-            let stmt = {
-                let mut stmt_cache = stmt_cache.lock().await;
-                stmt_cache
-                    .cached_prepared_statement(
-                        key,
-                        sql,
-                        is_safe_to_cache_prepared,
-                        &bind_collector.metadata,
-                        raw_connection.clone(),
-                    )
-                    .await?
-                    .0
-                    .clone()
-            };
-
-            let binds = bind_collector
-                .metadata
-                .into_iter()
-                .zip(bind_collector.binds)
-                .map(|(meta, bind)| ToSqlHelper(meta, bind))
-                .collect::<Vec<_>>();
-            let res = callback(raw_connection, stmt.clone(), binds).await;
-            let mut tm = tm.lock().await;
-            update_transaction_manager_status(res, &mut tm)
         }
-        .boxed()
     }
+    Ok(())
 }
 
 struct PgAsyncMetadataLookup {
@@ -550,40 +1417,184 @@ async fn lookup_type(
     Ok((r.get(0), r.get(1)))
 }
 
+/// Describes how the background task driving an [`AsyncPgConnection`]
+/// terminated, see [`AsyncPgConnection::closed`]
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct ConnectionClosed {
+    /// The time passed since the connection was established
+    pub connected_for: Duration,
+    /// The error the connection failed with, `None` if the
+    /// connection was closed without an error
+    pub error: Option<Arc<tokio_postgres::Error>>,
+}
+
+// Latches the error that terminated the background task driving the connection
+type ConnectionErrorReceiver = watch::Receiver<Option<Arc<tokio_postgres::Error>>>;
+
 async fn drive_future<R>(
-    connection_future: Option<broadcast::Receiver<Arc<tokio_postgres::Error>>>,
+    connection_error: Option<ConnectionErrorReceiver>,
     client_future: impl Future<Output = Result<R, diesel::result::Error>>,
 ) -> Result<R, diesel::result::Error> {
-    if let Some(mut connection_future) = connection_future {
+    if let Some(connection_error) = connection_error {
+        let latched_error = connection_error.clone();
         let client_future = std::pin::pin!(client_future);
-        let connection_future = std::pin::pin!(connection_future.recv());
-        match futures_util::future::select(client_future, connection_future).await {
-            Either::Left((res, _)) => res,
+        let connection_error = std::pin::pin!(wait_for_connection_error(connection_error));
+        match futures_util::future::select(connection_error, client_future).await {
             // we got an error from the background task
             // return it to the user
-            Either::Right((Ok(e), _)) => Err(self::error_helper::from_tokio_postgres_error(e)),
-            // seems like the background thread died for whatever reason
-            Either::Right((Err(e), _)) => Err(diesel::result::Error::DatabaseError(
-                DatabaseErrorKind::UnableToSendCommand,
-                Box::new(e.to_string()),
-            )),
+            Either::Left((e, _)) => Err(e),
+            // the client usually only reports that the connection is closed,
+            // so prefer the actual cause if the background task knows it
+            Either::Right((Err(e), _)) => match latched_error.borrow().clone() {
+                Some(e) => Err(self::error_helper::from_tokio_postgres_error(e)),
+                None => Err(e),
+            },
+            Either::Right((res, _)) => res,
         }
     } else {
         client_future.await
     }
 }
 
+async fn wait_for_connection_error(
+    connection_error: ConnectionErrorReceiver,
+) -> diesel::result::Error {
+    match wait_for_connection_close(connection_error).await {
+        Some(e) => self::error_helper::from_tokio_postgres_error(e),
+        None => diesel::result::Error::DatabaseError(
+            DatabaseErrorKind::UnableToSendCommand,
+            Box::new(String::from("Connection closed")),
+        ),
+    }
+}
+
+// Waits for the background task to fail or to finish,
+// returning the error it failed with
+async fn wait_for_connection_close(
+    mut connection_error: ConnectionErrorReceiver,
+) -> Option<Arc<tokio_postgres::Error>> {
+    loop {
+        if let Some(e) = connection_error.borrow_and_update().clone() {
+            return Some(e);
+        }
+        if connection_error.changed().await.is_err() {
+            // seems like the background task finished
+            // without reporting an error
+            return connection_error.borrow().clone();
+        }
+    }
+}
+
 #[cfg(any(
     feature = "deadpool",
     feature = "bb8",
     feature = "mobc",
     feature = "r2d2"
 ))]
+#[async_trait::async_trait]
 impl crate::pooled_connection::PoolableConnection for AsyncPgConnection {
+    async fn ping(
+        &mut self,
+        config: &crate::pooled_connection::RecyclingMethod<Self>,
+    ) -> diesel::QueryResult<()> {
+        use crate::pooled_connection::RecyclingMethod;
+        use crate::run_query_dsl::RunQueryDsl;
+
+        match config {
+            RecyclingMethod::Fast => Ok(()),
+            RecyclingMethod::Verified => AsyncConnection::ping_connection(self).await,
+            RecyclingMethod::CustomQuery(query) => diesel::sql_query(query.as_ref())
+                .execute(self)
+                .await
+                .map(|_| ()),
+            RecyclingMethod::CustomFunction(c) => c(self).await,
+        }
+    }
+
+    async fn rollback_open_transactions(&mut self) -> diesel::QueryResult<()> {
+        // also recovers from dropped transaction futures and failed rollbacks
+        AnsiTransactionManager::rollback_to_clean_state(self).await
+    }
+
+    fn apply_tcp_config<'a>(
+        database_url: &'a str,
+        config: &crate::pooled_connection::TcpConfig,
+    ) -> std::borrow::Cow<'a, str> {
+        let mut params = Vec::new();
+        if let Some(idle) = config.keepalive_idle {
+            params.push(("keepalives", String::from("1")));
+            // values below one second are ignored by `tokio-postgres`
+            params.push(("keepalives_idle", idle.as_secs().max(1).to_string()));
+        }
+        if let Some(interval) = config.keepalive_interval {
+            params.push(("keepalives_interval", interval.as_secs().max(1).to_string()));
+        }
+        if let Some(retries) = config.keepalive_retries {
+            params.push(("keepalives_retries", retries.to_string()));
+        }
+        if params.is_empty() {
+            std::borrow::Cow::Borrowed(database_url)
+        } else if database_url.starts_with("postgres://")
+            || database_url.starts_with("postgresql://")
+        {
+            crate::pooled_connection::append_url_params(database_url, &params).into()
+        } else {
+            // the key-value format
+            let mut url = String::from(database_url);
+            for (key, value) in params {
+                url.push_str(&format!(" {key}={value}"));
+            }
+            url.into()
+        }
+    }
+
+    async fn configure_statement_cache(
+        &mut self,
+        config: &crate::pooled_connection::StatementCacheConfig,
+    ) {
+        self.stmt_cache
+            .write()
+            .await
+            .configure(config.capacity(), config.query_id_only);
+    }
+
+    async fn reset_session(&mut self) -> diesel::QueryResult<()> {
+        // `DISCARD ALL` without `DEALLOCATE ALL`, which would
+        // invalidate the statements of the statement cache. `RESET ALL` also
+        // reverts settings applied by `ManagerConfig::warm_up`
+        self.batch_execute(
+            "CLOSE ALL; SET SESSION AUTHORIZATION DEFAULT; RESET ALL; UNLISTEN *; \
+             SELECT pg_advisory_unlock_all(); DISCARD PLANS; DISCARD TEMP; DISCARD SEQUENCES;",
+        )
+        .await?;
+        self.set_config_options().await
+    }
+
+    fn reclaim_handle(&self) -> Option<Box<dyn FnOnce() + Send>> {
+        let reclaimed = self.reclaimed.clone();
+        let cancel_token = self.conn.cancel_token();
+        Some(Box::new(move || {
+            reclaimed.store(true, Ordering::Release);
+            // aborts the query currently running on the connection, if any
+            tokio::spawn(async move {
+                let _ = cancel_token.cancel_query(tokio_postgres::NoTls).await;
+            });
+        }))
+    }
+
+    fn closed_notifier(&self) -> Option<futures_util::future::BoxFuture<'static, ()>> {
+        // connections without a background task never report their closure
+        self.connection_error.as_ref()?;
+        Some(self.closed().map(|_| ()).boxed())
+    }
+
     fn is_broken(&mut self) -> bool {
         use crate::TransactionManager;
 
-        Self::TransactionManager::is_broken_transaction_manager(self) || self.conn.is_closed()
+        Self::TransactionManager::is_broken_transaction_manager(self)
+            || self.conn.is_closed()
+            || self.reclaimed.load(Ordering::Acquire)
     }
 }
 
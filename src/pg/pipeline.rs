@@ -0,0 +1,230 @@
+use super::AsyncPgConnection;
+use diesel::QueryResult;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+/// Limits the number of operations running concurrently on an
+/// [`AsyncPgConnection`], see [`AsyncPgConnection::set_pipeline_limit`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum PipelineLimit {
+    /// Do not limit the number of concurrent operations
+    #[default]
+    Unlimited,
+    /// Operations exceeding the given limit wait until
+    /// earlier operations completed
+    Wait(usize),
+    /// Operations exceeding the given limit fail with
+    /// [`diesel::result::Error::QueryBuilderError`] wrapping a
+    /// [`PipelineLimitExceeded`]
+    Fail(usize),
+}
+
+/// The error of operations rejected by [`PipelineLimit::Fail`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PipelineLimitExceeded {
+    limit: usize,
+}
+
+impl PipelineLimitExceeded {
+    /// The number of operations allowed to run concurrently
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+}
+
+impl std::fmt::Display for PipelineLimitExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Too many operations in flight, the pipeline is limited to {}",
+            self.limit
+        )
+    }
+}
+
+impl std::error::Error for PipelineLimitExceeded {}
+
+pub(super) struct PipelinePermits {
+    semaphore: Semaphore,
+    limit: PipelineLimit,
+}
+
+impl PipelinePermits {
+    pub(super) async fn acquire(
+        &self,
+        counters: &PipelineCounters,
+    ) -> QueryResult<SemaphorePermit<'_>> {
+        let start = Instant::now();
+        let permit = match self.limit {
+            PipelineLimit::Fail(limit) => self.semaphore.try_acquire().map_err(|_| {
+                diesel::result::Error::QueryBuilderError(Box::new(PipelineLimitExceeded { limit }))
+            })?,
+            PipelineLimit::Wait(_) | PipelineLimit::Unlimited => self
+                .semaphore
+                .acquire()
+                .await
+                .expect("The semaphore is never closed"),
+        };
+        counters.wait_nanos.fetch_add(
+            u64::try_from(start.elapsed().as_nanos()).unwrap_or(u64::MAX),
+            Ordering::Relaxed,
+        );
+        Ok(permit)
+    }
+}
+
+/// A snapshot of the pipelining metrics of an [`AsyncPgConnection`],
+/// see [`AsyncPgConnection::pipeline_metrics`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct PipelineMetrics {
+    /// The number of operations currently in flight
+    pub in_flight: usize,
+    /// The highest number of operations in flight at the same time
+    pub max_in_flight: usize,
+    /// The number of operations started on this connection
+    pub operations: u64,
+    /// The total time operations waited for capacity,
+    /// see [`AsyncPgConnection::set_pipeline_limit`]
+    pub wait_time: Duration,
+}
+
+#[derive(Debug, Default)]
+pub(super) struct PipelineCounters {
+    in_flight: AtomicUsize,
+    max_in_flight: AtomicUsize,
+    operations: AtomicU64,
+    wait_nanos: AtomicU64,
+}
+
+impl PipelineCounters {
+    // Counts an operation as in flight until the returned guard is dropped
+    pub(super) fn start_operation(&self) -> InFlight<'_> {
+        let in_flight = self.in_flight.fetch_add(1, Ordering::Relaxed) + 1;
+        self.max_in_flight.fetch_max(in_flight, Ordering::Relaxed);
+        self.operations.fetch_add(1, Ordering::Relaxed);
+        InFlight(self)
+    }
+}
+
+pub(super) struct InFlight<'a>(&'a PipelineCounters);
+
+// Counts a query future as pending from its creation until it is dropped,
+// see `AnsiTransactionManager::pending_queries`
+pub(super) struct PendingQuery(Arc<AtomicUsize>);
+
+impl PendingQuery {
+    pub(super) fn new(pending_queries: &Arc<AtomicUsize>) -> Self {
+        pending_queries.fetch_add(1, Ordering::AcqRel);
+        Self(pending_queries.clone())
+    }
+}
+
+impl Drop for PendingQuery {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        self.0.in_flight.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl AsyncPgConnection {
+    /// Limit the number of operations running concurrently on this connection
+    ///
+    /// Futures of queries created from the same connection are pipelined on
+    /// the underlying socket, see the documentation of [`AsyncPgConnection`].
+    /// Without a limit, a fan-out over many queries queues all of them at once.
+    /// With a limit set, further operations either wait for earlier operations
+    /// to complete or fail immediately, depending on the given [`PipelineLimit`].
+    /// Operations already in flight are not affected by changing the limit.
+    ///
+    /// # Panics
+    ///
+    /// If the given limit is zero.
+    ///
+    /// ```rust
+    /// # include!("../doctest_setup.rs");
+    /// use diesel_async::pg::{PipelineLimit, PipelineLimitExceeded};
+    /// # use diesel_async::RunQueryDsl;
+    /// #
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// #     run_test().await.unwrap();
+    /// # }
+    /// #
+    /// # async fn run_test() -> QueryResult<()> {
+    /// #     use diesel::sql_types::Integer;
+    /// #     let conn = &mut establish_connection().await;
+    /// conn.set_pipeline_limit(PipelineLimit::Fail(1));
+    /// let first = diesel::select(1.into_sql::<Integer>()).get_result::<i32>(conn);
+    /// let second = diesel::select(2.into_sql::<Integer>()).get_result::<i32>(conn);
+    /// let (first, second) = futures_util::join!(first, second);
+    /// assert_eq!(1, first?);
+    /// let Err(diesel::result::Error::QueryBuilderError(e)) = second else {
+    ///     panic!("Expected the second query to be rejected");
+    /// };
+    /// assert_eq!(1, e.downcast_ref::<PipelineLimitExceeded>().unwrap().limit());
+    /// #     Ok(())
+    /// # }
+    /// ```
+    pub fn set_pipeline_limit(&mut self, limit: PipelineLimit) {
+        self.pipeline_permits = match limit {
+            PipelineLimit::Unlimited => None,
+            PipelineLimit::Wait(0) | PipelineLimit::Fail(0) => {
+                panic!("The pipeline limit must be greater than zero")
+            }
+            PipelineLimit::Wait(n) | PipelineLimit::Fail(n) => Some(Arc::new(PipelinePermits {
+                semaphore: Semaphore::new(n),
+                limit,
+            })),
+        };
+    }
+
+    /// Returns the pipelining metrics of this connection
+    ///
+    /// Many operations in flight or a long total wait time indicate that
+    /// the operations on this connection queue up behind each other, in
+    /// which case spreading them over more connections of a pool might
+    /// reduce their latency.
+    ///
+    /// ```rust
+    /// # include!("../doctest_setup.rs");
+    /// # use diesel_async::RunQueryDsl;
+    /// #
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// #     run_test().await.unwrap();
+    /// # }
+    /// #
+    /// # async fn run_test() -> QueryResult<()> {
+    /// #     use diesel::sql_types::Integer;
+    /// #     let conn = &mut establish_connection().await;
+    /// let before = conn.pipeline_metrics().operations;
+    /// let first = diesel::select(1.into_sql::<Integer>()).get_result::<i32>(conn);
+    /// let second = diesel::select(2.into_sql::<Integer>()).get_result::<i32>(conn);
+    /// futures_util::try_join!(first, second)?;
+    ///
+    /// let metrics = conn.pipeline_metrics();
+    /// assert_eq!(before + 2, metrics.operations);
+    /// assert_eq!(0, metrics.in_flight);
+    /// assert!(metrics.max_in_flight >= 2);
+    /// #     Ok(())
+    /// # }
+    /// ```
+    pub fn pipeline_metrics(&self) -> PipelineMetrics {
+        let counters = &self.pipeline_counters;
+        PipelineMetrics {
+            in_flight: counters.in_flight.load(Ordering::Relaxed),
+            max_in_flight: counters.max_in_flight.load(Ordering::Relaxed),
+            operations: counters.operations.load(Ordering::Relaxed),
+            wait_time: Duration::from_nanos(counters.wait_nanos.load(Ordering::Relaxed)),
+        }
+    }
+}
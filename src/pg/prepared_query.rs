@@ -0,0 +1,247 @@
+use super::interceptor::PendingQuery;
+use super::serialize::PooledBinds;
+use super::{
+    execute_prepared, load_prepared, resolve_types, update_transaction_manager_status,
+    AsyncPgConnection, InterceptedQuery, PgAsyncMetadataLookup, QueryStatement,
+};
+use crate::stmt_cache::PrepareCallback;
+use diesel::connection::statement_cache::PrepareForCache;
+use diesel::deserialize::FromSqlRow;
+use diesel::pg::{Pg, PgQueryBuilder, PgTypeMetadata};
+use diesel::query_builder::bind_collector::RawBytesBindCollector;
+use diesel::query_builder::{Query, QueryBuilder, QueryFragment, QueryId};
+use diesel::QueryResult;
+use futures_util::TryStreamExt;
+use std::marker::PhantomData;
+use std::sync::{Arc, Weak};
+use tokio_postgres::Statement;
+
+/// A statement prepared for queries of type `T`, see
+/// [`AsyncPgConnection::prepare_query`]
+///
+/// Executing a query via this handle only collects the bind values of the
+/// query and skips building the SQL as well as the statement cache.
+/// The queries still pass through the interceptors of the connection, see
+/// [`AsyncPgConnection::add_interceptor`]. Queries rewritten by an interceptor
+/// are executed without the prepared statement. The underlying prepared
+/// statement is deallocated once all clones of the handle are dropped.
+pub struct PreparedQuery<T> {
+    statement: Statement,
+    metadata: Vec<PgTypeMetadata>,
+    sql: String,
+    // Set for queries without a static query id or that are not safe
+    // to cache, as the SQL of those queries can change between values of `T`
+    check_sql: bool,
+    client: Weak<tokio_postgres::Client>,
+    _query: PhantomData<fn(T)>,
+}
+
+impl<T> Clone for PreparedQuery<T> {
+    fn clone(&self) -> Self {
+        Self {
+            statement: self.statement.clone(),
+            metadata: self.metadata.clone(),
+            sql: self.sql.clone(),
+            check_sql: self.check_sql,
+            client: self.client.clone(),
+            _query: PhantomData,
+        }
+    }
+}
+
+impl<T> std::fmt::Debug for PreparedQuery<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PreparedQuery")
+            .field("params", &self.statement.params())
+            .field("columns", &self.statement.columns().len())
+            .finish()
+    }
+}
+
+impl<T> PreparedQuery<T>
+where
+    T: QueryFragment<Pg> + QueryId,
+{
+    /// Execute `query` via this prepared statement, returning the number
+    /// of affected rows
+    pub async fn execute_query(
+        &self,
+        conn: &mut AsyncPgConnection,
+        query: T,
+    ) -> QueryResult<usize> {
+        conn.run_queued_statements().await?;
+        let (statement, binds, pending) = self.statement(conn, query)?;
+        let client = conn.conn.clone();
+        let execute = async move { execute_prepared(client, statement?, binds).await };
+        let res = conn.run_with_connection_future(execute).await;
+        let res = update_transaction_manager_status(res, &conn.serialization_failure);
+        if let Some(pending) = pending {
+            pending.finish(&res);
+        }
+        res
+    }
+
+    /// Execute `query` via this prepared statement and load the returned rows
+    pub async fn load_query<U>(&self, conn: &mut AsyncPgConnection, query: T) -> QueryResult<Vec<U>>
+    where
+        T: Query,
+        U: FromSqlRow<T::SqlType, Pg>,
+    {
+        conn.run_queued_statements().await?;
+        let (statement, binds, pending) = self.statement(conn, query)?;
+        let client = conn.conn.clone();
+        let load = async move { load_prepared(client, statement?, binds).await };
+        let rows = conn.run_with_connection_future(load).await;
+        let rows = update_transaction_manager_status(rows, &conn.serialization_failure);
+        if let Some(pending) = pending {
+            pending.finish(&rows);
+        }
+        let rows = rows?.try_collect::<Vec<_>>().await?;
+        rows.iter()
+            .map(|row| U::build_from_row(row).map_err(diesel::result::Error::DeserializationError))
+            .collect()
+    }
+
+    // Collects the binds of `query` and passes it through the interceptors
+    // of the connection. The statement is an error if the query was rewritten
+    // to SQL that cannot be executed, which needs to be reported to the
+    // interceptors as outcome of the query.
+    fn statement(
+        &self,
+        conn: &AsyncPgConnection,
+        query: T,
+    ) -> QueryResult<(
+        QueryResult<QueryStatement>,
+        PooledBinds,
+        Option<PendingQuery>,
+    )> {
+        if self.client.as_ptr() != Arc::as_ptr(&conn.conn) {
+            return Err(diesel::result::Error::QueryBuilderError(
+                "The query was prepared on a different connection".into(),
+            ));
+        }
+        if self.check_sql {
+            let mut query_builder = PgQueryBuilder::default();
+            query.to_sql(&mut query_builder, &Pg)?;
+            if query_builder.finish() != self.sql {
+                return Err(diesel::result::Error::QueryBuilderError(
+                    "The SQL of the query differs from the prepared statement".into(),
+                ));
+            }
+        }
+        let mut bind_collector = conn.bind_buffers.bind_collector();
+        query.collect_binds(&mut bind_collector, &mut PgAsyncMetadataLookup::new(), &Pg)?;
+        // The collected metadata might contain custom types that need to
+        // be looked up, use the types resolved while preparing instead
+        bind_collector.metadata.clear();
+        bind_collector.metadata.extend_from_slice(&self.metadata);
+
+        let mut statement = Ok(QueryStatement::Prepared(self.statement.clone()));
+        let pending = if conn.interceptors.is_empty() {
+            None
+        } else {
+            let query = InterceptedQuery::new(
+                self.sql.clone(),
+                std::mem::take(&mut bind_collector.binds),
+                bind_collector.metadata.clone(),
+                T::query_id(),
+            );
+            let pending = conn.interceptors.before_query(query)?;
+            bind_collector.binds = pending.query().binds().to_vec();
+            if pending.query().sql() != self.sql {
+                statement = QueryStatement::unprepared(
+                    pending.query().sql().to_owned(),
+                    &bind_collector.metadata,
+                );
+            }
+            Some(pending)
+        };
+        Ok((statement, conn.bind_buffers.binds(bind_collector), pending))
+    }
+}
+
+impl AsyncPgConnection {
+    /// Prepare a statement for queries of type `T`
+    ///
+    /// The returned [`PreparedQuery`] executes queries of the same type
+    /// with new bind values, without building their SQL or accessing the
+    /// statement cache of the connection. This is useful for queries executed
+    /// very frequently. The prepared statement can only be used on the connection
+    /// it was prepared on.
+    ///
+    /// For queries without a static SQL representation, like
+    /// [`diesel::sql_query`] or inserts of optional values rendering `DEFAULT`
+    /// for `None`, the SQL is still built for each execution to check that it
+    /// matches the prepared statement.
+    ///
+    /// ```rust
+    /// # include!("../doctest_setup.rs");
+    /// #
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// #     run_test().await.unwrap();
+    /// # }
+    /// #
+    /// # async fn run_test() -> QueryResult<()> {
+    /// #     use schema::users;
+    /// #     let conn = &mut establish_connection().await;
+    /// fn by_name(name: &str) -> users::BoxedQuery<'_, diesel::pg::Pg, diesel::sql_types::Text> {
+    ///     users::table
+    ///         .select(users::name)
+    ///         .filter(users::name.eq(name))
+    ///         .into_boxed()
+    /// }
+    ///
+    /// let insert = conn
+    ///     .prepare_query(diesel::insert_into(users::table).values(users::name.eq("")))
+    ///     .await?;
+    /// for name in ["Ruby", "Jim"] {
+    ///     let query = diesel::insert_into(users::table).values(users::name.eq(name));
+    ///     assert_eq!(1, insert.execute_query(conn, query).await?);
+    /// }
+    ///
+    /// let select = conn.prepare_query(by_name("")).await?;
+    /// let names = select.load_query::<String>(conn, by_name("Jim")).await?;
+    /// assert_eq!(vec!["Jim"], names);
+    /// #     Ok(())
+    /// # }
+    /// ```
+    pub async fn prepare_query<T>(&mut self, query: T) -> QueryResult<PreparedQuery<T>>
+    where
+        T: QueryFragment<Pg> + QueryId + Send,
+    {
+        let mut query_builder = PgQueryBuilder::default();
+        query.to_sql(&mut query_builder, &Pg)?;
+        let sql = query_builder.finish();
+        let check_sql = !T::HAS_STATIC_QUERY_ID || !query.is_safe_to_cache_prepared(&Pg)?;
+        let mut bind_collector = RawBytesBindCollector::<Pg>::new();
+        let mut metadata_lookup = PgAsyncMetadataLookup::new();
+        query.collect_binds(&mut bind_collector, &mut metadata_lookup, &Pg)?;
+        let mut metadata = bind_collector.metadata;
+
+        let raw_connection = self.conn.clone();
+        let metadata_cache = self.metadata_cache.clone();
+        let prepare = async move {
+            resolve_types(
+                metadata_lookup.unresolved_types,
+                &mut metadata,
+                &metadata_cache,
+                &raw_connection,
+            )
+            .await?;
+            let (statement, raw_connection) = raw_connection
+                .prepare(&sql, &metadata, PrepareForCache::No)
+                .await?;
+            Ok((statement, metadata, sql, Arc::downgrade(&raw_connection)))
+        };
+        let (statement, metadata, sql, client) = self.run_with_connection_future(prepare).await?;
+        Ok(PreparedQuery {
+            statement,
+            metadata,
+            sql,
+            check_sql,
+            client,
+            _query: PhantomData,
+        })
+    }
+}
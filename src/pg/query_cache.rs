@@ -0,0 +1,400 @@
+use super::sql_scanner::{self, Token};
+use super::{
+    AsyncPgConnection, InterceptedQuery, PgAsyncMetadataLookup, QueryInterceptor, QueryOutcome,
+};
+use crate::methods::LoadQuery;
+use crate::{AnsiTransactionManager, RunQueryDsl, TransactionManager};
+use diesel::pg::{Pg, PgQueryBuilder};
+use diesel::query_builder::bind_collector::RawBytesBindCollector;
+use diesel::query_builder::{QueryBuilder, QueryFragment};
+use diesel::QueryResult;
+use std::any::{Any, TypeId};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+/// The key of a cached query result, see [`QueryCacheStore`]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct QueryCacheKey {
+    sql: String,
+    binds: Vec<Option<Vec<u8>>>,
+    output: TypeId,
+}
+
+impl QueryCacheKey {
+    /// The SQL of the cached query
+    pub fn sql(&self) -> &str {
+        &self.sql
+    }
+
+    /// The serialized bind values of the cached query
+    pub fn binds(&self) -> &[Option<Vec<u8>>] {
+        &self.binds
+    }
+}
+
+/// A cached query result, see [`QueryCacheStore`]
+#[derive(Clone)]
+pub struct QueryCacheEntry {
+    rows: Arc<dyn Any + Send + Sync>,
+    tables: Arc<[String]>,
+    expires_at: Instant,
+}
+
+impl std::fmt::Debug for QueryCacheEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("QueryCacheEntry")
+            .field("tables", &self.tables)
+            .field("expires_at", &self.expires_at)
+            .finish_non_exhaustive()
+    }
+}
+
+impl QueryCacheEntry {
+    /// The tables the cached query reads from
+    pub fn tables(&self) -> &[String] {
+        &self.tables
+    }
+
+    /// When the entry expires
+    pub fn expires_at(&self) -> Instant {
+        self.expires_at
+    }
+
+    /// Whether the entry is expired
+    pub fn is_expired(&self) -> bool {
+        self.expires_at <= Instant::now()
+    }
+}
+
+/// Stores the results cached by a [`QueryCache`]
+///
+/// Expired entries returned by [`QueryCacheStore::get`] are ignored,
+/// so stores are free to remove them lazily.
+pub trait QueryCacheStore: Send + Sync {
+    /// Look up the entry for the given key
+    fn get(&self, key: &QueryCacheKey) -> Option<QueryCacheEntry>;
+
+    /// Store the given entry
+    fn insert(&self, key: QueryCacheKey, entry: QueryCacheEntry);
+
+    /// Remove all entries reading from the given table
+    fn invalidate_table(&self, table: &str);
+}
+
+/// A [`QueryCacheStore`] keeping the entries in memory
+///
+/// Once the store is full, expired entries are removed. If the store is
+/// still full afterwards, new entries are not stored until existing
+/// entries expire or are invalidated.
+#[derive(Debug)]
+pub struct MemoryQueryCacheStore {
+    entries: Mutex<HashMap<QueryCacheKey, QueryCacheEntry>>,
+    capacity: usize,
+}
+
+impl MemoryQueryCacheStore {
+    /// Construct a store keeping at most `capacity` entries
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: Mutex::new(HashMap::new()),
+            capacity,
+        }
+    }
+}
+
+impl QueryCacheStore for MemoryQueryCacheStore {
+    fn get(&self, key: &QueryCacheKey) -> Option<QueryCacheEntry> {
+        self.entries
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(key)
+            .cloned()
+    }
+
+    fn insert(&self, key: QueryCacheKey, entry: QueryCacheEntry) {
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        if entries.len() >= self.capacity && !entries.contains_key(&key) {
+            entries.retain(|_, entry| !entry.is_expired());
+            if entries.len() >= self.capacity {
+                return;
+            }
+        }
+        entries.insert(key, entry);
+    }
+
+    fn invalidate_table(&self, table: &str) {
+        self.entries
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .retain(|_, entry| !entry.tables.iter().any(|t| t == table));
+    }
+}
+
+/// Caches the results of read queries for a fixed time
+///
+/// Results are cached by the SQL and the bind values of the query. Each
+/// cached result lists the tables the query reads from. Once the cache is
+/// added as [`QueryInterceptor`] to a connection, writes to one of those
+/// tables executed on the connection invalidate the cached results. Add the
+/// same cache to all connections of a pool via
+/// [`ManagerConfig::warm_up`](crate::pooled_connection::ManagerConfig::warm_up)
+/// to share the cached results between them. Writes executed elsewhere are
+/// only reflected once the cached results expired.
+///
+/// Writes inside of a transaction invalidate the cached results both when
+/// they are executed and when the transaction commits, as other connections
+/// might cache the old results in the meantime. Each clone of the cache keeps
+/// track of the transaction of one connection, so add a separate clone to each
+/// connection instead of sharing the same [`Arc`] between connections.
+///
+/// Writes are detected from `INSERT`, `UPDATE`, `DELETE` and `TRUNCATE`
+/// statements as generated by diesel, other statements modifying tables,
+/// like writing common table expressions, are not detected.
+///
+/// ```rust
+/// # include!("../doctest_setup.rs");
+/// use diesel_async::pg::QueryCache;
+/// use std::sync::Arc;
+/// use std::time::Duration;
+/// #
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// #     run_test().await.unwrap();
+/// # }
+/// #
+/// # async fn run_test() -> QueryResult<()> {
+/// # use diesel_async::RunQueryDsl;
+/// #     use schema::users;
+/// #     let conn = &mut connection_no_transaction().await;
+/// #     create_tables(conn).await;
+/// let cache = QueryCache::new(Duration::from_secs(30));
+/// conn.add_interceptor(Arc::new(cache.clone()));
+///
+/// let query = || users::table.select(users::name).order(users::id);
+/// let names = cache.load_query::<_, String>(conn, query(), &["users"]).await?;
+/// assert_eq!(vec!["Sean", "Tess"], names);
+///
+/// // invalidates the cached result
+/// diesel::delete(users::table.filter(users::name.eq("Tess")))
+///     .execute(conn)
+///     .await?;
+/// let names = cache.load_query::<_, String>(conn, query(), &["users"]).await?;
+/// assert_eq!(vec!["Sean"], names);
+/// #     Ok(())
+/// # }
+/// ```
+pub struct QueryCache {
+    store: Arc<dyn QueryCacheStore>,
+    ttl: Duration,
+    // The tables written by the open transaction of the connection
+    // this cache was added to, `None` outside of transactions
+    transaction_writes: Mutex<Option<HashSet<String>>>,
+}
+
+impl Clone for QueryCache {
+    fn clone(&self) -> Self {
+        Self {
+            store: self.store.clone(),
+            ttl: self.ttl,
+            transaction_writes: Mutex::default(),
+        }
+    }
+}
+
+impl std::fmt::Debug for QueryCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("QueryCache")
+            .field("ttl", &self.ttl)
+            .finish_non_exhaustive()
+    }
+}
+
+impl QueryCache {
+    /// Construct a cache keeping results for `ttl` in a
+    /// [`MemoryQueryCacheStore`] with up to 1024 entries
+    pub fn new(ttl: Duration) -> Self {
+        Self::with_store(MemoryQueryCacheStore::new(1024), ttl)
+    }
+
+    /// Construct a cache keeping results for `ttl` in the given store
+    pub fn with_store(store: impl QueryCacheStore + 'static, ttl: Duration) -> Self {
+        Self {
+            store: Arc::new(store),
+            ttl,
+            transaction_writes: Mutex::default(),
+        }
+    }
+
+    /// Load the results of `query`, returning cached results if available
+    ///
+    /// `tables` lists the tables the query reads from, writes to these tables
+    /// invalidate the cached results. Queries executed inside of a transaction
+    /// bypass the cache, as they might observe uncommitted changes.
+    pub async fn load_query<'query, T, U>(
+        &self,
+        conn: &mut AsyncPgConnection,
+        query: T,
+        tables: &[&str],
+    ) -> QueryResult<Vec<U>>
+    where
+        T: QueryFragment<Pg> + LoadQuery<'query, AsyncPgConnection, U> + 'query,
+        U: Clone + Send + Sync + 'static,
+    {
+        let in_transaction = AnsiTransactionManager::transaction_manager_status_mut(conn)
+            .transaction_depth()?
+            .is_some();
+        if in_transaction {
+            return query.load(conn).await;
+        }
+
+        let mut query_builder = PgQueryBuilder::default();
+        query.to_sql(&mut query_builder, &Pg)?;
+        let mut bind_collector = RawBytesBindCollector::<Pg>::new();
+        query.collect_binds(&mut bind_collector, &mut PgAsyncMetadataLookup::new(), &Pg)?;
+        let key = QueryCacheKey {
+            sql: query_builder.finish(),
+            binds: bind_collector.binds,
+            output: TypeId::of::<U>(),
+        };
+
+        let cached = self
+            .store
+            .get(&key)
+            .filter(|entry| !entry.is_expired())
+            .and_then(|entry| entry.rows.downcast::<Vec<U>>().ok());
+        if let Some(rows) = cached {
+            return Ok(Vec::clone(&rows));
+        }
+        let rows = query.load::<U>(conn).await?;
+        self.store.insert(
+            key,
+            QueryCacheEntry {
+                rows: Arc::new(rows.clone()),
+                tables: tables.iter().map(|t| String::from(*t)).collect(),
+                expires_at: Instant::now() + self.ttl,
+            },
+        );
+        Ok(rows)
+    }
+
+    /// Remove all cached results reading from the given table
+    pub fn invalidate_table(&self, table: &str) {
+        self.store.invalidate_table(table);
+    }
+}
+
+impl QueryInterceptor for QueryCache {
+    fn after_query(&self, query: &InterceptedQuery, _outcome: &QueryOutcome<'_>) {
+        // Failed statements might still have modified a table as
+        // part of a batch, so they invalidate the cache as well
+        let mut transaction_writes = self
+            .transaction_writes
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        for statement in sql_scanner::statements(query.sql()) {
+            match transaction_statement(&statement) {
+                Some(TransactionStatement::Begin) => {
+                    *transaction_writes = Some(HashSet::new());
+                }
+                Some(TransactionStatement::Commit) => {
+                    for table in transaction_writes.take().into_iter().flatten() {
+                        self.store.invalidate_table(&table);
+                    }
+                }
+                Some(TransactionStatement::Rollback) => *transaction_writes = None,
+                None => {
+                    for table in written_tables(&statement) {
+                        self.store.invalidate_table(table);
+                        if let Some(ref mut writes) = *transaction_writes {
+                            writes.insert(table.to_owned());
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+enum TransactionStatement {
+    Begin,
+    Commit,
+    Rollback,
+}
+
+// Whether the statement starts, commits or rolls back a transaction,
+// statements controlling savepoints are not reported
+fn transaction_statement(statement: &[Token<'_>]) -> Option<TransactionStatement> {
+    let first = statement.first()?;
+    if first.is_keyword("BEGIN") || first.is_keyword("START") {
+        Some(TransactionStatement::Begin)
+    } else if first.is_keyword("COMMIT") || first.is_keyword("END") {
+        Some(TransactionStatement::Commit)
+    } else if (first.is_keyword("ROLLBACK") || first.is_keyword("ABORT"))
+        && !statement
+            .get(1)
+            .is_some_and(|second| second.is_keyword("TO"))
+    {
+        Some(TransactionStatement::Rollback)
+    } else {
+        None
+    }
+}
+
+// The tables written by the given statement, if the statement is
+// an `INSERT`, `UPDATE`, `DELETE` or `TRUNCATE` statement
+fn written_tables<'a>(statement: &[Token<'a>]) -> Vec<&'a str> {
+    let (mut rest, is_truncate) = match statement {
+        [first, second, rest @ ..] if first.is_keyword("INSERT") && second.is_keyword("INTO") => {
+            (rest, false)
+        }
+        [first, second, rest @ ..] if first.is_keyword("DELETE") && second.is_keyword("FROM") => {
+            (rest, false)
+        }
+        [first, second, rest @ ..]
+            if first.is_keyword("TRUNCATE") && second.is_keyword("TABLE") =>
+        {
+            (rest, true)
+        }
+        [first, rest @ ..] if first.is_keyword("UPDATE") => (rest, false),
+        [first, rest @ ..] if first.is_keyword("TRUNCATE") => (rest, true),
+        _ => return Vec::new(),
+    };
+    let mut tables = Vec::new();
+    loop {
+        if rest.first().is_some_and(|only| only.is_keyword("ONLY")) {
+            rest = &rest[1..];
+        }
+        let Some((table, remaining)) = table_name(rest) else {
+            break;
+        };
+        tables.push(table);
+        let remaining = match remaining {
+            [Token::Punctuation('*'), remaining @ ..] => remaining,
+            remaining => remaining,
+        };
+        // only `TRUNCATE` accepts a list of tables
+        match remaining {
+            [Token::Punctuation(','), remaining @ ..] if is_truncate => rest = remaining,
+            _ => break,
+        }
+    }
+    tables
+}
+
+// The last part of a possibly schema qualified name at the start
+// of `rest`, together with the remaining tokens
+fn table_name<'a, 'b>(mut rest: &'b [Token<'a>]) -> Option<(&'a str, &'b [Token<'a>])> {
+    loop {
+        let (part, remaining) = match rest {
+            [Token::Word(part) | Token::QuotedIdentifier(part), remaining @ ..] => {
+                (*part, remaining)
+            }
+            _ => return None,
+        };
+        match remaining {
+            [Token::Punctuation('.'), remaining @ ..] => rest = remaining,
+            _ => return Some((part, remaining)).filter(|(part, _)| !part.is_empty()),
+        }
+    }
+}
@@ -0,0 +1,162 @@
+use super::{sql_scanner, InterceptedQuery, QueryInterceptor};
+use diesel::QueryResult;
+
+/// A [`QueryInterceptor`] for connections to a read replica
+///
+/// Adding this interceptor marks a connection as replica-facing:
+///
+/// * Transactions are started as `READ ONLY`, both the transactions of
+///   [`AsyncConnection::transaction`](crate::AsyncConnection::transaction)
+///   and those of [`AsyncPgConnection::build_transaction`](super::AsyncPgConnection::build_transaction).
+///   Transactions explicitly built as `READ WRITE` are rejected.
+/// * Queries writing data, like `INSERT`, `UPDATE`, `DELETE`, `MERGE`,
+///   `TRUNCATE` or schema changes, are rejected before they are sent to the
+///   database. This includes row locks like `SELECT ... FOR UPDATE` or
+///   `SELECT ... FOR SHARE`, which standbys do not support either.
+///
+/// Rejected queries fail with a [`diesel::result::Error::QueryBuilderError`]
+/// wrapping a [`ReplicaWriteError`], instead of a less obvious error from the
+/// standby. Writes are detected by the keywords of the SQL outside of string
+/// literals (including escape and dollar quoted strings), quoted identifiers
+/// and comments, so writes hidden in functions are only rejected by the
+/// database itself.
+///
+/// To mark all connections of a pool as replica-facing, add the interceptor
+/// via [`ManagerConfig::warm_up`](crate::pooled_connection::ManagerConfig::warm_up).
+///
+/// ```rust
+/// # include!("../doctest_setup.rs");
+/// use diesel_async::pg::{ReplicaGuard, ReplicaWriteError};
+/// use std::sync::Arc;
+/// #
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// #     run_test().await.unwrap();
+/// # }
+/// #
+/// # async fn run_test() -> QueryResult<()> {
+/// # use diesel_async::RunQueryDsl;
+/// #     use schema::users;
+/// #     let conn = &mut establish_connection().await;
+/// conn.add_interceptor(Arc::new(ReplicaGuard));
+///
+/// let names = users::table.select(users::name).load::<String>(conn).await?;
+/// # assert_eq!(2, names.len());
+/// let Err(diesel::result::Error::QueryBuilderError(e)) =
+///     diesel::delete(users::table).execute(conn).await
+/// else {
+///     panic!("Replicas cannot be written to");
+/// };
+/// assert_eq!("DELETE", e.downcast_ref::<ReplicaWriteError>().unwrap().keyword());
+/// #     Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ReplicaGuard;
+
+impl QueryInterceptor for ReplicaGuard {
+    fn before_query(&self, query: &mut InterceptedQuery) -> QueryResult<()> {
+        let keywords = sql_scanner::keywords(query.sql());
+        if let Some(keyword) = keywords.iter().find(|keyword| {
+            WRITE_KEYWORDS
+                .iter()
+                .any(|write| keyword.eq_ignore_ascii_case(write))
+        }) {
+            return Err(write_error(query, keyword));
+        }
+        // `FOR UPDATE` and `FOR NO KEY UPDATE` are already covered above
+        let row_lock = keywords.iter().enumerate().find_map(|(idx, keyword)| {
+            if !keyword.eq_ignore_ascii_case("FOR") {
+                return None;
+            }
+            match &keywords[idx + 1..] {
+                [share, ..] if share.eq_ignore_ascii_case("SHARE") => Some("FOR SHARE"),
+                [key, share, ..]
+                    if key.eq_ignore_ascii_case("KEY") && share.eq_ignore_ascii_case("SHARE") =>
+                {
+                    Some("FOR KEY SHARE")
+                }
+                _ => None,
+            }
+        });
+        if let Some(row_lock) = row_lock {
+            return Err(write_error(query, row_lock));
+        }
+
+        let starts_transaction = matches!(
+            keywords.as_slice(),
+            [first, ..] if first.eq_ignore_ascii_case("BEGIN")
+        ) || matches!(
+            keywords.as_slice(),
+            [first, second, ..]
+                if first.eq_ignore_ascii_case("START") && second.eq_ignore_ascii_case("TRANSACTION")
+        );
+        if !starts_transaction || query.sql().contains(';') {
+            return Ok(());
+        }
+        let access_mode = keywords.windows(2).find_map(|w| match w {
+            [read, mode]
+                if read.eq_ignore_ascii_case("READ")
+                    && (mode.eq_ignore_ascii_case("ONLY")
+                        || mode.eq_ignore_ascii_case("WRITE")) =>
+            {
+                Some(*mode)
+            }
+            _ => None,
+        });
+        match access_mode {
+            Some(mode) if mode.eq_ignore_ascii_case("ONLY") => {}
+            Some(mode) if mode.eq_ignore_ascii_case("WRITE") => {
+                return Err(write_error(query, "READ WRITE"));
+            }
+            _ => {
+                let sql = format!("{} READ ONLY", query.sql().trim_end());
+                query.set_sql(sql);
+            }
+        }
+        Ok(())
+    }
+}
+
+const WRITE_KEYWORDS: [&str; 10] = [
+    "INSERT", "UPDATE", "DELETE", "MERGE", "TRUNCATE", "CREATE", "ALTER", "DROP", "GRANT", "REVOKE",
+];
+
+fn write_error(query: &InterceptedQuery, keyword: &str) -> diesel::result::Error {
+    diesel::result::Error::QueryBuilderError(Box::new(ReplicaWriteError {
+        sql: query.sql().to_owned(),
+        keyword: keyword.to_ascii_uppercase(),
+    }))
+}
+
+/// The error of queries rejected by a [`ReplicaGuard`]
+#[derive(Debug, Clone)]
+pub struct ReplicaWriteError {
+    sql: String,
+    keyword: String,
+}
+
+impl ReplicaWriteError {
+    /// The SQL of the rejected query
+    pub fn sql(&self) -> &str {
+        &self.sql
+    }
+
+    /// The keyword the query was rejected for, like `INSERT`
+    /// or `READ WRITE`
+    pub fn keyword(&self) -> &str {
+        &self.keyword
+    }
+}
+
+impl std::fmt::Display for ReplicaWriteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "The query `{}` cannot be executed on a replica, as it contains `{}`",
+            self.sql, self.keyword
+        )
+    }
+}
+
+impl std::error::Error for ReplicaWriteError {}
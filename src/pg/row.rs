@@ -1,7 +1,12 @@
+use super::error_helper::ErrorHelper;
 use diesel::backend::Backend;
 use diesel::row::{Field, PartialRow, RowIndex, RowSealed};
+use diesel::QueryResult;
+use futures_util::Stream;
+use std::pin::Pin;
+use std::task::{Context, Poll};
 use std::{error::Error, num::NonZeroU32};
-use tokio_postgres::{types::Type, Row};
+use tokio_postgres::{types::Type, Row, RowStream};
 
 pub struct PgRow {
     row: Row,
@@ -11,9 +16,40 @@ impl PgRow {
     pub(super) fn new(row: Row) -> Self {
         Self { row }
     }
+
+    pub(super) fn row(&self) -> &Row {
+        &self.row
+    }
 }
 impl RowSealed for PgRow {}
 
+pin_project_lite::pin_project! {
+    /// The stream of rows returned by `AsyncConnection::load` for `AsyncPgConnection`
+    pub struct PgRowStream {
+        #[pin]
+        rows: RowStream,
+    }
+}
+
+impl PgRowStream {
+    pub(super) fn new(rows: RowStream) -> Self {
+        Self { rows }
+    }
+}
+
+impl Stream for PgRowStream {
+    type Item = QueryResult<PgRow>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.project().rows.poll_next(cx).map(|row| {
+            row.map(|row| {
+                row.map(PgRow::new)
+                    .map_err(|e| diesel::result::Error::from(ErrorHelper(e)))
+            })
+        })
+    }
+}
+
 impl<'a> diesel::row::Row<'a, diesel::pg::Pg> for PgRow {
     type InnerPartialRow = Self;
     type Field<'b> = PgField<'b> where Self: 'b, 'a: 'b;
@@ -83,6 +119,8 @@ impl diesel::pg::TypeOidLookup for TyWrapper {
     }
 }
 
+// `tokio_postgres` passes the raw bytes of a field straight from the buffer
+// of the row, so the resulting values borrow from the row without any copies
 struct DieselFromSqlWrapper<'a>(Option<diesel::pg::PgValue<'a>>);
 
 impl<'a> tokio_postgres::types::FromSql<'a> for DieselFromSqlWrapper<'a> {
@@ -1,10 +1,90 @@
-use diesel::pg::PgTypeMetadata;
+use diesel::pg::{Pg, PgTypeMetadata};
+use diesel::query_builder::bind_collector::RawBytesBindCollector;
+use std::sync::{Arc, Mutex, PoisonError};
 use tokio_postgres::types::{private::BytesMut, IsNull, Type, WrongType};
 
+// Limits for the buffers kept around by `BindBufferPool`, so that a few
+// large queries do not keep their memory around forever
+const MAX_POOLED_BUFFERS: usize = 8;
+const MAX_POOLED_BIND_COUNT: usize = 256;
+
+type BindBuffers = (Vec<PgTypeMetadata>, Vec<Option<Vec<u8>>>);
+
+/// Buffers of previous bind collectors, reused by later queries
+/// on the same connection to avoid allocating them for each query
+#[derive(Default, Clone)]
+pub(super) struct BindBufferPool(Arc<Mutex<Vec<BindBuffers>>>);
+
+impl BindBufferPool {
+    pub(super) fn bind_collector(&self) -> RawBytesBindCollector<Pg> {
+        let mut bind_collector = RawBytesBindCollector::<Pg>::new();
+        let buffers = self.0.lock().unwrap_or_else(PoisonError::into_inner).pop();
+        if let Some((metadata, binds)) = buffers {
+            bind_collector.metadata = metadata;
+            bind_collector.binds = binds;
+        }
+        bind_collector
+    }
+
+    /// Return the buffers of the given bind collector to the pool
+    /// once the returned value is dropped
+    pub(super) fn binds(&self, bind_collector: RawBytesBindCollector<Pg>) -> PooledBinds {
+        PooledBinds {
+            metadata: bind_collector.metadata,
+            binds: bind_collector.binds,
+            pool: self.clone(),
+        }
+    }
+}
+
+/// The collected binds of a query
+pub(super) struct PooledBinds {
+    metadata: Vec<PgTypeMetadata>,
+    binds: Vec<Option<Vec<u8>>>,
+    pool: BindBufferPool,
+}
+
+impl PooledBinds {
+    pub(super) fn metadata(&self) -> &[PgTypeMetadata] {
+        &self.metadata
+    }
+
+    pub(super) fn iter(&self) -> impl ExactSizeIterator<Item = ToSqlHelper<'_>> {
+        self.metadata
+            .iter()
+            .zip(&self.binds)
+            .map(|(meta, bind)| ToSqlHelper::new(meta, bind.as_deref()))
+    }
+}
+
+impl Drop for PooledBinds {
+    fn drop(&mut self) {
+        if self.metadata.capacity() > MAX_POOLED_BIND_COUNT
+            || self.binds.capacity() > MAX_POOLED_BIND_COUNT
+        {
+            return;
+        }
+        let mut pool = self.pool.0.lock().unwrap_or_else(PoisonError::into_inner);
+        if pool.len() < MAX_POOLED_BUFFERS {
+            let mut metadata = std::mem::take(&mut self.metadata);
+            let mut binds = std::mem::take(&mut self.binds);
+            metadata.clear();
+            binds.clear();
+            pool.push((metadata, binds));
+        }
+    }
+}
+
 #[derive(Debug)]
-pub(super) struct ToSqlHelper(pub(super) PgTypeMetadata, pub(super) Option<Vec<u8>>);
+pub(super) struct ToSqlHelper<'a>(&'a PgTypeMetadata, Option<&'a [u8]>);
+
+impl<'a> ToSqlHelper<'a> {
+    pub(super) fn new(metadata: &'a PgTypeMetadata, bind: Option<&'a [u8]>) -> Self {
+        Self(metadata, bind)
+    }
+}
 
-impl tokio_postgres::types::ToSql for ToSqlHelper {
+impl tokio_postgres::types::ToSql for ToSqlHelper<'_> {
     fn to_sql(
         &self,
         _ty: &Type,
@@ -13,7 +93,7 @@ impl tokio_postgres::types::ToSql for ToSqlHelper {
     where
         Self: Sized,
     {
-        if let Some(ref bytes) = self.1 {
+        if let Some(bytes) = self.1 {
             out.extend_from_slice(bytes);
             Ok(IsNull::No)
         } else {
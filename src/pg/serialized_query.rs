@@ -0,0 +1,149 @@
+use super::interceptor::QueryOutput;
+use super::row::PgRow;
+use super::serialize::PooledBinds;
+use super::{
+    execute_prepared, load_prepared, AsyncPgConnection, PgAsyncMetadataLookup, QueryStatement,
+};
+use diesel::deserialize::QueryableByName;
+use diesel::pg::{Pg, PgQueryBuilder, PgTypeMetadata};
+use diesel::query_builder::bind_collector::RawBytesBindCollector;
+use diesel::query_builder::{QueryBuilder, QueryFragment, QueryId};
+use diesel::QueryResult;
+use futures_util::TryStreamExt;
+use std::any::TypeId;
+use std::future::Future;
+use std::sync::Arc;
+
+/// A query rendered to SQL together with its serialized bind values
+///
+/// Constructing a `SerializedQuery` does not require a connection, so queries
+/// can be prepared up front (for example by producers of a work queue) and
+/// executed later on any connection via [`AsyncPgConnection::execute_serialized`]
+/// or [`AsyncPgConnection::load_serialized`]. This keeps the time a pooled
+/// connection is held to the actual execution.
+///
+/// Custom types whose oid needs to be looked up are resolved once the query
+/// is executed.
+///
+/// ```rust
+/// # include!("../doctest_setup.rs");
+/// use diesel_async::pg::SerializedQuery;
+/// #
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// #     run_test().await.unwrap();
+/// # }
+/// #
+/// # async fn run_test() -> QueryResult<()> {
+/// #     use schema::users;
+/// #[derive(QueryableByName)]
+/// #[diesel(table_name = users)]
+/// struct User {
+///     name: String,
+/// }
+///
+/// let insert = SerializedQuery::new(diesel::insert_into(users::table).values(users::name.eq("Ruby")))?;
+/// let select = SerializedQuery::new(
+///     users::table
+///         .select(users::name)
+///         .filter(users::name.eq("Ruby")),
+/// )?;
+///
+/// let conn = &mut establish_connection().await;
+/// assert_eq!(1, conn.execute_serialized(&insert).await?);
+/// let users = conn.load_serialized::<User>(&select).await?;
+/// assert_eq!("Ruby", users[0].name);
+/// #     Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct SerializedQuery {
+    sql: String,
+    binds: Vec<Option<Vec<u8>>>,
+    metadata: Vec<PgTypeMetadata>,
+    unresolved_types: Vec<(Option<String>, String)>,
+    query_id: Option<TypeId>,
+    is_safe_to_cache_prepared: bool,
+}
+
+impl SerializedQuery {
+    /// Render the given query
+    pub fn new<T>(query: T) -> QueryResult<Self>
+    where
+        T: QueryFragment<Pg> + QueryId,
+    {
+        let mut query_builder = PgQueryBuilder::default();
+        query.to_sql(&mut query_builder, &Pg)?;
+        let mut bind_collector = RawBytesBindCollector::<Pg>::new();
+        let mut metadata_lookup = PgAsyncMetadataLookup::new();
+        query.collect_binds(&mut bind_collector, &mut metadata_lookup, &Pg)?;
+        Ok(Self {
+            sql: query_builder.finish(),
+            binds: bind_collector.binds,
+            metadata: bind_collector.metadata,
+            unresolved_types: metadata_lookup.unresolved_types,
+            query_id: T::query_id(),
+            is_safe_to_cache_prepared: query.is_safe_to_cache_prepared(&Pg)?,
+        })
+    }
+
+    /// The SQL of the query
+    pub fn sql(&self) -> &str {
+        &self.sql
+    }
+}
+
+impl AsyncPgConnection {
+    /// Execute a [`SerializedQuery`], returning the number of affected rows
+    pub async fn execute_serialized(&mut self, query: &SerializedQuery) -> QueryResult<usize> {
+        let execute = self.with_serialized_query(query, execute_prepared);
+        self.run_with_connection_future(execute).await
+    }
+
+    /// Execute a [`SerializedQuery`] and load the returned rows
+    ///
+    /// The columns are matched by name, like for [`diesel::sql_query`].
+    pub async fn load_serialized<U>(&mut self, query: &SerializedQuery) -> QueryResult<Vec<U>>
+    where
+        U: QueryableByName<Pg>,
+    {
+        let load = self.with_serialized_query(query, load_prepared);
+        let rows = self
+            .run_with_connection_future(load)
+            .await?
+            .try_collect::<Vec<PgRow>>()
+            .await?;
+        rows.iter()
+            .map(|row| U::build(row).map_err(diesel::result::Error::DeserializationError))
+            .collect()
+    }
+
+    fn with_serialized_query<'a, F, R>(
+        &mut self,
+        query: &SerializedQuery,
+        callback: fn(Arc<tokio_postgres::Client>, QueryStatement, PooledBinds) -> F,
+    ) -> impl Future<Output = QueryResult<R>> + Send + 'a
+    where
+        F: Future<Output = QueryResult<R>> + Send + 'a,
+        R: QueryOutput + Send,
+    {
+        let mut query_builder = PgQueryBuilder::default();
+        query_builder.push_sql(&query.sql);
+        let mut bind_collector = self.bind_buffers.bind_collector();
+        bind_collector.binds.extend_from_slice(&query.binds);
+        bind_collector.metadata.extend_from_slice(&query.metadata);
+        let metadata_lookup = PgAsyncMetadataLookup {
+            unresolved_types: query.unresolved_types.clone(),
+        };
+        self.with_prepared_statement_after_sql_built(
+            callback,
+            Ok(query.is_safe_to_cache_prepared),
+            query.query_id,
+            Ok(()),
+            Ok(()),
+            query_builder,
+            bind_collector,
+            metadata_lookup,
+        )
+    }
+}
@@ -0,0 +1,362 @@
+use super::error_helper::ErrorHelper;
+use super::{quote_identifier, AsyncPgConnection};
+use crate::{AsyncConnection, RunQueryDsl};
+use diesel::sql_types::{Bool, Text};
+use diesel::QueryResult;
+use scoped_futures::{ScopedBoxFuture, ScopedFutureExt};
+use std::borrow::Cow;
+use std::ops::{Deref, DerefMut};
+
+/// The scope of a configuration parameter set via
+/// [`AsyncPgConnection::set_config`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigScope {
+    /// The value applies to the session, like `SET`
+    Session,
+    /// The value applies to the current transaction, like `SET LOCAL`
+    ///
+    /// Outside of a transaction the value has no effect.
+    Local,
+}
+
+impl ConfigScope {
+    fn is_local(self) -> bool {
+        matches!(self, ConfigScope::Local)
+    }
+}
+
+/// Resets a configuration parameter set via [`AsyncPgConnection::set_config`]
+/// to its previous value
+///
+/// The guard dereferences to the underlying connection, so it can be used to
+/// execute queries with the configured value.
+///
+/// Use [`ConfigGuard::reset`] to reset the value right away. If the guard is
+/// dropped instead, the reset is queued on the connection and applied before
+/// the next statement executed via this connection, so later queries never
+/// observe the configured value.
+#[must_use = "The configuration parameter is reset as soon as the guard is dropped"]
+pub struct ConfigGuard<'a> {
+    connection: &'a mut AsyncPgConnection,
+    name: String,
+    previous: Option<String>,
+    scope: ConfigScope,
+    done: bool,
+}
+
+impl<'a> ConfigGuard<'a> {
+    /// The name of the configuration parameter
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The value of the configuration parameter before it was set,
+    /// `None` for custom parameters that were not set before
+    pub fn previous_value(&self) -> Option<&str> {
+        self.previous.as_deref()
+    }
+
+    /// Reset the configuration parameter to its previous value
+    ///
+    /// Parameters without a previous value are reset via `RESET`.
+    pub async fn reset(mut self) -> QueryResult<()> {
+        self.done = true;
+        let reset = self.take_reset();
+        self.connection.config_resets.push(reset);
+        self.connection.run_queued_statements().await
+    }
+
+    fn take_reset(&mut self) -> ConfigReset {
+        ConfigReset {
+            name: std::mem::take(&mut self.name),
+            previous: self.previous.take(),
+            scope: self.scope,
+        }
+    }
+
+    /// Keep the configured value instead of resetting it
+    pub fn keep(mut self) {
+        self.done = true;
+    }
+}
+
+impl Deref for ConfigGuard<'_> {
+    type Target = AsyncPgConnection;
+
+    fn deref(&self) -> &Self::Target {
+        self.connection
+    }
+}
+
+impl DerefMut for ConfigGuard<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.connection
+    }
+}
+
+impl Drop for ConfigGuard<'_> {
+    fn drop(&mut self) {
+        if !self.done {
+            let reset = self.take_reset();
+            self.connection.config_resets.push(reset);
+        }
+    }
+}
+
+// A reset of a configuration parameter queued by a dropped `ConfigGuard`
+pub(super) struct ConfigReset {
+    name: String,
+    previous: Option<String>,
+    scope: ConfigScope,
+}
+
+impl ConfigReset {
+    async fn apply(&self, client: &tokio_postgres::Client) -> Result<(), tokio_postgres::Error> {
+        match &self.previous {
+            Some(previous) => {
+                client
+                    .execute(
+                        "SELECT set_config($1, $2, $3)",
+                        &[&self.name, previous, &self.scope.is_local()],
+                    )
+                    .await?;
+            }
+            None => {
+                let name = self
+                    .name
+                    .split('.')
+                    .map(quote_identifier)
+                    .collect::<Vec<_>>()
+                    .join(".");
+                let sql = match self.scope {
+                    ConfigScope::Session => format!("RESET {name}"),
+                    ConfigScope::Local => format!("SET LOCAL {name} TO DEFAULT"),
+                };
+                client.batch_execute(&sql).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Apply the given resets in order, returning the first error
+///
+/// Errors of resets with [`ConfigScope::Local`] are ignored, as they only fail
+/// inside of a failed transaction, whose rollback discards the value anyway.
+pub(super) async fn apply_config_resets(
+    client: &tokio_postgres::Client,
+    resets: Vec<ConfigReset>,
+) -> QueryResult<()> {
+    let mut res = Ok(());
+    for reset in resets {
+        match reset.apply(client).await {
+            Err(e) if res.is_ok() && !reset.scope.is_local() => {
+                res = Err(ErrorHelper(e).into());
+            }
+            _ => {}
+        }
+    }
+    res
+}
+
+/// Per-request context exposed to the database, see
+/// [`AsyncPgConnection::transaction_with_context`]
+///
+/// The context consists of configuration parameters, like the
+/// `application_name` shown in `pg_stat_activity` or custom parameters
+/// such as `app.user_id`, which can be read via `current_setting` in
+/// queries, triggers or row level security policies.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SessionContext {
+    settings: Vec<(Cow<'static, str>, String)>,
+}
+
+impl SessionContext {
+    /// Construct an empty context
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the `application_name`, which is shown in `pg_stat_activity`
+    /// and can be included in the server log
+    pub fn with_application_name(self, application_name: impl Into<String>) -> Self {
+        self.with_setting("application_name", application_name)
+    }
+
+    /// Set the configuration parameter `name`, replacing a previously set value
+    ///
+    /// Custom parameters need to be prefixed with a namespace,
+    /// like `app.request_id`.
+    pub fn with_setting(
+        mut self,
+        name: impl Into<Cow<'static, str>>,
+        value: impl Into<String>,
+    ) -> Self {
+        let name = name.into();
+        let value = value.into();
+        match self.settings.iter_mut().find(|(n, _)| *n == name) {
+            Some((_, v)) => *v = value,
+            None => self.settings.push((name, value)),
+        }
+        self
+    }
+
+    /// The value set for the configuration parameter `name`
+    pub fn setting(&self, name: &str) -> Option<&str> {
+        self.settings
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Iterate over the configured parameters and their values
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.settings.iter().map(|(n, v)| (n.as_ref(), v.as_str()))
+    }
+
+    async fn apply(&self, conn: &mut AsyncPgConnection) -> QueryResult<()> {
+        if self.settings.is_empty() {
+            return Ok(());
+        }
+        let mut sql = String::from("SELECT ");
+        for idx in 0..self.settings.len() {
+            if idx > 0 {
+                sql += ", ";
+            }
+            sql += &format!("set_config(${}, ${}, true)", 2 * idx + 1, 2 * idx + 2);
+        }
+        let mut query = diesel::sql_query(sql).into_boxed();
+        for (name, value) in &self.settings {
+            query = query
+                .bind::<Text, _>(name.as_ref())
+                .bind::<Text, _>(value.as_str());
+        }
+        query.execute(conn).await?;
+        Ok(())
+    }
+}
+
+diesel::define_sql_function! {
+    #[sql_name = "set_config"]
+    fn set_config(name: Text, value: Text, is_local: Bool) -> Text;
+}
+
+diesel::define_sql_function! {
+    #[sql_name = "current_setting"]
+    fn current_setting(name: Text, missing_ok: Bool) -> Nullable<Text>;
+}
+
+impl AsyncPgConnection {
+    /// Set the configuration parameter `name` to `value`
+    ///
+    /// Both are passed as bind parameters, so they do not need to be quoted.
+    /// The parameter is reset to its previous value once the returned guard is
+    /// dropped or [`ConfigGuard::reset`] is called, see [`ConfigGuard`].
+    ///
+    /// ```rust
+    /// # include!("../doctest_setup.rs");
+    /// use diesel_async::pg::ConfigScope;
+    /// #
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// #     run_test().await.unwrap();
+    /// # }
+    /// #
+    /// # async fn run_test() -> QueryResult<()> {
+    /// #     let conn = &mut establish_connection().await;
+    /// let mut guard = conn.set_config("work_mem", "64MB", ConfigScope::Session).await?;
+    /// assert_eq!(Some("64MB"), guard.config("work_mem").await?.as_deref());
+    /// guard.reset().await?;
+    /// assert_ne!(Some("64MB"), conn.config("work_mem").await?.as_deref());
+    /// #     Ok(())
+    /// # }
+    /// ```
+    pub async fn set_config(
+        &mut self,
+        name: &str,
+        value: &str,
+        scope: ConfigScope,
+    ) -> QueryResult<ConfigGuard<'_>> {
+        let previous = self.config(name).await?;
+        diesel::select(set_config(name, value, scope.is_local()))
+            .execute(self)
+            .await?;
+        Ok(ConfigGuard {
+            connection: self,
+            name: name.to_owned(),
+            previous,
+            scope,
+            done: false,
+        })
+    }
+
+    /// Executes the given function inside of a transaction with the
+    /// given context applied
+    ///
+    /// The configuration parameters of the context are set via `SET LOCAL`
+    /// semantics before `f` runs, so they are cleared automatically once the
+    /// transaction ends and do not leak to later users of a pooled connection.
+    /// If the connection is already inside of a transaction, the parameters
+    /// remain set until the outer transaction ends.
+    ///
+    /// ```rust
+    /// # include!("../doctest_setup.rs");
+    /// use diesel::sql_types::Text;
+    /// use diesel_async::pg::SessionContext;
+    /// use scoped_futures::ScopedFutureExt;
+    /// #
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// #     run_test().await.unwrap();
+    /// # }
+    /// #
+    /// # async fn run_test() -> QueryResult<()> {
+    /// # use diesel_async::RunQueryDsl;
+    /// #     let conn = &mut connection_no_transaction().await;
+    /// let context = SessionContext::new()
+    ///     .with_application_name("checkout-service")
+    ///     .with_setting("app.request_id", "4b1c2d");
+    /// let request_id = conn
+    ///     .transaction_with_context(context, |conn| {
+    ///         async move {
+    ///             diesel::select(diesel::dsl::sql::<Text>("current_setting('app.request_id')"))
+    ///                 .get_result::<String>(conn)
+    ///                 .await
+    ///         }
+    ///         .scope_boxed()
+    ///     })
+    ///     .await?;
+    /// assert_eq!("4b1c2d", request_id);
+    /// assert_eq!(Some(""), conn.config("app.request_id").await?.as_deref());
+    /// #     Ok(())
+    /// # }
+    /// ```
+    pub async fn transaction_with_context<'a, R, E, F>(
+        &mut self,
+        context: SessionContext,
+        f: F,
+    ) -> Result<R, E>
+    where
+        F: for<'r> FnOnce(&'r mut Self) -> ScopedBoxFuture<'a, 'r, Result<R, E>> + Send + 'a,
+        E: From<diesel::result::Error> + Send + 'a,
+        R: Send + 'a,
+    {
+        self.transaction(|conn| {
+            async move {
+                context.apply(conn).await?;
+                f(conn).await
+            }
+            .scope_boxed()
+        })
+        .await
+    }
+
+    /// The current value of the configuration parameter `name`
+    ///
+    /// Returns `None` for custom parameters that were not set before.
+    pub async fn config(&mut self, name: &str) -> QueryResult<Option<String>> {
+        diesel::select(current_setting(name, true))
+            .get_result(self)
+            .await
+    }
+}
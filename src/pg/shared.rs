@@ -0,0 +1,163 @@
+use super::row::PgRowStream;
+use super::{execute_prepared, load_prepared, AsyncPgConnection};
+use crate::{AnsiTransactionManager, TransactionManager};
+use diesel::deserialize::FromSqlRow;
+use diesel::pg::Pg;
+use diesel::query_builder::{AsQuery, QueryFragment, QueryId};
+use diesel::QueryResult;
+use futures_util::future::BoxFuture;
+use futures_util::TryStreamExt;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+/// A postgres connection that can be shared between tasks
+///
+/// All queries are pipelined on the underlying [`AsyncPgConnection`], see
+/// its documentation for details. In contrast to [`AsyncPgConnection`],
+/// the methods of this type take `&self`, so that the connection can be
+/// shared via an [`Arc`](std::sync::Arc) without an additional mutex that
+/// serializes the queries of different tasks. The connection is only locked
+/// while building a query, not while waiting for its results. The methods are
+/// named differently from the methods of [`RunQueryDsl`](crate::RunQueryDsl),
+/// which would otherwise take precedence for an `Arc<SharedAsyncPgConnection>`.
+///
+/// As a transaction would affect the queries of all tasks sharing the
+/// connection, transactions are not supported. Use
+/// [`SharedAsyncPgConnection::into_inner`] to run a transaction once the
+/// connection is no longer shared.
+///
+/// ```rust
+/// # include!("../doctest_setup.rs");
+/// use diesel_async::pg::SharedAsyncPgConnection;
+/// use std::sync::Arc;
+/// #
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// #     run_test().await.unwrap();
+/// # }
+/// #
+/// # async fn run_test() -> QueryResult<()> {
+/// #     use schema::users;
+/// #     let mut conn = connection_no_transaction().await;
+/// #     create_tables(&mut conn).await;
+/// let conn = SharedAsyncPgConnection::new(conn)
+///     .ok()
+///     .expect("the connection has no open transaction");
+/// let conn = Arc::new(conn);
+///
+/// let tasks = ["Ruby", "Jim"].map(|name| {
+///     let conn = conn.clone();
+///     tokio::spawn(async move {
+///         conn.execute_query(diesel::insert_into(users::table).values(users::name.eq(name)))
+///             .await
+///     })
+/// });
+/// for task in tasks {
+///     assert_eq!(1, task.await.unwrap()?);
+/// }
+///
+/// let count = conn
+///     .load_query::<_, i64>(users::table.filter(users::name.eq_any(["Ruby", "Jim"])).count())
+///     .await?;
+/// assert_eq!(vec![2], count);
+/// #     Ok(())
+/// # }
+/// ```
+pub struct SharedAsyncPgConnection {
+    conn: Mutex<AsyncPgConnection>,
+}
+
+impl std::fmt::Debug for SharedAsyncPgConnection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SharedAsyncPgConnection")
+            .finish_non_exhaustive()
+    }
+}
+
+impl SharedAsyncPgConnection {
+    /// Wrap the given connection to share it between tasks
+    ///
+    /// Returns the connection again if it has an open transaction.
+    #[allow(clippy::result_large_err)]
+    pub fn new(mut conn: AsyncPgConnection) -> Result<Self, AsyncPgConnection> {
+        let in_transaction = AnsiTransactionManager::transaction_manager_status_mut(&mut conn)
+            .transaction_depth()
+            .map_or(true, |depth| depth.is_some());
+        if in_transaction {
+            return Err(conn);
+        }
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    /// Unwrap the underlying connection
+    pub fn into_inner(self) -> AsyncPgConnection {
+        self.conn
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Execute the given query, returning the number of affected rows
+    ///
+    /// This corresponds to [`RunQueryDsl::execute`](crate::RunQueryDsl::execute).
+    pub async fn execute_query<T>(&self, query: T) -> QueryResult<usize>
+    where
+        T: QueryFragment<Pg> + QueryId,
+    {
+        let execute = {
+            let mut conn = self.lock();
+            let execute = conn.with_prepared_statement(query, execute_prepared);
+            conn.run_with_connection_future(execute)
+        };
+        execute.await
+    }
+
+    /// Load the results of the given query
+    ///
+    /// This corresponds to [`RunQueryDsl::load`](crate::RunQueryDsl::load).
+    pub async fn load_query<T, U>(&self, query: T) -> QueryResult<Vec<U>>
+    where
+        T: AsQuery,
+        T::Query: QueryFragment<Pg> + QueryId,
+        U: FromSqlRow<T::SqlType, Pg>,
+    {
+        let rows = self.load_rows(query).await?.try_collect::<Vec<_>>().await?;
+        rows.iter()
+            .map(|row| U::build_from_row(row).map_err(diesel::result::Error::DeserializationError))
+            .collect()
+    }
+
+    /// Load the first result of the given query
+    ///
+    /// Returns [`diesel::result::Error::NotFound`] if the query returned no rows.
+    /// This corresponds to [`RunQueryDsl::get_result`](crate::RunQueryDsl::get_result).
+    pub async fn get_query_result<T, U>(&self, query: T) -> QueryResult<U>
+    where
+        T: AsQuery,
+        T::Query: QueryFragment<Pg> + QueryId,
+        U: FromSqlRow<T::SqlType, Pg>,
+    {
+        let rows = self.load_rows(query).await?;
+        let row = std::pin::pin!(rows)
+            .try_next()
+            .await?
+            .ok_or(diesel::result::Error::NotFound)?;
+        U::build_from_row(&row).map_err(diesel::result::Error::DeserializationError)
+    }
+
+    fn load_rows<T>(&self, query: T) -> BoxFuture<'static, QueryResult<PgRowStream>>
+    where
+        T: AsQuery,
+        T::Query: QueryFragment<Pg> + QueryId,
+    {
+        let mut conn = self.lock();
+        let load = conn.with_prepared_statement(query.as_query(), load_prepared);
+        conn.run_with_connection_future(load)
+    }
+
+    // The lock is only held while building a query, so a panic
+    // cannot leave the connection in an inconsistent state
+    fn lock(&self) -> MutexGuard<'_, AsyncPgConnection> {
+        self.conn.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
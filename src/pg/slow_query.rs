@@ -0,0 +1,194 @@
+use super::error_helper::ErrorHelper;
+use super::serialize::ToSqlHelper;
+use super::{type_from_oid, AsyncPgConnection, InterceptedQuery, QueryInterceptor, QueryOutcome};
+use diesel::QueryResult;
+use futures_util::TryStreamExt;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Type of the callback passed to
+/// [`AsyncPgConnection::explain_slow_queries`]
+pub type SlowQueryCallback = dyn Fn(&SlowQuery) + Send + Sync;
+
+/// A query that took longer than the configured threshold,
+/// together with its execution plan
+///
+/// See [`AsyncPgConnection::explain_slow_queries`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct SlowQuery {
+    /// The SQL of the query
+    pub sql: String,
+    /// The time the execution of the query took
+    pub duration: Duration,
+    /// The execution plan reported by `EXPLAIN`, or the error
+    /// explaining the query failed with
+    pub plan: QueryResult<String>,
+}
+
+impl std::fmt::Display for SlowQuery {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Slow query ({:?}): {}", self.duration, self.sql)?;
+        match self.plan {
+            Ok(ref plan) => write!(f, "{plan}"),
+            Err(ref e) => write!(f, "Failed to explain the query: {e}"),
+        }
+    }
+}
+
+// Slow queries waiting to be explained, shared between the
+// explainers and the connection that runs the `EXPLAIN` statements
+pub(super) type ExplainQueue = Arc<Mutex<Vec<PendingExplain>>>;
+
+pub(super) struct PendingExplain {
+    query: InterceptedQuery,
+    duration: Duration,
+    callback: Arc<SlowQueryCallback>,
+}
+
+struct SlowQueryExplainer {
+    threshold: Duration,
+    callback: Arc<SlowQueryCallback>,
+    queue: ExplainQueue,
+}
+
+impl QueryInterceptor for SlowQueryExplainer {
+    fn after_query(&self, query: &InterceptedQuery, outcome: &QueryOutcome<'_>) {
+        if outcome.duration < self.threshold || outcome.result.is_err() || !is_explainable(query) {
+            return;
+        }
+        self.queue
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(PendingExplain {
+                query: query.clone(),
+                duration: outcome.duration,
+                callback: self.callback.clone(),
+            });
+    }
+}
+
+// Explains the queued slow queries, which must only happen outside of a
+// transaction, as a failing `EXPLAIN` would abort the transaction
+pub(super) async fn explain_pending(client: &tokio_postgres::Client, pending: Vec<PendingExplain>) {
+    for PendingExplain {
+        query,
+        duration,
+        callback,
+    } in pending
+    {
+        let plan = explain(client, &query).await;
+        callback(&SlowQuery {
+            sql: query.sql().to_owned(),
+            duration,
+            plan,
+        });
+    }
+}
+
+// Whether the query is a single statement supported by `EXPLAIN`
+fn is_explainable(query: &InterceptedQuery) -> bool {
+    const KEYWORDS: [&str; 7] = [
+        "SELECT", "INSERT", "UPDATE", "DELETE", "MERGE", "WITH", "VALUES",
+    ];
+
+    let sql = query.sql().trim();
+    !sql.contains(';')
+        && sql.split_whitespace().next().is_some_and(|first| {
+            KEYWORDS
+                .iter()
+                .any(|keyword| first.eq_ignore_ascii_case(keyword))
+        })
+}
+
+async fn explain(client: &tokio_postgres::Client, query: &InterceptedQuery) -> QueryResult<String> {
+    let types = query
+        .bind_types()
+        .iter()
+        .map(type_from_oid)
+        .collect::<QueryResult<Vec<_>>>()?;
+    let stmt = client
+        .prepare_typed(&format!("EXPLAIN {}", query.sql()), &types)
+        .await
+        .map_err(ErrorHelper)?;
+    let binds = query
+        .bind_types()
+        .iter()
+        .zip(query.binds())
+        .map(|(metadata, bind)| ToSqlHelper::new(metadata, bind.as_deref()));
+    let lines = client
+        .query_raw(&stmt, binds)
+        .await
+        .map_err(ErrorHelper)?
+        .map_ok(|row| row.get::<_, String>(0))
+        .try_collect::<Vec<_>>()
+        .await
+        .map_err(ErrorHelper)?;
+    Ok(lines.join("\n"))
+}
+
+impl AsyncPgConnection {
+    /// Explain queries taking at least `threshold` and pass their
+    /// execution plan to the given callback
+    ///
+    /// This helps to notice missing indexes during development. Once a query
+    /// took at least `threshold`, it is queued to be explained on this
+    /// connection, without executing the query again. The queued queries are
+    /// explained right before the next statement that runs outside of a
+    /// transaction, so a failing `EXPLAIN` never aborts a transaction of the
+    /// application. The callback is called with the plan and the query, which
+    /// usually logs the [`SlowQuery`] via its `Display` implementation.
+    /// Queries still queued once the connection is closed are not explained.
+    ///
+    /// Slow queries are only explained in debug builds, in release builds
+    /// this function does nothing.
+    ///
+    /// To explain the slow queries of all connections of a pool, call this
+    /// function via
+    /// [`ManagerConfig::warm_up`](crate::pooled_connection::ManagerConfig::warm_up).
+    ///
+    /// ```rust
+    /// # include!("../doctest_setup.rs");
+    /// use diesel_async::pg::SlowQuery;
+    /// use std::time::Duration;
+    /// use tokio::sync::mpsc;
+    /// #
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// #     run_test().await.unwrap();
+    /// # }
+    /// #
+    /// # async fn run_test() -> QueryResult<()> {
+    /// # use diesel_async::RunQueryDsl;
+    /// #     let conn = &mut connection_no_transaction().await;
+    /// let (tx, mut rx) = mpsc::unbounded_channel();
+    /// conn.explain_slow_queries(Duration::ZERO, move |query: &SlowQuery| {
+    ///     if let Ok(ref plan) = query.plan {
+    ///         let _ = tx.send(plan.clone());
+    ///     }
+    /// });
+    ///
+    /// diesel::sql_query("SELECT relname FROM pg_class")
+    ///     .execute(conn)
+    ///     .await?;
+    /// // the slow query is explained before the next statement
+    /// // that runs outside of a transaction
+    /// diesel::sql_query("SELECT 1").execute(conn).await?;
+    /// let plan = rx.recv().await.unwrap();
+    /// assert!(plan.contains("Seq Scan on pg_class"));
+    /// #     Ok(())
+    /// # }
+    /// ```
+    pub fn explain_slow_queries<F>(&mut self, threshold: Duration, callback: F)
+    where
+        F: Fn(&SlowQuery) + Send + Sync + 'static,
+    {
+        if cfg!(debug_assertions) {
+            self.add_interceptor(Arc::new(SlowQueryExplainer {
+                threshold,
+                callback: Arc::new(callback),
+                queue: self.slow_queries.clone(),
+            }));
+        }
+    }
+}
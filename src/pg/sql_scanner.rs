@@ -0,0 +1,166 @@
+// A minimal scanner for the SQL of intercepted queries, used to find the
+// keywords and table names of statements without being misled by string
+// literals, quoted identifiers or comments
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Token<'a> {
+    // An unquoted keyword or identifier
+    Word(&'a str),
+    // A quoted identifier, without the surrounding quotes
+    QuotedIdentifier(&'a str),
+    // A string literal, a dollar quoted string, a number or a parameter like `$1`
+    Literal,
+    // Any other character, like `;`, `,` or `.`
+    Punctuation(char),
+}
+
+impl Token<'_> {
+    pub(super) fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self, Token::Word(word) if word.eq_ignore_ascii_case(keyword))
+    }
+}
+
+// The tokens of the given SQL, skipping whitespace and comments
+pub(super) fn tokens(sql: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let mut chars = sql.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {}
+            '\'' => {
+                for (_, next) in chars.by_ref() {
+                    if next == '\'' {
+                        break;
+                    }
+                }
+                tokens.push(Token::Literal);
+            }
+            '"' => {
+                let end = sql[start + 1..]
+                    .find('"')
+                    .map_or(sql.len(), |idx| start + 1 + idx);
+                while chars.next_if(|&(idx, _)| idx <= end).is_some() {}
+                tokens.push(Token::QuotedIdentifier(&sql[start + 1..end]));
+            }
+            // dollar quoted strings like `$$...$$` or `$tag$...$tag$`,
+            // and parameters like `$1`
+            '$' => {
+                let rest = &sql[start + 1..];
+                let tag_length = rest
+                    .find(|c: char| !c.is_alphanumeric() && c != '_')
+                    .unwrap_or(rest.len());
+                if rest.starts_with(|c: char| c.is_ascii_digit())
+                    || !rest[tag_length..].starts_with('$')
+                {
+                    while chars.next_if(|&(_, next)| next.is_ascii_digit()).is_some() {}
+                    tokens.push(Token::Literal);
+                    continue;
+                }
+                let delimiter = &sql[start..start + tag_length + 2];
+                let body = start + delimiter.len();
+                let end = sql[body..]
+                    .find(delimiter)
+                    .map_or(sql.len(), |idx| body + idx + delimiter.len());
+                while chars.next_if(|&(idx, _)| idx < end).is_some() {}
+                tokens.push(Token::Literal);
+            }
+            '-' if chars.next_if(|&(_, next)| next == '-').is_some() => {
+                for (_, next) in chars.by_ref() {
+                    if next == '\n' {
+                        break;
+                    }
+                }
+            }
+            // block comments may be nested
+            '/' if chars.next_if(|&(_, next)| next == '*').is_some() => {
+                let mut depth = 1;
+                while let Some((_, next)) = chars.next() {
+                    if next == '*' && chars.next_if(|&(_, next)| next == '/').is_some() {
+                        depth -= 1;
+                        if depth == 0 {
+                            break;
+                        }
+                    } else if next == '/' && chars.next_if(|&(_, next)| next == '*').is_some() {
+                        depth += 1;
+                    }
+                }
+            }
+            c if c.is_ascii_digit() => {
+                while chars
+                    .next_if(|&(_, next)| next.is_ascii_alphanumeric() || next == '.')
+                    .is_some()
+                {}
+                tokens.push(Token::Literal);
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let mut end = start + c.len_utf8();
+                while let Some((idx, next)) =
+                    chars.next_if(|&(_, next)| next.is_alphanumeric() || next == '_' || next == '$')
+                {
+                    end = idx + next.len_utf8();
+                }
+                let word = &sql[start..end];
+                // escape string constants like `E'it\'s'`
+                if word.eq_ignore_ascii_case("E")
+                    && chars.next_if(|&(_, next)| next == '\'').is_some()
+                {
+                    while let Some((_, next)) = chars.next() {
+                        match next {
+                            '\\' => {
+                                chars.next();
+                            }
+                            '\'' => break,
+                            _ => {}
+                        }
+                    }
+                    tokens.push(Token::Literal);
+                    continue;
+                }
+                tokens.push(Token::Word(word));
+            }
+            c => tokens.push(Token::Punctuation(c)),
+        }
+    }
+    tokens
+}
+
+// The tokens of each non empty statement of the given SQL
+pub(super) fn statements(sql: &str) -> Vec<Vec<Token<'_>>> {
+    tokens(sql)
+        .split(|token| *token == Token::Punctuation(';'))
+        .filter(|statement| !statement.is_empty())
+        .map(<[_]>::to_vec)
+        .collect()
+}
+
+// The unquoted words of the given SQL
+pub(super) fn keywords(sql: &str) -> Vec<&str> {
+    tokens(sql)
+        .into_iter()
+        .filter_map(|token| match token {
+            Token::Word(word) => Some(word),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn statements_are_split_outside_of_literals_and_comments() {
+        let statements = statements(
+            "SELECT $$a; UPDATE b$$, $tag$;$tag$, E'\\';', 'it''s;', \"c;\" -- d; UPDATE e
+             FROM f /* g; /* nested; */ UPDATE h; */ WHERE i = $1; DELETE FROM j;",
+        );
+        assert_eq!(2, statements.len());
+        assert_eq!(
+            keywords("SELECT $$a; UPDATE b$$ -- c\n /* d */ FROM e"),
+            vec!["SELECT", "FROM", "e"]
+        );
+        assert!(statements[0].contains(&Token::QuotedIdentifier("c;")));
+        assert!(statements[1][0].is_keyword("delete"));
+        assert_eq!(Token::Word("j"), statements[1][2]);
+    }
+}
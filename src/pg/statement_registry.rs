@@ -0,0 +1,175 @@
+use super::AsyncPgConnection;
+use crate::stmt_cache::PrepareCallback;
+use diesel::connection::statement_cache::{PrepareForCache, StatementCacheKey};
+use diesel::pg::PgTypeMetadata;
+use diesel::QueryResult;
+use std::any::TypeId;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+type RegistryKey = (Option<TypeId>, String);
+
+// The default number of statements recorded by a registry
+const DEFAULT_CAPACITY: usize = 256;
+
+/// Records the statements cached by a set of connections, so that they can
+/// be prepared again on new connections
+///
+/// Connections replacing broken connections of a pool otherwise start with an
+/// empty statement cache and need to prepare each statement again once it is
+/// used, which adds a round trip to all of the first queries after a failover.
+/// Attach all connections of a pool to the same registry via
+/// [`ManagerConfig::warm_up`](crate::pooled_connection::ManagerConfig::warm_up)
+/// to prepare the statements used so far on each new connection.
+///
+/// The registry records at most [`StatementRegistry::with_capacity`]
+/// statements, 256 by default. Once it is full, the statement that was least
+/// recently recorded by any of the attached connections is forgotten.
+///
+/// ```rust
+/// # include!("../doctest_setup.rs");
+/// use diesel_async::pg::StatementRegistry;
+/// use diesel_async::pooled_connection::{AsyncDieselConnectionManager, ManagerConfig};
+/// use futures_util::FutureExt;
+/// #
+/// # fn main() {
+/// #     let database_url = database_url();
+/// let registry = StatementRegistry::default();
+/// let mut config = ManagerConfig::default();
+/// config.warm_up = Some(Box::new(move |conn: &mut AsyncPgConnection| {
+///     let registry = registry.clone();
+///     async move { registry.attach(conn).await }.boxed()
+/// }));
+/// let manager = AsyncDieselConnectionManager::<AsyncPgConnection>::new_with_config(
+///     database_url,
+///     config,
+/// );
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct StatementRegistry {
+    inner: Arc<Mutex<RegistryInner>>,
+}
+
+#[derive(Debug)]
+struct RegistryInner {
+    // The bind types of each statement, together with the
+    // tick of the last time the statement was recorded
+    statements: HashMap<RegistryKey, (Vec<PgTypeMetadata>, u64)>,
+    capacity: usize,
+    tick: u64,
+}
+
+impl Default for StatementRegistry {
+    fn default() -> Self {
+        Self::with_capacity(DEFAULT_CAPACITY)
+    }
+}
+
+impl StatementRegistry {
+    /// Construct a registry recording at most `capacity` statements
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(RegistryInner {
+                statements: HashMap::new(),
+                capacity,
+                tick: 0,
+            })),
+        }
+    }
+
+    /// The maximal number of recorded statements
+    pub fn capacity(&self) -> usize {
+        self.lock().capacity
+    }
+
+    /// Prepare all recorded statements on the given connection and record
+    /// the statements the connection caches from now on
+    ///
+    /// Statements that fail to prepare, for example because a table
+    /// was removed in the meantime, are removed from the registry.
+    pub async fn attach(&self, conn: &mut AsyncPgConnection) -> QueryResult<()> {
+        let statements = self
+            .lock()
+            .statements
+            .iter()
+            .map(|(key, (metadata, _))| (key.clone(), metadata.clone()))
+            .collect::<Vec<_>>();
+        let raw_connection = conn.conn.clone();
+        let stmt_cache = conn.stmt_cache.clone();
+        let registry = self.clone();
+        let prepare = async move {
+            // Preparing all statements at once pipelines them on the connection
+            let prepared =
+                futures_util::future::join_all(statements.iter().map(|((_, sql), metadata)| {
+                    raw_connection
+                        .clone()
+                        .prepare(sql, metadata, PrepareForCache::Yes)
+                }))
+                .await;
+            let mut stmt_cache = stmt_cache.write().await;
+            for (((query_id, sql), metadata), stmt) in statements.into_iter().zip(prepared) {
+                let Ok((stmt, _)) = stmt else {
+                    registry.lock().statements.remove(&(query_id, sql));
+                    continue;
+                };
+                let key = match query_id {
+                    Some(id) => StatementCacheKey::Type(id),
+                    None => StatementCacheKey::Sql {
+                        sql,
+                        bind_types: metadata,
+                    },
+                };
+                stmt_cache.insert(key, stmt);
+            }
+            Ok(())
+        };
+        conn.run_with_connection_future(prepare).await?;
+        conn.statement_registry = Some(self.clone());
+        Ok(())
+    }
+
+    /// The number of recorded statements
+    pub fn len(&self) -> usize {
+        self.lock().statements.len()
+    }
+
+    /// Whether no statements were recorded yet
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Forget all recorded statements
+    pub fn clear(&self) {
+        self.lock().statements.clear();
+    }
+
+    pub(super) fn record(&self, query_id: Option<TypeId>, sql: &str, metadata: &[PgTypeMetadata]) {
+        let mut inner = self.lock();
+        inner.tick += 1;
+        let tick = inner.tick;
+        if inner.capacity == 0 {
+            return;
+        }
+        let key = (query_id, sql.to_owned());
+        if let Some((_, recorded_at)) = inner.statements.get_mut(&key) {
+            *recorded_at = tick;
+            return;
+        }
+        if inner.statements.len() >= inner.capacity {
+            let least_recent = inner
+                .statements
+                .iter()
+                .min_by_key(|(_, (_, recorded_at))| *recorded_at)
+                .map(|(key, _)| key.clone());
+            if let Some(least_recent) = least_recent {
+                inner.statements.remove(&least_recent);
+            }
+        }
+        inner.statements.insert(key, (metadata.to_vec(), tick));
+    }
+
+    fn lock(&self) -> MutexGuard<'_, RegistryInner> {
+        self.inner.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
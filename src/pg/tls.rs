@@ -0,0 +1,68 @@
+use super::AsyncPgConnection;
+use diesel::ConnectionResult;
+use futures_util::future::BoxFuture;
+use futures_util::FutureExt;
+use std::sync::OnceLock;
+
+/// Establish a TLS secured connection via `rustls`
+///
+/// If TLS is used, the certificate of the server is verified against the
+/// Mozilla root certificates of `webpki-roots`, including the host name.
+/// Whether TLS is used is controlled by the `sslmode` parameter of the
+/// database URL. The default `sslmode=prefer` falls back to an unencrypted
+/// connection if the server does not support TLS, so only together with
+/// `sslmode=require` this is equivalent to `sslmode=verify-full` of `libpq`. Use
+/// [`AsyncPgConnection::establish_with_tls`] to customize the TLS settings,
+/// for example to trust a private certificate authority.
+///
+/// The signature allows to use this function as
+/// [`ManagerConfig::custom_setup`](crate::pooled_connection::ManagerConfig::custom_setup):
+///
+/// ```rust
+/// # include!("../doctest_setup.rs");
+/// use diesel_async::pooled_connection::deadpool::Pool;
+/// use diesel_async::pooled_connection::{AsyncDieselConnectionManager, ManagerConfig};
+/// use diesel_async::pg::establish_with_rustls;
+/// #
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// #     run_test().await.unwrap();
+/// # }
+/// #
+/// # async fn run_test() -> Result<(), Box<dyn std::error::Error>> {
+/// #     use diesel_async::RunQueryDsl;
+/// #     let database_url = database_url();
+/// let mut config = ManagerConfig::<AsyncPgConnection>::default();
+/// config.custom_setup = Box::new(establish_with_rustls);
+///
+/// let manager = AsyncDieselConnectionManager::new_with_config(database_url, config);
+/// let pool = Pool::builder(manager).build()?;
+/// # let mut conn = pool.get().await?;
+/// # diesel::select(1.into_sql::<diesel::sql_types::Integer>()).execute(&mut conn).await?;
+/// #     Ok(())
+/// # }
+/// ```
+///
+/// This function is only available with the `postgres-rustls` feature.
+pub fn establish_with_rustls(
+    database_url: &str,
+) -> BoxFuture<'_, ConnectionResult<AsyncPgConnection>> {
+    static CONFIG: OnceLock<rustls::ClientConfig> = OnceLock::new();
+
+    let config = CONFIG.get_or_init(|| {
+        let mut roots = rustls::RootCertStore::empty();
+        roots.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|anchor| {
+            rustls::OwnedTrustAnchor::from_subject_spki_name_constraints(
+                anchor.subject,
+                anchor.spki,
+                anchor.name_constraints,
+            )
+        }));
+        rustls::ClientConfig::builder()
+            .with_safe_defaults()
+            .with_root_certificates(roots)
+            .with_no_client_auth()
+    });
+    let tls = tokio_postgres_rustls::MakeRustlsConnect::new(config.clone());
+    AsyncPgConnection::establish_with_tls(database_url, tls).boxed()
+}
@@ -1,7 +1,10 @@
-use crate::{AnsiTransactionManager, AsyncConnection, TransactionManager};
+use crate::transaction_manager::AbandonedTransactionGuard;
+use crate::{AnsiTransactionManager, AsyncConnection, RunQueryDsl, TransactionManager};
 use diesel::backend::Backend;
 use diesel::pg::Pg;
 use diesel::query_builder::{AstPass, QueryBuilder, QueryFragment};
+use diesel::result::Error;
+use diesel::sql_types::{BigInt, Bool};
 use diesel::QueryResult;
 use scoped_futures::ScopedBoxFuture;
 
@@ -21,6 +24,7 @@ pub struct TransactionBuilder<'a, C> {
     isolation_level: Option<IsolationLevel>,
     read_mode: Option<ReadMode>,
     deferrable: Option<Deferrable>,
+    advisory_locks: Vec<AdvisoryXactLock>,
 }
 
 impl<'a, C> TransactionBuilder<'a, C>
@@ -33,6 +37,7 @@ where
             isolation_level: None,
             read_mode: None,
             deferrable: None,
+            advisory_locks: Vec::new(),
         }
     }
 
@@ -275,6 +280,88 @@ where
         self
     }
 
+    /// Sets the isolation level of the transaction
+    ///
+    /// This allows to choose the isolation level at runtime, for example from
+    /// configuration. It is equivalent to calling
+    /// [`read_committed`](Self::read_committed),
+    /// [`repeatable_read`](Self::repeatable_read) or
+    /// [`serializable`](Self::serializable).
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # include!("../doctest_setup.rs");
+    /// use diesel_async::pg::IsolationLevel;
+    /// #
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// #     run_test().await.unwrap();
+    /// # }
+    /// #
+    /// # async fn run_test() -> QueryResult<()> {
+    /// #     let conn = &mut connection_no_transaction().await;
+    /// let isolation_level = if std::env::var("STRICT_TRANSACTIONS").is_ok() {
+    ///     IsolationLevel::Serializable
+    /// } else {
+    ///     IsolationLevel::ReadCommitted
+    /// };
+    /// conn.build_transaction()
+    ///     .isolation_level(isolation_level)
+    ///     .run(|conn| Box::pin(async { Ok(()) }) as _)
+    ///     .await
+    /// # }
+    /// ```
+    pub fn isolation_level(mut self, isolation_level: IsolationLevel) -> Self {
+        self.isolation_level = Some(isolation_level);
+        self
+    }
+
+    /// Acquire the transaction level advisory lock identified by `key`
+    /// before the transaction body runs
+    ///
+    /// This waits until the lock becomes available. The lock is released
+    /// automatically at the end of the transaction. Can be called multiple times
+    /// to acquire several locks, which are acquired in the order given.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # include!("../doctest_setup.rs");
+    /// #
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// #     run_test().await.unwrap();
+    /// # }
+    /// #
+    /// # async fn run_test() -> QueryResult<()> {
+    /// #     let conn = &mut connection_no_transaction().await;
+    /// conn.build_transaction()
+    ///     .advisory_xact_lock(42)
+    ///     .run(|conn| Box::pin(async { Ok(()) }) as _)
+    ///     .await
+    /// # }
+    /// ```
+    pub fn advisory_xact_lock(mut self, key: i64) -> Self {
+        self.advisory_locks
+            .push(AdvisoryXactLock { key, wait: true });
+        self
+    }
+
+    /// Try to acquire the transaction level advisory lock identified by `key`
+    /// before the transaction body runs
+    ///
+    /// If the lock is currently held by another session the transaction
+    /// is rolled back without running the transaction body and
+    /// [`run`](Self::run) returns [`Error::QueryBuilderError`] wrapping an
+    /// [`AdvisoryLockNotAcquired`]. The lock is released automatically at
+    /// the end of the transaction.
+    pub fn try_advisory_xact_lock(mut self, key: i64) -> Self {
+        self.advisory_locks
+            .push(AdvisoryXactLock { key, wait: false });
+        self
+    }
+
     /// Runs the given function inside of the transaction
     /// with the parameters given to this builder.
     ///
@@ -296,7 +383,23 @@ where
         self.to_sql(&mut query_builder, &Pg)?;
         let sql = query_builder.finish();
 
-        AnsiTransactionManager::begin_transaction_sql(&mut *self.connection, &sql).await?;
+        let guard = AbandonedTransactionGuard::new(self.connection.transaction_state());
+        let res = self.run_transaction(&sql, f).await;
+        guard.finish();
+        res
+    }
+
+    async fn run_transaction<'b, T, E, F>(&mut self, sql: &str, f: F) -> Result<T, E>
+    where
+        F: for<'r> FnOnce(&'r mut C) -> ScopedBoxFuture<'b, 'r, Result<T, E>> + Send + 'a,
+        T: 'b,
+        E: From<diesel::result::Error> + 'b,
+    {
+        AnsiTransactionManager::begin_transaction_sql(&mut *self.connection, sql).await?;
+        if let Err(e) = self.acquire_advisory_locks().await {
+            AnsiTransactionManager::rollback_transaction(&mut *self.connection).await?;
+            return Err(e.into());
+        }
         match f(&mut *self.connection).await {
             Ok(value) => {
                 AnsiTransactionManager::commit_transaction(&mut *self.connection).await?;
@@ -310,6 +413,58 @@ where
     }
 }
 
+impl<'a, C> TransactionBuilder<'a, C>
+where
+    C: AsyncConnection<Backend = Pg>,
+{
+    async fn acquire_advisory_locks(&mut self) -> QueryResult<()> {
+        for lock in &self.advisory_locks {
+            if lock.wait {
+                diesel::sql_query("SELECT pg_advisory_xact_lock($1)")
+                    .bind::<BigInt, _>(lock.key)
+                    .execute(&mut *self.connection)
+                    .await?;
+            } else {
+                let acquired = diesel::select(
+                    diesel::dsl::sql::<Bool>("pg_try_advisory_xact_lock(")
+                        .bind::<BigInt, _>(lock.key)
+                        .sql(")"),
+                )
+                .get_result::<bool>(&mut *self.connection)
+                .await?;
+                if !acquired {
+                    return Err(Error::QueryBuilderError(Box::new(
+                        AdvisoryLockNotAcquired { key: lock.key },
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The error of transactions whose advisory lock requested via
+/// [`TransactionBuilder::try_advisory_xact_lock`] is held by another session
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AdvisoryLockNotAcquired {
+    key: i64,
+}
+
+impl AdvisoryLockNotAcquired {
+    /// The key of the advisory lock which could not be acquired
+    pub fn key(&self) -> i64 {
+        self.key
+    }
+}
+
+impl std::fmt::Display for AdvisoryLockNotAcquired {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Could not acquire advisory lock {}", self.key)
+    }
+}
+
+impl std::error::Error for AdvisoryLockNotAcquired {}
+
 impl<'a, C> QueryFragment<Pg> for TransactionBuilder<'a, C> {
     fn walk_ast<'b>(&'b self, mut out: AstPass<'_, 'b, Pg>) -> QueryResult<()> {
         out.push_sql("BEGIN TRANSACTION");
@@ -327,9 +482,20 @@ impl<'a, C> QueryFragment<Pg> for TransactionBuilder<'a, C> {
 }
 
 #[derive(Debug, Clone, Copy)]
-enum IsolationLevel {
+struct AdvisoryXactLock {
+    key: i64,
+    wait: bool,
+}
+
+/// The isolation level of a transaction, see
+/// [`TransactionBuilder::isolation_level`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IsolationLevel {
+    /// `ISOLATION LEVEL READ COMMITTED`
     ReadCommitted,
+    /// `ISOLATION LEVEL REPEATABLE READ`
     RepeatableRead,
+    /// `ISOLATION LEVEL SERIALIZABLE`
     Serializable,
 }
 
@@ -428,6 +594,11 @@ mod tests {
             conn.build_transaction().serializable(),
             "BEGIN TRANSACTION ISOLATION LEVEL SERIALIZABLE"
         );
+        assert_sql!(
+            conn.build_transaction()
+                .isolation_level(IsolationLevel::RepeatableRead),
+            "BEGIN TRANSACTION ISOLATION LEVEL REPEATABLE READ"
+        );
         assert_sql!(
             conn.build_transaction()
                 .serializable()
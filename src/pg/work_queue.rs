@@ -0,0 +1,115 @@
+use super::AsyncPgConnection;
+use crate::methods::LoadQuery;
+use crate::{AsyncConnection, RunQueryDsl};
+use diesel::pg::Pg;
+use diesel::query_builder::{AstPass, Query, QueryFragment, QueryId};
+use diesel::sql_types::BigInt;
+use diesel::QueryResult;
+use scoped_futures::{ScopedBoxFuture, ScopedFutureExt};
+
+/// A query claiming up to `limit` rows via `FOR UPDATE SKIP LOCKED`
+///
+/// This type is constructed by [`AsyncPgConnection::process_claimed`].
+/// The generated SQL wraps the underlying query into a subquery and
+/// locks the selected rows of all tables used in that subquery.
+/// Rows already locked by other transactions are skipped.
+#[derive(Debug, Clone, Copy, QueryId)]
+pub struct Claim<T> {
+    query: T,
+    limit: i64,
+}
+
+impl<T: Query> Query for Claim<T> {
+    type SqlType = T::SqlType;
+}
+
+impl<T> QueryFragment<Pg> for Claim<T>
+where
+    T: QueryFragment<Pg>,
+{
+    fn walk_ast<'b>(&'b self, mut out: AstPass<'_, 'b, Pg>) -> QueryResult<()> {
+        out.push_sql("SELECT * FROM (");
+        self.query.walk_ast(out.reborrow())?;
+        out.push_sql(") AS claimed_rows LIMIT ");
+        out.push_bind_param::<BigInt, _>(&self.limit)?;
+        out.push_sql(" FOR UPDATE SKIP LOCKED");
+        Ok(())
+    }
+}
+
+impl AsyncPgConnection {
+    /// Claim up to `limit` rows returned by `query` and process them
+    /// inside of a transaction
+    ///
+    /// This implements the common work queue pattern: The rows are locked via
+    /// `FOR UPDATE SKIP LOCKED`, so that concurrent workers calling this method
+    /// with the same query claim distinct rows instead of blocking each other.
+    /// The rows stay locked until `process` finishes. If `process` returns
+    /// an error the transaction is rolled back and the rows become available
+    /// to other workers again. Otherwise the transaction is committed, so any
+    /// changes `process` made (for example deleting the processed rows or
+    /// marking them as done) become visible.
+    ///
+    /// `process` is also called if no row could be claimed.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # include!("../doctest_setup.rs");
+    /// # use scoped_futures::ScopedFutureExt;
+    /// #
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// #     run_test().await.unwrap();
+    /// # }
+    /// #
+    /// # async fn run_test() -> QueryResult<()> {
+    /// #     use diesel_async::RunQueryDsl;
+    /// #     use schema::users;
+    /// #     let conn = &mut establish_connection().await;
+    /// let processed = conn
+    ///     .process_claimed(
+    ///         users::table.order_by(users::id),
+    ///         1,
+    ///         |conn, claimed: Vec<(i32, String)>| {
+    ///             async move {
+    ///                 for (id, _) in &claimed {
+    ///                     diesel::delete(users::table.find(id)).execute(conn).await?;
+    ///                 }
+    ///                 QueryResult::Ok(claimed.len())
+    ///             }
+    ///             .scope_boxed()
+    ///         },
+    ///     )
+    ///     .await?;
+    /// assert_eq!(1, processed);
+    /// #     Ok(())
+    /// # }
+    /// ```
+    pub async fn process_claimed<'a, Q, U, R, E, F>(
+        &mut self,
+        query: Q,
+        limit: i64,
+        process: F,
+    ) -> Result<R, E>
+    where
+        Q: Send + 'a,
+        Claim<Q>: LoadQuery<'a, Self, U>,
+        U: Send + 'a,
+        F: for<'r> FnOnce(&'r mut Self, Vec<U>) -> ScopedBoxFuture<'a, 'r, Result<R, E>>
+            + Send
+            + 'a,
+        E: From<diesel::result::Error> + Send + 'a,
+        R: Send + 'a,
+    {
+        let claim = Claim { query, limit };
+        self.transaction(move |conn| {
+            async move {
+                let rows = claim.load::<U>(conn).await?;
+                process(conn, rows).await
+            }
+            .scope_boxed()
+        })
+        .await
+    }
+}
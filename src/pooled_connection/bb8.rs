@@ -48,7 +48,10 @@
 //! # }
 //! ```
 
-use super::{AsyncDieselConnectionManager, PoolError, PoolableConnection};
+use super::{
+    AsyncDieselConnectionManager, ConnectionLabels, HealthStatus, PoolError, PoolFromUrl,
+    PoolHealthCheck, PoolableConnection,
+};
 use bb8::ManageConnection;
 use diesel::query_builder::QueryFragment;
 
@@ -59,6 +62,15 @@ pub type PooledConnection<'a, C> = bb8::PooledConnection<'a, AsyncDieselConnecti
 /// Type alias for using [`bb8::RunError`] with [`diesel-async`]
 pub type RunError = bb8::RunError<super::PoolError>;
 
+impl From<RunError> for PoolError {
+    fn from(e: RunError) -> Self {
+        match e {
+            RunError::User(e) => e,
+            RunError::TimedOut => PoolError::Timeout,
+        }
+    }
+}
+
 #[async_trait::async_trait]
 impl<C> ManageConnection for AsyncDieselConnectionManager<C>
 where
@@ -72,18 +84,57 @@ where
     type Error = PoolError;
 
     async fn connect(&self) -> Result<Self::Connection, Self::Error> {
-        (self.manager_config.custom_setup)(&self.connection_url)
-            .await
-            .map_err(PoolError::ConnectionError)
+        self.establish_connection().await
     }
 
     async fn is_valid(&self, conn: &mut Self::Connection) -> Result<(), Self::Error> {
-        conn.ping(&self.manager_config.recycling_method)
+        self.check_connection(conn)
             .await
             .map_err(PoolError::QueryError)
     }
 
     fn has_broken(&self, conn: &mut Self::Connection) -> bool {
-        std::thread::panicking() || conn.is_broken()
+        self.is_broken_connection(conn)
+    }
+}
+
+#[async_trait::async_trait]
+impl<C> PoolHealthCheck for Pool<C>
+where
+    C: PoolableConnection + Send + 'static,
+    diesel::dsl::select<diesel::dsl::AsExprOf<i32, diesel::sql_types::Integer>>:
+        crate::methods::ExecuteDsl<C>,
+    diesel::query_builder::SqlQuery: QueryFragment<C::Backend>,
+{
+    async fn health_check(&self) -> HealthStatus {
+        let state = self.state();
+        let (latency, error) = match self.get().await {
+            Ok(mut conn) => super::probe_connection(&mut *conn).await,
+            Err(e) => (None, Some(e.to_string())),
+        };
+        HealthStatus {
+            connections: state.connections as usize,
+            idle_connections: state.idle_connections as usize,
+            latency,
+            error,
+            labels: ConnectionLabels::default(),
+        }
+    }
+}
+
+impl<C> PoolFromUrl for Pool<C>
+where
+    C: PoolableConnection + 'static,
+    diesel::dsl::select<diesel::dsl::AsExprOf<i32, diesel::sql_types::Integer>>:
+        crate::methods::ExecuteDsl<C>,
+    diesel::query_builder::SqlQuery: QueryFragment<C::Backend>,
+{
+    fn from_url(database_url: impl Into<String>) -> Self {
+        Pool::builder()
+            .max_size(super::DEFAULT_MAX_SIZE)
+            .connection_timeout(super::DEFAULT_CONNECTION_TIMEOUT)
+            .idle_timeout(super::DEFAULT_IDLE_TIMEOUT)
+            .max_lifetime(super::DEFAULT_MAX_LIFETIME)
+            .build_unchecked(AsyncDieselConnectionManager::new(database_url))
     }
 }
@@ -0,0 +1,90 @@
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Type of the callback passed to [`CheckoutTimer::new`]
+pub type CheckoutWaitCallback = dyn Fn(CheckoutWait) + Send + Sync;
+
+/// The time a single checkout waited for a connection, see [`CheckoutTimer`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct CheckoutWait {
+    /// The time until the pool handed out a connection or the checkout failed
+    pub duration: Duration,
+    /// Whether a connection was checked out
+    ///
+    /// Failed checkouts usually waited until the timeout of the pool.
+    pub succeeded: bool,
+}
+
+/// Measures the time spent waiting to check out connections from a pool
+///
+/// The wait time of checkouts is the key signal to diagnose an undersized
+/// pool: it grows once all connections are in use and requests queue up for
+/// the next free connection. Each checkout performed via
+/// [`CheckoutTimer::checkout`] is reported to the callback, which usually
+/// records the duration in a histogram of a metrics library.
+///
+/// ```rust
+/// # include!("../doctest_setup.rs");
+/// use diesel_async::pooled_connection::deadpool::Pool;
+/// use diesel_async::pooled_connection::{AsyncDieselConnectionManager, CheckoutTimer};
+/// #
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// #     run_test().await.unwrap();
+/// # }
+/// #
+/// # async fn run_test() -> Result<(), Box<dyn std::error::Error>> {
+/// #     let database_url = database_url();
+/// let manager = AsyncDieselConnectionManager::<DbConnection>::new(database_url);
+/// let pool = Pool::builder(manager).build()?;
+/// let timer = CheckoutTimer::new(|wait| {
+///     // for example `metrics::histogram!("db_pool_checkout_seconds")`
+///     println!("waited {:?} for a connection", wait.duration);
+/// });
+///
+/// let conn = timer.checkout(pool.get()).await?;
+/// #     Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct CheckoutTimer {
+    on_checkout: Arc<CheckoutWaitCallback>,
+}
+
+impl std::fmt::Debug for CheckoutTimer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CheckoutTimer").finish_non_exhaustive()
+    }
+}
+
+impl CheckoutTimer {
+    /// Construct a new timer reporting each checkout to the given callback
+    ///
+    /// The callback is called inline by [`CheckoutTimer::checkout`], so it
+    /// should return quickly.
+    pub fn new(on_checkout: impl Fn(CheckoutWait) + Send + Sync + 'static) -> Self {
+        Self {
+            on_checkout: Arc::new(on_checkout),
+        }
+    }
+
+    /// Check out a connection and report the time spent waiting for it
+    ///
+    /// `checkout` is the future checking out a connection from the pool,
+    /// for example `pool.get()`. Checkouts that are cancelled before they
+    /// complete are not reported.
+    pub async fn checkout<F, T, E>(&self, checkout: F) -> Result<T, E>
+    where
+        F: Future<Output = Result<T, E>>,
+    {
+        let start = Instant::now();
+        let res = checkout.await;
+        (self.on_checkout)(CheckoutWait {
+            duration: start.elapsed(),
+            succeeded: res.is_ok(),
+        });
+        res
+    }
+}
@@ -0,0 +1,112 @@
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+/// Configuration of the circuit breaker of a connection manager,
+/// see [`ManagerConfig::circuit_breaker`](super::ManagerConfig::circuit_breaker)
+///
+/// Once establishing a connection failed `failure_threshold` times in a row,
+/// the circuit opens and further attempts fail immediately instead of waiting
+/// for the database to respond. After `reset_timeout` a single attempt is let
+/// through to probe the database. The circuit closes again once this attempt
+/// succeeds, otherwise it stays open for another `reset_timeout`.
+#[derive(Debug, Clone, Copy)]
+#[non_exhaustive]
+pub struct CircuitBreakerConfig {
+    /// The number of consecutive failures to establish a connection
+    /// after which the circuit opens
+    pub failure_threshold: u32,
+    /// The time the circuit stays open before a new connection attempt
+    /// is let through
+    pub reset_timeout: Duration,
+}
+
+impl CircuitBreakerConfig {
+    /// Construct a new circuit breaker configuration
+    pub fn new(failure_threshold: u32, reset_timeout: Duration) -> Self {
+        Self {
+            failure_threshold,
+            reset_timeout,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum State {
+    Closed { failures: u32 },
+    Open { until: Instant },
+    HalfOpen,
+}
+
+#[derive(Debug)]
+pub(super) struct CircuitBreaker {
+    state: Mutex<State>,
+}
+
+impl CircuitBreaker {
+    pub(super) fn new() -> Self {
+        Self {
+            state: Mutex::new(State::Closed { failures: 0 }),
+        }
+    }
+
+    // Returns a permit for a new connection attempt if the circuit allows it
+    pub(super) fn try_acquire(&self) -> Option<Permit<'_>> {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        match *state {
+            State::Closed { .. } => {}
+            State::Open { until } if until <= Instant::now() => *state = State::HalfOpen,
+            // a probe is already in flight
+            State::Open { .. } | State::HalfOpen => return None,
+        }
+        Some(Permit {
+            circuit_breaker: self,
+            recorded: false,
+        })
+    }
+}
+
+pub(super) struct Permit<'a> {
+    circuit_breaker: &'a CircuitBreaker,
+    recorded: bool,
+}
+
+impl Permit<'_> {
+    pub(super) fn record(mut self, config: &CircuitBreakerConfig, success: bool) {
+        self.recorded = true;
+        let mut state = self
+            .circuit_breaker
+            .state
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        *state = match (&*state, success) {
+            (_, true) => State::Closed { failures: 0 },
+            (State::Closed { failures }, false) if failures + 1 < config.failure_threshold => {
+                State::Closed {
+                    failures: failures + 1,
+                }
+            }
+            (_, false) => State::Open {
+                until: Instant::now() + config.reset_timeout,
+            },
+        };
+    }
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        if !self.recorded {
+            // the connection attempt was cancelled, so let
+            // the next attempt probe the database instead
+            let mut state = self
+                .circuit_breaker
+                .state
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            if let State::HalfOpen = *state {
+                *state = State::Open {
+                    until: Instant::now(),
+                };
+            }
+        }
+    }
+}
@@ -46,7 +46,9 @@
 //! #     Ok(())
 //! # }
 //! ```
-use super::{AsyncDieselConnectionManager, PoolableConnection};
+use super::{
+    AsyncDieselConnectionManager, HealthStatus, PoolFromUrl, PoolHealthCheck, PoolableConnection,
+};
 use deadpool::managed::Manager;
 use diesel::query_builder::QueryFragment;
 
@@ -65,6 +67,29 @@ pub type Hook<C> = deadpool::managed::Hook<AsyncDieselConnectionManager<C>>;
 /// Type alias for using [`deadpool::managed::HookError`] with [`diesel-async`]
 pub type HookError = deadpool::managed::HookError<super::PoolError>;
 
+impl From<PoolError> for super::PoolError {
+    fn from(e: PoolError) -> Self {
+        match e {
+            PoolError::Timeout(_) => super::PoolError::Timeout,
+            PoolError::Backend(e) => e,
+            PoolError::Closed => super::PoolError::Closed,
+            PoolError::NoRuntimeSpecified => {
+                super::PoolError::Other("No runtime was specified for the pool".into())
+            }
+            PoolError::PostCreateHook(e) => e.into(),
+        }
+    }
+}
+
+impl From<HookError> for super::PoolError {
+    fn from(e: HookError) -> Self {
+        match e {
+            HookError::Message(msg) => super::PoolError::Other(msg.into_owned()),
+            HookError::Backend(e) => e,
+        }
+    }
+}
+
 impl<C> Manager for AsyncDieselConnectionManager<C>
 where
     C: PoolableConnection + Send + 'static,
@@ -77,9 +102,7 @@ where
     type Error = super::PoolError;
 
     async fn create(&self) -> Result<Self::Type, Self::Error> {
-        (self.manager_config.custom_setup)(&self.connection_url)
-            .await
-            .map_err(super::PoolError::ConnectionError)
+        self.establish_checked_out_connection().await
     }
 
     async fn recycle(
@@ -87,14 +110,58 @@ where
         obj: &mut Self::Type,
         _: &deadpool::managed::Metrics,
     ) -> deadpool::managed::RecycleResult<Self::Error> {
-        if std::thread::panicking() || obj.is_broken() {
+        self.rollback_before_reuse(obj).await;
+        if self.is_broken_connection(obj) {
             return Err(deadpool::managed::RecycleError::Message(
                 "Broken connection".into(),
             ));
         }
-        obj.ping(&self.manager_config.recycling_method)
+        self.check_connection(obj)
             .await
             .map_err(super::PoolError::QueryError)?;
         Ok(())
     }
 }
+
+#[async_trait::async_trait]
+impl<C> PoolHealthCheck for Pool<C>
+where
+    C: PoolableConnection + Send + 'static,
+    diesel::dsl::select<diesel::dsl::AsExprOf<i32, diesel::sql_types::Integer>>:
+        crate::methods::ExecuteDsl<C>,
+    diesel::query_builder::SqlQuery: QueryFragment<C::Backend>,
+{
+    async fn health_check(&self) -> HealthStatus {
+        let status = self.status();
+        let (latency, error) = match self.get().await {
+            Ok(mut conn) => super::probe_connection(&mut *conn).await,
+            Err(e) => (None, Some(e.to_string())),
+        };
+        HealthStatus {
+            connections: status.size,
+            idle_connections: status.available,
+            latency,
+            error,
+            labels: self.manager().manager_config.labels.clone(),
+        }
+    }
+}
+
+impl<C> PoolFromUrl for Pool<C>
+where
+    C: PoolableConnection + Send + 'static,
+    diesel::dsl::select<diesel::dsl::AsExprOf<i32, diesel::sql_types::Integer>>:
+        crate::methods::ExecuteDsl<C>,
+    diesel::query_builder::SqlQuery: QueryFragment<C::Backend>,
+{
+    fn from_url(database_url: impl Into<String>) -> Self {
+        Pool::builder(AsyncDieselConnectionManager::new(database_url))
+            .max_size(super::DEFAULT_MAX_SIZE as usize)
+            .wait_timeout(Some(super::DEFAULT_CONNECTION_TIMEOUT))
+            .create_timeout(Some(super::DEFAULT_CONNECTION_TIMEOUT))
+            .recycle_timeout(Some(super::DEFAULT_CONNECTION_TIMEOUT))
+            .runtime(deadpool::Runtime::Tokio1)
+            .build()
+            .expect("Building only fails if no runtime is specified")
+    }
+}
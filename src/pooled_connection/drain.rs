@@ -0,0 +1,124 @@
+use futures_util::future::BoxFuture;
+use std::future::Future;
+use std::sync::Arc;
+use tokio::sync::watch;
+
+#[derive(Debug, Clone, Copy, Default)]
+struct State {
+    draining: bool,
+    connections: usize,
+}
+
+/// A handle to drain the connections of an
+/// [`AsyncDieselConnectionManager`](super::AsyncDieselConnectionManager)
+///
+/// Returned by
+/// [`AsyncDieselConnectionManager::drain_handle`](super::AsyncDieselConnectionManager::drain_handle).
+/// Once [`DrainHandle::drain`] was called, the manager refuses to establish
+/// new connections with [`PoolError::Closed`](super::PoolError::Closed) and
+/// discards connections as soon as the pool checks them,
+/// that is when they are returned to a `bb8` pool or checked out from a
+/// `deadpool` or `mobc` pool. Idle connections are closed once the pool is
+/// closed or dropped. [`DrainHandle::closed`] resolves as soon as all
+/// connections created by the manager are closed, so that orchestration code
+/// knows when it is safe to exit.
+///
+/// Only connections reporting their closure are awaited. This is the case for
+/// `AsyncPgConnection`s, for which the future waits until the background task
+/// driving the connection finished.
+///
+/// ```rust
+/// # include!("../doctest_setup.rs");
+/// use diesel_async::pooled_connection::AsyncDieselConnectionManager;
+/// use diesel_async::pooled_connection::deadpool::Pool;
+/// #
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// #     run_test().await.unwrap();
+/// # }
+/// #
+/// # #[cfg(feature = "postgres")]
+/// # fn get_config() -> AsyncDieselConnectionManager<diesel_async::AsyncPgConnection> {
+/// #     let db_url = database_url_from_env("PG_DATABASE_URL");
+/// let config = AsyncDieselConnectionManager::<diesel_async::AsyncPgConnection>::new(db_url);
+/// #     config
+/// # }
+/// #
+/// # #[cfg(feature = "mysql")]
+/// # fn get_config() -> AsyncDieselConnectionManager<diesel_async::AsyncMysqlConnection> {
+/// #     let db_url = database_url_from_env("MYSQL_DATABASE_URL");
+/// #     AsyncDieselConnectionManager::<diesel_async::AsyncMysqlConnection>::new(db_url)
+/// # }
+/// #
+/// # #[cfg(feature = "sqlite")]
+/// # fn get_config() -> AsyncDieselConnectionManager<diesel_async::sync_connection_wrapper::SyncConnectionWrapper<diesel::SqliteConnection>> {
+/// #     let db_url = database_url_from_env("SQLITE_DATABASE_URL");
+/// #     AsyncDieselConnectionManager::<diesel_async::sync_connection_wrapper::SyncConnectionWrapper<diesel::SqliteConnection>>::new(db_url)
+/// # }
+/// #
+/// # async fn run_test() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+/// #     let config = get_config();
+/// let drain = config.drain_handle();
+/// let pool = Pool::builder(config).build()?;
+/// # let conn = pool.get().await?;
+/// # drop(conn);
+///
+/// // on shutdown
+/// drain.drain();
+/// pool.close();
+/// drain.closed().await;
+/// #     Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct DrainHandle {
+    state: Arc<watch::Sender<State>>,
+}
+
+impl DrainHandle {
+    pub(super) fn new() -> Self {
+        let (state, _) = watch::channel(State::default());
+        Self {
+            state: Arc::new(state),
+        }
+    }
+
+    /// Request to drain the connections of the manager
+    pub fn drain(&self) {
+        self.state.send_modify(|state| state.draining = true);
+    }
+
+    /// Whether [`DrainHandle::drain`] was called
+    pub fn is_draining(&self) -> bool {
+        self.state.borrow().draining
+    }
+
+    /// Returns a future resolving once a drain was requested and all
+    /// connections created by the manager are closed
+    pub fn closed(&self) -> impl Future<Output = ()> + Send + 'static {
+        let mut state = self.state.subscribe();
+        async move {
+            loop {
+                let done = {
+                    let state = state.borrow_and_update();
+                    state.draining && state.connections == 0
+                };
+                // the sender is only gone once the manager and all handles
+                // are dropped and no connection is left open
+                if done || state.changed().await.is_err() {
+                    return;
+                }
+            }
+        }
+    }
+
+    // Counts the connection as open until the given future resolves
+    pub(super) fn track(&self, closed: BoxFuture<'static, ()>) {
+        self.state.send_modify(|state| state.connections += 1);
+        let state = self.state.clone();
+        tokio::spawn(async move {
+            closed.await;
+            state.send_modify(|state| state.connections -= 1);
+        });
+    }
+}
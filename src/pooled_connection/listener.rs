@@ -0,0 +1,170 @@
+use super::{AsyncDieselConnectionManager, BackoffPolicy, BrokenReason, ConnectionEvent};
+use crate::pg::quote_identifier;
+use crate::{AsyncPgConnection, SimpleAsyncConnection};
+use futures_util::{Stream, StreamExt};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+// The time to wait before replacing a lost listener connection
+// if no reconnect backoff policy is configured
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+/// Receives notifications via dedicated connections that are kept
+/// outside of a connection pool
+///
+/// `LISTEN` on a regular pooled connection silently stops working once the
+/// pool discards or recycles that connection, and it keeps the connection from
+/// being used for other work. A `NotificationListener` instead establishes its
+/// own connections via an [`AsyncDieselConnectionManager`], so they are never
+/// handed out by a pool, and merges the notifications received by all of them
+/// into a single stream.
+///
+/// The channels are distributed over the connections, so each notification is
+/// received only once. Lost connections are replaced after a short delay and
+/// listen to their channels again, notifications sent in the meantime are
+/// missed. The delays follow
+/// [`ManagerConfig::reconnect_backoff`](super::ManagerConfig::reconnect_backoff)
+/// if configured. The stream ends once all connections were lost and could
+/// not be replaced within the number of attempts allowed by this policy. The
+/// connections are closed once the listener is dropped.
+///
+/// Only connections forwarding notifications, like the ones created via
+/// [`AsyncConnection::establish`](crate::AsyncConnection::establish) or
+/// [`AsyncPgConnection::try_from_client_and_connection`], can be used.
+///
+/// ```rust
+/// # include!("../doctest_setup.rs");
+/// use diesel_async::pooled_connection::{AsyncDieselConnectionManager, NotificationListener};
+/// use futures_util::StreamExt;
+/// #
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// #     let database_url = database_url();
+/// let manager = AsyncDieselConnectionManager::<AsyncPgConnection>::new(database_url);
+/// let mut listener = NotificationListener::new(manager, 2, ["orders", "invoices"]);
+/// # if false {
+/// while let Some(notification) = listener.next().await {
+///     println!("{}: {}", notification.channel(), notification.payload());
+/// }
+/// # }
+/// # }
+/// ```
+pub struct NotificationListener {
+    notifications: mpsc::UnboundedReceiver<tokio_postgres::Notification>,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl std::fmt::Debug for NotificationListener {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("NotificationListener")
+            .field("connections", &self.tasks.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl NotificationListener {
+    /// Listen to the given channels via at most `connections` dedicated
+    /// connections established by `manager`
+    ///
+    /// No more connections than channels are established. The connection
+    /// events of the manager are reported via
+    /// [`ManagerConfig::on_event`](super::ManagerConfig::on_event).
+    ///
+    /// This must be called from within a tokio runtime.
+    ///
+    /// # Panics
+    ///
+    /// If `connections` is zero.
+    pub fn new<I>(
+        manager: AsyncDieselConnectionManager<AsyncPgConnection>,
+        connections: usize,
+        channels: I,
+    ) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        assert!(
+            connections > 0,
+            "A listener requires at least one connection"
+        );
+        let channels = channels.into_iter().map(Into::into).collect::<Vec<_>>();
+        let connections = connections.min(channels.len());
+        let mut assigned = vec![Vec::new(); connections];
+        for (idx, channel) in channels.into_iter().enumerate() {
+            assigned[idx % connections].push(channel);
+        }
+
+        let manager = Arc::new(manager);
+        let (tx, notifications) = mpsc::unbounded_channel();
+        let tasks = assigned
+            .into_iter()
+            .map(|channels| tokio::spawn(listen(manager.clone(), channels, tx.clone())))
+            .collect();
+        Self {
+            notifications,
+            tasks,
+        }
+    }
+}
+
+impl Stream for NotificationListener {
+    type Item = tokio_postgres::Notification;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.notifications.poll_recv(cx)
+    }
+}
+
+impl Drop for NotificationListener {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
+
+async fn listen(
+    manager: Arc<AsyncDieselConnectionManager<AsyncPgConnection>>,
+    channels: Vec<String>,
+    tx: mpsc::UnboundedSender<tokio_postgres::Notification>,
+) {
+    let listen = channels
+        .iter()
+        .map(|channel| format!("LISTEN {};", quote_identifier(channel)))
+        .collect::<String>();
+    let policy = manager
+        .manager_config
+        .reconnect_backoff
+        .clone()
+        .unwrap_or_else(|| BackoffPolicy::constant(RECONNECT_DELAY));
+    let mut failed_attempts = 0;
+    loop {
+        if let Ok(mut conn) = manager.connect_once().await {
+            let error = conn.batch_execute(&listen).await.err();
+            if error.is_none() {
+                failed_attempts = 0;
+                // The stream ends once the connection is closed
+                let mut notifications = conn.notifications_stream();
+                while let Some(Ok(notification)) = notifications.next().await {
+                    if tx.send(notification).is_err() {
+                        return;
+                    }
+                }
+            }
+            manager.emit(ConnectionEvent::Broken {
+                reason: BrokenReason::Unusable,
+                error: error.as_ref(),
+            });
+        }
+        failed_attempts += 1;
+        if !policy.allows_retry(failed_attempts) {
+            return;
+        }
+        tokio::time::sleep(policy.delay(failed_attempts)).await;
+    }
+}
@@ -0,0 +1,127 @@
+use super::PoolableConnection;
+use std::future::Future;
+use std::ops::{Deref, DerefMut};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Type of the callback passed to [`MaxCheckoutDuration::on_exceeded`]
+pub type CheckoutExceededCallback = dyn Fn(Duration) + Send + Sync;
+
+/// What happens to connections held longer than a [`MaxCheckoutDuration`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum MaxCheckoutAction {
+    /// Only call the [`MaxCheckoutDuration::on_exceeded`] callback
+    Alert,
+    /// Call the callback, cancel the query currently running on the
+    /// connection and fail all further operations, so that the pool
+    /// discards the connection once it is returned
+    ///
+    /// Connections that do not support this, like `AsyncMysqlConnection`,
+    /// are only reported.
+    Reclaim,
+}
+
+/// Detects connections held longer than a configured maximum
+///
+/// This protects a pool from handlers that forget to drop their connection,
+/// for example because they got stuck. Connections checked out via
+/// [`MaxCheckoutDuration::checkout`] are watched until they are dropped. The
+/// pool itself can only reuse the connection once the handler drops it.
+///
+/// For example, `MaxCheckoutDuration::new(Duration::from_secs(30),
+/// MaxCheckoutAction::Reclaim).checkout(pool.get()).await` reclaims
+/// connections held for longer than 30 seconds.
+#[derive(Clone)]
+pub struct MaxCheckoutDuration {
+    duration: Duration,
+    action: MaxCheckoutAction,
+    on_exceeded: Option<Arc<CheckoutExceededCallback>>,
+}
+
+impl std::fmt::Debug for MaxCheckoutDuration {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MaxCheckoutDuration")
+            .field("duration", &self.duration)
+            .field("action", &self.action)
+            .finish_non_exhaustive()
+    }
+}
+
+impl MaxCheckoutDuration {
+    /// Construct a new limit applying the given action to
+    /// connections held longer than `duration`
+    pub fn new(duration: Duration, action: MaxCheckoutAction) -> Self {
+        Self {
+            duration,
+            action,
+            on_exceeded: None,
+        }
+    }
+
+    /// Set a callback that is called with the maximum duration
+    /// once a connection was held longer than that
+    pub fn on_exceeded(mut self, callback: impl Fn(Duration) + Send + Sync + 'static) -> Self {
+        self.on_exceeded = Some(Arc::new(callback));
+        self
+    }
+
+    /// Check out a connection and watch how long it is held
+    ///
+    /// `checkout` is the future checking out a connection from the pool,
+    /// for example `pool.get()`.
+    pub async fn checkout<F, T, E>(&self, checkout: F) -> Result<WatchedConnection<T>, E>
+    where
+        F: Future<Output = Result<T, E>>,
+        T: DerefMut,
+        T::Target: PoolableConnection,
+    {
+        let connection = checkout.await?;
+        let reclaim = match self.action {
+            MaxCheckoutAction::Alert => None,
+            MaxCheckoutAction::Reclaim => connection.reclaim_handle(),
+        };
+        let duration = self.duration;
+        let on_exceeded = self.on_exceeded.clone();
+        let watchdog = tokio::spawn(async move {
+            tokio::time::sleep(duration).await;
+            if let Some(on_exceeded) = on_exceeded {
+                on_exceeded(duration);
+            }
+            if let Some(reclaim) = reclaim {
+                reclaim();
+            }
+        });
+        Ok(WatchedConnection {
+            connection,
+            watchdog,
+        })
+    }
+}
+
+/// A connection checked out via [`MaxCheckoutDuration::checkout`]
+#[derive(Debug)]
+pub struct WatchedConnection<T> {
+    connection: T,
+    watchdog: tokio::task::JoinHandle<()>,
+}
+
+impl<T> Deref for WatchedConnection<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.connection
+    }
+}
+
+impl<T> DerefMut for WatchedConnection<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.connection
+    }
+}
+
+impl<T> Drop for WatchedConnection<T> {
+    fn drop(&mut self) {
+        self.watchdog.abort();
+    }
+}
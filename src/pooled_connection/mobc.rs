@@ -46,7 +46,10 @@
 //! #     Ok(())
 //! # }
 //! ```
-use super::{AsyncDieselConnectionManager, PoolError, PoolableConnection};
+use super::{
+    AsyncDieselConnectionManager, ConnectionLabels, HealthStatus, PoolError, PoolFromUrl,
+    PoolHealthCheck, PoolableConnection,
+};
 use diesel::query_builder::QueryFragment;
 use mobc::Manager;
 
@@ -59,6 +62,19 @@ pub type PooledConnection<C> = mobc::Connection<AsyncDieselConnectionManager<C>>
 /// Type alias for using [`mobc::Builder`] with [`diesel-async`]
 pub type Builder<C> = mobc::Builder<AsyncDieselConnectionManager<C>>;
 
+impl From<mobc::Error<PoolError>> for PoolError {
+    fn from(e: mobc::Error<PoolError>) -> Self {
+        match e {
+            mobc::Error::Inner(e) => e,
+            mobc::Error::Timeout => PoolError::Timeout,
+            mobc::Error::BadConn => PoolError::ConnectionError(
+                diesel::result::ConnectionError::BadConnection("Bad connection".into()),
+            ),
+            mobc::Error::PoolClosed => PoolError::Closed,
+        }
+    }
+}
+
 #[async_trait::async_trait]
 impl<C> Manager for AsyncDieselConnectionManager<C>
 where
@@ -72,15 +88,63 @@ where
     type Error = PoolError;
 
     async fn connect(&self) -> Result<Self::Connection, Self::Error> {
-        (self.manager_config.custom_setup)(&self.connection_url)
-            .await
-            .map_err(PoolError::ConnectionError)
+        self.establish_checked_out_connection().await
     }
 
     async fn check(&self, mut conn: Self::Connection) -> Result<Self::Connection, Self::Error> {
-        conn.ping(&self.manager_config.recycling_method)
+        if self.is_draining_connection() {
+            return Err(PoolError::Closed);
+        }
+        self.rollback_before_reuse(&mut conn).await;
+        if self.is_broken_connection(&mut conn) {
+            return Err(PoolError::ConnectionError(
+                diesel::result::ConnectionError::BadConnection(String::from("Broken connection")),
+            ));
+        }
+        self.check_connection(&mut conn)
             .await
             .map_err(PoolError::QueryError)?;
         Ok(conn)
     }
 }
+
+#[async_trait::async_trait]
+impl<C> PoolHealthCheck for Pool<C>
+where
+    C: PoolableConnection + Send + 'static,
+    diesel::dsl::select<diesel::dsl::AsExprOf<i32, diesel::sql_types::Integer>>:
+        crate::methods::ExecuteDsl<C>,
+    diesel::query_builder::SqlQuery: QueryFragment<C::Backend>,
+{
+    async fn health_check(&self) -> HealthStatus {
+        let state = self.state().await;
+        let (latency, error) = match self.get().await {
+            Ok(mut conn) => super::probe_connection(&mut *conn).await,
+            Err(e) => (None, Some(e.to_string())),
+        };
+        HealthStatus {
+            connections: state.connections as usize,
+            idle_connections: state.idle as usize,
+            latency,
+            error,
+            labels: ConnectionLabels::default(),
+        }
+    }
+}
+
+impl<C> PoolFromUrl for Pool<C>
+where
+    C: PoolableConnection + 'static,
+    diesel::dsl::select<diesel::dsl::AsExprOf<i32, diesel::sql_types::Integer>>:
+        crate::methods::ExecuteDsl<C>,
+    diesel::query_builder::SqlQuery: QueryFragment<C::Backend>,
+{
+    fn from_url(database_url: impl Into<String>) -> Self {
+        Pool::builder()
+            .max_open(super::DEFAULT_MAX_SIZE.into())
+            .get_timeout(Some(super::DEFAULT_CONNECTION_TIMEOUT))
+            .max_idle_lifetime(Some(super::DEFAULT_IDLE_TIMEOUT))
+            .max_lifetime(Some(super::DEFAULT_MAX_LIFETIME))
+            .build(AsyncDieselConnectionManager::new(database_url))
+    }
+}
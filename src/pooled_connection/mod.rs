@@ -13,22 +13,132 @@ use futures_util::{future, FutureExt};
 use std::borrow::Cow;
 use std::fmt;
 use std::ops::DerefMut;
+use std::time::{Duration, Instant};
 
 #[cfg(feature = "bb8")]
 pub mod bb8;
+#[cfg(any(feature = "bb8", feature = "deadpool", feature = "mobc"))]
+mod checkout_timer;
+mod circuit_breaker;
 #[cfg(feature = "deadpool")]
 pub mod deadpool;
+#[cfg(any(feature = "bb8", feature = "deadpool", feature = "mobc"))]
+mod drain;
+#[cfg(all(
+    feature = "postgres",
+    any(feature = "bb8", feature = "deadpool", feature = "mobc")
+))]
+mod listener;
+#[cfg(any(feature = "bb8", feature = "deadpool", feature = "mobc"))]
+mod max_checkout;
 #[cfg(feature = "mobc")]
 pub mod mobc;
+#[cfg(any(feature = "bb8", feature = "deadpool", feature = "mobc"))]
+mod priority;
+mod tls;
+#[cfg(all(
+    feature = "postgres",
+    any(feature = "bb8", feature = "deadpool", feature = "mobc")
+))]
+mod yugabyte;
 
-/// The error used when managing connections with `deadpool`.
+#[cfg(any(feature = "bb8", feature = "deadpool", feature = "mobc"))]
+pub use self::checkout_timer::{CheckoutTimer, CheckoutWait, CheckoutWaitCallback};
+use self::circuit_breaker::CircuitBreaker;
+pub use self::circuit_breaker::CircuitBreakerConfig;
+#[cfg(any(feature = "bb8", feature = "deadpool", feature = "mobc"))]
+pub use self::drain::DrainHandle;
+#[cfg(all(
+    feature = "postgres",
+    any(feature = "bb8", feature = "deadpool", feature = "mobc")
+))]
+pub use self::listener::NotificationListener;
+#[cfg(any(feature = "bb8", feature = "deadpool", feature = "mobc"))]
+pub use self::max_checkout::{
+    CheckoutExceededCallback, MaxCheckoutAction, MaxCheckoutDuration, WatchedConnection,
+};
+#[cfg(any(feature = "bb8", feature = "deadpool", feature = "mobc"))]
+pub use self::priority::{CheckoutPriority, PrioritizedConnection, PriorityGate};
+pub use self::tls::TlsConfig;
+#[cfg(all(
+    feature = "postgres",
+    any(feature = "bb8", feature = "deadpool", feature = "mobc")
+))]
+pub use self::yugabyte::{YugabyteLoadBalancer, YugabyteNode};
+pub use crate::backoff::BackoffPolicy;
+
+/// The error used when managing connections with `deadpool`, `bb8` or `mobc`
+///
+/// The errors of the pool implementations convert into this type via
+/// [`From`], so application error types only need a single variant for
+/// pool errors, independent of the used pool implementation:
+///
+/// ```rust
+/// # include!("../doctest_setup.rs");
+/// use diesel_async::pooled_connection::deadpool::Pool;
+/// use diesel_async::pooled_connection::{AsyncDieselConnectionManager, PoolError};
+/// #
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// #     run_test().await.unwrap();
+/// # }
+///
+/// #[derive(Debug)]
+/// enum AppError {
+///     Pool(PoolError),
+///     Query(diesel::result::Error),
+/// }
+///
+/// impl From<PoolError> for AppError {
+///     fn from(e: PoolError) -> Self {
+///         AppError::Pool(e)
+///     }
+/// }
+/// #
+/// # impl From<diesel::result::Error> for AppError {
+/// #     fn from(e: diesel::result::Error) -> Self {
+/// #         AppError::Query(e)
+/// #     }
+/// # }
+///
+/// async fn count_users(pool: &Pool<DbConnection>) -> Result<i64, AppError> {
+///     use diesel_async::RunQueryDsl;
+///     use schema::users;
+///
+///     let mut conn = pool.get().await.map_err(PoolError::from)?;
+///     Ok(users::table.count().get_result(&mut conn).await?)
+/// }
+/// #
+/// # async fn run_test() -> Result<(), AppError> {
+/// #     let db_url = database_url();
+/// #     let manager = AsyncDieselConnectionManager::<DbConnection>::new(db_url);
+/// #     let pool = Pool::builder(manager).max_size(1).build().unwrap();
+/// #     let mut conn = pool.get().await.map_err(PoolError::from)?;
+/// #     create_tables(&mut conn).await;
+/// #     drop(conn);
+/// #     count_users(&pool).await?;
+/// #     Ok(())
+/// # }
+/// ```
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum PoolError {
     /// An error occurred establishing the connection
     ConnectionError(diesel::result::ConnectionError),
 
     /// An error occurred pinging the database
     QueryError(diesel::result::Error),
+
+    /// No connection became available before the configured timeout
+    Timeout,
+
+    /// The pool has been closed, or its connections are drained
+    /// via a `DrainHandle`
+    Closed,
+
+    /// Any other error reported by the pool implementation,
+    /// like a failed `deadpool` hook
+    Other(String),
 }
 
 impl fmt::Display for PoolError {
@@ -36,6 +146,9 @@ impl fmt::Display for PoolError {
         match *self {
             PoolError::ConnectionError(ref e) => e.fmt(f),
             PoolError::QueryError(ref e) => e.fmt(f),
+            PoolError::Timeout => write!(f, "Timed out waiting for a connection"),
+            PoolError::Closed => write!(f, "The pool has been closed"),
+            PoolError::Other(ref e) => e.fmt(f),
         }
     }
 }
@@ -50,6 +163,221 @@ pub type SetupCallback<C> =
 pub type RecycleCheckCallback<C> =
     dyn Fn(&mut C) -> future::BoxFuture<QueryResult<()>> + Send + Sync;
 
+/// Type of the warm up callback passed to [`ManagerConfig::warm_up`]
+pub type WarmUpCallback<C> = dyn Fn(&mut C) -> future::BoxFuture<QueryResult<()>> + Send + Sync;
+
+/// Type of the callback of the [`CheckoutSetup::Function`] variant
+pub type CheckoutCallback<C> = dyn Fn(&mut C) -> future::BoxFuture<QueryResult<()>> + Send + Sync;
+
+/// Type of the event callback passed to [`ManagerConfig::on_event`]
+///
+/// The callback receives the [`ManagerConfig::labels`] of the manager
+/// together with each event.
+pub type EventCallback = dyn Fn(&ConnectionLabels, ConnectionEvent<'_>) + Send + Sync;
+
+/// Lifecycle events of the connections managed by an [`AsyncDieselConnectionManager`]
+///
+/// See [`ManagerConfig::on_event`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ConnectionEvent<'a> {
+    /// A new connection was established and warmed up
+    Connected {
+        /// The time it took to establish the connection
+        duration: Duration,
+    },
+    /// Establishing a new connection failed
+    ConnectFailed {
+        /// The time passed until establishing the connection failed
+        duration: Duration,
+        /// The error establishing the connection failed with
+        error: &'a PoolError,
+    },
+    /// A connection was found to be broken and is discarded by the pool
+    Broken {
+        /// Why the connection is considered to be broken
+        reason: BrokenReason,
+        /// The error the connection check failed with, if any
+        error: Option<&'a diesel::result::Error>,
+    },
+}
+
+/// The reason for a [`ConnectionEvent::Broken`] event
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum BrokenReason {
+    /// The connection reported itself as broken, for example because it was
+    /// closed or left an open transaction behind
+    Unusable,
+    /// The connection failed the check of the configured [`RecyclingMethod`]
+    /// or did not respond within [`ManagerConfig::ping_timeout`]
+    CheckFailed,
+    /// The connections of the manager are drained, see [`DrainHandle`]
+    #[cfg(any(feature = "bb8", feature = "deadpool", feature = "mobc"))]
+    Draining,
+}
+
+/// Static labels describing the connections of an [`AsyncDieselConnectionManager`]
+///
+/// Labels like the shard, region or purpose of a database distinguish the
+/// connections of several pools in deployments using multiple databases.
+/// They are only passed to [`ManagerConfig::on_event`] and reported by
+/// [`PoolHealthCheck::health_check`] of `deadpool` pools, as `bb8` and
+/// `mobc` do not provide access to the manager of a pool. The connections
+/// themselves do not know about them, so per query instrumentation like
+/// query interceptors or transaction events does not receive the labels.
+///
+/// ```rust
+/// use diesel_async::pooled_connection::ConnectionLabels;
+///
+/// let labels = ConnectionLabels::new()
+///     .with("shard", "3")
+///     .with("region", "eu-west");
+/// assert_eq!(Some("3"), labels.get("shard"));
+/// assert_eq!(None, labels.get("purpose"));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConnectionLabels {
+    labels: Vec<(Cow<'static, str>, Cow<'static, str>)>,
+}
+
+impl ConnectionLabels {
+    /// Construct an empty set of labels
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the label `key` with the given value, replacing
+    /// a previous value of the same label
+    pub fn with(
+        mut self,
+        key: impl Into<Cow<'static, str>>,
+        value: impl Into<Cow<'static, str>>,
+    ) -> Self {
+        let key = key.into();
+        let value = value.into();
+        match self.labels.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, v)) => *v = value,
+            None => self.labels.push((key, value)),
+        }
+        self
+    }
+
+    /// The value of the label `key`
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.labels
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| &**v)
+    }
+
+    /// Iterate over all labels in the order they were added
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.labels.iter().map(|(k, v)| (&**k, &**v))
+    }
+
+    /// Whether no labels were added
+    pub fn is_empty(&self) -> bool {
+        self.labels.is_empty()
+    }
+}
+
+/// The health of a connection pool, see [`PoolHealthCheck::health_check`]
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct HealthStatus {
+    /// The number of connections managed by the pool
+    pub connections: usize,
+    /// The number of connections currently not in use
+    pub idle_connections: usize,
+    /// The round trip time of the probe, `None` if the probe failed
+    pub latency: Option<Duration>,
+    /// The error the probe failed with
+    ///
+    /// This is also set if no connection could be checked out from
+    /// the pool, for example because establishing a new connection failed.
+    pub error: Option<String>,
+    /// The [`ManagerConfig::labels`] of the pool
+    ///
+    /// This is only reported for `deadpool` pools, as `bb8` and `mobc`
+    /// do not provide access to the manager of a pool.
+    pub labels: ConnectionLabels,
+}
+
+impl HealthStatus {
+    /// Whether the probe succeeded
+    pub fn is_healthy(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// The maximum number of connections of pools built via [`PoolFromUrl::from_url`]
+pub const DEFAULT_MAX_SIZE: u32 = 16;
+/// The time to wait for a connection of pools built via [`PoolFromUrl::from_url`]
+pub const DEFAULT_CONNECTION_TIMEOUT: Duration = Duration::from_secs(30);
+/// The time after which idle connections of pools built via
+/// [`PoolFromUrl::from_url`] are closed, if supported by the pool
+pub const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+/// The time after which connections of pools built via
+/// [`PoolFromUrl::from_url`] are replaced, if supported by the pool
+pub const DEFAULT_MAX_LIFETIME: Duration = Duration::from_secs(30 * 60);
+
+/// Build a connection pool with reasonable defaults in a single call
+///
+/// This is implemented for the pools of all supported pool implementations
+/// and all connection types. The pool uses the default [`ManagerConfig`]
+/// and is limited to [`DEFAULT_MAX_SIZE`] connections. Checking out a
+/// connection fails with a timeout after [`DEFAULT_CONNECTION_TIMEOUT`].
+/// Idle connections are closed after [`DEFAULT_IDLE_TIMEOUT`] and connections
+/// are replaced after [`DEFAULT_MAX_LIFETIME`], if supported by the pool
+/// implementation. Connections are established lazily. Use the builder of
+/// the pool implementation to customize the pool.
+///
+/// The constructor is not called `new`, as that would collide with the
+/// existing `mobc::Pool::new` function. The `bb8` and `mobc` pools need to
+/// be built inside of a Tokio runtime.
+///
+/// ```rust
+/// # include!("../doctest_setup.rs");
+/// use diesel_async::pooled_connection::deadpool::Pool;
+/// use diesel_async::pooled_connection::PoolFromUrl;
+/// use diesel_async::RunQueryDsl;
+/// #
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// #     run_test().await.unwrap();
+/// # }
+/// #
+/// # async fn run_test() -> Result<(), Box<dyn std::error::Error>> {
+/// let pool = Pool::<DbConnection>::from_url(database_url());
+///
+/// let mut conn = pool.get().await?;
+/// let one = diesel::select(1.into_sql::<diesel::sql_types::Integer>())
+///     .get_result::<i32>(&mut conn)
+///     .await?;
+/// assert_eq!(1, one);
+/// #     Ok(())
+/// # }
+/// ```
+pub trait PoolFromUrl: Sized {
+    /// Build a pool for the database at the given URL
+    fn from_url(database_url: impl Into<String>) -> Self;
+}
+
+/// Check the health of a connection pool, for example to report
+/// it from the health check endpoint of a service
+///
+/// This is implemented for the pools of all supported pool implementations.
+#[async_trait::async_trait]
+pub trait PoolHealthCheck {
+    /// Check out a connection and perform a round trip to the database
+    ///
+    /// The probe is performed like the check of [`RecyclingMethod::Verified`].
+    /// The returned statistics describe the pool before the connection was
+    /// checked out.
+    async fn health_check(&self) -> HealthStatus;
+}
+
 /// Possible methods of how a connection is recycled.
 #[derive(Default)]
 pub enum RecyclingMethod<C> {
@@ -83,6 +411,148 @@ impl<C: fmt::Debug> fmt::Debug for RecyclingMethod<C> {
     }
 }
 
+/// Setup performed each time a connection is checked out, see
+/// [`ManagerConfig::on_checkout`]
+pub enum CheckoutSetup<C> {
+    /// Execute the given SQL, which may contain several statements
+    /// separated by semicolons, like `SET search_path TO tenant_1`
+    Sql(Cow<'static, str>),
+    /// Call the given function with the checked out connection
+    ///
+    /// This allows to apply settings depending on the current request, for
+    /// example a role stored in a task local variable.
+    Function(Box<CheckoutCallback<C>>),
+}
+
+impl<C> fmt::Debug for CheckoutSetup<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Sql(sql) => f.debug_tuple("Sql").field(sql).finish(),
+            Self::Function(_) => f.debug_tuple("Function").finish(),
+        }
+    }
+}
+
+/// Statement cache settings of the connections of a manager, see
+/// [`ManagerConfig::statement_cache`]
+///
+/// By default the statements of all queries are cached without a limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct StatementCacheConfig {
+    /// Whether prepared statements are cached at all
+    ///
+    /// Disabling the cache is required for poolers like PgBouncer in
+    /// transaction mode, which do not support prepared statements that
+    /// outlive a transaction.
+    pub enabled: bool,
+    /// The maximal number of cached statements per connection
+    ///
+    /// Once the cache is full, the statements of further queries are no
+    /// longer cached. `None` means unbounded.
+    pub max_size: Option<usize>,
+    /// Only cache the statements of queries with a static query id
+    ///
+    /// Queries without a static query id, like `sql_query` or queries with
+    /// a dynamic number of binds, are cached by their SQL. Their statements
+    /// are prepared for each execution instead if this is set.
+    pub query_id_only: bool,
+}
+
+impl StatementCacheConfig {
+    /// Settings disabling the statement cache
+    pub fn disabled() -> Self {
+        Self {
+            enabled: false,
+            ..Default::default()
+        }
+    }
+
+    #[cfg(any(feature = "postgres", feature = "mysql"))]
+    pub(crate) fn capacity(&self) -> Option<usize> {
+        if self.enabled {
+            self.max_size
+        } else {
+            Some(0)
+        }
+    }
+}
+
+impl Default for StatementCacheConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_size: None,
+            query_id_only: false,
+        }
+    }
+}
+
+/// TCP settings of the connections of a manager, see [`ManagerConfig::tcp`]
+///
+/// Settings left at `None` keep the defaults of the database driver or the
+/// values given in the database URL. `tokio-postgres` sends the first keepalive
+/// probe after two hours of inactivity by default, while `mysql_async` does not
+/// enable keepalive at all.
+///
+/// ```rust
+/// # use std::time::Duration;
+/// use diesel_async::pooled_connection::TcpConfig;
+///
+/// // detect dead connections before a NAT gateway drops them after 5 minutes
+/// let mut tcp = TcpConfig::keepalive(Duration::from_secs(60));
+/// tcp.keepalive_interval = Some(Duration::from_secs(10));
+/// tcp.keepalive_retries = Some(3);
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct TcpConfig {
+    /// The time a connection is idle before the first keepalive probe is sent
+    ///
+    /// Setting this enables TCP keepalive. The time is rounded down to whole
+    /// seconds for PostgreSQL.
+    pub keepalive_idle: Option<Duration>,
+    /// The time between keepalive probes that are not acknowledged
+    ///
+    /// Only supported for PostgreSQL, MySQL uses the default of the
+    /// operating system.
+    pub keepalive_interval: Option<Duration>,
+    /// The number of unacknowledged keepalive probes after which the
+    /// connection is considered dead
+    ///
+    /// Only supported for PostgreSQL, MySQL uses the default of the
+    /// operating system.
+    pub keepalive_retries: Option<u32>,
+    /// Whether `TCP_NODELAY` is set, which disables Nagle's algorithm
+    ///
+    /// Both drivers set `TCP_NODELAY` by default. `tokio-postgres` always
+    /// sets it, so this is only supported for MySQL.
+    pub nodelay: Option<bool>,
+}
+
+impl TcpConfig {
+    /// Settings enabling TCP keepalive with the given idle time
+    pub fn keepalive(idle: Duration) -> Self {
+        Self {
+            keepalive_idle: Some(idle),
+            ..Default::default()
+        }
+    }
+}
+
+// Appends the given parameters to the query of a database URL
+#[cfg(any(feature = "postgres", feature = "mysql"))]
+pub(crate) fn append_url_params(database_url: &str, params: &[(&str, String)]) -> String {
+    let mut url = String::from(database_url);
+    for (key, value) in params {
+        url.push(if url.contains('?') { '&' } else { '?' });
+        url.push_str(key);
+        url.push('=');
+        url.push_str(value);
+    }
+    url
+}
+
 /// Configuration object for a Manager.
 ///
 /// This makes it possible to specify which [`RecyclingMethod`]
@@ -98,6 +568,121 @@ pub struct ManagerConfig<C> {
     /// This can be used to for example establish a SSL secured
     /// postgres connection
     pub custom_setup: SetupCallback<C>,
+    /// Establish encrypted connections with the given TLS settings
+    ///
+    /// If set, new connections are established via [`TlsConfig`] instead of
+    /// [`ManagerConfig::custom_setup`]. Defaults to `None`.
+    pub tls: Option<TlsConfig<C>>,
+    /// The maximal time the check performed by the [`RecyclingMethod`]
+    /// may take when a connection is checked out from the pool
+    ///
+    /// The timeout also bounds rolling back transactions left open by the
+    /// previous user, see [`PoolableConnection::rollback_open_transactions`].
+    ///
+    /// Connections whose peer vanished without closing the connection, for
+    /// example due to a network partition, do not respond to the check at all.
+    /// With a timeout set, such connections are detected and discarded instead
+    /// of blocking the checkout until the operating system gives up on the
+    /// connection. Defaults to `None`, which means no timeout.
+    pub ping_timeout: Option<Duration>,
+    /// A callback executed for each newly established connection
+    /// before the pool hands it out
+    ///
+    /// This can be used to prepare the statements of frequently executed
+    /// queries up front, so that the first use of a fresh connection does not
+    /// pay the latency of preparing them, for example via
+    /// `AsyncPgConnection::prepare_cached`. Connections for which the callback
+    /// fails are discarded. Defaults to `None`.
+    ///
+    /// Session settings applied by the callback, like `SET statement_timeout`,
+    /// are reverted once the connection is reset via
+    /// [`ManagerConfig::reset_session`]. Apply such settings via
+    /// [`ManagerConfig::on_checkout`] instead.
+    pub warm_up: Option<Box<WarmUpCallback<C>>>,
+    /// Setup performed each time a connection is checked out from the pool
+    ///
+    /// Unlike [`ManagerConfig::warm_up`], which runs once per connection, this
+    /// runs for every checkout, after the check of the [`RecyclingMethod`].
+    /// This can be used to reset session state per request, for example via
+    /// `SET ROLE` or `SET search_path`. Connections for which the setup fails
+    /// are discarded. Defaults to `None`.
+    ///
+    /// `bb8` and `mobc` only check connections on checkout with
+    /// `test_on_check_out` enabled, which is the default. For `mobc` the
+    /// `health_check_interval` must not be set either. The setup runs
+    /// exactly once per checkout, for new connections as well.
+    pub on_checkout: Option<CheckoutSetup<C>>,
+    /// Reset the session state of connections before they are reused
+    ///
+    /// With this option enabled, connections returned to the pool are reset
+    /// via [`PoolableConnection::reset_session`] before the check of the
+    /// [`RecyclingMethod`], so that no session settings, temporary tables or
+    /// advisory locks leak between checkouts. For PostgreSQL this is
+    /// equivalent to `DISCARD ALL`, except that prepared statements are kept.
+    /// For MySQL `COM_RESET_CONNECTION` is used. Connections that cannot be
+    /// reset are discarded. Defaults to `false`.
+    ///
+    /// The reset also reverts session settings applied by
+    /// [`ManagerConfig::warm_up`], as for PostgreSQL it includes `RESET ALL`.
+    /// Settings that should apply to every checkout belong into
+    /// [`ManagerConfig::on_checkout`], which runs after the reset.
+    ///
+    /// Like [`ManagerConfig::on_checkout`] this requires `bb8` and `mobc` to
+    /// check connections on checkout.
+    pub reset_session: bool,
+    /// The statement cache settings applied to newly established connections
+    ///
+    /// Defaults to `None`, which keeps the settings the connection was
+    /// established with.
+    pub statement_cache: Option<StatementCacheConfig>,
+    /// The TCP settings of newly established connections
+    ///
+    /// This allows to enable TCP keepalive, so that pooled connections idle
+    /// for a long time survive the timeouts of NAT gateways and firewalls,
+    /// which silently drop idle connections otherwise. The settings are
+    /// passed to [`ManagerConfig::custom_setup`] and [`ManagerConfig::tls`]
+    /// as parameters of the database URL, see
+    /// [`PoolableConnection::apply_tcp_config`]. Defaults to `None`.
+    pub tcp: Option<TcpConfig>,
+    /// A callback receiving the lifecycle events of the managed connections
+    ///
+    /// This helps to debug flapping database connectivity, for example by
+    /// logging each event or by collecting metrics. The callback is called
+    /// inline by the pool, so it should return quickly. Defaults to `None`.
+    ///
+    /// See `AsyncPgConnection::closed` to observe the termination of
+    /// individual connections.
+    pub on_event: Option<Box<EventCallback>>,
+    /// Fail connection attempts immediately while the database
+    /// is consistently unreachable
+    ///
+    /// This protects services and the database from storms of reconnection
+    /// attempts, see [`CircuitBreakerConfig`] for details. Checkouts that need
+    /// to establish a new connection while the circuit is open fail with a
+    /// [`PoolError::ConnectionError`]. Defaults to `None`.
+    pub circuit_breaker: Option<CircuitBreakerConfig>,
+    /// Retry failed connection attempts with the delays of the given policy
+    ///
+    /// Transient failures, like a database restarting, are then hidden from
+    /// the pool, as long as the pool does not give up on the connection attempt
+    /// before, for example due to a timeout. Only errors establishing the
+    /// connection are retried, not the errors of [`ManagerConfig::warm_up`]
+    /// or [`ManagerConfig::on_checkout`]. Each failed attempt is reported
+    /// via [`ManagerConfig::on_event`]. Defaults to `None`, which means that
+    /// each connection is attempted once.
+    ///
+    /// The policy also controls how a
+    /// [`NotificationListener`](self::NotificationListener) replaces lost
+    /// connections, which happens every second otherwise.
+    pub reconnect_backoff: Option<BackoffPolicy>,
+    /// Static labels describing the connections of this manager
+    ///
+    /// The labels are passed to [`ManagerConfig::on_event`], so that the
+    /// events of several pools can be told apart. [`PoolHealthCheck::health_check`]
+    /// only reports them for `deadpool` pools, the health checks of `bb8` and
+    /// `mobc` pools report empty labels. They are not propagated to the
+    /// connections, see [`ConnectionLabels`]. Defaults to no labels.
+    pub labels: ConnectionLabels,
 }
 
 impl<C> Default for ManagerConfig<C>
@@ -108,6 +693,17 @@ where
         Self {
             recycling_method: Default::default(),
             custom_setup: Box::new(|url| C::establish(url).boxed()),
+            tls: None,
+            ping_timeout: None,
+            warm_up: None,
+            on_checkout: None,
+            reset_session: false,
+            statement_cache: None,
+            tcp: None,
+            on_event: None,
+            circuit_breaker: None,
+            reconnect_backoff: None,
+            labels: ConnectionLabels::default(),
         }
     }
 }
@@ -121,6 +717,9 @@ where
 pub struct AsyncDieselConnectionManager<C> {
     connection_url: String,
     manager_config: ManagerConfig<C>,
+    circuit_breaker: CircuitBreaker,
+    #[cfg(any(feature = "bb8", feature = "deadpool", feature = "mobc"))]
+    drain: DrainHandle,
 }
 
 impl<C> fmt::Debug for AsyncDieselConnectionManager<C> {
@@ -158,10 +757,239 @@ where
         Self {
             connection_url: connection_url.into(),
             manager_config,
+            circuit_breaker: CircuitBreaker::new(),
+            #[cfg(any(feature = "bb8", feature = "deadpool", feature = "mobc"))]
+            drain: DrainHandle::new(),
+        }
+    }
+
+    /// Returns a handle to drain the connections of this manager
+    ///
+    /// The handle stays usable after the manager was moved into a pool,
+    /// see [`DrainHandle`] for details.
+    #[cfg(any(feature = "bb8", feature = "deadpool", feature = "mobc"))]
+    pub fn drain_handle(&self) -> DrainHandle {
+        self.drain.clone()
+    }
+}
+
+#[cfg(any(feature = "bb8", feature = "deadpool", feature = "mobc"))]
+impl<C> AsyncDieselConnectionManager<C>
+where
+    C: PoolableConnection + 'static,
+    diesel::dsl::select<diesel::dsl::AsExprOf<i32, diesel::sql_types::Integer>>:
+        crate::methods::ExecuteDsl<C>,
+    diesel::query_builder::SqlQuery: crate::methods::ExecuteDsl<C>,
+{
+    // Establishes a new connection, retrying failed
+    // attempts according to the reconnect backoff policy
+    async fn establish_connection(&self) -> Result<C, PoolError> {
+        let Some(ref policy) = self.manager_config.reconnect_backoff else {
+            return self.connect_once().await;
+        };
+        let mut failed_attempts = 0;
+        loop {
+            // a drained manager or an open circuit breaker are not retried
+            let permit = self.acquire_attempt()?;
+            match self.attempt_connection(permit).await {
+                Err(PoolError::ConnectionError(_))
+                    if policy.allows_retry(failed_attempts + 1) && !self.drain.is_draining() =>
+                {
+                    failed_attempts += 1;
+                    tokio::time::sleep(policy.delay(failed_attempts)).await;
+                }
+                res => return res,
+            }
+        }
+    }
+
+    // Establishes a new connection via the configured setup callback
+    // and warms it up if configured
+    async fn connect_once(&self) -> Result<C, PoolError> {
+        let permit = self.acquire_attempt()?;
+        self.attempt_connection(permit).await
+    }
+
+    // Checks whether a connection attempt is allowed, returning
+    // the permit of the circuit breaker if one is configured
+    fn acquire_attempt(&self) -> Result<Option<AttemptPermit<'_>>, PoolError> {
+        if self.drain.is_draining() {
+            return Err(PoolError::Closed);
+        }
+        match self.manager_config.circuit_breaker {
+            Some(ref config) => match self.circuit_breaker.try_acquire() {
+                Some(permit) => Ok(Some((config, permit))),
+                None => Err(PoolError::ConnectionError(
+                    diesel::result::ConnectionError::BadConnection(String::from(
+                        "Circuit breaker is open, the database is unreachable",
+                    )),
+                )),
+            },
+            None => Ok(None),
+        }
+    }
+
+    async fn attempt_connection(&self, permit: Option<AttemptPermit<'_>>) -> Result<C, PoolError> {
+        let start = Instant::now();
+        let res = async {
+            let connection_url = match self.manager_config.tcp {
+                Some(ref tcp) => C::apply_tcp_config(&self.connection_url, tcp),
+                None => Cow::Borrowed(self.connection_url.as_str()),
+            };
+            let establish = match self.manager_config.tls {
+                Some(ref tls) => tls.establish(&connection_url),
+                None => (self.manager_config.custom_setup)(&connection_url),
+            };
+            let mut conn = establish.await.map_err(PoolError::ConnectionError)?;
+            if let Some(ref statement_cache) = self.manager_config.statement_cache {
+                conn.configure_statement_cache(statement_cache).await;
+            }
+            if let Some(ref warm_up) = self.manager_config.warm_up {
+                warm_up(&mut conn).await.map_err(PoolError::QueryError)?;
+            }
+            Ok(conn)
+        }
+        .await;
+        let duration = start.elapsed();
+        if let Some((config, permit)) = permit {
+            permit.record(config, res.is_ok());
+        }
+        match res {
+            Ok(ref conn) => {
+                if let Some(closed) = conn.closed_notifier() {
+                    self.drain.track(closed);
+                }
+                self.emit(ConnectionEvent::Connected { duration })
+            }
+            Err(ref error) => self.emit(ConnectionEvent::ConnectFailed { duration, error }),
+        }
+        res
+    }
+
+    // Checks whether the connection is broken without performing a round trip
+    fn is_broken_connection(&self, conn: &mut C) -> bool {
+        if self.is_draining_connection() {
+            return true;
+        }
+        let broken = std::thread::panicking() || conn.is_broken();
+        if broken {
+            self.emit(ConnectionEvent::Broken {
+                reason: BrokenReason::Unusable,
+                error: None,
+            });
+        }
+        broken
+    }
+
+    // Checks whether connections are discarded as the manager is drained
+    fn is_draining_connection(&self) -> bool {
+        let draining = self.drain.is_draining();
+        if draining {
+            self.emit(ConnectionEvent::Broken {
+                reason: BrokenReason::Draining,
+                error: None,
+            });
+        }
+        draining
+    }
+
+    // Checks the connection via the configured recycling method,
+    // respecting the configured ping timeout
+    async fn check_connection(&self, conn: &mut C) -> QueryResult<()> {
+        let res = async {
+            if self.manager_config.reset_session {
+                conn.reset_session().await?;
+            }
+            self.ping_connection(conn).await?;
+            self.checkout_setup(conn).await
+        }
+        .await;
+        if let Err(ref error) = res {
+            self.emit(ConnectionEvent::Broken {
+                reason: BrokenReason::CheckFailed,
+                error: Some(error),
+            });
+        }
+        res
+    }
+
+    // Establishes a connection for a checkout. Unlike bb8, which checks new
+    // connections on checkout as well, deadpool and mobc hand them out
+    // without a check, so the checkout setup runs here instead
+    #[cfg(any(feature = "deadpool", feature = "mobc"))]
+    async fn establish_checked_out_connection(&self) -> Result<C, PoolError> {
+        let mut conn = self.establish_connection().await?;
+        self.checkout_setup(&mut conn)
+            .await
+            .map_err(PoolError::QueryError)?;
+        Ok(conn)
+    }
+
+    async fn checkout_setup(&self, conn: &mut C) -> QueryResult<()> {
+        match self.manager_config.on_checkout {
+            Some(CheckoutSetup::Sql(ref sql)) => conn.batch_execute(sql).await,
+            Some(CheckoutSetup::Function(ref setup)) => setup(conn).await,
+            None => Ok(()),
+        }
+    }
+
+    // Rolls back the transactions left open by the last user of the
+    // connection, respecting the configured ping timeout. A failed or timed
+    // out rollback leaves the transaction open, so the connection is
+    // considered to be broken afterwards
+    #[cfg(any(feature = "deadpool", feature = "mobc"))]
+    async fn rollback_before_reuse(&self, conn: &mut C) {
+        let rollback = conn.rollback_open_transactions();
+        let _ = match self.manager_config.ping_timeout {
+            Some(timeout) => tokio::time::timeout(timeout, rollback)
+                .await
+                .unwrap_or(Ok(())),
+            None => rollback.await,
+        };
+    }
+
+    async fn ping_connection(&self, conn: &mut C) -> QueryResult<()> {
+        let ping = PoolableConnection::ping(conn, &self.manager_config.recycling_method);
+        match self.manager_config.ping_timeout {
+            Some(timeout) => tokio::time::timeout(timeout, ping)
+                .await
+                .unwrap_or_else(|_| {
+                    Err(diesel::result::Error::DatabaseError(
+                        diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                        Box::new(format!("Connection check timed out after {timeout:?}")),
+                    ))
+                }),
+            None => ping.await,
+        }
+    }
+
+    fn emit(&self, event: ConnectionEvent<'_>) {
+        if let Some(ref on_event) = self.manager_config.on_event {
+            on_event(&self.manager_config.labels, event);
         }
     }
 }
 
+#[cfg(any(feature = "bb8", feature = "deadpool", feature = "mobc"))]
+type AttemptPermit<'a> = (&'a CircuitBreakerConfig, circuit_breaker::Permit<'a>);
+
+// Performs a round trip on the given connection, returning
+// the time it took or the error it failed with
+#[cfg(any(feature = "bb8", feature = "deadpool", feature = "mobc"))]
+async fn probe_connection<C>(conn: &mut C) -> (Option<Duration>, Option<String>)
+where
+    C: PoolableConnection + 'static,
+    diesel::dsl::select<diesel::dsl::AsExprOf<i32, diesel::sql_types::Integer>>:
+        crate::methods::ExecuteDsl<C>,
+    diesel::query_builder::SqlQuery: crate::methods::ExecuteDsl<C>,
+{
+    let start = Instant::now();
+    match PoolableConnection::ping(conn, &RecyclingMethod::Verified).await {
+        Ok(()) => (Some(start.elapsed()), None),
+        Err(e) => (None, Some(e.to_string())),
+    }
+}
+
 #[async_trait::async_trait]
 impl<C> SimpleAsyncConnection for C
 where
@@ -339,6 +1167,92 @@ pub trait PoolableConnection: AsyncConnection {
         }
     }
 
+    /// Roll back the transactions left open by the last user of the connection
+    ///
+    /// The `deadpool` integration calls this function when recycling a
+    /// connection and the `mobc` integration when checking out an idle
+    /// connection, before checking whether it is broken. Connections with a
+    /// dangling transaction, for example due to an early return, are therefore
+    /// reused instead of being discarded. The rollback is bounded by
+    /// [`ManagerConfig::ping_timeout`]. `bb8` checks connections returned
+    /// to the pool synchronously and therefore still discards them. Test
+    /// transactions started via [AsyncConnection::begin_test_transaction] are
+    /// not rolled back.
+    ///
+    /// The default implementation rolls back the open transaction and its
+    /// savepoints via [TransactionManager::rollback_transaction].
+    async fn rollback_open_transactions(&mut self) -> diesel::QueryResult<()>
+    where
+        for<'a> Self: 'a,
+    {
+        use diesel::connection::ValidTransactionManagerStatus;
+
+        loop {
+            match Self::TransactionManager::transaction_manager_status_mut(self).transaction_state()
+            {
+                Ok(ValidTransactionManagerStatus {
+                    in_transaction: Some(s),
+                    ..
+                }) if !s.test_transaction => {
+                    Self::TransactionManager::rollback_transaction(self).await?
+                }
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    /// Reset the session state of this connection, as if it was newly established
+    ///
+    /// Used by [`ManagerConfig::reset_session`] to ensure that no session
+    /// settings, temporary tables or locks leak from one checkout to the next.
+    /// Implementations keep the statement cache of the connection consistent
+    /// with the statements prepared on the server. The default implementation
+    /// fails, as resetting is not supported, so that such connections are
+    /// discarded instead of being reused.
+    async fn reset_session(&mut self) -> diesel::QueryResult<()> {
+        Err(diesel::result::Error::QueryBuilderError(
+            "Resetting the session state is not supported by this connection".into(),
+        ))
+    }
+
+    /// Apply the given statement cache settings to this connection
+    ///
+    /// Used by [`ManagerConfig::statement_cache`]. Statements already in the
+    /// cache are kept. The default implementation does nothing, as the
+    /// connection has no statement cache.
+    async fn configure_statement_cache(&mut self, _config: &StatementCacheConfig) {}
+
+    /// Apply the given TCP settings to the database URL used to establish connections
+    ///
+    /// Used by [`ManagerConfig::tcp`]. The default implementation returns the
+    /// URL unchanged, as the connection does not use TCP.
+    fn apply_tcp_config<'a>(database_url: &'a str, _config: &TcpConfig) -> Cow<'a, str>
+    where
+        Self: Sized,
+    {
+        Cow::Borrowed(database_url)
+    }
+
+    /// Returns a function that makes this connection unusable from another task
+    ///
+    /// Used to reclaim connections held longer than allowed, see
+    /// [`MaxCheckoutAction::Reclaim`](self::MaxCheckoutAction::Reclaim). After
+    /// calling the function, [`PoolableConnection::is_broken`] must return
+    /// `true`. The default implementation returns `None`, as reclaiming is
+    /// not supported.
+    fn reclaim_handle(&self) -> Option<Box<dyn FnOnce() + Send>> {
+        None
+    }
+
+    /// Returns a future resolving once this connection is closed
+    ///
+    /// Used to await the connections of a drained pool, see
+    /// [`DrainHandle`](self::DrainHandle). The default implementation returns
+    /// `None`, such connections are not awaited.
+    fn closed_notifier(&self) -> Option<future::BoxFuture<'static, ()>> {
+        None
+    }
+
     /// Checks if the connection is broken and should not be reused
     ///
     /// This method should return only contain a fast non-blocking check
@@ -0,0 +1,100 @@
+use std::future::Future;
+use std::ops::{Deref, DerefMut};
+use std::sync::Arc;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// The priority class of a checkout, see [`PriorityGate`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum CheckoutPriority {
+    /// Latency sensitive work, for example request handlers
+    Interactive,
+    /// Background work that may wait for connections
+    Batch,
+}
+
+/// Keeps background work from starving latency sensitive work
+/// of the connections of a saturated pool
+///
+/// The gate limits the number of connections held by checkouts of the
+/// [`CheckoutPriority::Batch`] class, which reserves the remaining connections
+/// of the pool for [`CheckoutPriority::Interactive`] checkouts. Batch checkouts
+/// exceeding the limit wait in front of the pool instead of queueing up in the
+/// pool ahead of interactive checkouts. This works with any pool implementation.
+///
+/// For example with a pool of 10 connections, `PriorityGate::new(7)` keeps 3
+/// connections available for interactive checkouts:
+/// `gate.checkout(CheckoutPriority::Batch, pool.get()).await`.
+#[derive(Debug, Clone)]
+pub struct PriorityGate {
+    batch_permits: Arc<Semaphore>,
+}
+
+impl PriorityGate {
+    /// Construct a new gate allowing batch checkouts to hold
+    /// at most `max_batch_connections` connections at once
+    pub fn new(max_batch_connections: usize) -> Self {
+        Self {
+            batch_permits: Arc::new(Semaphore::new(max_batch_connections)),
+        }
+    }
+
+    /// Check out a connection with the given priority
+    ///
+    /// `checkout` is the future checking out a connection from the pool,
+    /// for example `pool.get()`. The returned guard releases the capacity of
+    /// the priority class once it is dropped together with the connection.
+    pub async fn checkout<F, T, E>(
+        &self,
+        priority: CheckoutPriority,
+        checkout: F,
+    ) -> Result<PrioritizedConnection<T>, E>
+    where
+        F: Future<Output = Result<T, E>>,
+    {
+        let permit = match priority {
+            CheckoutPriority::Interactive => None,
+            CheckoutPriority::Batch => Some(
+                self.batch_permits
+                    .clone()
+                    .acquire_owned()
+                    .await
+                    .expect("The semaphore is never closed"),
+            ),
+        };
+        Ok(PrioritizedConnection {
+            connection: checkout.await?,
+            _permit: permit,
+        })
+    }
+}
+
+/// A connection checked out via [`PriorityGate::checkout`]
+#[derive(Debug)]
+pub struct PrioritizedConnection<T> {
+    // dropped before the permit, so the connection
+    // is back in the pool once the next batch checkout starts
+    connection: T,
+    _permit: Option<OwnedSemaphorePermit>,
+}
+
+impl<T> PrioritizedConnection<T> {
+    /// Release the capacity of the priority class and return the connection
+    pub fn into_inner(self) -> T {
+        self.connection
+    }
+}
+
+impl<T> Deref for PrioritizedConnection<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.connection
+    }
+}
+
+impl<T> DerefMut for PrioritizedConnection<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.connection
+    }
+}
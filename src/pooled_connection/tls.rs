@@ -0,0 +1,125 @@
+use diesel::ConnectionResult;
+use futures_util::future::BoxFuture;
+use std::fmt;
+use std::sync::Arc;
+
+type EstablishCallback<C> = dyn Fn(&str) -> BoxFuture<'_, ConnectionResult<C>> + Send + Sync;
+
+/// TLS settings for the connections established by an
+/// [`AsyncDieselConnectionManager`](super::AsyncDieselConnectionManager)
+///
+/// Pass this to [`ManagerConfig::tls`](super::ManagerConfig::tls) instead of
+/// implementing [`ManagerConfig::custom_setup`](super::ManagerConfig::custom_setup)
+/// to establish encrypted connections.
+///
+/// For PostgreSQL any TLS connector supported by `tokio-postgres` can be
+/// used, for example `MakeRustlsConnect` of `tokio-postgres-rustls`. For
+/// MySQL the TLS settings of `mysql_async` are used, which requires the
+/// `mysql-rustls` feature for encrypted connections.
+///
+/// ```rust
+/// # include!("../doctest_setup.rs");
+/// use diesel_async::pooled_connection::deadpool::Pool;
+/// use diesel_async::pooled_connection::{AsyncDieselConnectionManager, ManagerConfig, TlsConfig};
+/// #
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// #     run_test().await.unwrap();
+/// # }
+/// #
+/// # async fn run_test() -> Result<(), Box<dyn std::error::Error>> {
+/// # #[cfg(feature = "postgres")]
+/// # {
+/// #     use diesel_async::RunQueryDsl;
+/// #     let database_url = database_url();
+/// let mut config = ManagerConfig::<AsyncPgConnection>::default();
+/// // usually a TLS connector like `MakeRustlsConnect`
+/// config.tls = Some(TlsConfig::new(tokio_postgres::NoTls));
+///
+/// let manager = AsyncDieselConnectionManager::new_with_config(database_url, config);
+/// let pool = Pool::builder(manager).build()?;
+/// # let mut conn = pool.get().await?;
+/// # diesel::select(1.into_sql::<diesel::sql_types::Integer>()).execute(&mut conn).await?;
+/// # }
+/// #     Ok(())
+/// # }
+/// ```
+pub struct TlsConfig<C> {
+    establish: Arc<EstablishCallback<C>>,
+}
+
+impl<C> TlsConfig<C> {
+    pub(super) fn establish<'a>(&'a self, url: &'a str) -> BoxFuture<'a, ConnectionResult<C>> {
+        (self.establish)(url)
+    }
+}
+
+impl<C> Clone for TlsConfig<C> {
+    fn clone(&self) -> Self {
+        Self {
+            establish: self.establish.clone(),
+        }
+    }
+}
+
+impl<C> fmt::Debug for TlsConfig<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TlsConfig").finish_non_exhaustive()
+    }
+}
+
+#[cfg(feature = "postgres")]
+impl TlsConfig<crate::AsyncPgConnection> {
+    /// Establish connections with the given TLS connector of `tokio-postgres`
+    ///
+    /// Whether TLS is required is controlled by the `sslmode` parameter
+    /// of the database URL, see
+    /// [`AsyncPgConnection::establish_with_tls`](crate::AsyncPgConnection::establish_with_tls).
+    pub fn new<T>(tls: T) -> Self
+    where
+        T: tokio_postgres::tls::MakeTlsConnect<tokio_postgres::Socket>
+            + Clone
+            + Send
+            + Sync
+            + 'static,
+        T::Stream: Send + 'static,
+        T::TlsConnect: Send,
+        <T::TlsConnect as tokio_postgres::tls::TlsConnect<tokio_postgres::Socket>>::Future: Send,
+    {
+        Self {
+            establish: Arc::new(move |url| {
+                let tls = tls.clone();
+                Box::pin(crate::AsyncPgConnection::establish_with_tls(url, tls))
+            }),
+        }
+    }
+}
+
+#[cfg(feature = "mysql")]
+impl TlsConfig<crate::AsyncMysqlConnection> {
+    /// Establish connections with the given TLS mode
+    ///
+    /// Use [`TlsConfig::with_builder`] to configure trusted certificate
+    /// authorities or a client certificate as well.
+    pub fn new(ssl_mode: crate::mysql::SslMode) -> Self {
+        Self::with_builder(move |builder| builder.ssl_mode(ssl_mode))
+    }
+
+    /// Establish connections with the TLS settings applied by the given
+    /// function to an [`AsyncMysqlConnectionBuilder`](crate::mysql::AsyncMysqlConnectionBuilder)
+    pub fn with_builder<F>(configure: F) -> Self
+    where
+        F: Fn(
+                crate::mysql::AsyncMysqlConnectionBuilder,
+            ) -> crate::mysql::AsyncMysqlConnectionBuilder
+            + Send
+            + Sync
+            + 'static,
+    {
+        Self {
+            establish: Arc::new(move |url| {
+                Box::pin(configure(crate::mysql::AsyncMysqlConnectionBuilder::new(url)).establish())
+            }),
+        }
+    }
+}
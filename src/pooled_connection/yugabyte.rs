@@ -0,0 +1,495 @@
+use super::SetupCallback;
+use crate::{AsyncPgConnection, RunQueryDsl};
+use diesel::sql_types::{BigInt, Nullable, Text};
+use diesel::{ConnectionError, ConnectionResult};
+use futures_util::future::BoxFuture;
+use futures_util::FutureExt;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+type ConnectCallback = dyn Fn(tokio_postgres::Config) -> BoxFuture<'static, ConnectionResult<AsyncPgConnection>>
+    + Send
+    + Sync;
+
+const TOPOLOGY_QUERY: &str = "SELECT host::text AS host, port::bigint AS port, \
+     cloud::text AS cloud, region::text AS region, zone::text AS zone, \
+     public_ip::text AS public_ip FROM yb_servers()";
+
+/// A YugabyteDB tablet server as reported by `yb_servers()`,
+/// see [`YugabyteLoadBalancer::nodes`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct YugabyteNode {
+    /// The host name or address of the node
+    pub host: String,
+    /// The port of the YSQL API of the node
+    pub port: u16,
+    /// The cloud the node is placed in
+    pub cloud: String,
+    /// The region the node is placed in
+    pub region: String,
+    /// The zone the node is placed in
+    pub zone: String,
+    /// The public address of the node, if any
+    pub public_ip: Option<String>,
+    /// The number of open connections established to this
+    /// node by the load balancer
+    pub connections: usize,
+}
+
+// A placement like `cloud.region.zone`, where each
+// part may be `*` to match any value
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Placement([String; 3]);
+
+impl Placement {
+    fn matches(&self, node: &YugabyteNode) -> bool {
+        [&node.cloud, &node.region, &node.zone]
+            .into_iter()
+            .zip(&self.0)
+            .all(|(value, pattern)| pattern == "*" || pattern == value)
+    }
+}
+
+// The host and port of a node as reported by `yb_servers()`
+type NodeKey = (String, u16);
+
+#[derive(Debug, Default)]
+struct State {
+    nodes: Vec<YugabyteNode>,
+    refreshed_at: Option<Instant>,
+    failed_until: HashMap<NodeKey, Instant>,
+}
+
+/// Balances the connections of a pool across the nodes of a YugabyteDB
+/// cluster, like the smart drivers of YugabyteDB
+///
+/// Plain connections to a YugabyteDB cluster all end up on the node given in
+/// the database URL, unless an external load balancer is used. This load
+/// balancer instead discovers the nodes of the cluster via `yb_servers()` and
+/// establishes each new connection to the node with the fewest connections
+/// opened by this load balancer. The topology is refreshed periodically, so
+/// nodes added to the cluster receive connections as well.
+///
+/// Nodes can be preferred based on their placement, so that connections stay
+/// within the zone or region of the application. Preferences are given as
+/// `cloud.region.zone`, where each part may be `*` to match any value. A node
+/// matching an earlier preference is always chosen over nodes matching later
+/// ones. Nodes not matching any preference are only used if no preferred node
+/// is available and [`YugabyteLoadBalancer::fallback_to_any_node`] is enabled.
+///
+/// Nodes that fail to accept a connection are skipped for a while. If the
+/// topology cannot be discovered, for example because the server is a regular
+/// PostgreSQL server, connections are established via the given URL instead.
+///
+/// Use [`YugabyteLoadBalancer::setup`] as
+/// [`ManagerConfig::custom_setup`](super::ManagerConfig::custom_setup):
+///
+/// ```rust
+/// # include!("../doctest_setup.rs");
+/// use diesel_async::pooled_connection::deadpool::Pool;
+/// use diesel_async::pooled_connection::{
+///     AsyncDieselConnectionManager, ManagerConfig, YugabyteLoadBalancer,
+/// };
+/// #
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// #     run_test().await.unwrap();
+/// # }
+/// #
+/// # async fn run_test() -> Result<(), Box<dyn std::error::Error>> {
+/// #     use diesel_async::RunQueryDsl;
+/// #     let database_url = database_url();
+/// let balancer = YugabyteLoadBalancer::new()
+///     .prefer("aws.us-east-1.us-east-1a")
+///     .prefer("aws.us-east-1.*");
+///
+/// let mut config = ManagerConfig::<AsyncPgConnection>::default();
+/// config.custom_setup = balancer.setup();
+/// let manager = AsyncDieselConnectionManager::new_with_config(database_url, config);
+/// let pool = Pool::builder(manager).build()?;
+/// # let mut conn = pool.get().await?;
+/// # diesel::select(1.into_sql::<diesel::sql_types::Integer>()).execute(&mut conn).await?;
+/// #     Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct YugabyteLoadBalancer {
+    preferences: Vec<Placement>,
+    fallback_to_any_node: bool,
+    refresh_interval: Duration,
+    failed_node_delay: Duration,
+    use_public_ip: bool,
+    connect: Arc<ConnectCallback>,
+    state: Arc<Mutex<State>>,
+    refresh: Arc<tokio::sync::Mutex<()>>,
+}
+
+impl std::fmt::Debug for YugabyteLoadBalancer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("YugabyteLoadBalancer")
+            .field("preferences", &self.preferences)
+            .field("fallback_to_any_node", &self.fallback_to_any_node)
+            .field("refresh_interval", &self.refresh_interval)
+            .field("failed_node_delay", &self.failed_node_delay)
+            .field("use_public_ip", &self.use_public_ip)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for YugabyteLoadBalancer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl YugabyteLoadBalancer {
+    /// Construct a new load balancer establishing unencrypted connections
+    ///
+    /// The topology is refreshed every 5 minutes, nodes that failed to accept
+    /// a connection are skipped for 5 seconds and nodes not matching any
+    /// preference are used as fallback.
+    pub fn new() -> Self {
+        Self::with_tls(tokio_postgres::NoTls)
+    }
+
+    /// Construct a new load balancer establishing connections
+    /// with the given TLS connector of `tokio-postgres`
+    ///
+    /// See [`AsyncPgConnection::establish_with_tls`].
+    pub fn with_tls<T>(tls: T) -> Self
+    where
+        T: tokio_postgres::tls::MakeTlsConnect<tokio_postgres::Socket>
+            + Clone
+            + Send
+            + Sync
+            + 'static,
+        T::Stream: Send + 'static,
+        T::TlsConnect: Send,
+        <T::TlsConnect as tokio_postgres::tls::TlsConnect<tokio_postgres::Socket>>::Future: Send,
+    {
+        Self {
+            preferences: Vec::new(),
+            fallback_to_any_node: true,
+            refresh_interval: Duration::from_secs(5 * 60),
+            failed_node_delay: Duration::from_secs(5),
+            use_public_ip: false,
+            connect: Arc::new(move |config| {
+                AsyncPgConnection::establish_with_config(config, tls.clone()).boxed()
+            }),
+            state: Arc::default(),
+            refresh: Arc::default(),
+        }
+    }
+
+    /// Prefer the nodes placed in the given `cloud.region.zone`
+    ///
+    /// Each part may be `*` to match any value, like `aws.us-east-1.*`.
+    /// Preferences are ordered by the calls of this function.
+    ///
+    /// # Panics
+    ///
+    /// If the placement does not consist of three parts separated by dots.
+    pub fn prefer(mut self, placement: &str) -> Self {
+        let parts = placement.split('.').map(String::from).collect::<Vec<_>>();
+        let parts = <[String; 3]>::try_from(parts).unwrap_or_else(|_| {
+            panic!("Expected a placement like `cloud.region.zone`, got `{placement}`")
+        });
+        self.preferences.push(Placement(parts));
+        self
+    }
+
+    /// Set whether nodes not matching any preference are used once
+    /// no preferred node is available, which is the default
+    pub fn fallback_to_any_node(mut self, fallback: bool) -> Self {
+        self.fallback_to_any_node = fallback;
+        self
+    }
+
+    /// Set the interval in which the topology of the cluster is refreshed
+    pub fn refresh_interval(mut self, interval: Duration) -> Self {
+        self.refresh_interval = interval;
+        self
+    }
+
+    /// Set the time nodes that failed to accept a connection are skipped
+    pub fn failed_node_delay(mut self, delay: Duration) -> Self {
+        self.failed_node_delay = delay;
+        self
+    }
+
+    /// Set whether connections are established via the public
+    /// address of the nodes instead of their host name
+    ///
+    /// This is required if the application runs outside of
+    /// the network of the cluster.
+    pub fn use_public_ip(mut self, use_public_ip: bool) -> Self {
+        self.use_public_ip = use_public_ip;
+        self
+    }
+
+    /// The nodes of the cluster discovered so far together
+    /// with the number of connections established to them
+    pub fn nodes(&self) -> Vec<YugabyteNode> {
+        self.lock_state().nodes.clone()
+    }
+
+    /// A setup callback establishing connections via this load balancer,
+    /// see [`ManagerConfig::custom_setup`](super::ManagerConfig::custom_setup)
+    ///
+    /// The database URL passed to the callback is used to discover the
+    /// topology of the cluster. All other settings of the URL, like the
+    /// user or the database, are used for the connections to the nodes.
+    pub fn setup(&self) -> SetupCallback<AsyncPgConnection> {
+        let balancer = self.clone();
+        Box::new(move |database_url| {
+            let balancer = balancer.clone();
+            let database_url = database_url.to_owned();
+            async move { balancer.establish(&database_url).await }.boxed()
+        })
+    }
+
+    /// Establish a connection to a node of the cluster
+    /// described by the given database URL
+    pub async fn establish(&self, database_url: &str) -> ConnectionResult<AsyncPgConnection> {
+        let config = database_url
+            .parse::<tokio_postgres::Config>()
+            .map_err(|e| ConnectionError::InvalidConnectionUrl(e.to_string()))?;
+        self.refresh_if_outdated(&config).await;
+
+        let mut last_error = None;
+        // each node is attempted at most once, even if
+        // failed nodes become available again right away
+        let mut attempted = HashSet::new();
+        while let Some((node, address)) = self.choose_node(&attempted) {
+            attempted.insert(node.clone());
+            match (self.connect)(node_config(&config, &address, node.1)).await {
+                Ok(conn) => {
+                    self.track(node, &conn);
+                    return Ok(conn);
+                }
+                Err(error) => {
+                    let until = Instant::now() + self.failed_node_delay;
+                    self.lock_state().failed_until.insert(node, until);
+                    last_error = Some(error);
+                }
+            }
+        }
+        match last_error {
+            Some(error) => Err(error),
+            // the topology is unknown
+            None if self.lock_state().nodes.is_empty() => (self.connect)(config).await,
+            None => Err(ConnectionError::BadConnection(String::from(
+                "No node of the YugabyteDB cluster is available",
+            ))),
+        }
+    }
+
+    fn lock_state(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    async fn refresh_if_outdated(&self, config: &tokio_postgres::Config) {
+        let is_outdated = |state: &State| {
+            state
+                .refreshed_at
+                .map_or(true, |at| at.elapsed() >= self.refresh_interval)
+        };
+        if !is_outdated(&self.lock_state()) {
+            return;
+        }
+        let _refresh = self.refresh.lock().await;
+        // another task might have refreshed the topology in the meantime
+        if !is_outdated(&self.lock_state()) {
+            return;
+        }
+
+        // try the known nodes if the node of the URL is unavailable
+        let mut configs = vec![config.clone()];
+        configs.extend(
+            self.lock_state()
+                .nodes
+                .iter()
+                .map(|node| node_config(config, &self.address(node), node.port)),
+        );
+        let mut nodes = None;
+        for config in configs {
+            if let Ok(mut conn) = (self.connect)(config).await {
+                nodes = Some(query_topology(&mut conn).await);
+                break;
+            }
+        }
+
+        let mut state = self.lock_state();
+        state.refreshed_at = Some(Instant::now());
+        match nodes {
+            Some(Ok(mut nodes)) => {
+                let connections = state
+                    .nodes
+                    .iter()
+                    .map(|node| ((node.host.clone(), node.port), node.connections))
+                    .collect::<HashMap<_, _>>();
+                for node in &mut nodes {
+                    node.connections = connections
+                        .get(&(node.host.clone(), node.port))
+                        .copied()
+                        .unwrap_or_default();
+                }
+                state.nodes = nodes;
+            }
+            // not a YugabyteDB cluster
+            Some(Err(_)) => state.nodes.clear(),
+            // keep the last known topology if all nodes are unavailable
+            None => {}
+        }
+    }
+
+    // Chooses the available node with the fewest connections of the most
+    // preferred placement that was not attempted yet, returning its key
+    // and its address
+    fn choose_node(&self, attempted: &HashSet<NodeKey>) -> Option<(NodeKey, String)> {
+        let state = self.lock_state();
+        let now = Instant::now();
+        let available = state
+            .nodes
+            .iter()
+            .filter(|node| {
+                let key = (node.host.clone(), node.port);
+                !attempted.contains(&key)
+                    && state
+                        .failed_until
+                        .get(&key)
+                        .map_or(true, |until| *until <= now)
+            })
+            .collect::<Vec<_>>();
+        let least_used = |nodes: Vec<&YugabyteNode>| {
+            nodes
+                .into_iter()
+                .min_by_key(|node| node.connections)
+                .map(|node| ((node.host.clone(), node.port), self.address(node)))
+        };
+        for placement in &self.preferences {
+            let matching = available
+                .iter()
+                .copied()
+                .filter(|node| placement.matches(node))
+                .collect::<Vec<_>>();
+            if !matching.is_empty() {
+                return least_used(matching);
+            }
+        }
+        if self.preferences.is_empty() || self.fallback_to_any_node {
+            least_used(available)
+        } else {
+            None
+        }
+    }
+
+    fn address(&self, node: &YugabyteNode) -> String {
+        match node.public_ip {
+            Some(ref public_ip) if self.use_public_ip && !public_ip.is_empty() => public_ip.clone(),
+            _ => node.host.clone(),
+        }
+    }
+
+    // Counts the connection until it is closed
+    fn track(&self, node: NodeKey, conn: &AsyncPgConnection) {
+        let update = {
+            let state = self.state.clone();
+            move |delta: isize| {
+                let mut state = state.lock().unwrap_or_else(PoisonError::into_inner);
+                if let Some(node) = state
+                    .nodes
+                    .iter_mut()
+                    .find(|n| n.host == node.0 && n.port == node.1)
+                {
+                    node.connections = node.connections.saturating_add_signed(delta);
+                }
+            }
+        };
+        update(1);
+        let closed = conn.closed();
+        tokio::spawn(async move {
+            closed.await;
+            update(-1);
+        });
+    }
+}
+
+#[derive(diesel::QueryableByName)]
+struct ServerRow {
+    #[diesel(sql_type = Text)]
+    host: String,
+    #[diesel(sql_type = BigInt)]
+    port: i64,
+    #[diesel(sql_type = Nullable<Text>)]
+    cloud: Option<String>,
+    #[diesel(sql_type = Nullable<Text>)]
+    region: Option<String>,
+    #[diesel(sql_type = Nullable<Text>)]
+    zone: Option<String>,
+    #[diesel(sql_type = Nullable<Text>)]
+    public_ip: Option<String>,
+}
+
+async fn query_topology(conn: &mut AsyncPgConnection) -> diesel::QueryResult<Vec<YugabyteNode>> {
+    let rows = diesel::sql_query(TOPOLOGY_QUERY)
+        .load::<ServerRow>(conn)
+        .await?;
+    Ok(rows
+        .into_iter()
+        .filter_map(|row| {
+            Some(YugabyteNode {
+                host: row.host,
+                port: u16::try_from(row.port).ok()?,
+                cloud: row.cloud.unwrap_or_default(),
+                region: row.region.unwrap_or_default(),
+                zone: row.zone.unwrap_or_default(),
+                public_ip: row.public_ip,
+                connections: 0,
+            })
+        })
+        .collect())
+}
+
+// Copies the given configuration, connecting to the given node instead
+fn node_config(config: &tokio_postgres::Config, host: &str, port: u16) -> tokio_postgres::Config {
+    let mut node_config = tokio_postgres::Config::new();
+    node_config
+        .host(host)
+        .port(port)
+        .ssl_mode(config.get_ssl_mode())
+        .keepalives(config.get_keepalives())
+        .keepalives_idle(config.get_keepalives_idle())
+        .target_session_attrs(config.get_target_session_attrs())
+        .channel_binding(config.get_channel_binding());
+    if let Some(user) = config.get_user() {
+        node_config.user(user);
+    }
+    if let Some(password) = config.get_password() {
+        node_config.password(password);
+    }
+    if let Some(dbname) = config.get_dbname() {
+        node_config.dbname(dbname);
+    }
+    if let Some(options) = config.get_options() {
+        node_config.options(options);
+    }
+    if let Some(application_name) = config.get_application_name() {
+        node_config.application_name(application_name);
+    }
+    if let Some(connect_timeout) = config.get_connect_timeout() {
+        node_config.connect_timeout(*connect_timeout);
+    }
+    if let Some(tcp_user_timeout) = config.get_tcp_user_timeout() {
+        node_config.tcp_user_timeout(*tcp_user_timeout);
+    }
+    if let Some(interval) = config.get_keepalives_interval() {
+        node_config.keepalives_interval(interval);
+    }
+    if let Some(retries) = config.get_keepalives_retries() {
+        node_config.keepalives_retries(retries);
+    }
+    node_config
+}
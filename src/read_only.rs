@@ -0,0 +1,150 @@
+use crate::methods::LoadQuery;
+use crate::{AsyncConnection, RunQueryDsl};
+use diesel::QueryResult;
+
+/// A connection wrapper that only allows to load data
+///
+/// `ReadOnly` does not implement [`AsyncConnection`], so it cannot be passed
+/// to [`RunQueryDsl::execute`] or to functions expecting a regular
+/// connection. Instead it only exposes methods to load the results of
+/// queries, which makes it suitable as a handle to a read replica. Code
+/// accepting a `ReadOnly` connection cannot execute insert, update or
+/// delete statements.
+///
+/// As raw SQL queries and statements with a `RETURNING` clause can still be
+/// loaded, this is no security boundary. For PostgreSQL and MySQL
+/// connections the session can additionally be set to read only via
+/// `ReadOnly::with_read_only_session`, so that the database rejects writes.
+///
+/// The methods are named differently from the methods of [`RunQueryDsl`],
+/// which would otherwise take precedence.
+///
+/// # Example
+///
+/// ```rust
+/// # include!("doctest_setup.rs");
+/// use diesel_async::ReadOnly;
+/// #
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// #     run_test().await.unwrap();
+/// # }
+/// #
+/// # async fn run_test() -> QueryResult<()> {
+/// #     use schema::users::dsl::*;
+/// #     let conn = establish_connection().await;
+/// let mut replica = ReadOnly::new(conn);
+///
+/// let all_names = replica
+///     .load_query::<_, String>(users.select(name).order(id))
+///     .await?;
+/// assert_eq!(vec!["Sean", "Tess"], all_names);
+///
+/// let first_name = replica
+///     .get_query_result::<_, String>(users.select(name).filter(id.eq(1)))
+///     .await?;
+/// assert_eq!("Sean", first_name);
+/// #     Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct ReadOnly<C> {
+    conn: C,
+}
+
+impl<C> ReadOnly<C>
+where
+    C: AsyncConnection,
+{
+    /// Wrap the given connection
+    ///
+    /// This does not change the session of the connection.
+    pub fn new(conn: C) -> Self {
+        Self { conn }
+    }
+
+    /// Unwrap the underlying connection
+    pub fn into_inner(self) -> C {
+        self.conn
+    }
+
+    /// Load the results of the given query
+    ///
+    /// This corresponds to [`RunQueryDsl::load`].
+    pub async fn load_query<'query, Q, U>(&mut self, query: Q) -> QueryResult<Vec<U>>
+    where
+        U: Send,
+        Q: LoadQuery<'query, C, U> + 'query,
+    {
+        query.load(&mut self.conn).await
+    }
+
+    /// Load the first result of the given query
+    ///
+    /// Returns [`diesel::result::Error::NotFound`] if the query returned no rows.
+    /// This corresponds to [`RunQueryDsl::get_result`].
+    pub async fn get_query_result<'query, Q, U>(&mut self, query: Q) -> QueryResult<U>
+    where
+        U: Send,
+        Q: LoadQuery<'query, C, U> + 'query,
+    {
+        query.get_result(&mut self.conn).await
+    }
+}
+
+#[cfg(feature = "postgres")]
+impl ReadOnly<crate::AsyncPgConnection> {
+    /// Wrap the given connection and set its session to read only
+    ///
+    /// This sets `default_transaction_read_only` for the session, so
+    /// PostgreSQL rejects writes issued via raw SQL queries as well.
+    ///
+    /// ```rust
+    /// # include!("doctest_setup.rs");
+    /// use diesel::dsl::sql;
+    /// use diesel::sql_types::Text;
+    /// use diesel_async::ReadOnly;
+    /// #
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// #     run_test().await.unwrap();
+    /// # }
+    /// #
+    /// # async fn run_test() -> QueryResult<()> {
+    /// #     let conn = connection_no_transaction().await;
+    /// let mut replica = ReadOnly::with_read_only_session(conn).await?;
+    ///
+    /// let read_only = replica
+    ///     .get_query_result::<_, String>(diesel::select(sql::<Text>(
+    ///         "current_setting('transaction_read_only')",
+    ///     )))
+    ///     .await?;
+    /// assert_eq!("on", read_only);
+    /// #     Ok(())
+    /// # }
+    /// ```
+    pub async fn with_read_only_session(mut conn: crate::AsyncPgConnection) -> QueryResult<Self> {
+        use crate::SimpleAsyncConnection;
+
+        conn.batch_execute("SET SESSION CHARACTERISTICS AS TRANSACTION READ ONLY")
+            .await?;
+        Ok(Self::new(conn))
+    }
+}
+
+#[cfg(feature = "mysql")]
+impl ReadOnly<crate::AsyncMysqlConnection> {
+    /// Wrap the given connection and set its session to read only
+    ///
+    /// This sets the access mode of all further transactions of the
+    /// session, so MySQL rejects writes issued via raw SQL queries as well.
+    pub async fn with_read_only_session(
+        mut conn: crate::AsyncMysqlConnection,
+    ) -> QueryResult<Self> {
+        use crate::SimpleAsyncConnection;
+
+        conn.batch_execute("SET SESSION TRANSACTION READ ONLY")
+            .await?;
+        Ok(Self::new(conn))
+    }
+}
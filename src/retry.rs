@@ -0,0 +1,240 @@
+//! Automatically retry operations aborted due to a deadlock
+//!
+//! Both PostgreSQL and MySQL resolve deadlocks between concurrent transactions by
+//! aborting one of the involved transactions. The aborted operation can usually just
+//! be executed again. [`RetryPolicy`] does that with capped exponential backoff,
+//! so that the competing transactions do not immediately run into the same
+//! deadlock again. Serialization failures of transactions running with the
+//! `SERIALIZABLE` or `REPEATABLE READ` isolation level are retried the same way,
+//! see [`is_transaction_conflict`].
+//!
+//! # Migrating from earlier versions
+//!
+//! Deadlocks used to be reported as [`DatabaseErrorKind::Unknown`]. They are
+//! now reported as [`DatabaseErrorKind::SerializationFailure`] for all
+//! connections, whether a [`RetryPolicy`] is used or not. Code that matches
+//! on `SerializationFailure` therefore observes deadlocks as well. Use the
+//! SQLSTATE (`40P01` for deadlocks, `40001` for serialization failures) or
+//! the MySQL error number (1213 for deadlocks) to tell them apart where that
+//! matters.
+//!
+//! ```rust
+//! # include!("doctest_setup.rs");
+//! use diesel_async::retry::RetryPolicy;
+//! use diesel_async::{AsyncConnection, RunQueryDsl};
+//! use scoped_futures::ScopedFutureExt;
+//! use std::time::Duration;
+//! #
+//! # #[tokio::main(flavor = "current_thread")]
+//! # async fn main() {
+//! #     run_test().await.unwrap();
+//! # }
+//! #
+//! # async fn run_test() -> QueryResult<()> {
+//! #     use schema::users;
+//! #     let conn = &mut establish_connection().await;
+//! let policy = RetryPolicy::default()
+//!     .max_retries(5)
+//!     .max_backoff(Duration::from_millis(500))
+//!     .on_retry(|attempt| eprintln!("Retrying after deadlock: {}", attempt.error));
+//!
+//! let updated = policy
+//!     .run(conn, |conn| {
+//!         async move {
+//!             conn.transaction(|conn| {
+//!                 async move {
+//!                     diesel::update(users::table)
+//!                         .set(users::name.eq("Sean"))
+//!                         .execute(conn)
+//!                         .await
+//!                 }
+//!                 .scope_boxed()
+//!             })
+//!             .await
+//!         }
+//!         .scope_boxed()
+//!     })
+//!     .await?;
+//! assert_eq!(2, updated);
+//! #     Ok(())
+//! # }
+//! ```
+
+pub use crate::backoff::BackoffPolicy;
+use crate::{AsyncConnection, TransactionManager};
+use diesel::result::{DatabaseErrorKind, Error};
+use diesel::QueryResult;
+use scoped_futures::ScopedBoxFuture;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+type RetryHook = Arc<dyn Fn(&RetryAttempt<'_>) + Send + Sync>;
+
+tokio::task_local! {
+    static RETRY: u32;
+}
+
+/// The number of the retry currently executed by [`RetryPolicy::run`],
+/// 0 outside of it or for the initial attempt
+pub(crate) fn current_retry() -> u32 {
+    RETRY.try_with(|retry| *retry).unwrap_or(0)
+}
+
+/// A policy for retrying operations that failed due to a deadlock
+///
+/// The delays between retries are determined by a [`BackoffPolicy`], see
+/// [`RetryPolicy::backoff`]. Its random jitter prevents transactions that
+/// deadlocked with each other from retrying in lockstep.
+///
+/// By default an operation is retried up to 3 times, starting with a backoff
+/// of 10 milliseconds that doubles after each retry, capped at 1 second.
+///
+/// See the [module documentation](self) for an example.
+#[derive(Clone)]
+pub struct RetryPolicy {
+    max_retries: u32,
+    backoff: BackoffPolicy,
+    on_retry: Option<RetryHook>,
+}
+
+/// Information about a retry scheduled by a [`RetryPolicy`]
+///
+/// See [`RetryPolicy::on_retry`]
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct RetryAttempt<'a> {
+    /// The number of the upcoming retry, starting at 1
+    pub attempt: u32,
+    /// The time waited before the operation is executed again
+    pub delay: Duration,
+    /// The error returned by the failed attempt
+    pub error: &'a Error,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            backoff: BackoffPolicy::default()
+                .initial_delay(Duration::from_millis(10))
+                .max_delay(Duration::from_secs(1)),
+            on_retry: None,
+        }
+    }
+}
+
+impl fmt::Debug for RetryPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RetryPolicy")
+            .field("max_retries", &self.max_retries)
+            .field("backoff", &self.backoff)
+            .field("on_retry", &self.on_retry.is_some())
+            .finish()
+    }
+}
+
+impl RetryPolicy {
+    /// Set the maximal number of retries after the initial attempt
+    pub fn max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Set the backoff used for the first retry
+    pub fn initial_backoff(mut self, initial_backoff: Duration) -> Self {
+        self.backoff = self.backoff.initial_delay(initial_backoff);
+        self
+    }
+
+    /// Set the upper limit of the backoff between retries
+    pub fn max_backoff(mut self, max_backoff: Duration) -> Self {
+        self.backoff = self.backoff.max_delay(max_backoff);
+        self
+    }
+
+    /// Set the policy determining the delays between retries
+    ///
+    /// The number of retries is still limited by [`RetryPolicy::max_retries`],
+    /// the maximal number of attempts of `backoff` is not used.
+    pub fn backoff(mut self, backoff: BackoffPolicy) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Register a function that is called before each retry
+    ///
+    /// This can be used to log or count the retries.
+    pub fn on_retry<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&RetryAttempt<'_>) + Send + Sync + 'static,
+    {
+        self.on_retry = Some(Arc::new(hook));
+        self
+    }
+
+    /// Run `operation`, retrying it as long as it fails due to a deadlock
+    /// or a serialization failure, see [`is_transaction_conflict`]
+    ///
+    /// The error of the last attempt is returned once the maximal number
+    /// of retries is exhausted. Other errors are returned immediately.
+    ///
+    /// Retrying a single statement inside of a transaction does not help, as the
+    /// database aborts the whole transaction in case of a deadlock. `operation`
+    /// should therefore contain the whole transaction, as shown in the
+    /// [module documentation](self). The operation is only retried as long as
+    /// the connection is not inside of a transaction after the failed attempt,
+    /// which is the case if `operation` is the outermost transaction.
+    pub async fn run<'a, C, R, F>(&self, conn: &mut C, mut operation: F) -> QueryResult<R>
+    where
+        C: AsyncConnection,
+        F: for<'r> FnMut(&'r mut C) -> ScopedBoxFuture<'a, 'r, QueryResult<R>> + Send + 'a,
+        R: Send + 'a,
+    {
+        let mut attempt = 0;
+        loop {
+            let error = match RETRY.scope(attempt, operation(conn)).await {
+                Err(error)
+                    if attempt < self.max_retries
+                        && is_transaction_conflict(&error)
+                        && !in_transaction(conn) =>
+                {
+                    error
+                }
+                res => return res,
+            };
+            attempt += 1;
+            let delay = self.backoff.delay(attempt);
+            if let Some(hook) = &self.on_retry {
+                hook(&RetryAttempt {
+                    attempt,
+                    delay,
+                    error: &error,
+                });
+            }
+            tokio::time::sleep(delay).await;
+        }
+    }
+}
+
+fn in_transaction<C: AsyncConnection>(conn: &mut C) -> bool {
+    !matches!(
+        C::TransactionManager::transaction_manager_status_mut(conn).transaction_depth(),
+        Ok(None)
+    )
+}
+
+/// Check whether the given error was caused by the database aborting a
+/// transaction due to a conflict with a concurrent transaction
+///
+/// This is the case for deadlocks (SQLSTATE `40P01` for PostgreSQL, error
+/// 1213 for MySQL) and for serialization failures (SQLSTATE `40001`), which
+/// are both reported as [`DatabaseErrorKind::SerializationFailure`], as the
+/// database aborts the whole transaction for them. These are the errors
+/// retried by [`RetryPolicy::run`].
+pub fn is_transaction_conflict(error: &Error) -> bool {
+    matches!(
+        error,
+        Error::DatabaseError(DatabaseErrorKind::SerializationFailure, _)
+    )
+}
@@ -144,8 +144,10 @@ pub mod methods {
 pub mod return_futures {
     use super::methods::LoadQuery;
     use diesel::QueryResult;
-    use futures_util::{future, stream};
+    use futures_util::{future, stream, Future, Stream};
+    use std::marker::PhantomData;
     use std::pin::Pin;
+    use std::task::{ready, Context, Poll};
 
     /// The future returned by [`RunQueryDsl::load`](super::RunQueryDsl::load)
     /// and [`RunQueryDsl::get_results`](super::RunQueryDsl::get_results)
@@ -173,6 +175,99 @@ pub mod return_futures {
             fn((Option<QueryResult<U>>, Pin<Box<Q::Stream<'conn>>>)) -> QueryResult<U>,
         >,
     >;
+
+    /// The future returned by [`RunQueryDsl::get_results_chunked`](super::RunQueryDsl::get_results_chunked)
+    ///
+    /// This is essentially `impl Future<Output = QueryResult<impl Stream<Item = QueryResult<Vec<U>>>>>`
+    pub type GetResultsChunked<'conn, 'query, Q: LoadQuery<'query, Conn, U>, Conn, U> =
+        LoadChunked<Q::LoadFuture<'conn>, U>;
+
+    /// A future resolving to a [`Chunks`] stream once the query was executed
+    ///
+    /// See [`RunQueryDsl::get_results_chunked`](super::RunQueryDsl::get_results_chunked)
+    #[must_use = "futures do nothing unless you `.await` or poll them"]
+    pub struct LoadChunked<F, U> {
+        future: Pin<Box<F>>,
+        chunk_size: usize,
+        _item: PhantomData<fn() -> U>,
+    }
+
+    impl<F, U> LoadChunked<F, U> {
+        pub(super) fn new(future: F, chunk_size: usize) -> Self {
+            assert!(chunk_size > 0, "The chunk size must be greater than zero");
+            Self {
+                future: Box::pin(future),
+                chunk_size,
+                _item: PhantomData,
+            }
+        }
+    }
+
+    impl<F, S, U> Future for LoadChunked<F, U>
+    where
+        F: Future<Output = QueryResult<S>>,
+        S: Stream<Item = QueryResult<U>>,
+    {
+        type Output = QueryResult<Chunks<S, U>>;
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+            let chunk_size = self.chunk_size;
+            let stream = ready!(self.future.as_mut().poll(cx))?;
+            Poll::Ready(Ok(Chunks {
+                stream: Box::pin(stream),
+                chunk_size,
+                buffer: Vec::with_capacity(chunk_size),
+                done: false,
+            }))
+        }
+    }
+
+    /// A stream yielding the results of a query in `Vec`s of up to
+    /// a fixed number of elements
+    ///
+    /// See [`RunQueryDsl::get_results_chunked`](super::RunQueryDsl::get_results_chunked)
+    #[must_use = "streams do nothing unless polled"]
+    pub struct Chunks<S, U> {
+        stream: Pin<Box<S>>,
+        chunk_size: usize,
+        buffer: Vec<U>,
+        done: bool,
+    }
+
+    // the stream is boxed and the buffered values are never pinned
+    impl<S, U> Unpin for Chunks<S, U> {}
+
+    impl<S, U> Stream for Chunks<S, U>
+    where
+        S: Stream<Item = QueryResult<U>>,
+    {
+        type Item = QueryResult<Vec<U>>;
+
+        fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+            let this = self.get_mut();
+            while !this.done {
+                match ready!(this.stream.as_mut().poll_next(cx)) {
+                    Some(Ok(item)) => {
+                        this.buffer.push(item);
+                        if this.buffer.len() == this.chunk_size {
+                            let chunk = std::mem::replace(
+                                &mut this.buffer,
+                                Vec::with_capacity(this.chunk_size),
+                            );
+                            return Poll::Ready(Some(Ok(chunk)));
+                        }
+                    }
+                    Some(Err(e)) => return Poll::Ready(Some(Err(e))),
+                    None => this.done = true,
+                }
+            }
+            if this.buffer.is_empty() {
+                Poll::Ready(None)
+            } else {
+                Poll::Ready(Some(Ok(std::mem::take(&mut this.buffer))))
+            }
+        }
+    }
 }
 
 /// Methods used to execute queries.
@@ -472,6 +567,47 @@ pub trait RunQueryDsl<Conn>: Sized {
     /// #     Ok(())
     /// # }
     /// ```
+    ///
+    /// ## Streaming hand-written SQL
+    ///
+    /// Results of [`diesel::sql_query`] are deserialized via `QueryableByName`
+    /// and can be streamed as well.
+    ///
+    /// ```rust
+    /// # include!("../doctest_setup.rs");
+    /// #
+    /// use diesel::sql_types::{Integer, Text};
+    /// use diesel_async::RunQueryDsl;
+    ///
+    /// #[derive(QueryableByName, PartialEq, Debug)]
+    /// struct User {
+    ///     #[diesel(sql_type = Integer)]
+    ///     id: i32,
+    ///     #[diesel(sql_type = Text)]
+    ///     name: String,
+    /// }
+    ///
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// #     run_test().await;
+    /// # }
+    /// #
+    /// # async fn run_test() -> QueryResult<()> {
+    /// #     use futures_util::stream::TryStreamExt;
+    /// #     let connection = &mut establish_connection().await;
+    /// let data = diesel::sql_query("SELECT id, name FROM users ORDER BY id")
+    ///     .load_stream::<User>(connection)
+    ///     .await?
+    ///     .try_collect::<Vec<_>>()
+    ///     .await?;
+    /// let expected_data = vec![
+    ///     User { id: 1, name: String::from("Sean") },
+    ///     User { id: 2, name: String::from("Tess") },
+    /// ];
+    /// assert_eq!(expected_data, data);
+    /// #     Ok(())
+    /// # }
+    /// ```
     fn load_stream<'conn, 'query, U>(self, conn: &'conn mut Conn) -> Self::LoadFuture<'conn>
     where
         Conn: AsyncConnection,
@@ -481,6 +617,64 @@ pub trait RunQueryDsl<Conn>: Sized {
         self.internal_load(conn)
     }
 
+    /// Executes the given query, returning a [`Stream`] of the returned
+    /// values in `Vec`s of `chunk_size` elements
+    ///
+    /// This wraps [`load_stream`](RunQueryDsl::load_stream) for consumers
+    /// processing the results in batches, for example to insert them somewhere
+    /// else a thousand rows at a time. Only the last chunk may contain less than
+    /// `chunk_size` elements. An error returned while loading is yielded as
+    /// soon as it occurs, discarding the elements of the incomplete chunk.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `chunk_size` is zero.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # include!("../doctest_setup.rs");
+    /// #
+    /// use diesel_async::RunQueryDsl;
+    ///
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// #     run_test().await.unwrap();
+    /// # }
+    /// #
+    /// # async fn run_test() -> QueryResult<()> {
+    /// #     use schema::users::dsl::*;
+    /// #     use futures_util::stream::TryStreamExt;
+    /// #     let connection = &mut establish_connection().await;
+    /// diesel::insert_into(users)
+    ///     .values(name.eq("Jim"))
+    ///     .execute(connection)
+    ///     .await?;
+    ///
+    /// let chunks = users
+    ///     .select(name)
+    ///     .order(id)
+    ///     .get_results_chunked::<String>(connection, 2)
+    ///     .await?
+    ///     .try_collect::<Vec<_>>()
+    ///     .await?;
+    /// assert_eq!(vec![vec!["Sean", "Tess"], vec!["Jim"]], chunks);
+    /// #     Ok(())
+    /// # }
+    /// ```
+    fn get_results_chunked<'conn, 'query, U>(
+        self,
+        conn: &'conn mut Conn,
+        chunk_size: usize,
+    ) -> return_futures::GetResultsChunked<'conn, 'query, Self, Conn, U>
+    where
+        U: Send + 'conn,
+        Conn: AsyncConnection,
+        Self: methods::LoadQuery<'query, Conn, U> + 'query,
+    {
+        return_futures::LoadChunked::new(self.load_stream(conn), chunk_size)
+    }
+
     /// Runs the command, and returns the affected row.
     ///
     /// `Err(NotFound)` will be returned if the query affected 0 rows. You can
@@ -9,6 +9,10 @@ use futures_util::{future, FutureExt};
 #[derive(Default)]
 pub struct StmtCache<DB: Backend, S> {
     cache: HashMap<StatementCacheKey<DB>, S>,
+    // `None` means unbounded
+    capacity: Option<usize>,
+    // only cache statements of queries with a static query id
+    query_id_only: bool,
 }
 
 type PrepareFuture<'a, F, S> = future::Either<
@@ -28,11 +32,87 @@ pub trait PrepareCallback<S, M>: Sized {
 
 impl<S, DB: Backend> StmtCache<DB, S> {
     pub fn new() -> Self {
+        Self::with_capacity(None)
+    }
+
+    /// Once the cache holds `capacity` statements, further statements are
+    /// prepared as if they were not safe to cache
+    pub fn with_capacity(capacity: Option<usize>) -> Self {
         Self {
             cache: HashMap::new(),
+            capacity,
+            query_id_only: false,
         }
     }
 
+    /// Change the capacity of the cache and whether only statements of
+    /// queries with a static query id are cached
+    ///
+    /// Statements already in the cache are kept.
+    #[cfg_attr(
+        not(any(
+            feature = "bb8",
+            feature = "deadpool",
+            feature = "mobc",
+            feature = "r2d2"
+        )),
+        allow(dead_code)
+    )]
+    pub fn configure(&mut self, capacity: Option<usize>, query_id_only: bool) {
+        self.capacity = capacity;
+        self.query_id_only = query_id_only;
+    }
+
+    /// Whether a statement for the given key would be cached
+    pub fn accepts(&self, cache_key: &StatementCacheKey<DB>) -> bool
+    where
+        StatementCacheKey<DB>: Hash + Eq,
+    {
+        if self.cache.contains_key(cache_key) {
+            return true;
+        }
+        let is_full = self
+            .capacity
+            .is_some_and(|capacity| self.cache.len() >= capacity);
+        !is_full && (!self.query_id_only || matches!(cache_key, StatementCacheKey::Type(_)))
+    }
+
+    /// Remove all statements, keeping the capacity
+    #[cfg_attr(
+        not(all(
+            feature = "mysql",
+            any(
+                feature = "bb8",
+                feature = "deadpool",
+                feature = "mobc",
+                feature = "r2d2"
+            )
+        )),
+        allow(dead_code)
+    )]
+    pub fn clear(&mut self) {
+        self.cache.clear();
+    }
+
+    // only used by connections sharing the cache between queries
+    #[cfg_attr(not(feature = "postgres"), allow(dead_code))]
+    pub fn get(&self, cache_key: &StatementCacheKey<DB>) -> Option<&S>
+    where
+        StatementCacheKey<DB>: Hash + Eq,
+    {
+        self.cache.get(cache_key)
+    }
+
+    /// Statements already in the cache are kept
+    #[cfg_attr(not(feature = "postgres"), allow(dead_code))]
+    pub fn insert(&mut self, cache_key: StatementCacheKey<DB>, statement: S)
+    where
+        StatementCacheKey<DB>: Hash + Eq,
+    {
+        self.cache.entry(cache_key).or_insert(statement);
+    }
+
+    #[cfg_attr(not(feature = "mysql"), allow(dead_code))]
     pub fn cached_prepared_statement<'a, F>(
         &'a mut self,
         cache_key: StatementCacheKey<DB>,
@@ -50,7 +130,7 @@ impl<S, DB: Backend> StmtCache<DB, S> {
     {
         use std::collections::hash_map::Entry::{Occupied, Vacant};
 
-        if !is_query_safe_to_cache {
+        if !is_query_safe_to_cache || !self.accepts(&cache_key) {
             let metadata = metadata.to_vec();
             let f = async move {
                 let stmt = prepare_fn
@@ -0,0 +1,209 @@
+//! Start databases in containers for integration tests
+//!
+//! [`TestDatabase`] starts a PostgreSQL or MySQL container via
+//! [`testcontainers`](::testcontainers), waits until the database accepts
+//! connections and builds a [`deadpool`](crate::pooled_connection::deadpool)
+//! pool for it. The container is removed once the [`TestDatabase`] is
+//! dropped. This requires a running Docker daemon.
+//!
+//! ```rust,no_run
+//! # include!("doctest_setup.rs");
+//! use diesel_async::test_containers::TestDatabase;
+//! use diesel_async::RunQueryDsl;
+//! #
+//! # #[tokio::main(flavor = "current_thread")]
+//! # async fn main() {
+//! #     run_test().await.unwrap();
+//! # }
+//! #
+//! # async fn run_test() -> Result<(), Box<dyn std::error::Error>> {
+//! # #[cfg(feature = "postgres")]
+//! # {
+//! let db = TestDatabase::postgres().await?;
+//! let pool = db.pool()?;
+//! let mut conn = pool.get().await?;
+//!
+//! let one = diesel::select(1.into_sql::<diesel::sql_types::Integer>())
+//!     .get_result::<i32>(&mut conn)
+//!     .await?;
+//! assert_eq!(1, one);
+//! # }
+//! #     Ok(())
+//! # }
+//! ```
+//!
+//! The `testcontainers` feature requires Rust 1.88 or newer, as required by
+//! the `testcontainers` crate, while the rest of this crate supports Rust
+//! 1.78.
+
+use crate::pooled_connection::deadpool::{BuildError, Pool};
+use crate::pooled_connection::{AsyncDieselConnectionManager, PoolableConnection};
+use crate::AsyncConnection;
+use ::testcontainers::core::IntoContainerPort;
+use ::testcontainers::runners::AsyncRunner;
+use ::testcontainers::{ContainerAsync, ContainerRequest, GenericImage, ImageExt};
+use diesel::query_builder::QueryFragment;
+use diesel::ConnectionResult;
+use std::fmt;
+use std::marker::PhantomData;
+use std::time::{Duration, Instant};
+
+/// The time to wait for a started database to accept connections
+const READINESS_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// A database running in a container
+///
+/// See the [module documentation](self) for an example.
+pub struct TestDatabase<C> {
+    // stops and removes the container once dropped
+    container: ContainerAsync<GenericImage>,
+    database_url: String,
+    connection: PhantomData<fn() -> C>,
+}
+
+impl<C> fmt::Debug for TestDatabase<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TestDatabase")
+            .field("container", &self.container.id())
+            .field("database_url", &self.database_url)
+            .finish()
+    }
+}
+
+#[cfg(feature = "postgres")]
+impl TestDatabase<crate::AsyncPgConnection> {
+    /// Start a PostgreSQL container
+    ///
+    /// Uses the `postgres:16-alpine` image. The database accepts
+    /// connections of the `postgres` user without a password.
+    pub async fn postgres() -> Result<Self, TestDatabaseError> {
+        let image = GenericImage::new("postgres", "16-alpine")
+            .with_exposed_port(5432.tcp())
+            .with_env_var("POSTGRES_HOST_AUTH_METHOD", "trust");
+        Self::start(image, 5432, |host, port| {
+            format!("postgres://postgres@{host}:{port}/postgres")
+        })
+        .await
+    }
+}
+
+#[cfg(feature = "mysql")]
+impl TestDatabase<crate::AsyncMysqlConnection> {
+    /// Start a MySQL container
+    ///
+    /// Uses the `mysql:8.0` image. The database `test` accepts
+    /// connections of the `root` user without a password.
+    pub async fn mysql() -> Result<Self, TestDatabaseError> {
+        let image = GenericImage::new("mysql", "8.0")
+            .with_exposed_port(3306.tcp())
+            .with_env_var("MYSQL_ALLOW_EMPTY_PASSWORD", "yes")
+            .with_env_var("MYSQL_DATABASE", "test");
+        Self::start(image, 3306, |host, port| {
+            format!("mysql://root@{host}:{port}/test")
+        })
+        .await
+    }
+}
+
+impl<C> TestDatabase<C>
+where
+    C: AsyncConnection,
+{
+    async fn start(
+        image: ContainerRequest<GenericImage>,
+        internal_port: u16,
+        database_url: impl FnOnce(&str, u16) -> String,
+    ) -> Result<Self, TestDatabaseError> {
+        let container = image.start().await?;
+        let host = container.get_host().await?.to_string();
+        let port = container.get_host_port_ipv4(internal_port).await?;
+        let database = Self {
+            container,
+            database_url: database_url(&host, port),
+            connection: PhantomData,
+        };
+        database.wait_until_ready().await?;
+        Ok(database)
+    }
+
+    // The database may restart during its initialization,
+    // so the log output is no reliable sign of readiness
+    async fn wait_until_ready(&self) -> ConnectionResult<()> {
+        let start = Instant::now();
+        loop {
+            match self.connection().await {
+                Ok(_) => return Ok(()),
+                Err(e) if start.elapsed() > READINESS_TIMEOUT => return Err(e),
+                Err(_) => tokio::time::sleep(Duration::from_millis(250)).await,
+            }
+        }
+    }
+
+    /// The URL to connect to the database
+    pub fn database_url(&self) -> &str {
+        &self.database_url
+    }
+
+    /// Establish a new connection to the database
+    pub async fn connection(&self) -> ConnectionResult<C> {
+        C::establish(&self.database_url).await
+    }
+
+    /// Build a connection pool for the database
+    ///
+    /// The pool must not be used after dropping this [`TestDatabase`].
+    pub fn pool(&self) -> Result<Pool<C>, TestDatabaseError>
+    where
+        C: PoolableConnection + Send + 'static,
+        diesel::dsl::select<diesel::dsl::AsExprOf<i32, diesel::sql_types::Integer>>:
+            crate::methods::ExecuteDsl<C>,
+        diesel::query_builder::SqlQuery: QueryFragment<C::Backend>,
+    {
+        let manager = AsyncDieselConnectionManager::new(self.database_url.clone());
+        Ok(Pool::builder(manager).build()?)
+    }
+}
+
+/// The error returned when starting a [`TestDatabase`] fails
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum TestDatabaseError {
+    /// The container could not be started
+    Container(::testcontainers::TestcontainersError),
+    /// The database did not accept connections in time
+    Connection(diesel::result::ConnectionError),
+    /// The connection pool could not be built
+    Pool(BuildError),
+}
+
+impl fmt::Display for TestDatabaseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            TestDatabaseError::Container(ref e) => write!(f, "Failed to start the container: {e}"),
+            TestDatabaseError::Connection(ref e) => {
+                write!(f, "Failed to connect to the database: {e}")
+            }
+            TestDatabaseError::Pool(ref e) => write!(f, "Failed to build the pool: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for TestDatabaseError {}
+
+impl From<::testcontainers::TestcontainersError> for TestDatabaseError {
+    fn from(e: ::testcontainers::TestcontainersError) -> Self {
+        TestDatabaseError::Container(e)
+    }
+}
+
+impl From<diesel::result::ConnectionError> for TestDatabaseError {
+    fn from(e: diesel::result::ConnectionError) -> Self {
+        TestDatabaseError::Connection(e)
+    }
+}
+
+impl From<BuildError> for TestDatabaseError {
+    fn from(e: BuildError) -> Self {
+        TestDatabaseError::Pool(e)
+    }
+}
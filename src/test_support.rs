@@ -0,0 +1,35 @@
+//! Runtime support for the [`test`](crate::test) attribute
+//!
+//! This module is not part of the public API.
+
+use crate::pooled_connection::deadpool::{Object, Pool};
+use crate::pooled_connection::{AsyncDieselConnectionManager, PoolableConnection};
+use diesel::query_builder::QueryFragment;
+
+pub fn runtime() -> tokio::runtime::Runtime {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .expect("Failed to build the test runtime")
+}
+
+// Each test runs on its own runtime and the connections are bound to the
+// runtime they were established on, so the pool cannot be shared between tests
+pub async fn test_connection<C>(env: &str) -> Object<C>
+where
+    C: PoolableConnection + Send + 'static,
+    diesel::dsl::select<diesel::dsl::AsExprOf<i32, diesel::sql_types::Integer>>:
+        crate::methods::ExecuteDsl<C>,
+    diesel::query_builder::SqlQuery: QueryFragment<C::Backend>,
+{
+    let database_url = std::env::var(env)
+        .unwrap_or_else(|_| panic!("The `{env}` environment variable must be set"));
+    let manager = AsyncDieselConnectionManager::new(database_url);
+    let pool = Pool::builder(manager)
+        .max_size(1)
+        .build()
+        .expect("Failed to build the test pool");
+    pool.get()
+        .await
+        .unwrap_or_else(|e| panic!("Failed to check out a test connection: {e}"))
+}
@@ -0,0 +1,112 @@
+use crate::AsyncConnection;
+use std::ops::{Deref, DerefMut};
+
+/// A connection with an open transaction
+///
+/// This type can only be obtained inside of
+/// [`AsyncConnection::typed_transaction`]. Functions that must not run in
+/// auto-commit mode can accept a `TransactionConnection` (or a type
+/// implementing [`InTransaction`]) instead of the plain connection, so that
+/// calling them outside of a transaction fails to compile.
+///
+/// The wrapper dereferences to the underlying connection. Queries are
+/// executed by passing `&mut *conn` to the methods of
+/// [`RunQueryDsl`](crate::RunQueryDsl).
+///
+/// # Example
+///
+/// ```rust
+/// # include!("doctest_setup.rs");
+/// use diesel::result::Error;
+/// use diesel_async::{AsyncConnection, RunQueryDsl, TransactionConnection};
+/// use scoped_futures::ScopedFutureExt;
+/// #
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// #     run_test().await.unwrap();
+/// # }
+///
+/// // can only be called within a transaction
+/// async fn rename_user(
+///     conn: &mut TransactionConnection<'_, DbConnection>,
+///     user_id: i32,
+///     new_name: &str,
+/// ) -> QueryResult<usize> {
+/// #   use schema::users::dsl::*;
+///     diesel::update(users.find(user_id))
+///         .set(name.eq(new_name))
+///         .execute(&mut **conn)
+///         .await
+/// }
+///
+/// # async fn run_test() -> QueryResult<()> {
+/// #     use schema::users::dsl::*;
+/// #     let conn = &mut establish_connection().await;
+/// conn.typed_transaction::<_, Error, _>(|mut conn| {
+///     async move {
+///         rename_user(&mut conn, 1, "Ruby").await?;
+///         Ok(())
+///     }
+///     .scope_boxed()
+/// })
+/// .await?;
+///
+/// let all_names = users.select(name).order(id).load::<String>(conn).await?;
+/// assert_eq!(vec!["Ruby", "Tess"], all_names);
+/// #     Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct TransactionConnection<'a, C> {
+    conn: &'a mut C,
+}
+
+impl<'a, C> TransactionConnection<'a, C> {
+    pub(crate) fn new(conn: &'a mut C) -> Self {
+        Self { conn }
+    }
+}
+
+impl<C> Deref for TransactionConnection<'_, C> {
+    type Target = C;
+
+    fn deref(&self) -> &Self::Target {
+        self.conn
+    }
+}
+
+impl<C> DerefMut for TransactionConnection<'_, C> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.conn
+    }
+}
+
+/// A connection that is known to be inside of a transaction
+///
+/// This trait is sealed and only implemented for [`TransactionConnection`],
+/// so generic functions can require a transaction at compile time via
+/// `conn: &mut impl InTransaction<Connection = C>`.
+pub trait InTransaction: Send + private::Sealed {
+    /// The underlying connection type
+    type Connection: AsyncConnection;
+
+    /// Returns the underlying connection to execute queries with
+    fn connection(&mut self) -> &mut Self::Connection;
+}
+
+impl<C> InTransaction for TransactionConnection<'_, C>
+where
+    C: AsyncConnection,
+{
+    type Connection = C;
+
+    fn connection(&mut self) -> &mut Self::Connection {
+        self.conn
+    }
+}
+
+mod private {
+    pub trait Sealed {}
+
+    impl<C> Sealed for super::TransactionConnection<'_, C> {}
+}
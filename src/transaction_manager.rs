@@ -7,14 +7,25 @@ use diesel::QueryResult;
 use scoped_futures::ScopedBoxFuture;
 use std::borrow::Cow;
 use std::num::NonZeroU32;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
+#[cfg(any(feature = "postgres", feature = "mysql"))]
+use crate::retry::current_retry;
 use crate::AsyncConnection;
 // TODO: refactor this to share more code with diesel
 
+#[cfg(not(any(feature = "postgres", feature = "mysql")))]
+fn current_retry() -> u32 {
+    0
+}
+
 /// Manages the internal transaction state for a connection.
 ///
 /// You will not need to interact with this trait, unless you are writing an
-/// implementation of [`AsyncConnection`].
+/// implementation of [`AsyncConnection`] or a custom transaction manager
+/// used via [`WithTransactionManager`](crate::WithTransactionManager).
 #[async_trait::async_trait]
 pub trait TransactionManager<Conn: AsyncConnection>: Send {
     /// Data stored as part of the connection implementation
@@ -60,22 +71,7 @@ pub trait TransactionManager<Conn: AsyncConnection>: Send {
         E: From<Error> + Send,
         R: Send,
     {
-        Self::begin_transaction(conn).await?;
-        match callback(&mut *conn).await {
-            Ok(value) => {
-                Self::commit_transaction(conn).await?;
-                Ok(value)
-            }
-            Err(user_error) => match Self::rollback_transaction(conn).await {
-                Ok(()) => Err(user_error),
-                Err(Error::BrokenTransactionManager) => {
-                    // In this case we are probably more interested by the
-                    // original error, which likely caused this
-                    Err(user_error)
-                }
-                Err(rollback_error) => Err(rollback_error.into()),
-            },
-        }
+        run_transaction::<Self, _, _, _, _>(conn, callback).await
     }
 
     /// This methods checks if the connection manager is considered to be broken
@@ -87,32 +83,168 @@ pub trait TransactionManager<Conn: AsyncConnection>: Send {
     /// in an error state.
     #[doc(hidden)]
     fn is_broken_transaction_manager(conn: &mut Conn) -> bool {
-        match Self::transaction_manager_status_mut(conn).transaction_state() {
-            // all transactions are closed
-            // so we don't consider this connection broken
-            Ok(ValidTransactionManagerStatus {
-                in_transaction: None,
-                ..
-            }) => false,
-            // The transaction manager is in an error state
-            // Therefore we consider this connection broken
-            Err(_) => true,
-            // The transaction manager contains a open transaction
-            // we do consider this connection broken
-            // if that transaction was not opened by `begin_test_transaction`
-            Ok(ValidTransactionManagerStatus {
-                in_transaction: Some(s),
-                ..
-            }) => !s.test_transaction,
+        is_broken_status(Self::transaction_manager_status_mut(conn))
+    }
+}
+
+async fn run_transaction<'a, TM, Conn, F, R, E>(conn: &mut Conn, callback: F) -> Result<R, E>
+where
+    TM: TransactionManager<Conn> + ?Sized,
+    Conn: AsyncConnection,
+    F: for<'r> FnOnce(&'r mut Conn) -> ScopedBoxFuture<'a, 'r, Result<R, E>> + Send + 'a,
+    E: From<Error> + Send,
+    R: Send,
+{
+    TM::begin_transaction(conn).await?;
+    match callback(&mut *conn).await {
+        Ok(value) => {
+            TM::commit_transaction(conn).await?;
+            Ok(value)
         }
+        Err(user_error) => match TM::rollback_transaction(conn).await {
+            Ok(()) => Err(user_error),
+            Err(Error::BrokenTransactionManager) => {
+                // In this case we are probably more interested by the
+                // original error, which likely caused this
+                Err(user_error)
+            }
+            Err(rollback_error) => Err(rollback_error.into()),
+        },
     }
 }
 
+fn is_broken_status(status: &mut TransactionManagerStatus) -> bool {
+    match status.transaction_state() {
+        // all transactions are closed
+        // so we don't consider this connection broken
+        Ok(ValidTransactionManagerStatus {
+            in_transaction: None,
+            ..
+        }) => false,
+        // The transaction manager is in an error state
+        // Therefore we consider this connection broken
+        Err(_) => true,
+        // The transaction manager contains a open transaction
+        // we do consider this connection broken
+        // if that transaction was not opened by `begin_test_transaction`
+        Ok(ValidTransactionManagerStatus {
+            in_transaction: Some(s),
+            ..
+        }) => !s.test_transaction,
+    }
+}
+
+/// Type of the callback passed to [`AnsiTransactionManager::on_event`]
+pub type TransactionEventCallback = dyn Fn(&TransactionEvent<'_>) + Send + Sync;
+
+/// A lifecycle event of a transaction or savepoint managed by an
+/// [`AnsiTransactionManager`]
+///
+/// See [`AnsiTransactionManager::on_event`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct TransactionEvent<'a> {
+    /// What happened to the transaction
+    pub kind: TransactionEventKind,
+    /// The depth of the transaction, 1 for the top level transaction
+    /// and greater values for savepoints
+    pub depth: NonZeroU32,
+    /// The time since the transaction was started, zero for
+    /// [`TransactionEventKind::Begin`]
+    pub duration: Duration,
+    /// The number of the retry the transaction is executed in, if it is executed
+    /// via [`RetryPolicy::run`](crate::retry::RetryPolicy::run), otherwise 0
+    pub retry: u32,
+    /// The error the statement failed with, if any
+    pub error: Option<&'a Error>,
+}
+
+/// The kind of a [`TransactionEvent`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum TransactionEventKind {
+    /// A transaction or savepoint was started
+    Begin,
+    /// A transaction was committed or a savepoint was released
+    Commit,
+    /// A transaction or savepoint was rolled back
+    Rollback,
+}
+
 /// An implementation of `TransactionManager` which can be used for backends
 /// which use ANSI standard syntax for savepoints such as SQLite and PostgreSQL.
-#[derive(Default, Debug)]
+#[derive(Default)]
 pub struct AnsiTransactionManager {
     pub(crate) status: TransactionManagerStatus,
+    // Set if the future driving a top level transaction was dropped
+    // before the transaction was committed or rolled back,
+    // see `AbandonedTransactionGuard`
+    pub(crate) abandoned: Arc<AtomicBool>,
+    // The number of query futures created from the connection that did not
+    // finish yet. Connections pipelining queries count them here, so that no
+    // transaction is started before the queries are executed
+    pub(crate) pending_queries: Arc<AtomicUsize>,
+    on_event: Option<Arc<TransactionEventCallback>>,
+    // When the currently open transaction and savepoints were started,
+    // indexed by their depth - 1
+    started_at: Vec<Instant>,
+}
+
+impl std::fmt::Debug for AnsiTransactionManager {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AnsiTransactionManager")
+            .field("status", &self.status)
+            .field("abandoned", &self.abandoned)
+            .field("pending_queries", &self.pending_queries)
+            .field("on_event", &self.on_event.is_some())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Marks the transaction manager as abandoned if it is dropped
+/// before [`AbandonedTransactionGuard::finish`] is called
+///
+/// This is used to detect futures of top level transactions that were
+/// dropped while the transaction was still open, for example because
+/// they were cancelled by a timeout. The next statement or transaction
+/// executed on the connection then rolls back the leftover transaction first.
+pub(crate) struct AbandonedTransactionGuard(Option<Arc<AtomicBool>>);
+
+impl AbandonedTransactionGuard {
+    pub(crate) fn new(transaction_manager: &AnsiTransactionManager) -> Self {
+        // Only top level transactions are tracked. Dropping the future
+        // of a nested transaction also drops the future of the enclosing one
+        let is_top_level = matches!(transaction_manager.status.transaction_depth(), Ok(None));
+        Self(is_top_level.then(|| transaction_manager.abandoned.clone()))
+    }
+
+    pub(crate) fn finish(mut self) {
+        self.0 = None;
+    }
+}
+
+#[cfg(any(feature = "postgres", feature = "mysql"))]
+impl AnsiTransactionManager {
+    // Takes the abandoned flag before a statement is executed, so that the
+    // statement does not run inside of the leftover transaction. The status is
+    // reset right away, the caller rolls back the transaction before executing
+    // the statement and calls `finish` on the returned guard afterwards. If the
+    // rollback fails or its future is dropped, the flag is set again.
+    pub(crate) fn take_abandoned(&mut self) -> Option<AbandonedTransactionGuard> {
+        if !self.abandoned.swap(false, Ordering::AcqRel) {
+            return None;
+        }
+        self.status = TransactionManagerStatus::default();
+        Some(AbandonedTransactionGuard(Some(self.abandoned.clone())))
+    }
+}
+
+impl Drop for AbandonedTransactionGuard {
+    fn drop(&mut self) {
+        if let Some(abandoned) = self.0.take() {
+            abandoned.store(true, Ordering::Release);
+        }
+    }
 }
 
 // /// Status of the transaction manager
@@ -255,6 +387,77 @@ pub struct AnsiTransactionManager {
 // }
 
 impl AnsiTransactionManager {
+    /// Register a function that is called for each transaction and savepoint
+    /// started, committed or rolled back on the given connection
+    ///
+    /// This allows to log or measure transactions, for example to spot long
+    /// running transactions before they cause table bloat or pile up locks.
+    /// The function replaces previously registered functions and is called
+    /// inline, so it should return quickly. To register it for all connections
+    /// of a pool, use
+    /// [`ManagerConfig::warm_up`](crate::pooled_connection::ManagerConfig::warm_up).
+    ///
+    /// ```rust
+    /// # include!("doctest_setup.rs");
+    /// use diesel_async::{AnsiTransactionManager, AsyncConnection, TransactionEventKind};
+    /// use scoped_futures::ScopedFutureExt;
+    /// use std::time::Duration;
+    /// #
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// #     run_test().await.unwrap();
+    /// # }
+    /// #
+    /// # async fn run_test() -> QueryResult<()> {
+    /// #     let conn = &mut connection_no_transaction().await;
+    /// AnsiTransactionManager::on_event(conn, |event| {
+    ///     if event.kind != TransactionEventKind::Begin && event.duration > Duration::from_secs(5) {
+    ///         eprintln!("Transaction at depth {} took {:?}", event.depth, event.duration);
+    ///     }
+    /// });
+    /// conn.transaction(|_conn| async { QueryResult::Ok(()) }.scope_boxed())
+    ///     .await?;
+    /// #     Ok(())
+    /// # }
+    /// ```
+    pub fn on_event<Conn, F>(conn: &mut Conn, callback: F)
+    where
+        Conn: AsyncConnection<TransactionManager = Self>,
+        F: Fn(&TransactionEvent<'_>) + Send + Sync + 'static,
+    {
+        conn.transaction_state().on_event = Some(Arc::new(callback));
+    }
+
+    /// Report an event for the transaction at the given depth and
+    /// forget the start times of transactions that are no longer open
+    fn emit(&mut self, kind: TransactionEventKind, depth: NonZeroU32, error: Option<&Error>) {
+        let index = depth.get() as usize - 1;
+        if kind == TransactionEventKind::Begin {
+            self.started_at.truncate(index);
+            self.started_at.push(Instant::now());
+        }
+        if let Some(ref on_event) = self.on_event {
+            let duration = self
+                .started_at
+                .get(index)
+                .map_or(Duration::ZERO, Instant::elapsed);
+            on_event(&TransactionEvent {
+                kind,
+                depth,
+                duration,
+                retry: current_retry(),
+                error,
+            });
+        }
+        let open = self
+            .status
+            .transaction_depth()
+            .ok()
+            .flatten()
+            .map_or(0, |depth| depth.get() as usize);
+        self.started_at.truncate(open);
+    }
+
     fn get_transaction_state<Conn>(
         conn: &mut Conn,
     ) -> QueryResult<&mut ValidTransactionManagerStatus>
@@ -264,6 +467,34 @@ impl AnsiTransactionManager {
         conn.transaction_state().status.transaction_state()
     }
 
+    /// Roll back a transaction left open by a dropped transaction future
+    ///
+    /// This resets the transaction manager to a clean state afterwards.
+    /// If the rollback fails the transaction manager is put into an error state,
+    /// so the connection is considered to be broken.
+    pub(crate) async fn rollback_abandoned_transaction<Conn>(conn: &mut Conn) -> QueryResult<()>
+    where
+        Conn: AsyncConnection<TransactionManager = Self>,
+    {
+        if !conn
+            .transaction_state()
+            .abandoned
+            .swap(false, Ordering::AcqRel)
+        {
+            return Ok(());
+        }
+        match conn.batch_execute("ROLLBACK").await {
+            Ok(()) => {
+                conn.transaction_state().status = TransactionManagerStatus::default();
+                Ok(())
+            }
+            Err(e) => {
+                conn.transaction_state().status.set_in_error();
+                Err(e)
+            }
+        }
+    }
+
     /// Begin a transaction with custom SQL
     ///
     /// This is used by connections to implement more complex transaction APIs
@@ -273,42 +504,164 @@ impl AnsiTransactionManager {
     where
         Conn: AsyncConnection<TransactionManager = Self>,
     {
+        Self::rollback_abandoned_transaction(conn).await?;
+        Self::check_pending_queries(conn)?;
         let state = Self::get_transaction_state(conn)?;
         match state.transaction_depth() {
             None => {
-                conn.batch_execute(sql).await?;
-                Self::get_transaction_state(conn)?
-                    .change_transaction_depth(TransactionDepthChange::IncreaseDepth)?;
-                Ok(())
+                let res = conn.batch_execute(sql).await;
+                Self::finish_begin(conn, NonZeroU32::MIN, res)
             }
             Some(_depth) => Err(Error::AlreadyInTransaction),
         }
     }
-}
 
-#[async_trait::async_trait]
-impl<Conn> TransactionManager<Conn> for AnsiTransactionManager
-where
-    Conn: AsyncConnection<TransactionManager = Self>,
-{
-    type TransactionStateData = Self;
-
-    async fn begin_transaction(conn: &mut Conn) -> QueryResult<()> {
-        let transaction_state = Self::get_transaction_state(conn)?;
-        let start_transaction_sql = match transaction_state.transaction_depth() {
-            None => Cow::from("BEGIN"),
-            Some(transaction_depth) => {
-                Cow::from(format!("SAVEPOINT diesel_savepoint_{transaction_depth}"))
+    /// Roll back all open transactions and savepoints to bring the connection
+    /// back into a usable state
+    ///
+    /// This is useful after errors left the connection inside of a transaction
+    /// or put the transaction manager into an error state, for example after
+    /// a failed rollback or a dropped transaction future. If the connection is
+    /// inside of a test transaction started via
+    /// [`AsyncConnection::begin_test_transaction`], only the savepoints inside of
+    /// the test transaction are rolled back, as long as the transaction manager
+    /// is not in an error state.
+    ///
+    /// Returns an error if the connection itself is broken, in which case the
+    /// transaction manager remains in its error state.
+    pub async fn rollback_to_clean_state<Conn>(conn: &mut Conn) -> QueryResult<()>
+    where
+        Conn: AsyncConnection<TransactionManager = Self>,
+    {
+        Self::rollback_abandoned_transaction(conn).await?;
+        loop {
+            match conn.transaction_state().status {
+                TransactionManagerStatus::InError => {
+                    conn.batch_execute("ROLLBACK").await?;
+                    conn.transaction_state().status = TransactionManagerStatus::default();
+                    return Ok(());
+                }
+                TransactionManagerStatus::Valid(ValidTransactionManagerStatus {
+                    in_transaction: None,
+                    ..
+                }) => return Ok(()),
+                TransactionManagerStatus::Valid(ValidTransactionManagerStatus {
+                    in_transaction:
+                        Some(InTransactionStatus {
+                            transaction_depth,
+                            test_transaction: true,
+                            ..
+                        }),
+                    ..
+                }) if transaction_depth.get() == 1 => return Ok(()),
+                TransactionManagerStatus::Valid(_) => {
+                    // Each failed rollback either decrements the transaction depth
+                    // or puts the transaction manager into an error state,
+                    // which is handled by the next iteration
+                    let _ = Self::rollback_transaction(conn).await;
+                }
             }
-        };
-        conn.batch_execute(&start_transaction_sql).await?;
-        Self::get_transaction_state(conn)?
-            .change_transaction_depth(TransactionDepthChange::IncreaseDepth)?;
+        }
+    }
 
-        Ok(())
+    /// Create a savepoint with the given name inside of the current transaction
+    ///
+    /// Together with [`AnsiTransactionManager::rollback_to_savepoint`] and
+    /// [`AnsiTransactionManager::release_savepoint`] this allows to roll back
+    /// parts of a transaction without nesting the work into closures.
+    /// Savepoints created by these functions are not tracked by the transaction
+    /// manager, so they need to be rolled back to or released within the
+    /// transaction or nested transaction they were created in.
+    ///
+    /// Savepoint names need to consist of ASCII letters, digits and
+    /// underscores and may not start with a digit. Returns an error if
+    /// called outside of a transaction.
+    ///
+    /// ```rust
+    /// # include!("doctest_setup.rs");
+    /// use diesel_async::{AnsiTransactionManager, RunQueryDsl};
+    /// #
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// #     run_test().await.unwrap();
+    /// # }
+    /// #
+    /// # async fn run_test() -> QueryResult<()> {
+    /// #     use schema::users;
+    /// #     let conn = &mut establish_connection().await;
+    /// AnsiTransactionManager::create_savepoint(conn, "before_insert").await?;
+    /// diesel::insert_into(users::table)
+    ///     .values(users::name.eq("Ruby"))
+    ///     .execute(conn)
+    ///     .await?;
+    /// AnsiTransactionManager::rollback_to_savepoint(conn, "before_insert").await?;
+    /// AnsiTransactionManager::release_savepoint(conn, "before_insert").await?;
+    ///
+    /// assert_eq!(2, users::table.count().get_result::<i64>(conn).await?);
+    /// #     Ok(())
+    /// # }
+    /// ```
+    pub async fn create_savepoint<Conn>(conn: &mut Conn, name: &str) -> QueryResult<()>
+    where
+        Conn: AsyncConnection<TransactionManager = Self>,
+    {
+        Self::execute_savepoint_sql(conn, "SAVEPOINT", name).await
     }
 
-    async fn rollback_transaction(conn: &mut Conn) -> QueryResult<()> {
+    /// Roll back all changes made since the savepoint with the given name
+    /// was created
+    ///
+    /// The savepoint remains in place, so it can be rolled back to again.
+    /// This also recovers transactions that failed after the savepoint was
+    /// created. See [`AnsiTransactionManager::create_savepoint`] for details.
+    pub async fn rollback_to_savepoint<Conn>(conn: &mut Conn, name: &str) -> QueryResult<()>
+    where
+        Conn: AsyncConnection<TransactionManager = Self>,
+    {
+        Self::execute_savepoint_sql(conn, "ROLLBACK TO SAVEPOINT", name).await
+    }
+
+    /// Release the savepoint with the given name, keeping the changes made
+    /// since it was created
+    ///
+    /// See [`AnsiTransactionManager::create_savepoint`] for details.
+    pub async fn release_savepoint<Conn>(conn: &mut Conn, name: &str) -> QueryResult<()>
+    where
+        Conn: AsyncConnection<TransactionManager = Self>,
+    {
+        Self::execute_savepoint_sql(conn, "RELEASE SAVEPOINT", name).await
+    }
+
+    async fn execute_savepoint_sql<Conn>(
+        conn: &mut Conn,
+        statement: &str,
+        name: &str,
+    ) -> QueryResult<()>
+    where
+        Conn: AsyncConnection<TransactionManager = Self>,
+    {
+        // Names are validated instead of quoted, as the quoting
+        // of identifiers differs between the supported backends
+        let is_valid_name = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !is_valid_name {
+            return Err(Error::QueryBuilderError(
+                format!("Invalid savepoint name `{name}`").into(),
+            ));
+        }
+        if Self::get_transaction_state(conn)?
+            .transaction_depth()
+            .is_none()
+        {
+            return Err(Error::NotInTransaction);
+        }
+        conn.batch_execute(&format!("{statement} {name}")).await
+    }
+
+    async fn execute_rollback<Conn>(conn: &mut Conn) -> QueryResult<()>
+    where
+        Conn: AsyncConnection<TransactionManager = Self>,
+    {
         let transaction_state = Self::get_transaction_state(conn)?;
 
         let (
@@ -346,7 +699,7 @@ where
                 Ok(())
             }
             Err(rollback_error) => {
-                let tm_status = Self::transaction_manager_status_mut(conn);
+                let tm_status = &mut conn.transaction_state().status;
                 match tm_status {
                     TransactionManagerStatus::Valid(ValidTransactionManagerStatus {
                         in_transaction:
@@ -389,6 +742,76 @@ where
         }
     }
 
+    // Queries created before a transaction or savepoint is started would be
+    // executed inside of it once they are polled, so starting it is rejected
+    fn check_pending_queries<Conn>(conn: &mut Conn) -> QueryResult<()>
+    where
+        Conn: AsyncConnection<TransactionManager = Self>,
+    {
+        if conn
+            .transaction_state()
+            .pending_queries
+            .load(Ordering::Acquire)
+            > 0
+        {
+            return Err(Error::QueryBuilderError(
+                "Cannot begin a transaction while queries created from this connection are pending"
+                    .into(),
+            ));
+        }
+        Ok(())
+    }
+
+    fn finish_begin<Conn>(
+        conn: &mut Conn,
+        depth: NonZeroU32,
+        res: QueryResult<()>,
+    ) -> QueryResult<()>
+    where
+        Conn: AsyncConnection<TransactionManager = Self>,
+    {
+        let res = res.and_then(|()| {
+            Self::get_transaction_state(conn)?
+                .change_transaction_depth(TransactionDepthChange::IncreaseDepth)
+        });
+        conn.transaction_state()
+            .emit(TransactionEventKind::Begin, depth, res.as_ref().err());
+        res
+    }
+}
+
+#[async_trait::async_trait]
+impl<Conn> TransactionManager<Conn> for AnsiTransactionManager
+where
+    Conn: AsyncConnection<TransactionManager = Self>,
+{
+    type TransactionStateData = Self;
+
+    async fn begin_transaction(conn: &mut Conn) -> QueryResult<()> {
+        Self::rollback_abandoned_transaction(conn).await?;
+        Self::check_pending_queries(conn)?;
+        let transaction_state = Self::get_transaction_state(conn)?;
+        let (start_transaction_sql, depth) = match transaction_state.transaction_depth() {
+            None => (Cow::from("BEGIN"), NonZeroU32::MIN),
+            Some(transaction_depth) => (
+                Cow::from(format!("SAVEPOINT diesel_savepoint_{transaction_depth}")),
+                transaction_depth.saturating_add(1),
+            ),
+        };
+        let res = conn.batch_execute(&start_transaction_sql).await;
+        Self::finish_begin(conn, depth, res)
+    }
+
+    async fn rollback_transaction(conn: &mut Conn) -> QueryResult<()> {
+        let depth = Self::get_transaction_state(conn)?
+            .transaction_depth()
+            .ok_or(Error::NotInTransaction)?;
+        let res = Self::execute_rollback(conn).await;
+        conn.transaction_state()
+            .emit(TransactionEventKind::Rollback, depth, res.as_ref().err());
+        res
+    }
+
     /// If the transaction fails to commit due to a `SerializationFailure` or a
     /// `ReadOnlyTransaction` a rollback will be attempted. If the rollback succeeds,
     /// the original error will be returned, otherwise the error generated by the rollback
@@ -397,10 +820,10 @@ where
     async fn commit_transaction(conn: &mut Conn) -> QueryResult<()> {
         let transaction_state = Self::get_transaction_state(conn)?;
         let transaction_depth = transaction_state.transaction_depth();
-        let (commit_sql, committing_top_level) = match transaction_depth {
+        let (commit_sql, committing_top_level, depth) = match transaction_depth {
             None => return Err(Error::NotInTransaction),
             Some(transaction_depth) if transaction_depth.get() == 1 => {
-                (Cow::Borrowed("COMMIT"), true)
+                (Cow::Borrowed("COMMIT"), true, transaction_depth)
             }
             Some(transaction_depth) => (
                 Cow::Owned(format!(
@@ -408,23 +831,35 @@ where
                     transaction_depth.get() - 1
                 )),
                 false,
+                transaction_depth,
             ),
         };
         match conn.batch_execute(&commit_sql).await {
             Ok(()) => {
-                match Self::get_transaction_state(conn)?
+                let res = match Self::get_transaction_state(conn)?
                     .change_transaction_depth(TransactionDepthChange::DecreaseDepth)
                 {
-                    Ok(()) => {}
+                    Ok(()) => Ok(()),
                     Err(Error::NotInTransaction) if committing_top_level => {
                         // Transaction exit may have already been detected by connection.
                         // It's fine
+                        Ok(())
                     }
-                    Err(e) => return Err(e),
-                }
-                Ok(())
+                    Err(e) => Err(e),
+                };
+                conn.transaction_state().emit(
+                    TransactionEventKind::Commit,
+                    depth,
+                    res.as_ref().err(),
+                );
+                res
             }
             Err(commit_error) => {
+                conn.transaction_state().emit(
+                    TransactionEventKind::Commit,
+                    depth,
+                    Some(&commit_error),
+                );
                 if let TransactionManagerStatus::Valid(ValidTransactionManagerStatus {
                     in_transaction:
                         Some(InTransactionStatus {
@@ -453,4 +888,26 @@ where
     fn transaction_manager_status_mut(conn: &mut Conn) -> &mut TransactionManagerStatus {
         &mut conn.transaction_state().status
     }
+
+    /// If the future returned by this function is dropped before the transaction
+    /// is committed or rolled back, the open transaction is rolled back as soon
+    /// as the next statement or transaction is executed on this connection.
+    /// Until then the connection is considered to be broken by connection pools.
+    async fn transaction<'a, F, R, E>(conn: &mut Conn, callback: F) -> Result<R, E>
+    where
+        F: for<'r> FnOnce(&'r mut Conn) -> ScopedBoxFuture<'a, 'r, Result<R, E>> + Send + 'a,
+        E: From<Error> + Send,
+        R: Send,
+    {
+        let guard = AbandonedTransactionGuard::new(conn.transaction_state());
+        let res = run_transaction::<Self, _, _, _, _>(conn, callback).await;
+        guard.finish();
+        res
+    }
+
+    fn is_broken_transaction_manager(conn: &mut Conn) -> bool {
+        let transaction_manager = conn.transaction_state();
+        transaction_manager.abandoned.load(Ordering::Acquire)
+            || is_broken_status(&mut transaction_manager.status)
+    }
 }
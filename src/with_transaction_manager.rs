@@ -0,0 +1,230 @@
+use crate::{AsyncConnection, SimpleAsyncConnection, TransactionManager};
+use diesel::query_builder::{AsQuery, QueryFragment, QueryId};
+use diesel::{ConnectionResult, QueryResult};
+use std::marker::PhantomData;
+
+/// A connection using a custom [`TransactionManager`]
+///
+/// The transaction manager of the connection types provided by this crate is
+/// fixed to [`AnsiTransactionManager`](crate::AnsiTransactionManager). This
+/// wrapper replaces it with the given transaction manager `TM`, for example
+/// to inject retry logic into [`AsyncConnection::transaction`], to emit
+/// events or to speak the retry protocol of a database like CockroachDB.
+/// All other operations are forwarded to the wrapped connection.
+///
+/// The custom transaction manager shares the transaction state of the
+/// wrapped connection, so it usually delegates to the transaction manager
+/// of the wrapped connection via [`WithTransactionManager::inner_mut`].
+///
+/// # Example
+///
+/// ```rust
+/// # include!("doctest_setup.rs");
+/// use diesel::connection::TransactionManagerStatus;
+/// use diesel_async::{
+///     AsyncConnection, SimpleAsyncConnection, TransactionManager, WithTransactionManager,
+/// };
+/// use scoped_futures::ScopedFutureExt;
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+///
+/// type Inner = <DbConnection as AsyncConnection>::TransactionManager;
+/// type CountingConnection = WithTransactionManager<DbConnection, CountingTransactionManager>;
+///
+/// static COMMITS: AtomicUsize = AtomicUsize::new(0);
+///
+/// // counts the committed transactions and savepoints
+/// struct CountingTransactionManager;
+///
+/// #[async_trait::async_trait]
+/// impl TransactionManager<CountingConnection> for CountingTransactionManager {
+///     type TransactionStateData = <Inner as TransactionManager<DbConnection>>::TransactionStateData;
+///
+///     async fn begin_transaction(conn: &mut CountingConnection) -> QueryResult<()> {
+///         Inner::begin_transaction(conn.inner_mut()).await
+///     }
+///
+///     async fn rollback_transaction(conn: &mut CountingConnection) -> QueryResult<()> {
+///         Inner::rollback_transaction(conn.inner_mut()).await
+///     }
+///
+///     async fn commit_transaction(conn: &mut CountingConnection) -> QueryResult<()> {
+///         Inner::commit_transaction(conn.inner_mut()).await?;
+///         COMMITS.fetch_add(1, Ordering::Relaxed);
+///         Ok(())
+///     }
+///
+///     fn transaction_manager_status_mut(
+///         conn: &mut CountingConnection,
+///     ) -> &mut TransactionManagerStatus {
+///         Inner::transaction_manager_status_mut(conn.inner_mut())
+///     }
+/// }
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// #     run_test().await.unwrap();
+/// # }
+/// #
+/// # async fn run_test() -> QueryResult<()> {
+/// let mut conn = CountingConnection::new(establish_connection().await);
+/// conn.transaction::<_, diesel::result::Error, _>(|conn| {
+///     async move {
+///         conn.batch_execute("INSERT INTO users (name) VALUES ('Ruby')")
+///             .await
+///     }
+///     .scope_boxed()
+/// })
+/// .await?;
+/// assert_eq!(1, COMMITS.load(Ordering::Relaxed));
+/// #     Ok(())
+/// # }
+/// ```
+pub struct WithTransactionManager<C, TM> {
+    conn: C,
+    transaction_manager: PhantomData<fn() -> TM>,
+}
+
+impl<C, TM> std::fmt::Debug for WithTransactionManager<C, TM>
+where
+    C: std::fmt::Debug,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WithTransactionManager")
+            .field("conn", &self.conn)
+            .field("transaction_manager", &std::any::type_name::<TM>())
+            .finish()
+    }
+}
+
+impl<C, TM> WithTransactionManager<C, TM> {
+    /// Wrap the given connection
+    pub fn new(conn: C) -> Self {
+        Self {
+            conn,
+            transaction_manager: PhantomData,
+        }
+    }
+
+    /// Returns a reference to the wrapped connection
+    pub fn inner(&self) -> &C {
+        &self.conn
+    }
+
+    /// Returns a mutable reference to the wrapped connection
+    ///
+    /// Transactions started on the wrapped connection bypass the custom
+    /// transaction manager.
+    pub fn inner_mut(&mut self) -> &mut C {
+        &mut self.conn
+    }
+
+    /// Unwrap the wrapped connection
+    pub fn into_inner(self) -> C {
+        self.conn
+    }
+}
+
+#[async_trait::async_trait]
+impl<C, TM> SimpleAsyncConnection for WithTransactionManager<C, TM>
+where
+    C: SimpleAsyncConnection + Send,
+{
+    async fn batch_execute(&mut self, query: &str) -> QueryResult<()> {
+        self.conn.batch_execute(query).await
+    }
+}
+
+#[async_trait::async_trait]
+impl<C, TM> AsyncConnection for WithTransactionManager<C, TM>
+where
+    C: AsyncConnection,
+    TM: TransactionManager<
+        Self,
+        TransactionStateData = <C::TransactionManager as TransactionManager<C>>::TransactionStateData,
+    >,
+{
+    type ExecuteFuture<'conn, 'query> = C::ExecuteFuture<'conn, 'query>;
+    type LoadFuture<'conn, 'query> = C::LoadFuture<'conn, 'query>;
+    type Stream<'conn, 'query> = C::Stream<'conn, 'query>;
+    type Row<'conn, 'query> = C::Row<'conn, 'query>;
+
+    type Backend = C::Backend;
+
+    type TransactionManager = TM;
+
+    async fn establish(database_url: &str) -> ConnectionResult<Self> {
+        C::establish(database_url).await.map(Self::new)
+    }
+
+    async fn ping_connection(&mut self) -> QueryResult<()> {
+        self.conn.ping_connection().await
+    }
+
+    fn load<'conn, 'query, T>(&'conn mut self, source: T) -> Self::LoadFuture<'conn, 'query>
+    where
+        T: AsQuery + 'query,
+        T::Query: QueryFragment<Self::Backend> + QueryId + 'query,
+    {
+        self.conn.load(source)
+    }
+
+    fn execute_returning_count<'conn, 'query, T>(
+        &'conn mut self,
+        source: T,
+    ) -> Self::ExecuteFuture<'conn, 'query>
+    where
+        T: QueryFragment<Self::Backend> + QueryId + 'query,
+    {
+        self.conn.execute_returning_count(source)
+    }
+
+    fn transaction_state(
+        &mut self,
+    ) -> &mut <Self::TransactionManager as TransactionManager<Self>>::TransactionStateData {
+        self.conn.transaction_state()
+    }
+}
+
+#[cfg(any(
+    feature = "deadpool",
+    feature = "bb8",
+    feature = "mobc",
+    feature = "r2d2"
+))]
+#[async_trait::async_trait]
+impl<C, TM> crate::pooled_connection::PoolableConnection for WithTransactionManager<C, TM>
+where
+    C: crate::pooled_connection::PoolableConnection,
+    TM: TransactionManager<Self>,
+    Self: AsyncConnection<TransactionManager = TM>,
+{
+    async fn reset_session(&mut self) -> QueryResult<()> {
+        self.conn.reset_session().await
+    }
+
+    fn apply_tcp_config<'a>(
+        database_url: &'a str,
+        config: &crate::pooled_connection::TcpConfig,
+    ) -> std::borrow::Cow<'a, str> {
+        C::apply_tcp_config(database_url, config)
+    }
+
+    async fn configure_statement_cache(
+        &mut self,
+        config: &crate::pooled_connection::StatementCacheConfig,
+    ) {
+        self.conn.configure_statement_cache(config).await
+    }
+
+    fn reclaim_handle(&self) -> Option<Box<dyn FnOnce() + Send>> {
+        self.conn.reclaim_handle()
+    }
+
+    fn closed_notifier(&self) -> Option<futures_util::future::BoxFuture<'static, ()>> {
+        self.conn.closed_notifier()
+    }
+
+    fn is_broken(&mut self) -> bool {
+        self.conn.is_broken() || TM::is_broken_transaction_manager(self)
+    }
+}
@@ -0,0 +1,106 @@
+use crate::connection;
+use diesel::QueryResult;
+use diesel_async::pg::{AdvisoryLockNotAcquired, AdvisoryLockNotHeld};
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl, SimpleAsyncConnection};
+use scoped_futures::ScopedFutureExt;
+
+const KEY: i64 = 881_001;
+const XACT_KEY: i64 = 882_001;
+const ABORTED_KEY: i64 = 881_002;
+
+#[tokio::test]
+async fn advisory_lock_excludes_other_sessions() {
+    let conn1 = &mut connection().await;
+    let conn2 = &mut connection().await;
+
+    let lock = conn1.advisory_lock(KEY).await.unwrap();
+    assert_eq!(lock.key(), KEY);
+    assert!(conn2.try_advisory_lock(KEY).await.unwrap().is_none());
+
+    lock.release().await.unwrap();
+    let lock = conn2.try_advisory_lock(KEY).await.unwrap();
+    assert!(lock.is_some());
+
+    // dropping the guard releases the lock before the next statement
+    drop(lock);
+    conn2.batch_execute("SELECT 1").await.unwrap();
+    let mut lock = conn1.try_advisory_lock(KEY).await.unwrap().unwrap();
+
+    // releasing a lock that is not held anymore fails
+    diesel::sql_query("SELECT pg_advisory_unlock_all()")
+        .execute(&mut *lock)
+        .await
+        .unwrap();
+    match lock.release().await {
+        Err(diesel::result::Error::QueryBuilderError(e)) => {
+            let e = e.downcast_ref::<AdvisoryLockNotHeld>().unwrap();
+            assert_eq!(e.key(), KEY);
+        }
+        res => panic!("Unexpected result: {res:?}"),
+    }
+}
+
+#[tokio::test]
+async fn advisory_locks_dropped_in_aborted_transactions_are_released() {
+    let conn1 = &mut connection().await;
+    let conn2 = &mut connection().await;
+
+    let res = conn1
+        .transaction::<(), _, _>(|conn| {
+            async move {
+                let mut lock = conn.advisory_lock(ABORTED_KEY).await?;
+                // aborts the transaction
+                let res = lock.batch_execute("SELECT 1 / 0").await;
+                drop(lock);
+                res
+            }
+            .scope_boxed()
+        })
+        .await;
+    assert!(res.is_err());
+
+    conn1.batch_execute("SELECT 1").await.unwrap();
+    let lock = conn2.try_advisory_lock(ABORTED_KEY).await.unwrap();
+    assert!(lock.is_some());
+}
+
+#[tokio::test]
+async fn transaction_builder_acquires_advisory_xact_lock() {
+    let db_url = std::env::var("DATABASE_URL").unwrap();
+    let conn1 = &mut AsyncPgConnection::establish(&db_url).await.unwrap();
+    let mut conn2 = AsyncPgConnection::establish(&db_url).await.unwrap();
+
+    conn1
+        .build_transaction()
+        .advisory_xact_lock(XACT_KEY)
+        .run(|_| {
+            let conn2 = &mut conn2;
+            async move {
+                let res = conn2
+                    .build_transaction()
+                    .try_advisory_xact_lock(XACT_KEY)
+                    .run(|_| async { QueryResult::Ok(()) }.scope_boxed())
+                    .await;
+                match res {
+                    Err(diesel::result::Error::QueryBuilderError(e)) => {
+                        let e = e.downcast_ref::<AdvisoryLockNotAcquired>().unwrap();
+                        assert_eq!(e.key(), XACT_KEY);
+                    }
+                    res => panic!("Unexpected result: {res:?}"),
+                }
+                QueryResult::Ok(())
+            }
+            .scope_boxed()
+        })
+        .await
+        .unwrap();
+
+    // the lock is released at the end of the transaction
+    let acquired = conn2
+        .build_transaction()
+        .try_advisory_xact_lock(XACT_KEY)
+        .run(|_| async { QueryResult::Ok(true) }.scope_boxed())
+        .await
+        .unwrap();
+    assert!(acquired);
+}
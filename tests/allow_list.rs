@@ -0,0 +1,99 @@
+use crate::{connection, users};
+use diesel::prelude::*;
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl, SimpleAsyncConnection};
+use scoped_futures::ScopedFutureExt;
+
+#[tokio::test]
+async fn allow_list_rejects_unknown_statements() {
+    use diesel_async::pg::{PolicyViolation, StatementAllowList};
+    use std::sync::Arc;
+
+    let conn = &mut connection().await;
+    let allow_list = StatementAllowList::new()
+        .allow_query(&users::table.count())
+        .unwrap()
+        .allow_sql("INSERT INTO \"users\" (\"name\") VALUES ($1)");
+    conn.add_interceptor(Arc::new(allow_list));
+
+    let count = conn
+        .transaction::<_, diesel::result::Error, _>(|conn| {
+            async move {
+                diesel::insert_into(users::table)
+                    .values(users::name.eq("Sean"))
+                    .execute(conn)
+                    .await?;
+                users::table.count().get_result::<i64>(conn).await
+            }
+            .scope_boxed()
+        })
+        .await
+        .unwrap();
+    assert_eq!(1, count);
+
+    let res = users::table.select(users::name).load::<String>(conn).await;
+    let Err(diesel::result::Error::QueryBuilderError(e)) = res else {
+        panic!("Expected the query to be rejected");
+    };
+    let violation = e.downcast_ref::<PolicyViolation>().unwrap();
+    assert_eq!(
+        StatementAllowList::fingerprint(violation.sql()),
+        violation.fingerprint()
+    );
+    assert!(conn
+        .batch_execute("COMMIT; DROP TABLE users")
+        .await
+        .is_err());
+    // only the exact transaction statements of diesel are allowed
+    for sql in [
+        "COMMIT PREPARED 'gid'",
+        "ROLLBACK PREPARED 'gid'",
+        "BEGINxyz",
+        "BEGIN TRANSACTION; DROP TABLE users",
+        "SAVEPOINT diesel_savepoint_1 DROP",
+        "ROLLBACK TO SAVEPOINT \"a\"\"b\"",
+    ] {
+        let Err(diesel::result::Error::QueryBuilderError(e)) = conn.batch_execute(sql).await else {
+            panic!("Expected `{sql}` to be rejected");
+        };
+        assert!(e.is::<PolicyViolation>(), "{sql}: {e}");
+    }
+    // the statements of the transaction builder are allowed
+    let db_url = std::env::var("DATABASE_URL").unwrap();
+    let mut other = AsyncPgConnection::establish(&db_url).await.unwrap();
+    other.add_interceptor(Arc::new(StatementAllowList::new()));
+    other
+        .build_transaction()
+        .read_only()
+        .serializable()
+        .run::<_, diesel::result::Error, _>(|conn| {
+            async move {
+                conn.batch_execute("SAVEPOINT custom_savepoint").await?;
+                conn.batch_execute("RELEASE SAVEPOINT custom_savepoint")
+                    .await
+            }
+            .scope_boxed()
+        })
+        .await
+        .unwrap();
+
+    // prepared queries are checked as well
+    let delete = conn
+        .prepare_query(diesel::delete(users::table))
+        .await
+        .unwrap();
+    let res = delete
+        .execute_query(conn, diesel::delete(users::table))
+        .await;
+    let Err(diesel::result::Error::QueryBuilderError(e)) = res else {
+        panic!("Expected the prepared query to be rejected");
+    };
+    assert!(e.is::<PolicyViolation>());
+    let count = conn.prepare_query(users::table.count()).await.unwrap();
+    assert_eq!(
+        vec![1],
+        count
+            .load_query::<i64>(conn, users::table.count())
+            .await
+            .unwrap()
+    );
+}
@@ -0,0 +1,46 @@
+use crate::{connection, users};
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+
+#[tokio::test]
+async fn audit_logger_records_queries_with_context() {
+    use diesel_async::pg::{AuditContext, AuditLogger, AuditRecord};
+    use std::sync::{Arc, Mutex};
+
+    let records = Arc::new(Mutex::new(Vec::new()));
+    let conn = &mut connection().await;
+    conn.add_interceptor(Arc::new(AuditLogger::new({
+        let records = records.clone();
+        move |record: &AuditRecord<'_>| {
+            records.lock().unwrap().push((
+                record.bind_fingerprint,
+                record.context.and_then(|c| c.user()).map(String::from),
+                record.context.and_then(|c| c.tenant()).map(String::from),
+                record.result.is_ok(),
+            ));
+        }
+    })));
+
+    let by_name = |name| users::table.filter(users::name.eq(name)).count();
+    let context = AuditContext::new().with_user("alice").with_tenant("acme");
+    context
+        .scope(async {
+            by_name("Sean").get_result::<i64>(conn).await.unwrap();
+            by_name("Sean").get_result::<i64>(conn).await.unwrap();
+        })
+        .await;
+    by_name("Tess").get_result::<i64>(conn).await.unwrap();
+    assert!(diesel::sql_query("SELECT invalid")
+        .execute(conn)
+        .await
+        .is_err());
+
+    let records = records.lock().unwrap();
+    assert_eq!(4, records.len());
+    assert_eq!(records[0], records[1]);
+    assert_eq!(Some("alice"), records[0].1.as_deref());
+    assert_eq!(Some("acme"), records[0].2.as_deref());
+    assert_ne!(records[0].0, records[2].0);
+    assert!(records[2].1.is_none() && records[2].2.is_none() && records[2].3);
+    assert!(!records[3].3);
+}
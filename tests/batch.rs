@@ -0,0 +1,83 @@
+use crate::{connection, users, User};
+use diesel::prelude::*;
+use diesel::upsert::excluded;
+use diesel_async::RunQueryDsl;
+
+#[derive(Insertable)]
+#[diesel(table_name = users)]
+struct NewUser {
+    id: i32,
+    name: String,
+}
+
+#[tokio::test]
+async fn upsert_returning_in_chunks_keeps_input_order() {
+    let conn = &mut connection().await;
+    diesel::insert_into(users::table)
+        .values([
+            (users::id.eq(1), users::name.eq("John")),
+            (users::id.eq(2), users::name.eq("Jane")),
+        ])
+        .execute(conn)
+        .await
+        .unwrap();
+
+    let records = [5, 1, 4, 2, 3]
+        .into_iter()
+        .map(|id| NewUser {
+            id,
+            name: format!("User {id}"),
+        })
+        .collect::<Vec<_>>();
+
+    let rows = diesel_async::batch::upsert_returning_in_chunks(
+        conn,
+        &records,
+        2,
+        |chunk| {
+            diesel::insert_into(users::table)
+                .values(chunk)
+                .on_conflict(users::id)
+                .do_update()
+                .set(users::name.eq(excluded(users::name)))
+                .returning(User::as_returning())
+        },
+        |record| record.id,
+        |user: &User| user.id,
+    )
+    .await
+    .unwrap();
+
+    let expected = records
+        .iter()
+        .map(|r| User {
+            id: r.id,
+            name: r.name.clone(),
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(expected, rows);
+
+    let count = users::table.count().get_result::<i64>(conn).await.unwrap();
+    assert_eq!(count, 5);
+}
+
+#[tokio::test]
+async fn insert_stream_writes_all_records() {
+    let conn = &mut connection().await;
+
+    let names = futures_util::stream::iter((0..25).map(|i| format!("User {i}")));
+    let written = diesel_async::batch::insert_stream(conn, names, 4, 3, |batch| {
+        diesel::insert_into(users::table).values(
+            batch
+                .into_iter()
+                .map(|name| users::name.eq(name))
+                .collect::<Vec<_>>(),
+        )
+    })
+    .await
+    .unwrap();
+    assert_eq!(written, 25);
+
+    let count = users::table.count().get_result::<i64>(conn).await.unwrap();
+    assert_eq!(count, 25);
+}
@@ -0,0 +1,212 @@
+use crate::connection;
+#[cfg(feature = "postgres")]
+use diesel_async::AsyncPgConnection;
+use diesel_async::{AsyncConnection, RunQueryDsl};
+
+#[cfg(any(feature = "postgres", feature = "mysql"))]
+#[tokio::test]
+async fn ping_reports_terminated_connections() {
+    let conn = &mut connection().await;
+    let other = &mut connection().await;
+    conn.ping_connection().await.unwrap();
+    assert!(conn.is_valid(std::time::Duration::from_secs(5)).await);
+
+    #[cfg(feature = "postgres")]
+    let (id, kill) = (
+        "pg_backend_pid()::bigint",
+        "SELECT pg_terminate_backend($1::integer)",
+    );
+    #[cfg(feature = "mysql")]
+    let (id, kill) = ("CAST(CONNECTION_ID() AS SIGNED)", "KILL ?");
+    let id = diesel::select(diesel::dsl::sql::<diesel::sql_types::BigInt>(id))
+        .get_result::<i64>(conn)
+        .await
+        .unwrap();
+    diesel::sql_query(kill)
+        .bind::<diesel::sql_types::BigInt, _>(id)
+        .execute(other)
+        .await
+        .unwrap();
+
+    let mut res = Ok(());
+    for _ in 0..50 {
+        res = conn.ping_connection().await;
+        if res.is_err() {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    }
+    assert!(res.is_err());
+    assert!(!conn.is_valid(std::time::Duration::from_secs(5)).await);
+}
+
+#[cfg(feature = "postgres")]
+#[tokio::test]
+async fn postgres_connection_error_is_latched() {
+    use std::time::Duration;
+
+    let conn = &mut connection().await;
+    let other = &mut connection().await;
+    assert!(conn.connection_error().is_none());
+
+    let pid = diesel::select(diesel::dsl::sql::<diesel::sql_types::Integer>(
+        "pg_backend_pid()",
+    ))
+    .get_result::<i32>(conn)
+    .await
+    .unwrap();
+    diesel::sql_query("SELECT pg_terminate_backend($1)")
+        .bind::<diesel::sql_types::Integer, _>(pid)
+        .execute(other)
+        .await
+        .unwrap();
+
+    let mut attempts = 0;
+    while conn.connection_error().is_none() && attempts < 50 {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        attempts += 1;
+    }
+    let error = conn
+        .connection_error()
+        .expect("Connection error is reported");
+    assert_eq!(
+        error.code(),
+        Some(&tokio_postgres::error::SqlState::ADMIN_SHUTDOWN)
+    );
+
+    // all following operations report the same error
+    for _ in 0..2 {
+        let res = diesel::select(diesel::dsl::sql::<diesel::sql_types::Integer>("1"))
+            .execute(conn)
+            .await;
+        match res {
+            Err(diesel::result::Error::DatabaseError(_, info)) => {
+                assert_eq!(info.message(), error.as_db_error().unwrap().message())
+            }
+            res => panic!("Unexpected result: {res:?}"),
+        }
+    }
+}
+
+#[cfg(feature = "postgres")]
+#[tokio::test]
+async fn postgres_connection_error_is_latched_for_lagged_receivers() {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    let db_url = std::env::var("DATABASE_URL").unwrap();
+    let (client, connection) = tokio_postgres::connect(&db_url, tokio_postgres::NoTls)
+        .await
+        .unwrap();
+    tokio::spawn(connection);
+
+    let mut errors = Vec::new();
+    for port in [1, 2] {
+        let Err(error) = tokio_postgres::connect(
+            &format!("host=127.0.0.1 port={port} connect_timeout=1"),
+            tokio_postgres::NoTls,
+        )
+        .await
+        else {
+            panic!("Nothing listens on this port");
+        };
+        errors.push(Arc::new(error));
+    }
+
+    let (tx, rx) = tokio::sync::broadcast::channel(1);
+    let conn = AsyncPgConnection::try_from(client, Some(rx), None)
+        .await
+        .unwrap();
+    // the receiver lags behind as both errors are sent before it is polled again
+    for error in errors {
+        tx.send(error).unwrap();
+    }
+    let mut attempts = 0;
+    while conn.connection_error().is_none() && attempts < 50 {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        attempts += 1;
+    }
+    assert!(conn.connection_error().is_some());
+}
+
+#[cfg(feature = "postgres")]
+#[tokio::test]
+async fn postgres_closed_reports_connection_error() {
+    let conn = &mut connection().await;
+    let other = &mut connection().await;
+    let closed = conn.closed();
+
+    let pid = diesel::select(diesel::dsl::sql::<diesel::sql_types::Integer>(
+        "pg_backend_pid()",
+    ))
+    .get_result::<i32>(conn)
+    .await
+    .unwrap();
+    diesel::sql_query("SELECT pg_terminate_backend($1)")
+        .bind::<diesel::sql_types::Integer, _>(pid)
+        .execute(other)
+        .await
+        .unwrap();
+
+    let closed = tokio::time::timeout(std::time::Duration::from_secs(5), closed)
+        .await
+        .expect("The connection is closed");
+    assert_eq!(
+        closed.error.and_then(|e| e.code().cloned()),
+        Some(tokio_postgres::error::SqlState::ADMIN_SHUTDOWN)
+    );
+}
+
+#[cfg(feature = "postgres")]
+#[tokio::test]
+async fn postgres_into_client() {
+    let mut conn = connection().await;
+
+    // a pipelined query keeps a reference to the client alive
+    let query = diesel::select(diesel::dsl::sql::<diesel::sql_types::Integer>("1"))
+        .get_result::<i32>(&mut conn);
+    let conn = match AsyncPgConnection::into_client(conn) {
+        Ok(_) => panic!("Query is still in flight"),
+        Err(conn) => conn,
+    };
+    assert_eq!(1, query.await.unwrap());
+
+    let client = conn
+        .into_client()
+        .unwrap_or_else(|_| panic!("No query is in flight"));
+    // the background task is still driving the connection
+    let row = client.query_one("SELECT 2", &[]).await.unwrap();
+    assert_eq!(2, row.get::<_, i32>(0));
+}
+
+#[cfg(feature = "postgres")]
+#[tokio::test]
+async fn postgres_close_terminates_the_session() {
+    let db_url = std::env::var("DATABASE_URL").unwrap();
+    let mut conn = AsyncPgConnection::establish(&db_url).await.unwrap();
+    let pid = diesel::select(diesel::dsl::sql::<diesel::sql_types::Integer>(
+        "pg_backend_pid()",
+    ))
+    .get_result::<i32>(&mut conn)
+    .await
+    .unwrap();
+
+    conn.close().await.unwrap();
+
+    // outside of a transaction, as statistics are cached until it ends
+    let other = &mut AsyncPgConnection::establish(&db_url).await.unwrap();
+    let mut running = true;
+    for _ in 0..50 {
+        running = diesel::select(diesel::dsl::sql::<diesel::sql_types::Bool>(&format!(
+            "EXISTS (SELECT 1 FROM pg_stat_activity WHERE pid = {pid})"
+        )))
+        .get_result::<bool>(other)
+        .await
+        .unwrap();
+        if !running {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    }
+    assert!(!running);
+}
@@ -0,0 +1,61 @@
+use crate::TestConnection;
+use diesel_async::{AsyncConnection, SimpleAsyncConnection};
+use scoped_futures::ScopedFutureExt;
+
+#[tokio::test]
+async fn set_constraints_defers_foreign_keys() {
+    use diesel_async::pg::ConstraintCheck;
+
+    let db_url = std::env::var("DATABASE_URL").unwrap();
+    let conn = &mut TestConnection::establish(&db_url).await.unwrap();
+    conn.batch_execute(
+        "CREATE TEMPORARY TABLE parents (id INTEGER PRIMARY KEY);
+         CREATE TEMPORARY TABLE children (
+             parent_id INTEGER CONSTRAINT \"Children_Parent\" REFERENCES parents
+                 DEFERRABLE INITIALLY IMMEDIATE
+         );",
+    )
+    .await
+    .unwrap();
+
+    let outside = conn.set_all_constraints(ConstraintCheck::Deferred).await;
+    assert!(matches!(
+        outside,
+        Err(diesel::result::Error::NotInTransaction)
+    ));
+
+    conn.transaction::<_, diesel::result::Error, _>(|conn| {
+        async move {
+            assert!(conn
+                .set_constraints(&[], ConstraintCheck::Deferred)
+                .await
+                .is_err());
+            conn.set_constraints(&["pg_temp.Children_Parent"], ConstraintCheck::Deferred)
+                .await?;
+            conn.batch_execute("INSERT INTO children VALUES (1); INSERT INTO parents VALUES (1);")
+                .await?;
+            conn.set_constraints(&["Children_Parent"], ConstraintCheck::Immediate)
+                .await
+        }
+        .scope_boxed()
+    })
+    .await
+    .unwrap();
+
+    let result = conn
+        .transaction::<_, diesel::result::Error, _>(|conn| {
+            async move {
+                conn.set_all_constraints(ConstraintCheck::Deferred).await?;
+                conn.batch_execute("INSERT INTO children VALUES (2)").await
+            }
+            .scope_boxed()
+        })
+        .await;
+    assert!(matches!(
+        result,
+        Err(diesel::result::Error::DatabaseError(
+            diesel::result::DatabaseErrorKind::ForeignKeyViolation,
+            _
+        ))
+    ));
+}
@@ -0,0 +1,105 @@
+use super::{connection, users, TestBackend};
+use diesel::deserialize::{self, FromSql};
+use diesel::prelude::*;
+use diesel::sql_types::{Integer, Text, Untyped};
+use diesel_async::RunQueryDsl;
+use diesel_dynamic_schema::dynamic_value::{Any, DynamicRow, NamedField};
+use diesel_dynamic_schema::DynamicSelectClause;
+
+#[derive(Debug, PartialEq)]
+enum DynamicValue {
+    String(String),
+    Integer(i32),
+}
+
+#[cfg(feature = "postgres")]
+impl FromSql<Any, TestBackend> for DynamicValue {
+    fn from_sql(value: diesel::pg::PgValue<'_>) -> deserialize::Result<Self> {
+        const TEXT_OID: u32 = 25;
+        const VARCHAR_OID: u32 = 1043;
+        const INTEGER_OID: u32 = 23;
+
+        match value.get_oid().get() {
+            TEXT_OID | VARCHAR_OID => {
+                <String as FromSql<Text, TestBackend>>::from_sql(value).map(DynamicValue::String)
+            }
+            INTEGER_OID => {
+                <i32 as FromSql<Integer, TestBackend>>::from_sql(value).map(DynamicValue::Integer)
+            }
+            oid => Err(format!("Unknown type: {oid}").into()),
+        }
+    }
+}
+
+#[cfg(feature = "mysql")]
+impl FromSql<Any, TestBackend> for DynamicValue {
+    fn from_sql(value: diesel::mysql::MysqlValue<'_>) -> deserialize::Result<Self> {
+        use diesel::mysql::MysqlType;
+
+        match value.value_type() {
+            MysqlType::String => {
+                <String as FromSql<Text, TestBackend>>::from_sql(value).map(DynamicValue::String)
+            }
+            MysqlType::Long => {
+                <i32 as FromSql<Integer, TestBackend>>::from_sql(value).map(DynamicValue::Integer)
+            }
+            ty => Err(format!("Unknown type: {ty:?}").into()),
+        }
+    }
+}
+
+#[tokio::test]
+async fn dynamic_tables_and_columns() {
+    let conn = &mut connection().await;
+    diesel::insert_into(users::table)
+        .values([users::name.eq("Sean"), users::name.eq("Tess")])
+        .execute(conn)
+        .await
+        .unwrap();
+
+    let table = diesel_dynamic_schema::table("users");
+    let id = table.column::<Integer, _>("id");
+    let name = table.column::<Text, _>("name");
+
+    let names = table
+        .select(name)
+        .order(id)
+        .load::<String>(conn)
+        .await
+        .unwrap();
+    assert_eq!(vec!["Sean", "Tess"], names);
+
+    let name = table
+        .select(name)
+        .filter(name.eq("Tess"))
+        .get_result::<String>(conn)
+        .await
+        .unwrap();
+    assert_eq!("Tess", name);
+}
+
+#[tokio::test]
+async fn dynamic_select_clauses() {
+    let conn = &mut connection().await;
+    diesel::insert_into(users::table)
+        .values([users::name.eq("Sean"), users::name.eq("Tess")])
+        .execute(conn)
+        .await
+        .unwrap();
+
+    let table = diesel_dynamic_schema::table("users");
+    let mut select = DynamicSelectClause::new();
+    select.add_field(table.column::<Untyped, _>("id"));
+    select.add_field(table.column::<Untyped, _>("name"));
+
+    let rows: Vec<DynamicRow<NamedField<DynamicValue>>> = table
+        .select(select)
+        .order(table.column::<Integer, _>("id"))
+        .load(conn)
+        .await
+        .unwrap();
+    assert_eq!(2, rows.len());
+    assert_eq!(DynamicValue::String("Sean".into()), rows[0]["name"]);
+    assert_eq!(DynamicValue::String("Tess".into()), rows[1]["name"]);
+    assert!(matches!(rows[1]["id"], DynamicValue::Integer(_)));
+}
@@ -0,0 +1,29 @@
+use crate::{connection, users};
+use diesel::prelude::*;
+use diesel_async::{RunQueryDsl, SimpleAsyncConnection};
+
+#[tokio::test]
+async fn explain_reports_index_usage() {
+    let conn = &mut connection().await;
+    conn.batch_execute("SET LOCAL enable_seqscan = off")
+        .await
+        .unwrap();
+
+    let plan = conn.explain(users::table.find(1)).await.unwrap();
+    assert!(plan.plan.uses_index("users_pkey"));
+    assert_eq!(plan.plan.relation_name.as_deref(), Some("users"));
+    assert!(plan.execution_time.is_none());
+
+    diesel::insert_into(users::table)
+        .values(users::name.eq("John Doe"))
+        .execute(conn)
+        .await
+        .unwrap();
+    let plan = conn
+        .explain_analyze(users::table.filter(users::name.eq("John Doe")))
+        .await
+        .unwrap();
+    assert_eq!(plan.plan.actual_rows, Some(1.0));
+    assert!(plan.execution_time.is_some());
+    assert!(!plan.plan.uses_index("users_pkey"));
+}
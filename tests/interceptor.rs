@@ -0,0 +1,77 @@
+use crate::{connection, users};
+use diesel::prelude::*;
+use diesel::QueryResult;
+use diesel_async::{RunQueryDsl, SimpleAsyncConnection};
+
+#[tokio::test]
+async fn interceptors_rewrite_and_reject_queries() {
+    use diesel::sql_types::Integer;
+    use diesel::IntoSql;
+    use diesel_async::pg::{InterceptedQuery, QueryInterceptor, QueryOutcome};
+    use std::sync::{Arc, Mutex};
+
+    type Record = (String, Result<Option<usize>, String>);
+
+    #[derive(Default)]
+    struct Recorder(Mutex<Vec<Record>>);
+
+    impl QueryInterceptor for Recorder {
+        fn after_query(&self, query: &InterceptedQuery, outcome: &QueryOutcome<'_>) {
+            let result = outcome.result.map_err(|e| e.to_string());
+            self.0
+                .lock()
+                .unwrap()
+                .push((query.sql().to_owned(), result));
+        }
+    }
+
+    struct Policy;
+
+    impl QueryInterceptor for Policy {
+        fn before_query(&self, query: &mut InterceptedQuery) -> QueryResult<()> {
+            if query.sql().contains("DELETE") {
+                return Err(diesel::result::Error::QueryBuilderError("rejected".into()));
+            }
+            // replace a bind value of 1 with 42
+            for bind in query.binds_mut() {
+                if bind.as_deref() == Some(&1_i32.to_be_bytes()[..]) {
+                    *bind = Some(42_i32.to_be_bytes().to_vec());
+                }
+            }
+            Ok(())
+        }
+    }
+
+    let conn = &mut connection().await;
+    let recorder = Arc::new(Recorder::default());
+    conn.add_interceptor(recorder.clone());
+    conn.add_interceptor(Arc::new(Policy));
+
+    let res = diesel::select(1.into_sql::<Integer>())
+        .get_result::<i32>(conn)
+        .await
+        .unwrap();
+    assert_eq!(42, res);
+    let deleted = diesel::delete(users::table).execute(conn).await;
+    assert!(matches!(
+        deleted,
+        Err(diesel::result::Error::QueryBuilderError(_))
+    ));
+    let inserted = diesel::insert_into(users::table)
+        .values(users::name.eq("Sean"))
+        .execute(conn)
+        .await
+        .unwrap();
+    assert_eq!(1, inserted);
+
+    let recorded = std::mem::take(&mut *recorder.0.lock().unwrap());
+    assert_eq!(3, recorded.len());
+    assert_eq!(Ok(None), recorded[0].1);
+    assert!(recorded[1].0.starts_with("DELETE"));
+    assert_eq!(Err(String::from("rejected")), recorded[1].1);
+    assert_eq!(Ok(Some(1)), recorded[2].1);
+
+    conn.clear_interceptors();
+    conn.batch_execute("SELECT 1").await.unwrap();
+    assert!(recorder.0.lock().unwrap().is_empty());
+}
@@ -0,0 +1,56 @@
+use crate::connection;
+use diesel_async::pg::LargeObjectMode;
+use std::pin::Pin;
+use std::task::Poll;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt, BufWriter, ReadBuf};
+
+#[tokio::test]
+async fn large_object_round_trip() {
+    let conn = &mut connection().await;
+    let content = (0..300_000).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+
+    let oid = conn.lo_create().await.unwrap();
+    let large_object = conn.lo_open(oid, LargeObjectMode::Write).await.unwrap();
+    let mut writer = BufWriter::with_capacity(64 * 1024, large_object);
+    writer.write_all(&content).await.unwrap();
+    writer.flush().await.unwrap();
+    writer.into_inner().close().await.unwrap();
+
+    let mut large_object = conn.lo_open(oid, LargeObjectMode::Read).await.unwrap();
+    let mut read = Vec::new();
+    large_object.read_to_end(&mut read).await.unwrap();
+    assert_eq!(read, content);
+
+    conn.lo_unlink(oid).await.unwrap();
+    assert!(conn.lo_open(oid, LargeObjectMode::Read).await.is_err());
+}
+
+#[tokio::test]
+async fn large_object_read_into_smaller_buffer() {
+    let conn = &mut connection().await;
+    let content = (0..100).collect::<Vec<u8>>();
+
+    let oid = conn.lo_create().await.unwrap();
+    let mut large_object = conn.lo_open(oid, LargeObjectMode::Write).await.unwrap();
+    large_object.write_all(&content).await.unwrap();
+    large_object.close().await.unwrap();
+
+    let mut large_object = conn.lo_open(oid, LargeObjectMode::Read).await.unwrap();
+    // start a read requesting 64 bytes, then continue it with a smaller buffer
+    let mut large = [0; 64];
+    futures_util::future::poll_fn(|cx| {
+        let res = Pin::new(&mut large_object).poll_read(cx, &mut ReadBuf::new(&mut large));
+        assert!(res.is_pending());
+        Poll::Ready(())
+    })
+    .await;
+    let mut small = [0; 10];
+    large_object.read_exact(&mut small).await.unwrap();
+    assert_eq!(small, content[..10]);
+
+    let mut rest = Vec::new();
+    large_object.read_to_end(&mut rest).await.unwrap();
+    assert_eq!(rest, content[10..]);
+
+    conn.lo_unlink(oid).await.unwrap();
+}
@@ -5,13 +5,68 @@ use scoped_futures::ScopedFutureExt;
 use std::fmt::Debug;
 use std::pin::Pin;
 
+#[cfg(feature = "postgres")]
+mod advisory_lock;
+#[cfg(feature = "postgres")]
+mod allow_list;
+#[cfg(feature = "postgres")]
+mod audit;
+#[cfg(feature = "postgres")]
+mod batch;
+#[cfg(any(feature = "postgres", feature = "mysql"))]
+mod connection_errors;
+#[cfg(feature = "postgres")]
+mod constraints;
 #[cfg(feature = "postgres")]
 mod custom_types;
+#[cfg(any(feature = "postgres", feature = "mysql"))]
+mod dynamic_schema;
+#[cfg(all(feature = "postgres", feature = "serde_json"))]
+mod explain;
+#[cfg(feature = "postgres")]
+mod interceptor;
+#[cfg(feature = "postgres")]
+mod large_object;
+#[cfg(feature = "postgres")]
+mod logical_decoding;
+#[cfg(feature = "postgres")]
+mod migrations;
+#[cfg(feature = "postgres")]
+mod notifications;
+#[cfg(any(feature = "postgres", feature = "mysql"))]
+mod pagination;
+#[cfg(feature = "postgres")]
+mod pipeline;
 #[cfg(any(feature = "bb8", feature = "deadpool", feature = "mobc"))]
 mod pooling;
+#[cfg(feature = "postgres")]
+mod prepared_query;
+#[cfg(feature = "postgres")]
+mod query_cache;
+mod read_only;
+#[cfg(feature = "postgres")]
+mod replica;
+#[cfg(feature = "postgres")]
+mod retry;
+#[cfg(feature = "postgres")]
+mod row_metadata;
+#[cfg(feature = "postgres")]
+mod session_config;
+#[cfg(feature = "explain-slow-queries")]
+mod slow_query;
+#[cfg(feature = "postgres")]
+mod statement_registry;
+mod streaming;
 #[cfg(feature = "async-connection-wrapper")]
 mod sync_wrapper;
+#[cfg(all(feature = "postgres", feature = "testcontainers"))]
+mod test_containers;
+mod transactions;
 mod type_check;
+#[cfg(all(feature = "postgres", unix))]
+mod unix_socket;
+#[cfg(feature = "postgres")]
+mod work_queue;
 
 async fn transaction_test<C: AsyncConnection<Backend = TestBackend>>(
     conn: &mut C,
@@ -0,0 +1,86 @@
+use diesel::sql_types::Text;
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl, SimpleAsyncConnection};
+use futures_util::{StreamExt, TryStreamExt};
+
+#[derive(diesel::QueryableByName)]
+struct WalLevel {
+    #[diesel(sql_type = Text)]
+    wal_level: String,
+}
+
+#[tokio::test]
+#[ignore = "requires a server running with `wal_level = logical`"]
+async fn stream_logical_changes() {
+    let db_url = std::env::var("DATABASE_URL").unwrap();
+    let conn = &mut AsyncPgConnection::establish(&db_url).await.unwrap();
+
+    let wal_level = diesel::sql_query("SHOW wal_level")
+        .get_result::<WalLevel>(conn)
+        .await
+        .unwrap();
+    assert_eq!(
+        "logical", wal_level.wal_level,
+        "logical decoding requires a server configured with `wal_level = logical`"
+    );
+
+    conn.batch_execute(
+        "DROP TABLE IF EXISTS logical_decoding_test; \
+         CREATE TABLE logical_decoding_test (id INTEGER PRIMARY KEY); \
+         SELECT pg_drop_replication_slot(slot_name) FROM pg_replication_slots \
+         WHERE slot_name = 'diesel_async_test_slot';",
+    )
+    .await
+    .unwrap();
+    conn.create_logical_replication_slot("diesel_async_test_slot", "test_decoding")
+        .await
+        .unwrap();
+    conn.batch_execute("INSERT INTO logical_decoding_test (id) VALUES (1), (2)")
+        .await
+        .unwrap();
+
+    // changes that were not fully processed are delivered again
+    let first = std::pin::pin!(conn.logical_changes_stream(
+        "diesel_async_test_slot",
+        1,
+        &[("include-xids", "0")]
+    ))
+    .next()
+    .await
+    .unwrap()
+    .unwrap();
+    assert_eq!(Some("BEGIN"), first.data_as_str());
+
+    let changes = conn
+        .logical_changes_stream("diesel_async_test_slot", 1, &[("include-xids", "0")])
+        .try_collect::<Vec<_>>()
+        .await
+        .unwrap();
+    let changes = changes
+        .iter()
+        .map(|c| c.data_as_str().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        changes,
+        vec![
+            "BEGIN",
+            "table public.logical_decoding_test: INSERT: id[integer]:1",
+            "table public.logical_decoding_test: INSERT: id[integer]:2",
+            "COMMIT",
+        ]
+    );
+
+    // the changes were acknowledged once the stream was drained
+    let changes = conn
+        .logical_changes_stream("diesel_async_test_slot", 1, &[])
+        .try_collect::<Vec<_>>()
+        .await
+        .unwrap();
+    assert!(changes.is_empty());
+
+    conn.drop_replication_slot("diesel_async_test_slot")
+        .await
+        .unwrap();
+    conn.batch_execute("DROP TABLE logical_decoding_test")
+        .await
+        .unwrap();
+}
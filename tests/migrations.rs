@@ -0,0 +1,86 @@
+use crate::connection;
+use diesel::migration::MigrationVersion;
+use diesel_async::migrations::AsyncMigrationHarness;
+use diesel_async::SimpleAsyncConnection;
+use diesel_migrations::{embed_migrations, EmbeddedMigrations};
+
+const MIGRATIONS: EmbeddedMigrations = embed_migrations!("tests/migrations");
+
+#[tokio::test]
+async fn run_and_revert_migrations() {
+    let conn = &mut connection().await;
+
+    assert!(conn.has_pending_migration(MIGRATIONS).await.unwrap());
+
+    let applied = conn.run_pending_migrations(MIGRATIONS).await.unwrap();
+    assert_eq!(
+        applied,
+        vec![
+            MigrationVersion::from("00000000000001"),
+            MigrationVersion::from("00000000000002"),
+        ]
+    );
+    assert!(!conn.has_pending_migration(MIGRATIONS).await.unwrap());
+    assert!(conn
+        .run_pending_migrations(MIGRATIONS)
+        .await
+        .unwrap()
+        .is_empty());
+    conn.batch_execute("INSERT INTO harness_test (id, name) VALUES (1, 'Sean')")
+        .await
+        .unwrap();
+
+    let reverted = conn.revert_last_migration(MIGRATIONS).await.unwrap();
+    assert_eq!(reverted, MigrationVersion::from("00000000000002"));
+    assert_eq!(
+        conn.applied_migrations().await.unwrap(),
+        vec![MigrationVersion::from("00000000000001")]
+    );
+    assert!(conn.has_pending_migration(MIGRATIONS).await.unwrap());
+    assert!(conn
+        .batch_execute("SELECT name FROM harness_test")
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn report_migration_progress() {
+    let conn = &mut connection().await;
+
+    assert_eq!(
+        conn.pending_migrations(MIGRATIONS).await.unwrap(),
+        vec![
+            String::from("00000000000001_create_harness_test"),
+            String::from("00000000000002_add_harness_test_name"),
+        ]
+    );
+
+    let mut reported = Vec::new();
+    let applied = conn
+        .run_pending_migrations_with_progress(MIGRATIONS, |progress| {
+            assert!(progress.result.is_ok());
+            reported.push((progress.name.to_owned(), progress.version.as_owned()));
+        })
+        .await
+        .unwrap();
+
+    assert_eq!(
+        reported,
+        vec![
+            (
+                String::from("00000000000001_create_harness_test"),
+                MigrationVersion::from("00000000000001")
+            ),
+            (
+                String::from("00000000000002_add_harness_test_name"),
+                MigrationVersion::from("00000000000002")
+            ),
+        ]
+    );
+    assert_eq!(applied.len(), 2);
+    assert!(conn
+        .pending_migrations(MIGRATIONS)
+        .await
+        .unwrap()
+        .is_empty());
+}
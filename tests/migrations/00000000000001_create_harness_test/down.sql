@@ -0,0 +1 @@
+DROP TABLE harness_test;
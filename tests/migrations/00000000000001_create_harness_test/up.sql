@@ -0,0 +1,3 @@
+CREATE TABLE harness_test (
+    id INTEGER PRIMARY KEY
+);
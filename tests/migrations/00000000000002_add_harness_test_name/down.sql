@@ -0,0 +1 @@
+ALTER TABLE harness_test DROP COLUMN name;
@@ -0,0 +1 @@
+ALTER TABLE harness_test ADD COLUMN name VARCHAR(255);
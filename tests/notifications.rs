@@ -0,0 +1,175 @@
+#[cfg(feature = "serde_json")]
+use diesel_async::pg::{TableChange, TableChangeOperation};
+use diesel_async::{AsyncConnection, AsyncPgConnection, SimpleAsyncConnection};
+use futures_util::StreamExt;
+
+#[tokio::test]
+async fn receive_notifications_from_other_session() {
+    let db_url = std::env::var("DATABASE_URL").unwrap();
+    let mut listener = AsyncPgConnection::establish(&db_url).await.unwrap();
+    let mut sender = AsyncPgConnection::establish(&db_url).await.unwrap();
+
+    listener
+        .batch_execute("LISTEN notifications_test")
+        .await
+        .unwrap();
+    sender
+        .batch_execute("NOTIFY notifications_test, 'first'; NOTIFY notifications_test, 'second'")
+        .await
+        .unwrap();
+
+    let payloads = listener
+        .notifications_stream()
+        .take(2)
+        .map(|n| n.unwrap().payload().to_owned())
+        .collect::<Vec<_>>()
+        .await;
+    assert_eq!(payloads, vec!["first", "second"]);
+}
+
+#[cfg(feature = "serde_json")]
+#[tokio::test]
+async fn change_feed_reports_row_changes() {
+    let db_url = std::env::var("DATABASE_URL").unwrap();
+    let mut listener = AsyncPgConnection::establish(&db_url).await.unwrap();
+    let mut writer = AsyncPgConnection::establish(&db_url).await.unwrap();
+
+    writer
+        .batch_execute(
+            "DROP TABLE IF EXISTS change_feed_test; \
+             CREATE TABLE change_feed_test (id INTEGER PRIMARY KEY, name TEXT NOT NULL);",
+        )
+        .await
+        .unwrap();
+    writer
+        .install_change_feed("change_feed_test", &["change_feed_test"])
+        .await
+        .unwrap();
+    listener
+        .listen_change_feed("change_feed_test")
+        .await
+        .unwrap();
+
+    writer
+        .batch_execute(
+            "INSERT INTO change_feed_test (id, name) VALUES (1, 'Sean'); \
+             UPDATE change_feed_test SET name = 'Tess' WHERE id = 1; \
+             DELETE FROM change_feed_test WHERE id = 1;",
+        )
+        .await
+        .unwrap();
+
+    let changes = listener
+        .change_feed_stream("change_feed_test")
+        .take(3)
+        .map(Result::unwrap)
+        .collect::<Vec<_>>()
+        .await;
+    let change = |operation, row| TableChange {
+        operation,
+        schema: String::from("public"),
+        table: String::from("change_feed_test"),
+        row,
+        key_only: false,
+    };
+    assert_eq!(
+        changes,
+        vec![
+            change(
+                TableChangeOperation::Insert,
+                serde_json::json!({"id": 1, "name": "Sean"})
+            ),
+            change(
+                TableChangeOperation::Update,
+                serde_json::json!({"id": 1, "name": "Tess"})
+            ),
+            change(
+                TableChangeOperation::Delete,
+                serde_json::json!({"id": 1, "name": "Tess"})
+            ),
+        ]
+    );
+
+    // rows exceeding the payload limit of notifications are reported via their key
+    writer
+        .batch_execute(
+            "INSERT INTO change_feed_test (id, name) VALUES (2, repeat('x', 10000)); \
+             CREATE TEMPORARY TABLE change_feed_test_without_key (name TEXT NOT NULL);",
+        )
+        .await
+        .unwrap();
+    writer
+        .install_change_feed("change_feed_test", &["change_feed_test_without_key"])
+        .await
+        .unwrap();
+    writer
+        .batch_execute("INSERT INTO change_feed_test_without_key VALUES (repeat('x', 10000))")
+        .await
+        .unwrap();
+    let changes = listener
+        .change_feed_stream("change_feed_test")
+        .take(2)
+        .map(Result::unwrap)
+        .collect::<Vec<_>>()
+        .await;
+    assert_eq!(changes[0].row, serde_json::json!({"id": 2}));
+    assert!(changes[0].key_only);
+    assert_eq!(changes[1].table, "change_feed_test_without_key");
+    assert_eq!(changes[1].row, serde_json::json!({}));
+    assert!(changes[1].key_only);
+
+    writer
+        .remove_change_feed("change_feed_test", &["change_feed_test"])
+        .await
+        .unwrap();
+    writer
+        .batch_execute("DROP TABLE change_feed_test")
+        .await
+        .unwrap();
+}
+
+#[cfg(feature = "serde_json")]
+#[tokio::test]
+async fn change_feed_quotes_tables_and_keeps_long_channels_apart() {
+    use diesel_async::RunQueryDsl;
+
+    let db_url = std::env::var("DATABASE_URL").unwrap();
+    let mut conn = AsyncPgConnection::establish(&db_url).await.unwrap();
+    conn.batch_execute(
+        "CREATE TEMPORARY TABLE \"Change Feed\" (id INTEGER PRIMARY KEY); \
+         CREATE TEMPORARY TABLE change_feed_victim (id INTEGER PRIMARY KEY);",
+    )
+    .await
+    .unwrap();
+
+    let res = conn
+        .install_change_feed(
+            "injection",
+            &["change_feed_victim; DROP TABLE change_feed_victim"],
+        )
+        .await;
+    assert!(res.is_err());
+    let table = conn.batch_execute("SELECT * FROM change_feed_victim").await;
+    assert!(table.is_ok(), "{table:?}");
+
+    // both names exceed the maximal length of identifiers
+    // and only differ after it
+    let prefix = "c".repeat(60);
+    let channels = [format!("{prefix}_first"), format!("{prefix}_second")];
+    for channel in &channels {
+        conn.install_change_feed(channel, &["pg_temp.Change Feed"])
+            .await
+            .unwrap();
+    }
+    let triggers = || {
+        diesel::select(diesel::dsl::sql::<diesel::sql_types::BigInt>(
+            "count(*) FROM pg_trigger WHERE tgrelid = 'pg_temp.\"Change Feed\"'::regclass",
+        ))
+    };
+    assert_eq!(2, triggers().get_result::<i64>(&mut conn).await.unwrap());
+
+    conn.remove_change_feed(&channels[0], &["pg_temp.Change Feed"])
+        .await
+        .unwrap();
+    assert_eq!(1, triggers().get_result::<i64>(&mut conn).await.unwrap());
+}
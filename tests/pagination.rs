@@ -0,0 +1,92 @@
+use crate::{connection, users, User};
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use futures_util::TryStreamExt;
+
+async fn insert_users(conn: &mut crate::TestConnection, count: usize) {
+    let names = (0..count)
+        .map(|i| users::name.eq(format!("User {i}")))
+        .collect::<Vec<_>>();
+    diesel::insert_into(users::table)
+        .values(names)
+        .execute(conn)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn keyset_stream_loads_all_pages() {
+    let conn = &mut connection().await;
+    insert_users(conn, 7).await;
+
+    let mut pages = 0;
+    let users = diesel_async::pagination::keyset_stream(
+        conn,
+        3,
+        |last_id, page_size| {
+            pages += 1;
+            users::table
+                .filter(users::id.gt(last_id.unwrap_or(0)))
+                .order_by(users::id)
+                .limit(page_size)
+                .select(User::as_select())
+        },
+        |user: &User| user.id,
+    )
+    .try_collect::<Vec<_>>()
+    .await
+    .unwrap();
+
+    assert_eq!(pages, 3);
+    let names = users.into_iter().map(|u| u.name).collect::<Vec<_>>();
+    let expected = (0..7).map(|i| format!("User {i}")).collect::<Vec<_>>();
+    assert_eq!(expected, names);
+}
+
+#[tokio::test]
+async fn paginate_loads_page_and_total_count() {
+    use diesel_async::pagination::Paginate;
+
+    let conn = &mut connection().await;
+    insert_users(conn, 7).await;
+
+    let (page, total) = users::table
+        .select(users::name)
+        .order_by(users::id)
+        .paginate(3)
+        .per_page(3)
+        .load_and_count_total::<String, _>(conn)
+        .await
+        .unwrap();
+    assert_eq!(vec![String::from("User 6")], page);
+    assert_eq!(7, total);
+
+    let (page, total) = users::table
+        .select(users::name)
+        .paginate(4)
+        .per_page(3)
+        .load_and_count_total::<String, _>(conn)
+        .await
+        .unwrap();
+    assert!(page.is_empty());
+    assert_eq!(0, total);
+}
+
+#[tokio::test]
+async fn paginate_rejects_invalid_pages() {
+    use diesel_async::pagination::Paginate;
+
+    let conn = &mut connection().await;
+    for (page, per_page) in [(0, 10), (1, 0), (-1, 10), (i64::MAX, 2)] {
+        let res = users::table
+            .select(users::name)
+            .paginate(page)
+            .per_page(per_page)
+            .load_and_count_total::<String, _>(conn)
+            .await;
+        assert!(
+            matches!(res, Err(diesel::result::Error::QueryBuilderError(_))),
+            "{page}, {per_page}: {res:?}"
+        );
+    }
+}
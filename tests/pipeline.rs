@@ -0,0 +1,115 @@
+use crate::{connection, pg_sleep, users, TestConnection};
+use diesel::prelude::*;
+use diesel_async::{AsyncConnection, RunQueryDsl, SimpleAsyncConnection};
+use scoped_futures::ScopedFutureExt;
+
+#[tokio::test]
+async fn pipeline_limit_waits_for_capacity() {
+    use diesel::sql_types::Integer;
+    use diesel::IntoSql;
+    use diesel_async::pg::{PipelineLimit, PipelineLimitExceeded};
+
+    let conn = &mut connection().await;
+    conn.set_pipeline_limit(PipelineLimit::Wait(1));
+    let operations = conn.pipeline_metrics().operations;
+    let queries = (0..4)
+        .map(|i| diesel::select(i.into_sql::<Integer>()).get_result::<i32>(conn))
+        .collect::<Vec<_>>();
+    let results = futures_util::future::try_join_all(queries).await.unwrap();
+    assert_eq!(vec![0, 1, 2, 3], results);
+    let metrics = conn.pipeline_metrics();
+    assert_eq!(0, metrics.in_flight);
+    assert_eq!(operations + 4, metrics.operations);
+
+    conn.set_pipeline_limit(PipelineLimit::Fail(2));
+    let queries = (0..3)
+        .map(|i| diesel::select(i.into_sql::<Integer>()).get_result::<i32>(conn))
+        .collect::<Vec<_>>();
+    let results = futures_util::future::join_all(queries).await;
+    assert!(results[0].is_ok() && results[1].is_ok());
+    let Err(diesel::result::Error::QueryBuilderError(ref e)) = results[2] else {
+        panic!("Expected the third query to be rejected: {:?}", results[2]);
+    };
+    let e = e.downcast_ref::<PipelineLimitExceeded>().unwrap();
+    assert_eq!(2, e.limit());
+}
+
+#[tokio::test]
+async fn shared_connection_runs_queries_of_multiple_tasks() {
+    use diesel_async::pg::SharedAsyncPgConnection;
+    use std::sync::Arc;
+
+    // connections with an open transaction cannot be shared
+    let conn = connection().await;
+    assert!(SharedAsyncPgConnection::new(conn).is_err());
+
+    let db_url = std::env::var("DATABASE_URL").unwrap();
+    let mut conn = TestConnection::establish(&db_url).await.unwrap();
+    conn.batch_execute("CREATE TEMPORARY TABLE users (id SERIAL PRIMARY KEY, name TEXT NOT NULL)")
+        .await
+        .unwrap();
+    let conn = Arc::new(SharedAsyncPgConnection::new(conn).ok().unwrap());
+
+    let tasks = (0..10)
+        .map(|i| {
+            let conn = conn.clone();
+            tokio::spawn(async move {
+                conn.execute_query(
+                    diesel::insert_into(users::table).values(users::name.eq(format!("User {i}"))),
+                )
+                .await?;
+                conn.get_query_result::<_, String>(
+                    users::table
+                        .select(users::name)
+                        .filter(users::name.eq(format!("User {i}"))),
+                )
+                .await
+            })
+        })
+        .collect::<Vec<_>>();
+    for (i, task) in tasks.into_iter().enumerate() {
+        assert_eq!(format!("User {i}"), task.await.unwrap().unwrap());
+    }
+
+    let count = conn
+        .load_query::<_, i64>(users::table.count())
+        .await
+        .unwrap();
+    assert_eq!(vec![10], count);
+    let not_found = conn
+        .get_query_result::<_, String>(users::table.select(users::name).filter(users::id.eq(-1)))
+        .await;
+    assert!(matches!(not_found, Err(diesel::result::Error::NotFound)));
+
+    let mut conn = Arc::into_inner(conn).unwrap().into_inner();
+    conn.begin_test_transaction().await.unwrap();
+}
+
+#[tokio::test]
+async fn transaction_with_pipelined_query_in_flight() {
+    let conn = &mut connection().await;
+
+    // the query would be executed inside of the transaction otherwise
+    let in_flight = diesel::select(pg_sleep(0.1)).execute(conn);
+    let transaction = conn.transaction::<_, diesel::result::Error, _>(|conn| {
+        async move { users::table.count().get_result::<i64>(conn).await }.scope_boxed()
+    });
+    let (in_flight, transaction) = futures_util::join!(in_flight, transaction);
+    assert_eq!(Ok(1), in_flight);
+    assert!(
+        matches!(
+            transaction,
+            Err(diesel::result::Error::QueryBuilderError(_))
+        ),
+        "{transaction:?}"
+    );
+
+    // transactions can be started once the query finished
+    let count = conn
+        .transaction::<_, diesel::result::Error, _>(|conn| {
+            async move { users::table.count().get_result::<i64>(conn).await }.scope_boxed()
+        })
+        .await
+        .unwrap();
+    assert_eq!(count, 0);
+}
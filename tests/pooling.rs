@@ -105,3 +105,972 @@ async fn save_changes_mobc() {
         assert_eq!(u2.name, "Jane");
     }
 }
+
+#[tokio::test]
+#[cfg(all(feature = "deadpool", feature = "postgres"))]
+async fn ping_timeout_discards_unresponsive_connections() {
+    use diesel_async::pooled_connection::deadpool::Pool;
+    use diesel_async::pooled_connection::{
+        AsyncDieselConnectionManager, ManagerConfig, RecyclingMethod,
+    };
+    use diesel_async::AsyncPgConnection;
+    use std::time::Duration;
+
+    let db_url = std::env::var("DATABASE_URL").unwrap();
+
+    let mut manager_config = ManagerConfig::default();
+    // simulates a connection that does not respond in time
+    manager_config.recycling_method = RecyclingMethod::CustomQuery("SELECT pg_sleep(1)".into());
+    manager_config.ping_timeout = Some(Duration::from_millis(50));
+    let config =
+        AsyncDieselConnectionManager::<AsyncPgConnection>::new_with_config(db_url, manager_config);
+    let pool = Pool::builder(config).max_size(1).build().unwrap();
+
+    let backend_pid = diesel::dsl::sql::<diesel::sql_types::Integer>("pg_backend_pid()");
+    let mut conn = pool.get().await.unwrap();
+    let first_pid = diesel::select(backend_pid.clone())
+        .get_result::<i32>(&mut conn)
+        .await
+        .unwrap();
+    drop(conn);
+
+    let mut conn = pool.get().await.unwrap();
+    let second_pid = diesel::select(backend_pid)
+        .get_result::<i32>(&mut conn)
+        .await
+        .unwrap();
+    assert_ne!(first_pid, second_pid);
+}
+
+#[tokio::test]
+#[cfg(all(feature = "deadpool", feature = "mysql"))]
+async fn ping_detects_closed_mysql_connections() {
+    use diesel::sql_types::{BigInt, Unsigned};
+    use diesel_async::pooled_connection::deadpool::Pool;
+    use diesel_async::pooled_connection::AsyncDieselConnectionManager;
+    use diesel_async::{AsyncConnection, AsyncMysqlConnection};
+
+    let db_url = std::env::var("DATABASE_URL").unwrap();
+
+    let config = AsyncDieselConnectionManager::<AsyncMysqlConnection>::new(db_url.clone());
+    let pool = Pool::builder(config).max_size(1).build().unwrap();
+
+    let connection_id = diesel::dsl::sql::<Unsigned<BigInt>>("CONNECTION_ID()");
+    let mut conn = pool.get().await.unwrap();
+    let first_id = diesel::select(connection_id.clone())
+        .get_result::<u64>(&mut conn)
+        .await
+        .unwrap();
+    drop(conn);
+
+    // close the pooled connection from the server side
+    let other = &mut AsyncMysqlConnection::establish(&db_url).await.unwrap();
+    diesel::sql_query(format!("KILL {first_id}"))
+        .execute(other)
+        .await
+        .unwrap();
+
+    let mut conn = pool.get().await.unwrap();
+    let second_id = diesel::select(connection_id)
+        .get_result::<u64>(&mut conn)
+        .await
+        .unwrap();
+    assert_ne!(first_id, second_id);
+}
+
+#[tokio::test]
+#[cfg(all(feature = "deadpool", feature = "postgres"))]
+async fn warm_up_prepares_statements_of_new_connections() {
+    use diesel::sql_types::{BigInt, Integer};
+    use diesel_async::pooled_connection::deadpool::Pool;
+    use diesel_async::pooled_connection::{AsyncDieselConnectionManager, ManagerConfig};
+    use diesel_async::AsyncPgConnection;
+    use futures_util::FutureExt;
+
+    let db_url = std::env::var("DATABASE_URL").unwrap();
+
+    let mut manager_config = ManagerConfig::default();
+    manager_config.warm_up = Some(Box::new(|conn: &mut AsyncPgConnection| {
+        conn.prepare_cached(diesel::select(0.into_sql::<Integer>()))
+            .boxed()
+    }));
+    let config =
+        AsyncDieselConnectionManager::<AsyncPgConnection>::new_with_config(db_url, manager_config);
+    let pool = Pool::builder(config).max_size(1).build().unwrap();
+
+    let mut conn = pool.get().await.unwrap();
+    let prepared = diesel::dsl::sql::<BigInt>(
+        "(SELECT count(*) FROM pg_prepared_statements WHERE statement = 'SELECT $1')",
+    );
+    let count = diesel::select(prepared)
+        .get_result::<i64>(&mut conn)
+        .await
+        .unwrap();
+    assert_eq!(1, count);
+}
+
+#[tokio::test]
+#[cfg(all(feature = "deadpool", feature = "postgres"))]
+async fn on_checkout_runs_for_each_checkout() {
+    use diesel::sql_types::Text;
+    use diesel_async::pooled_connection::deadpool::Pool;
+    use diesel_async::pooled_connection::{
+        AsyncDieselConnectionManager, CheckoutSetup, ManagerConfig,
+    };
+    use diesel_async::{AsyncPgConnection, SimpleAsyncConnection};
+
+    let db_url = std::env::var("DATABASE_URL").unwrap();
+
+    let mut manager_config = ManagerConfig::default();
+    manager_config.on_checkout = Some(CheckoutSetup::Sql(
+        "SET application_name TO 'checked_out'".into(),
+    ));
+    let config =
+        AsyncDieselConnectionManager::<AsyncPgConnection>::new_with_config(db_url, manager_config);
+    let pool = Pool::builder(config).max_size(1).build().unwrap();
+    let application_name = || {
+        diesel::select(diesel::dsl::sql::<Text>(
+            "current_setting('application_name')",
+        ))
+    };
+
+    let mut conn = pool.get().await.unwrap();
+    let name = application_name().get_result::<String>(&mut conn).await;
+    assert_eq!(Ok(String::from("checked_out")), name);
+    conn.batch_execute("SET application_name TO 'changed'")
+        .await
+        .unwrap();
+    drop(conn);
+
+    // the setting is restored for the reused connection
+    let mut conn = pool.get().await.unwrap();
+    let name = application_name().get_result::<String>(&mut conn).await;
+    assert_eq!(Ok(String::from("checked_out")), name);
+}
+
+#[tokio::test]
+#[cfg(all(feature = "bb8", feature = "postgres"))]
+async fn on_checkout_runs_once_per_checkout_bb8() {
+    use diesel_async::pooled_connection::bb8::Pool;
+    use diesel_async::pooled_connection::{
+        AsyncDieselConnectionManager, CheckoutSetup, ManagerConfig,
+    };
+    use diesel_async::AsyncPgConnection;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    let db_url = std::env::var("DATABASE_URL").unwrap();
+
+    let checkouts = Arc::new(AtomicUsize::new(0));
+    let mut manager_config = ManagerConfig::default();
+    manager_config.on_checkout = Some(CheckoutSetup::Function(Box::new({
+        let checkouts = checkouts.clone();
+        move |_| {
+            checkouts.fetch_add(1, Ordering::SeqCst);
+            Box::pin(async { Ok(()) })
+        }
+    })));
+    let config =
+        AsyncDieselConnectionManager::<AsyncPgConnection>::new_with_config(db_url, manager_config);
+    let pool = Pool::builder().max_size(1).build(config).await.unwrap();
+
+    // bb8 checks new connections on checkout as well
+    drop(pool.get().await.unwrap());
+    assert_eq!(1, AtomicUsize::load(&checkouts, Ordering::SeqCst));
+    drop(pool.get().await.unwrap());
+    assert_eq!(2, AtomicUsize::load(&checkouts, Ordering::SeqCst));
+}
+
+#[tokio::test]
+#[cfg(all(feature = "deadpool", feature = "postgres"))]
+async fn reset_session_discards_session_state_on_recycle() {
+    use diesel::sql_types::{BigInt, Integer, Text};
+    use diesel_async::pooled_connection::deadpool::Pool;
+    use diesel_async::pooled_connection::{AsyncDieselConnectionManager, ManagerConfig};
+    use diesel_async::{AsyncPgConnection, SimpleAsyncConnection};
+
+    let db_url = std::env::var("DATABASE_URL").unwrap();
+
+    let mut manager_config = ManagerConfig::default();
+    manager_config.reset_session = true;
+    let config =
+        AsyncDieselConnectionManager::<AsyncPgConnection>::new_with_config(db_url, manager_config);
+    let pool = Pool::builder(config).max_size(1).build().unwrap();
+    let query = || diesel::select(1.into_sql::<Integer>());
+    let advisory_locks = || {
+        diesel::select(diesel::dsl::sql::<BigInt>(
+            "(SELECT count(*) FROM pg_locks WHERE locktype = 'advisory' AND pid = pg_backend_pid())",
+        ))
+    };
+
+    let mut conn = pool.get().await.unwrap();
+    assert_eq!(Ok(1), query().get_result::<i32>(&mut conn).await);
+    conn.batch_execute(
+        "SET application_name TO 'leaked'; \
+         CREATE TEMPORARY TABLE leaked (id INTEGER); \
+         SELECT pg_advisory_lock(42);",
+    )
+    .await
+    .unwrap();
+    assert_eq!(Ok(1), advisory_locks().get_result::<i64>(&mut conn).await);
+    drop(conn);
+
+    let mut conn = pool.get().await.unwrap();
+    let name = diesel::select(diesel::dsl::sql::<Text>(
+        "current_setting('application_name')",
+    ))
+    .get_result::<String>(&mut conn)
+    .await;
+    assert_eq!(Ok(String::new()), name);
+    assert!(conn.batch_execute("SELECT * FROM leaked").await.is_err());
+    assert_eq!(Ok(0), advisory_locks().get_result::<i64>(&mut conn).await);
+    // the cached statement is still prepared on the server
+    assert_eq!(Ok(1), query().get_result::<i32>(&mut conn).await);
+}
+
+#[tokio::test]
+#[cfg(all(feature = "deadpool", feature = "postgres"))]
+async fn statement_cache_config_applies_to_pooled_connections() {
+    use diesel::sql_types::{BigInt, Integer};
+    use diesel_async::pooled_connection::deadpool::Pool;
+    use diesel_async::pooled_connection::{
+        AsyncDieselConnectionManager, ManagerConfig, StatementCacheConfig,
+    };
+    use diesel_async::AsyncPgConnection;
+
+    let db_url = std::env::var("DATABASE_URL").unwrap();
+    let prepared_statements = || {
+        diesel::select(diesel::dsl::sql::<BigInt>(
+            "(SELECT count(*) FROM pg_prepared_statements)",
+        ))
+    };
+
+    let mut manager_config = ManagerConfig::default();
+    manager_config.statement_cache = Some(StatementCacheConfig::disabled());
+    let config = AsyncDieselConnectionManager::<AsyncPgConnection>::new_with_config(
+        db_url.clone(),
+        manager_config,
+    );
+    let pool = Pool::builder(config).max_size(1).build().unwrap();
+    let mut conn = pool.get().await.unwrap();
+    let one = diesel::select(1.into_sql::<Integer>())
+        .get_result::<i32>(&mut conn)
+        .await;
+    assert_eq!(Ok(1), one);
+    assert_eq!(
+        Ok(0),
+        prepared_statements().get_result::<i64>(&mut conn).await
+    );
+
+    let mut manager_config = ManagerConfig::default();
+    let mut statement_cache = StatementCacheConfig::default();
+    statement_cache.query_id_only = true;
+    manager_config.statement_cache = Some(statement_cache);
+    let config =
+        AsyncDieselConnectionManager::<AsyncPgConnection>::new_with_config(db_url, manager_config);
+    let pool = Pool::builder(config).max_size(1).build().unwrap();
+    let mut conn = pool.get().await.unwrap();
+    let one = diesel::select(1.into_sql::<Integer>())
+        .get_result::<i32>(&mut conn)
+        .await;
+    assert_eq!(Ok(1), one);
+    diesel::sql_query("SELECT 1")
+        .execute(&mut conn)
+        .await
+        .unwrap();
+    // only the statement of the query with a static query id is cached
+    assert_eq!(
+        Ok(1),
+        prepared_statements().get_result::<i64>(&mut conn).await
+    );
+}
+
+#[tokio::test]
+#[cfg(all(feature = "deadpool", feature = "postgres"))]
+async fn tcp_config_applies_keepalive_to_pooled_connections() {
+    use diesel::sql_types::Integer;
+    use diesel_async::pooled_connection::deadpool::Pool;
+    use diesel_async::pooled_connection::{
+        AsyncDieselConnectionManager, ManagerConfig, PoolableConnection, TcpConfig,
+    };
+    use diesel_async::AsyncPgConnection;
+    use std::time::Duration;
+
+    let db_url = std::env::var("DATABASE_URL").unwrap();
+    let mut tcp = TcpConfig::keepalive(Duration::from_secs(60));
+    tcp.keepalive_interval = Some(Duration::from_secs(10));
+    tcp.keepalive_retries = Some(3);
+
+    for url in [
+        db_url.as_str(),
+        "postgres://postgres@localhost/postgres?sslmode=disable",
+        "host=localhost user=postgres",
+    ] {
+        let url = AsyncPgConnection::apply_tcp_config(url, &tcp);
+        let config = url.parse::<tokio_postgres::Config>().unwrap();
+        assert!(config.get_keepalives());
+        assert_eq!(Duration::from_secs(60), config.get_keepalives_idle());
+        assert_eq!(
+            Some(Duration::from_secs(10)),
+            config.get_keepalives_interval()
+        );
+        assert_eq!(Some(3), config.get_keepalives_retries());
+    }
+
+    let mut manager_config = ManagerConfig::default();
+    manager_config.tcp = Some(tcp);
+    let config =
+        AsyncDieselConnectionManager::<AsyncPgConnection>::new_with_config(db_url, manager_config);
+    let pool = Pool::builder(config).max_size(1).build().unwrap();
+    let mut conn = pool.get().await.unwrap();
+    let one = diesel::select(1.into_sql::<Integer>())
+        .get_result::<i32>(&mut conn)
+        .await;
+    assert_eq!(Ok(1), one);
+}
+
+#[tokio::test]
+#[cfg(all(feature = "deadpool", feature = "postgres"))]
+async fn reconnect_backoff_retries_failed_connection_attempts() {
+    use diesel_async::pooled_connection::deadpool::Pool;
+    use diesel_async::pooled_connection::{
+        AsyncDieselConnectionManager, BackoffPolicy, ManagerConfig,
+    };
+    use diesel_async::{AsyncConnection, AsyncPgConnection};
+    use futures_util::FutureExt;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    let db_url = std::env::var("DATABASE_URL").unwrap();
+    let build_pool = |max_attempts| {
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let mut manager_config = ManagerConfig::default();
+        manager_config.custom_setup = Box::new({
+            let attempts = attempts.clone();
+            // the first two attempts fail
+            move |url| {
+                let attempt = attempts.fetch_add(1, Ordering::SeqCst);
+                async move {
+                    if attempt < 2 {
+                        Err(diesel::ConnectionError::BadConnection(String::from(
+                            "database is restarting",
+                        )))
+                    } else {
+                        AsyncPgConnection::establish(url).await
+                    }
+                }
+                .boxed()
+            }
+        });
+        manager_config.reconnect_backoff =
+            Some(BackoffPolicy::constant(Duration::from_millis(1)).max_attempts(max_attempts));
+        let config = AsyncDieselConnectionManager::<AsyncPgConnection>::new_with_config(
+            db_url.clone(),
+            manager_config,
+        );
+        (Pool::builder(config).max_size(1).build().unwrap(), attempts)
+    };
+
+    let (pool, attempts) = build_pool(3);
+    assert!(pool.get().await.is_ok());
+    assert_eq!(3, AtomicUsize::load(&attempts, Ordering::SeqCst));
+
+    let (pool, attempts) = build_pool(2);
+    assert!(pool.get().await.is_err());
+    assert_eq!(2, AtomicUsize::load(&attempts, Ordering::SeqCst));
+}
+
+#[tokio::test]
+#[cfg(feature = "deadpool")]
+async fn checkout_timer_reports_wait_time() {
+    use diesel_async::pooled_connection::deadpool::Pool;
+    use diesel_async::pooled_connection::{
+        AsyncDieselConnectionManager, CheckoutTimer, CheckoutWait,
+    };
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    let db_url = std::env::var("DATABASE_URL").unwrap();
+    let waits = Arc::new(Mutex::new(Vec::<CheckoutWait>::new()));
+    let timer = CheckoutTimer::new({
+        let waits = waits.clone();
+        move |wait| waits.lock().unwrap().push(wait)
+    });
+    let config = AsyncDieselConnectionManager::<super::TestConnection>::new(db_url);
+    let pool = Pool::builder(config)
+        .max_size(1)
+        .wait_timeout(Some(Duration::from_millis(200)))
+        .runtime(deadpool::Runtime::Tokio1)
+        .build()
+        .unwrap();
+
+    let conn = timer.checkout(pool.get()).await.unwrap();
+    // the pool is exhausted until the connection is returned
+    assert!(timer.checkout(pool.get()).await.is_err());
+    let release = tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        drop(conn);
+    });
+    assert!(timer.checkout(pool.get()).await.is_ok());
+    release.await.unwrap();
+
+    let waits = waits.lock().unwrap();
+    assert_eq!(3, waits.len());
+    assert!(waits[0].succeeded);
+    assert!(!waits[1].succeeded);
+    assert!(waits[1].duration >= Duration::from_millis(200));
+    assert!(waits[2].succeeded);
+    assert!(waits[2].duration >= Duration::from_millis(50));
+}
+
+#[tokio::test]
+#[cfg(all(feature = "deadpool", feature = "postgres"))]
+async fn yugabyte_load_balancer_chooses_nodes_by_topology() {
+    use diesel_async::{AsyncConnection, AsyncPgConnection, SimpleAsyncConnection};
+    use futures_util::FutureExt;
+    use std::panic::AssertUnwindSafe;
+
+    let db_url = std::env::var("DATABASE_URL").unwrap();
+    let config = db_url.parse::<tokio_postgres::Config>().unwrap();
+    let Some(tokio_postgres::config::Host::Tcp(host)) = config.get_hosts().first() else {
+        return;
+    };
+    let port = config.get_ports().first().copied().unwrap_or(5432);
+    let mut conn = AsyncPgConnection::establish(&db_url).await.unwrap();
+    // emulates a cluster with an unreachable node in the first zone and the
+    // test database in the second. The function is created in a separate
+    // schema, which is only part of the search path of the balanced connections
+    conn.batch_execute(&format!(
+        "DROP SCHEMA IF EXISTS yugabyte_load_balancer CASCADE; \
+         CREATE SCHEMA yugabyte_load_balancer; \
+         CREATE FUNCTION yugabyte_load_balancer.yb_servers() RETURNS TABLE(host text, \
+         port bigint, cloud text, region text, zone text, public_ip text) AS $$ VALUES \
+         ('127.0.0.1', 1::bigint, 'cloud1', 'region1', 'zone1', NULL::text), \
+         ('{host}', {port}, 'cloud1', 'region1', 'zone2', NULL) $$ LANGUAGE sql"
+    ))
+    .await
+    .unwrap();
+    let separator = if db_url.contains('?') { '&' } else { '?' };
+    let balanced_url =
+        format!("{db_url}{separator}options=-c%20search_path%3Dyugabyte_load_balancer");
+
+    let res = AssertUnwindSafe(check_yugabyte_load_balancer(&balanced_url))
+        .catch_unwind()
+        .await;
+    conn.batch_execute("DROP SCHEMA yugabyte_load_balancer CASCADE")
+        .await
+        .unwrap();
+    if let Err(panic) = res {
+        std::panic::resume_unwind(panic);
+    }
+}
+
+#[cfg(all(feature = "deadpool", feature = "postgres"))]
+async fn check_yugabyte_load_balancer(db_url: &str) {
+    use diesel_async::pooled_connection::deadpool::Pool;
+    use diesel_async::pooled_connection::{
+        AsyncDieselConnectionManager, ManagerConfig, YugabyteLoadBalancer,
+    };
+    use diesel_async::AsyncPgConnection;
+    use std::time::Duration;
+
+    let balancer = YugabyteLoadBalancer::new();
+    let balanced = balancer.establish(db_url).await.unwrap();
+    let nodes = balancer.nodes();
+    assert_eq!(2, nodes.len());
+    assert_eq!(("zone1", 0), (nodes[0].zone.as_str(), nodes[0].connections));
+    assert_eq!(("zone2", 1), (nodes[1].zone.as_str(), nodes[1].connections));
+    drop(balanced);
+    let mut closed = false;
+    for _ in 0..100 {
+        if balancer.nodes()[1].connections == 0 {
+            closed = true;
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert!(closed);
+
+    let balancer = YugabyteLoadBalancer::new()
+        .prefer("cloud1.region1.zone1")
+        .fallback_to_any_node(false);
+    assert!(balancer.establish(db_url).await.is_err());
+
+    // failed nodes are attempted only once per call, even
+    // if they are considered available again right away
+    let balancer = YugabyteLoadBalancer::new()
+        .prefer("cloud1.region1.zone1")
+        .fallback_to_any_node(false)
+        .failed_node_delay(Duration::ZERO);
+    assert!(balancer.establish(db_url).await.is_err());
+
+    let balancer = YugabyteLoadBalancer::new().prefer("cloud1.*.zone2");
+    let mut manager_config = ManagerConfig::default();
+    manager_config.custom_setup = balancer.setup();
+    let manager =
+        AsyncDieselConnectionManager::<AsyncPgConnection>::new_with_config(db_url, manager_config);
+    let pool = Pool::builder(manager).build().unwrap();
+    let _first = pool.get().await.unwrap();
+    let _second = pool.get().await.unwrap();
+    assert_eq!(2, balancer.nodes()[1].connections);
+}
+
+#[tokio::test]
+#[cfg(all(feature = "deadpool", feature = "postgres"))]
+async fn on_event_reports_connection_lifecycle() {
+    use diesel_async::pooled_connection::deadpool::Pool;
+    use diesel_async::pooled_connection::{
+        AsyncDieselConnectionManager, BrokenReason, ConnectionEvent, ConnectionLabels,
+        ManagerConfig, PoolHealthCheck, RecyclingMethod,
+    };
+    use diesel_async::AsyncPgConnection;
+    use std::sync::{Arc, Mutex};
+
+    let db_url = std::env::var("DATABASE_URL").unwrap();
+    let events = Arc::new(Mutex::new(Vec::new()));
+
+    let mut manager_config = ManagerConfig::default();
+    // fails the check of each recycled connection
+    manager_config.recycling_method = RecyclingMethod::CustomQuery("SELECT 1 / 0".into());
+    manager_config.labels = ConnectionLabels::new().with("shard", "1");
+    manager_config.on_event = Some(Box::new({
+        let events = events.clone();
+        move |labels, event| {
+            assert_eq!(Some("1"), labels.get("shard"));
+            let event = match event {
+                ConnectionEvent::Connected { .. } => "connected",
+                ConnectionEvent::Broken {
+                    reason: BrokenReason::CheckFailed,
+                    error: Some(_),
+                } => "check failed",
+                event => panic!("Unexpected event: {event:?}"),
+            };
+            events.lock().unwrap().push(event);
+        }
+    }));
+    let config =
+        AsyncDieselConnectionManager::<AsyncPgConnection>::new_with_config(db_url, manager_config);
+    let pool = Pool::builder(config).max_size(1).build().unwrap();
+
+    drop(pool.get().await.unwrap());
+    drop(pool.get().await.unwrap());
+    assert_eq!(
+        vec!["connected", "check failed", "connected"],
+        *events.lock().unwrap()
+    );
+
+    let status = pool.health_check().await;
+    assert_eq!(
+        vec![("shard", "1")],
+        status.labels.iter().collect::<Vec<_>>()
+    );
+}
+
+#[tokio::test]
+#[cfg(all(feature = "deadpool", feature = "postgres"))]
+async fn dangling_transactions_are_rolled_back_on_recycle() {
+    use diesel::sql_types::Integer;
+    use diesel_async::pooled_connection::deadpool::Pool;
+    use diesel_async::pooled_connection::AsyncDieselConnectionManager;
+    use diesel_async::{AnsiTransactionManager, AsyncPgConnection, TransactionManager};
+
+    let db_url = std::env::var("DATABASE_URL").unwrap();
+    let config = AsyncDieselConnectionManager::<AsyncPgConnection>::new(db_url);
+    let pool = Pool::builder(config).max_size(1).build().unwrap();
+
+    let backend_pid = diesel::dsl::sql::<Integer>("pg_backend_pid()");
+    let mut conn = pool.get().await.unwrap();
+    let first_pid = diesel::select(backend_pid.clone())
+        .get_result::<i32>(&mut conn)
+        .await
+        .unwrap();
+    // simulates user code returning early from inside of a transaction
+    AnsiTransactionManager::begin_transaction(&mut *conn)
+        .await
+        .unwrap();
+    drop(conn);
+
+    let mut conn = pool.get().await.unwrap();
+    let second_pid = diesel::select(backend_pid)
+        .get_result::<i32>(&mut conn)
+        .await
+        .unwrap();
+    assert_eq!(first_pid, second_pid);
+    let status = AnsiTransactionManager::transaction_manager_status_mut(&mut *conn);
+    assert_eq!(None, status.transaction_depth().unwrap());
+}
+
+#[tokio::test]
+#[cfg(all(feature = "mobc", feature = "postgres"))]
+async fn dangling_transactions_are_rolled_back_on_mobc_checkout() {
+    use diesel::sql_types::Integer;
+    use diesel_async::pooled_connection::mobc::Pool;
+    use diesel_async::pooled_connection::AsyncDieselConnectionManager;
+    use diesel_async::{AnsiTransactionManager, AsyncPgConnection, TransactionManager};
+
+    let db_url = std::env::var("DATABASE_URL").unwrap();
+    let config = AsyncDieselConnectionManager::<AsyncPgConnection>::new(db_url);
+    let pool = Pool::builder().max_open(1).build(config);
+
+    let backend_pid = diesel::dsl::sql::<Integer>("pg_backend_pid()");
+    let mut conn = pool.get().await.unwrap();
+    let first_pid = diesel::select(backend_pid.clone())
+        .get_result::<i32>(&mut conn)
+        .await
+        .unwrap();
+    // simulates user code returning early from inside of a transaction
+    AnsiTransactionManager::begin_transaction(&mut *conn)
+        .await
+        .unwrap();
+    drop(conn);
+
+    let mut conn = pool.get().await.unwrap();
+    let second_pid = diesel::select(backend_pid)
+        .get_result::<i32>(&mut conn)
+        .await
+        .unwrap();
+    assert_eq!(first_pid, second_pid);
+    let status = AnsiTransactionManager::transaction_manager_status_mut(&mut *conn);
+    assert_eq!(None, status.transaction_depth().unwrap());
+}
+
+#[tokio::test]
+#[cfg(all(feature = "deadpool", feature = "postgres"))]
+async fn circuit_breaker_fails_fast_while_open() {
+    use diesel_async::pooled_connection::deadpool::Pool;
+    use diesel_async::pooled_connection::{
+        AsyncDieselConnectionManager, CircuitBreakerConfig, ManagerConfig,
+    };
+    use diesel_async::AsyncPgConnection;
+    use futures_util::FutureExt;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    let attempts = Arc::new(AtomicUsize::new(0));
+
+    let mut manager_config = ManagerConfig::<AsyncPgConnection>::default();
+    manager_config.custom_setup = Box::new({
+        let attempts = attempts.clone();
+        move |_url| {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            // simulates an unreachable database
+            async {
+                Err(diesel::ConnectionError::BadConnection(String::from(
+                    "unreachable",
+                )))
+            }
+            .boxed()
+        }
+    });
+    manager_config.circuit_breaker = Some(CircuitBreakerConfig::new(2, Duration::from_millis(100)));
+    let config = AsyncDieselConnectionManager::<AsyncPgConnection>::new_with_config(
+        "postgres://unreachable",
+        manager_config,
+    );
+    let pool = Pool::builder(config).max_size(1).build().unwrap();
+
+    for _ in 0..3 {
+        assert!(pool.get().await.is_err());
+    }
+    // the third checkout failed without attempting to connect
+    assert_eq!(2, AtomicUsize::load(&attempts, Ordering::SeqCst));
+
+    tokio::time::sleep(Duration::from_millis(150)).await;
+    assert!(pool.get().await.is_err());
+    assert!(pool.get().await.is_err());
+    // only a single probe is let through once the reset timeout passed
+    assert_eq!(3, AtomicUsize::load(&attempts, Ordering::SeqCst));
+}
+
+#[tokio::test]
+#[cfg(all(feature = "bb8", feature = "postgres"))]
+async fn health_check_reports_probe_results() {
+    use diesel_async::pooled_connection::bb8::Pool;
+    use diesel_async::pooled_connection::{
+        AsyncDieselConnectionManager, ManagerConfig, PoolHealthCheck,
+    };
+    use diesel_async::AsyncPgConnection;
+    use futures_util::FutureExt;
+    use std::time::Duration;
+
+    let db_url = std::env::var("DATABASE_URL").unwrap();
+    let config = AsyncDieselConnectionManager::<AsyncPgConnection>::new(db_url);
+    let pool = Pool::builder().max_size(1).build(config).await.unwrap();
+
+    let status = pool.health_check().await;
+    assert!(status.is_healthy(), "{status:?}");
+    assert!(status.latency.is_some());
+    // the connection checked out by the first probe is reused
+    let status = pool.health_check().await;
+    assert!(status.is_healthy(), "{status:?}");
+    assert_eq!(1, status.connections);
+    assert_eq!(1, status.idle_connections);
+
+    let mut manager_config = ManagerConfig::<AsyncPgConnection>::default();
+    manager_config.custom_setup = Box::new(|_url| {
+        async {
+            Err(diesel::ConnectionError::BadConnection(String::from(
+                "unreachable",
+            )))
+        }
+        .boxed()
+    });
+    let config = AsyncDieselConnectionManager::<AsyncPgConnection>::new_with_config(
+        "postgres://unreachable",
+        manager_config,
+    );
+    let pool = Pool::builder()
+        .connection_timeout(Duration::from_millis(100))
+        .build_unchecked(config);
+
+    let status = pool.health_check().await;
+    assert!(!status.is_healthy());
+    assert!(status.latency.is_none());
+    assert_eq!(0, status.connections);
+}
+
+#[tokio::test]
+#[cfg(all(feature = "deadpool", feature = "postgres"))]
+async fn priority_gate_reserves_connections_for_interactive_checkouts() {
+    use diesel_async::pooled_connection::deadpool::Pool;
+    use diesel_async::pooled_connection::{
+        AsyncDieselConnectionManager, CheckoutPriority, PriorityGate,
+    };
+    use diesel_async::AsyncPgConnection;
+    use std::time::Duration;
+
+    let db_url = std::env::var("DATABASE_URL").unwrap();
+    let config = AsyncDieselConnectionManager::<AsyncPgConnection>::new(db_url);
+    let pool = Pool::builder(config).max_size(2).build().unwrap();
+    let gate = PriorityGate::new(1);
+
+    let batch = gate
+        .checkout(CheckoutPriority::Batch, pool.get())
+        .await
+        .unwrap();
+    // the second batch checkout waits for the first one
+    let second_batch = gate.checkout(CheckoutPriority::Batch, pool.get());
+    assert!(
+        tokio::time::timeout(Duration::from_millis(50), second_batch)
+            .await
+            .is_err()
+    );
+    let mut interactive = gate
+        .checkout(CheckoutPriority::Interactive, pool.get())
+        .await
+        .unwrap();
+    diesel::select(1.into_sql::<diesel::sql_types::Integer>())
+        .execute(&mut interactive)
+        .await
+        .unwrap();
+
+    drop(batch);
+    gate.checkout(CheckoutPriority::Batch, pool.get())
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+#[cfg(all(feature = "deadpool", feature = "postgres"))]
+async fn connections_held_too_long_are_reclaimed() {
+    use diesel_async::pooled_connection::deadpool::Pool;
+    use diesel_async::pooled_connection::{
+        AsyncDieselConnectionManager, MaxCheckoutAction, MaxCheckoutDuration,
+    };
+    use diesel_async::AsyncPgConnection;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    let db_url = std::env::var("DATABASE_URL").unwrap();
+    let config = AsyncDieselConnectionManager::<AsyncPgConnection>::new(db_url);
+    let pool = Pool::builder(config).max_size(1).build().unwrap();
+    let exceeded = Arc::new(AtomicBool::new(false));
+    let max_checkout =
+        MaxCheckoutDuration::new(Duration::from_millis(50), MaxCheckoutAction::Reclaim)
+            .on_exceeded({
+                let exceeded = exceeded.clone();
+                move |_| exceeded.store(true, Ordering::SeqCst)
+            });
+
+    let backend_pid = diesel::dsl::sql::<diesel::sql_types::Integer>("pg_backend_pid()");
+    let mut conn = max_checkout.checkout(pool.get()).await.unwrap();
+    let first_pid = diesel::select(backend_pid.clone())
+        .get_result::<i32>(&mut conn)
+        .await
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(AtomicBool::load(&exceeded, Ordering::SeqCst));
+    assert!(diesel::select(backend_pid.clone())
+        .get_result::<i32>(&mut conn)
+        .await
+        .is_err());
+    drop(conn);
+
+    let mut conn = max_checkout.checkout(pool.get()).await.unwrap();
+    let second_pid = diesel::select(backend_pid)
+        .get_result::<i32>(&mut conn)
+        .await
+        .unwrap();
+    assert_ne!(first_pid, second_pid);
+}
+
+#[tokio::test]
+#[cfg(all(feature = "deadpool", feature = "postgres"))]
+async fn notification_listener_survives_lost_connections() {
+    use diesel_async::pooled_connection::{AsyncDieselConnectionManager, NotificationListener};
+    use diesel_async::{AsyncConnection, AsyncPgConnection, SimpleAsyncConnection};
+    use futures_util::StreamExt;
+    use std::collections::HashSet;
+    use std::time::Duration;
+
+    async fn receive_all(conn: &mut AsyncPgConnection, listener: &mut NotificationListener) {
+        let mut pending = HashSet::from(["listener_a", "listener_b", "listener_c"]);
+        // The listener connections might not listen yet, so notify until
+        // each channel reported a notification
+        for _ in 0..50 {
+            conn.batch_execute("NOTIFY listener_a; NOTIFY listener_b; NOTIFY listener_c")
+                .await
+                .unwrap();
+            while let Ok(Some(notification)) =
+                tokio::time::timeout(Duration::from_millis(100), listener.next()).await
+            {
+                pending.remove(notification.channel());
+            }
+            if pending.is_empty() {
+                return;
+            }
+        }
+        panic!("Missing notifications for {pending:?}");
+    }
+
+    let db_url = std::env::var("DATABASE_URL").unwrap();
+    let manager = AsyncDieselConnectionManager::<AsyncPgConnection>::new(db_url.clone());
+    let mut listener =
+        NotificationListener::new(manager, 2, ["listener_a", "listener_b", "listener_c"]);
+    let conn = &mut AsyncPgConnection::establish(&db_url).await.unwrap();
+
+    receive_all(conn, &mut listener).await;
+
+    conn.batch_execute(
+        "SELECT pg_terminate_backend(pid) FROM pg_stat_activity \
+         WHERE query LIKE 'LISTEN \"listener_%'",
+    )
+    .await
+    .unwrap();
+    receive_all(conn, &mut listener).await;
+}
+
+#[tokio::test]
+#[cfg(all(feature = "bb8", feature = "postgres"))]
+async fn drained_pools_resolve_once_all_connections_are_closed() {
+    use diesel_async::pooled_connection::bb8::Pool;
+    use diesel_async::pooled_connection::AsyncDieselConnectionManager;
+    use diesel_async::AsyncPgConnection;
+    use std::time::Duration;
+
+    let db_url = std::env::var("DATABASE_URL").unwrap();
+    let config = AsyncDieselConnectionManager::<AsyncPgConnection>::new(db_url);
+    let drain = config.drain_handle();
+    let pool = Pool::builder()
+        .max_size(2)
+        .connection_timeout(Duration::from_millis(100))
+        .build(config)
+        .await
+        .unwrap();
+
+    let conn = pool.get().await.unwrap();
+    let closed = drain.closed();
+    tokio::pin!(closed);
+    // nothing is closed before a drain was requested
+    assert!(tokio::time::timeout(Duration::from_millis(50), &mut closed)
+        .await
+        .is_err());
+
+    drain.drain();
+    assert!(drain.is_draining());
+    assert!(pool.get().await.is_err());
+    // the checked out connection is still open
+    assert!(tokio::time::timeout(Duration::from_millis(50), &mut closed)
+        .await
+        .is_err());
+
+    // bb8 discards the connection once it is returned
+    drop(conn);
+    tokio::time::timeout(Duration::from_secs(5), closed)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+#[cfg(all(feature = "bb8", feature = "deadpool", feature = "postgres"))]
+async fn pool_errors_convert_into_a_common_error() {
+    use diesel_async::pooled_connection::{
+        bb8, deadpool, AsyncDieselConnectionManager, ManagerConfig, PoolError,
+    };
+    use diesel_async::AsyncPgConnection;
+    use futures_util::FutureExt;
+    use std::time::Duration;
+
+    let db_url = std::env::var("DATABASE_URL").unwrap();
+    let config = AsyncDieselConnectionManager::<AsyncPgConnection>::new(db_url);
+    let pool = bb8::Pool::builder()
+        .max_size(1)
+        .connection_timeout(Duration::from_millis(100))
+        .build(config)
+        .await
+        .unwrap();
+    let _conn = pool.get().await.unwrap();
+    let err = PoolError::from(pool.get().await.err().unwrap());
+    assert!(matches!(err, PoolError::Timeout), "{err:?}");
+
+    let mut manager_config = ManagerConfig::<AsyncPgConnection>::default();
+    manager_config.custom_setup = Box::new(|_url| {
+        async {
+            Err(diesel::ConnectionError::BadConnection(String::from(
+                "unreachable",
+            )))
+        }
+        .boxed()
+    });
+    let config = AsyncDieselConnectionManager::<AsyncPgConnection>::new_with_config(
+        "postgres://unreachable",
+        manager_config,
+    );
+    let pool = deadpool::Pool::builder(config).build().unwrap();
+    let err = PoolError::from(pool.get().await.err().unwrap());
+    assert!(matches!(err, PoolError::ConnectionError(_)), "{err:?}");
+    pool.close();
+    let err = PoolError::from(pool.get().await.err().unwrap());
+    assert!(matches!(err, PoolError::Closed), "{err:?}");
+}
+
+#[tokio::test]
+#[cfg(all(feature = "bb8", feature = "deadpool", feature = "mobc"))]
+async fn pools_from_url_run_queries() {
+    use diesel_async::pooled_connection::{bb8, deadpool, mobc, PoolFromUrl};
+
+    let db_url = std::env::var("DATABASE_URL").unwrap();
+    let query = || diesel::select(1.into_sql::<diesel::sql_types::Integer>());
+
+    let pool = deadpool::Pool::<super::TestConnection>::from_url(db_url.clone());
+    let one = query()
+        .get_result::<i32>(&mut pool.get().await.unwrap())
+        .await;
+    assert_eq!(Ok(1), one);
+    assert_eq!(16, pool.status().max_size);
+
+    let pool = bb8::Pool::<super::TestConnection>::from_url(db_url.clone());
+    let one = query()
+        .get_result::<i32>(&mut pool.get().await.unwrap())
+        .await;
+    assert_eq!(Ok(1), one);
+
+    let pool = mobc::Pool::<super::TestConnection>::from_url(db_url);
+    let one = query()
+        .get_result::<i32>(&mut pool.get().await.unwrap())
+        .await;
+    assert_eq!(Ok(1), one);
+}
@@ -0,0 +1,117 @@
+use crate::{connection, users};
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+
+#[tokio::test]
+async fn serialized_query_runs_on_any_connection() {
+    use diesel::sql_types::{Integer, Text};
+    use diesel_async::pg::SerializedQuery;
+
+    #[derive(diesel::QueryableByName, Debug, PartialEq)]
+    struct Row {
+        #[diesel(sql_type = Text)]
+        name: String,
+        #[diesel(sql_type = Integer)]
+        n: i32,
+    }
+
+    let query = SerializedQuery::new(
+        diesel::sql_query("SELECT $1 AS name, $2 AS n")
+            .bind::<Text, _>("Sean")
+            .bind::<Integer, _>(42),
+    )
+    .unwrap();
+    assert_eq!("SELECT $1 AS name, $2 AS n", query.sql());
+
+    let expected = vec![Row {
+        name: "Sean".into(),
+        n: 42,
+    }];
+    for _ in 0..2 {
+        let conn = &mut connection().await;
+        assert_eq!(expected, conn.load_serialized::<Row>(&query).await.unwrap());
+        // the statement might be cached now
+        assert_eq!(expected, conn.load_serialized::<Row>(&query).await.unwrap());
+        assert_eq!(1, conn.execute_serialized(&query).await.unwrap());
+    }
+}
+
+#[tokio::test]
+async fn prepared_query_checks_connection_and_sql() {
+    use diesel::sql_types::Integer;
+
+    let conn = &mut connection().await;
+    let other = &mut connection().await;
+    let query = |sql: &str, n: i32| diesel::sql_query(sql.to_owned()).bind::<Integer, _>(n);
+
+    let prepared = conn.prepare_query(query("SELECT $1", 1)).await.unwrap();
+    assert_eq!(
+        1,
+        prepared
+            .execute_query(conn, query("SELECT $1", 2))
+            .await
+            .unwrap()
+    );
+
+    let res = prepared
+        .execute_query(conn, query("SELECT $1 + 1", 2))
+        .await;
+    assert!(
+        matches!(res, Err(diesel::result::Error::QueryBuilderError(_))),
+        "{res:?}"
+    );
+    let res = prepared.execute_query(other, query("SELECT $1", 2)).await;
+    assert!(
+        matches!(res, Err(diesel::result::Error::QueryBuilderError(_))),
+        "{res:?}"
+    );
+}
+
+#[tokio::test]
+async fn prepared_query_checks_sql_of_optional_values() {
+    let conn = &mut connection().await;
+    let insert = |id: Option<i32>, name: &'static str| {
+        diesel::insert_into(users::table)
+            .values((id.map(|id| users::id.eq(id)), users::name.eq(name)))
+    };
+
+    // `None` renders `DEFAULT` instead of a bind parameter, so the SQL of
+    // the query depends on the values even though the type is the same
+    let prepared = conn.prepare_query(insert(Some(42), "Sean")).await.unwrap();
+    let res = prepared.execute_query(conn, insert(None, "Tess")).await;
+    assert!(
+        matches!(res, Err(diesel::result::Error::QueryBuilderError(_))),
+        "{res:?}"
+    );
+    assert_eq!(
+        1,
+        prepared
+            .execute_query(conn, insert(Some(43), "Tess"))
+            .await
+            .unwrap()
+    );
+}
+
+#[tokio::test]
+async fn reports_preparation_errors_of_cache_misses() {
+    diesel::table! {
+        no_such_table {
+            id -> Integer,
+        }
+    }
+
+    // connections run inside of a test transaction, which is aborted by the
+    // failed preparation before the pipelined execution of the query
+    let conn = &mut connection().await;
+    let res = no_such_table::table
+        .filter(no_such_table::id.eq(1))
+        .select(no_such_table::id)
+        .load::<i32>(conn)
+        .await;
+    match res {
+        Err(diesel::result::Error::DatabaseError(_, info)) => {
+            assert_eq!("relation \"no_such_table\" does not exist", info.message());
+        }
+        res => panic!("Unexpected result: {res:?}"),
+    }
+}
@@ -0,0 +1,137 @@
+use crate::users;
+use diesel::prelude::*;
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl, SimpleAsyncConnection};
+use scoped_futures::ScopedFutureExt;
+
+#[tokio::test]
+async fn query_cache_is_invalidated_by_writes() {
+    use diesel_async::pg::QueryCache;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    let db_url = std::env::var("DATABASE_URL").unwrap();
+    // `connection()` opens a test transaction, which would bypass the cache
+    let conn = &mut AsyncPgConnection::establish(&db_url).await.unwrap();
+    crate::setup(conn).await;
+    let cache = QueryCache::new(Duration::from_secs(60));
+    let query = || users::table.select(users::name).order(users::id);
+    let insert = |name| diesel::insert_into(users::table).values(users::name.eq(name));
+
+    let names = cache.load_query::<_, String>(conn, query(), &["users"]);
+    assert!(names.await.unwrap().is_empty());
+    // not observed by the cache yet
+    insert("Sean").execute(conn).await.unwrap();
+    let names = cache.load_query::<_, String>(conn, query(), &["users"]);
+    assert!(names.await.unwrap().is_empty());
+
+    conn.add_interceptor(Arc::new(cache.clone()));
+    insert("Tess").execute(conn).await.unwrap();
+    let names = cache.load_query::<_, String>(conn, query(), &["users"]);
+    assert_eq!(vec!["Sean", "Tess"], names.await.unwrap());
+
+    let names = conn
+        .transaction::<_, diesel::result::Error, _>(|conn| {
+            let cache = cache.clone();
+            async move {
+                insert("Ruby").execute(conn).await?;
+                cache
+                    .load_query::<_, String>(conn, query(), &["users"])
+                    .await
+            }
+            .scope_boxed()
+        })
+        .await
+        .unwrap();
+    assert_eq!(3, names.len());
+
+    let expiring = QueryCache::new(Duration::ZERO);
+    let names = expiring.load_query::<_, String>(conn, query(), &["users"]);
+    assert_eq!(3, names.await.unwrap().len());
+    diesel::delete(users::table).execute(conn).await.unwrap();
+    let names = expiring.load_query::<_, String>(conn, query(), &["users"]);
+    assert!(names.await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn query_cache_is_invalidated_on_commit() {
+    use diesel::dsl::sql;
+    use diesel::sql_types::BigInt;
+    use diesel_async::pg::QueryCache;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    let db_url = std::env::var("DATABASE_URL").unwrap();
+    let writer = &mut AsyncPgConnection::establish(&db_url).await.unwrap();
+    let reader = &mut AsyncPgConnection::establish(&db_url).await.unwrap();
+    writer
+        .batch_execute(
+            "DROP TABLE IF EXISTS query_cache_first, query_cache_second; \
+             CREATE TABLE query_cache_first (id INTEGER); \
+             CREATE TABLE query_cache_second (id INTEGER);",
+        )
+        .await
+        .unwrap();
+    let cache = QueryCache::new(Duration::from_secs(60));
+    writer.add_interceptor(Arc::new(cache.clone()));
+    reader.add_interceptor(Arc::new(cache.clone()));
+    async fn counts(cache: &QueryCache, conn: &mut AsyncPgConnection) -> Vec<i64> {
+        let mut counts = Vec::new();
+        for table in ["query_cache_first", "query_cache_second"] {
+            let count = diesel::select(sql::<BigInt>(&format!("count(*) FROM {table}")));
+            counts.extend(
+                cache
+                    .load_query::<_, i64>(conn, count, &[table])
+                    .await
+                    .unwrap(),
+            );
+        }
+        counts
+    }
+
+    let cached = writer
+        .transaction::<_, diesel::result::Error, _>(|conn| {
+            let (cache, reader) = (&cache, &mut *reader);
+            async move {
+                conn.batch_execute("INSERT INTO query_cache_first (id) VALUES (1)")
+                    .await?;
+                // caches the results of before the commit
+                Ok(counts(cache, reader).await)
+            }
+            .scope_boxed()
+        })
+        .await
+        .unwrap();
+    assert_eq!(vec![0, 0], cached);
+    assert_eq!(vec![1, 0], counts(&cache, reader).await);
+
+    // all statements of a batch invalidate the cache
+    writer
+        .batch_execute(
+            "INSERT INTO query_cache_second (id) VALUES (1); \
+             INSERT INTO query_cache_first (id) VALUES (2)",
+        )
+        .await
+        .unwrap();
+    assert_eq!(vec![2, 1], counts(&cache, reader).await);
+    writer
+        .batch_execute("TRUNCATE query_cache_first, query_cache_second")
+        .await
+        .unwrap();
+    assert_eq!(vec![0, 0], counts(&cache, reader).await);
+
+    // semicolons inside of dollar quoted strings and comments do not hide writes
+    writer
+        .batch_execute(
+            "SELECT $$;$$, $tag$ ; $tag$ /* ; */; -- ;\n\
+             INSERT INTO query_cache_first (id) VALUES (length($$a;b$$)); \
+             INSERT /* ; */ INTO -- ;\n query_cache_second (id) VALUES (1)",
+        )
+        .await
+        .unwrap();
+    assert_eq!(vec![1, 1], counts(&cache, reader).await);
+
+    writer
+        .batch_execute("DROP TABLE query_cache_first, query_cache_second")
+        .await
+        .unwrap();
+}
@@ -0,0 +1,53 @@
+use crate::{connection, users};
+use diesel::prelude::*;
+use diesel_async::{ReadOnly, RunQueryDsl};
+
+#[tokio::test]
+async fn read_only_connection_loads_results() {
+    let mut conn = connection().await;
+    diesel::insert_into(users::table)
+        .values(users::name.eq("John Doe"))
+        .execute(&mut conn)
+        .await
+        .unwrap();
+
+    let mut conn = ReadOnly::new(conn);
+    let names = conn
+        .load_query::<_, String>(users::table.select(users::name))
+        .await
+        .unwrap();
+    assert_eq!(vec!["John Doe"], names);
+    let not_found = conn
+        .get_query_result::<_, String>(users::table.select(users::name).filter(users::id.eq(-1)))
+        .await;
+    assert!(matches!(not_found, Err(diesel::result::Error::NotFound)));
+}
+
+#[cfg(feature = "postgres")]
+#[tokio::test]
+async fn postgres_read_only_session_rejects_writes() {
+    use diesel::dsl::sql;
+    use diesel::sql_types::Text;
+    use diesel_async::{AsyncConnection, AsyncPgConnection, SimpleAsyncConnection};
+
+    let db_url = std::env::var("DATABASE_URL").unwrap();
+    let conn = AsyncPgConnection::establish(&db_url).await.unwrap();
+    let mut conn = ReadOnly::with_read_only_session(conn).await.unwrap();
+    let read_only = conn
+        .get_query_result::<_, String>(diesel::select(sql::<Text>(
+            "current_setting('transaction_read_only')",
+        )))
+        .await
+        .unwrap();
+    assert_eq!("on", read_only);
+
+    // the session stays read only after unwrapping the connection
+    let mut conn = conn.into_inner();
+    let res = conn
+        .batch_execute("CREATE TABLE read_only_session_test (id INTEGER)")
+        .await;
+    assert!(
+        matches!(res, Err(diesel::result::Error::DatabaseError(_, ref info)) if info.message().contains("read-only transaction")),
+        "{res:?}"
+    );
+}
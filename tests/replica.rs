@@ -0,0 +1,90 @@
+use crate::users;
+use diesel::prelude::*;
+use diesel::QueryResult;
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
+use scoped_futures::ScopedFutureExt;
+
+#[tokio::test]
+async fn replica_guard_rejects_writes() {
+    use diesel::dsl::sql;
+    use diesel::sql_types::Text;
+    use diesel_async::pg::{ReplicaGuard, ReplicaWriteError};
+    use std::sync::Arc;
+
+    fn rejected_keyword<T: std::fmt::Debug>(res: QueryResult<T>) -> String {
+        let Err(diesel::result::Error::QueryBuilderError(e)) = res else {
+            panic!("Expected the query to be rejected: {res:?}");
+        };
+        e.downcast_ref::<ReplicaWriteError>()
+            .unwrap()
+            .keyword()
+            .to_owned()
+    }
+
+    let db_url = std::env::var("DATABASE_URL").unwrap();
+    let conn = &mut AsyncPgConnection::establish(&db_url).await.unwrap();
+    crate::setup(conn).await;
+    conn.add_interceptor(Arc::new(ReplicaGuard));
+
+    let read_only = conn
+        .transaction::<_, diesel::result::Error, _>(|conn| {
+            async move {
+                diesel::select(sql::<Text>("current_setting('transaction_read_only')"))
+                    .get_result::<String>(conn)
+                    .await
+            }
+            .scope_boxed()
+        })
+        .await
+        .unwrap();
+    assert_eq!("on", read_only);
+    let res = conn
+        .build_transaction()
+        .read_write()
+        .run::<_, diesel::result::Error, _>(|_| async { Ok(()) }.scope_boxed())
+        .await;
+    assert_eq!("READ WRITE", rejected_keyword(res));
+
+    let res = diesel::insert_into(users::table)
+        .values(users::name.eq("John Doe"))
+        .execute(conn)
+        .await;
+    assert_eq!("INSERT", rejected_keyword(res));
+    let res = users::table
+        .select(users::name)
+        .for_update()
+        .load::<String>(conn)
+        .await;
+    assert_eq!("UPDATE", rejected_keyword(res));
+    let res = users::table
+        .select(users::name)
+        .for_share()
+        .load::<String>(conn)
+        .await;
+    assert_eq!("FOR SHARE", rejected_keyword(res));
+    let res = users::table
+        .select(users::name)
+        .for_key_share()
+        .load::<String>(conn)
+        .await;
+    assert_eq!("FOR KEY SHARE", rejected_keyword(res));
+
+    // keywords inside of string literals are no writes
+    let names = users::table
+        .select(users::name)
+        .filter(users::name.eq("DELETE"))
+        .or_filter(sql::<diesel::sql_types::Bool>("name = 'DROP TABLE users'"))
+        .or_filter(sql::<diesel::sql_types::Bool>(
+            "name = $$DROP TABLE users$$",
+        ))
+        .or_filter(sql::<diesel::sql_types::Bool>(
+            "name = $tag$ $$ DROP TABLE users $tag$",
+        ))
+        .or_filter(sql::<diesel::sql_types::Bool>(
+            "name = E'it\\'s DROP TABLE users'",
+        ))
+        .load::<String>(conn)
+        .await
+        .unwrap();
+    assert!(names.is_empty());
+}
@@ -0,0 +1,141 @@
+use diesel::QueryResult;
+use diesel_async::retry::RetryPolicy;
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
+use scoped_futures::ScopedFutureExt;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+// deadlocks are detected based on the error code, not the message
+const RAISE_DEADLOCK: &str = "DO $$ BEGIN RAISE EXCEPTION 'simulated' \
+                              USING ERRCODE = 'deadlock_detected'; END $$";
+
+#[tokio::test]
+async fn retry_policy_retries_deadlocks() {
+    let db_url = std::env::var("DATABASE_URL").unwrap();
+    let conn = &mut AsyncPgConnection::establish(&db_url).await.unwrap();
+
+    let retries = Arc::new(AtomicU32::new(0));
+    let policy = RetryPolicy::default()
+        .initial_backoff(Duration::from_millis(1))
+        .on_retry({
+            let retries = retries.clone();
+            move |attempt| {
+                assert!(attempt.delay <= Duration::from_millis(2));
+                retries.store(attempt.attempt, Ordering::SeqCst);
+            }
+        });
+
+    let mut attempts = 0;
+    let res = policy
+        .run(conn, |conn| {
+            attempts += 1;
+            let fail = attempts < 3;
+            async move {
+                conn.transaction(|conn| {
+                    async move {
+                        if fail {
+                            diesel::sql_query(RAISE_DEADLOCK).execute(conn).await?;
+                        }
+                        QueryResult::Ok(attempts)
+                    }
+                    .scope_boxed()
+                })
+                .await
+            }
+            .scope_boxed()
+        })
+        .await;
+    assert_eq!(res.unwrap(), 3);
+    assert_eq!(AtomicU32::load(&retries, Ordering::SeqCst), 2);
+
+    // other errors and exhausted retries are returned to the caller
+    let mut attempts = 0;
+    let res = policy
+        .max_retries(1)
+        .run(conn, |conn| {
+            attempts += 1;
+            async move { diesel::sql_query(RAISE_DEADLOCK).execute(conn).await }.scope_boxed()
+        })
+        .await;
+    assert!(diesel_async::retry::is_transaction_conflict(
+        &res.unwrap_err()
+    ));
+    assert_eq!(attempts, 2);
+
+    let mut attempts = 0;
+    let res = RetryPolicy::default()
+        .run(conn, |conn| {
+            attempts += 1;
+            async move { diesel::sql_query("SELECT 1/0").execute(conn).await }.scope_boxed()
+        })
+        .await;
+    assert!(res.is_err());
+    assert_eq!(attempts, 1);
+}
+
+#[tokio::test]
+async fn transaction_events_report_depth_and_retries() {
+    use diesel_async::{AnsiTransactionManager, TransactionEventKind};
+    use std::sync::Mutex;
+
+    let db_url = std::env::var("DATABASE_URL").unwrap();
+    let conn = &mut AsyncPgConnection::establish(&db_url).await.unwrap();
+    let events = Arc::new(Mutex::new(Vec::new()));
+    AnsiTransactionManager::on_event(conn, {
+        let events = events.clone();
+        move |event| {
+            events.lock().unwrap().push((
+                event.kind,
+                event.depth.get(),
+                event.retry,
+                event.error.is_some(),
+            ));
+        }
+    });
+
+    let mut attempts = 0;
+    RetryPolicy::default()
+        .initial_backoff(Duration::from_millis(1))
+        .run(conn, |conn| {
+            attempts += 1;
+            let fail = attempts < 2;
+            async move {
+                conn.transaction(|conn| {
+                    async move {
+                        let nested = conn
+                            .transaction(|_conn| {
+                                async { QueryResult::<()>::Err(diesel::result::Error::NotFound) }
+                                    .scope_boxed()
+                            })
+                            .await;
+                        assert!(nested.is_err());
+                        if fail {
+                            diesel::sql_query(RAISE_DEADLOCK).execute(conn).await?;
+                        }
+                        QueryResult::Ok(())
+                    }
+                    .scope_boxed()
+                })
+                .await
+            }
+            .scope_boxed()
+        })
+        .await
+        .unwrap();
+
+    use TransactionEventKind::*;
+    assert_eq!(
+        *events.lock().unwrap(),
+        vec![
+            (Begin, 1, 0, false),
+            (Begin, 2, 0, false),
+            (Rollback, 2, 0, false),
+            (Rollback, 1, 0, false),
+            (Begin, 1, 1, false),
+            (Begin, 2, 1, false),
+            (Rollback, 2, 1, false),
+            (Commit, 1, 1, false),
+        ]
+    );
+}
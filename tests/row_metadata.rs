@@ -0,0 +1,77 @@
+use crate::{connection, users};
+use diesel::prelude::*;
+
+#[tokio::test]
+async fn load_with_column_metadata_describes_empty_results() {
+    use diesel_async::SimpleAsyncConnection;
+
+    let conn = &mut connection().await;
+    conn.batch_execute("ALTER TABLE users ADD COLUMN nickname TEXT")
+        .await
+        .unwrap();
+
+    let query = users::table
+        .select((
+            users::id,
+            diesel::dsl::sql::<diesel::sql_types::Nullable<diesel::sql_types::Text>>("nickname"),
+            users::id + 1,
+        ))
+        .filter(users::id.lt(0));
+    let (rows, columns) = conn
+        .load_with_column_metadata::<_, (i32, Option<String>, i32)>(query)
+        .await
+        .unwrap();
+    assert!(rows.is_empty());
+
+    let columns = columns
+        .iter()
+        .map(|c| (c.name.as_str(), c.type_name.as_str(), c.nullable))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        vec![
+            ("id", "int4", Some(false)),
+            ("nickname", "text", Some(true)),
+            ("?column?", "int4", None),
+        ],
+        columns
+    );
+}
+
+#[tokio::test]
+async fn load_dynamic_decodes_values_by_type() {
+    use diesel_async::pg::Value;
+
+    let conn = &mut connection().await;
+    let rows = conn
+        .load_dynamic(diesel::sql_query(
+            "SELECT true AS b, 2::smallint AS i, 1.5::float8 AS f, 'a'::varchar AS t, \
+             '\\x0102'::bytea AS bytes, '{\"a\": 1}'::jsonb AS j, NULL::text AS n, \
+             1.5::numeric AS other",
+        ))
+        .await
+        .unwrap();
+    assert_eq!(1, rows.len());
+    let values = rows[0].iter().collect::<Vec<_>>();
+    assert_eq!(
+        vec![
+            ("b", &Value::Bool(true)),
+            ("i", &Value::Int(2)),
+            ("f", &Value::Float(1.5)),
+            ("t", &Value::Text("a".into())),
+            ("bytes", &Value::Bytes(vec![1, 2])),
+            ("j", &Value::Text(r#"{"a": 1}"#.into())),
+            ("n", &Value::Null),
+        ],
+        values[..7]
+    );
+    assert!(matches!(
+        values[7],
+        ("other", Value::Raw { type_oid: 1700, .. })
+    ));
+
+    let rows = conn
+        .load_dynamic(diesel::sql_query("SELECT 1 AS one WHERE false"))
+        .await
+        .unwrap();
+    assert!(rows.is_empty());
+}
@@ -0,0 +1,91 @@
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
+use scoped_futures::ScopedFutureExt;
+
+#[tokio::test]
+async fn set_config_resets_previous_value() {
+    use diesel_async::pg::ConfigScope;
+
+    let db_url = std::env::var("DATABASE_URL").unwrap();
+    let conn = &mut AsyncPgConnection::establish(&db_url).await.unwrap();
+
+    let mut guard = conn
+        .set_config("app.user", "it's \"quoted\"", ConfigScope::Session)
+        .await
+        .unwrap();
+    assert_eq!(None, guard.previous_value());
+    let value = guard.config("app.user").await.unwrap();
+    assert_eq!(Some("it's \"quoted\""), value.as_deref());
+    guard.reset().await.unwrap();
+    // placeholders keep existing after `RESET`, with an empty value
+    assert_eq!(Some(""), conn.config("app.user").await.unwrap().as_deref());
+
+    conn.set_config("statement_timeout", "5s", ConfigScope::Session)
+        .await
+        .unwrap()
+        .keep();
+    let guard = conn
+        .set_config("statement_timeout", "10s", ConfigScope::Session)
+        .await
+        .unwrap();
+    assert_eq!(Some("5s"), guard.previous_value());
+    drop(guard);
+    // the reset is applied before the next statement
+    let value = conn.config("statement_timeout").await.unwrap();
+    assert_eq!(Some("5s"), value.as_deref());
+
+    conn.transaction::<_, diesel::result::Error, _>(|conn| {
+        async move {
+            let mut guard = conn
+                .set_config("statement_timeout", "20s", ConfigScope::Local)
+                .await?;
+            let value = guard.config("statement_timeout").await?;
+            assert_eq!(Some("20s"), value.as_deref());
+            guard.keep();
+            Ok(())
+        }
+        .scope_boxed()
+    })
+    .await
+    .unwrap();
+    let value = conn.config("statement_timeout").await.unwrap();
+    assert_eq!(Some("5s"), value.as_deref());
+}
+
+#[tokio::test]
+async fn session_context_is_cleared_after_the_transaction() {
+    use diesel::sql_types::Text;
+    use diesel_async::pg::SessionContext;
+
+    let db_url = std::env::var("DATABASE_URL").unwrap();
+    let conn = &mut AsyncPgConnection::establish(&db_url).await.unwrap();
+    let application_name = || {
+        diesel::select(diesel::dsl::sql::<Text>(
+            "(SELECT application_name FROM pg_stat_activity WHERE pid = pg_backend_pid())",
+        ))
+    };
+    let before = application_name().get_result::<String>(conn).await.unwrap();
+
+    let context = SessionContext::new()
+        .with_application_name("worker")
+        .with_setting("app.user_id", "1")
+        .with_setting("app.user_id", "42");
+    assert_eq!(Some("42"), context.setting("app.user_id"));
+    let (name, user_id) = conn
+        .transaction_with_context::<_, diesel::result::Error, _>(context, |conn| {
+            async move {
+                let name = application_name().get_result::<String>(conn).await?;
+                let user_id = conn.config("app.user_id").await?;
+                Ok((name, user_id))
+            }
+            .scope_boxed()
+        })
+        .await
+        .unwrap();
+    assert_eq!("worker", name);
+    assert_eq!(Some("42"), user_id.as_deref());
+
+    let after = application_name().get_result::<String>(conn).await.unwrap();
+    assert_eq!(before, after);
+    let user_id = conn.config("app.user_id").await.unwrap();
+    assert_eq!(Some(""), user_id.as_deref());
+}
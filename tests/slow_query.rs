@@ -0,0 +1,90 @@
+use crate::connection;
+use diesel::QueryResult;
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl, SimpleAsyncConnection};
+use scoped_futures::ScopedFutureExt;
+
+#[tokio::test]
+async fn slow_queries_are_explained() {
+    use diesel::dsl::sql;
+    use diesel::sql_types::Bool;
+    use std::time::Duration;
+
+    // slow queries are not explained inside of the test transaction
+    let db_url = std::env::var("DATABASE_URL").unwrap();
+    let conn = &mut AsyncPgConnection::establish(&db_url).await.unwrap();
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    conn.explain_slow_queries(Duration::from_millis(200), move |query| {
+        let _ = tx.send((
+            query.sql.clone(),
+            query.duration,
+            query.plan.as_ref().unwrap().clone(),
+        ));
+    });
+
+    conn.batch_execute("SELECT 1").await.unwrap();
+    diesel::select(sql::<Bool>("(SELECT true FROM pg_sleep(0.3))"))
+        .get_result::<bool>(conn)
+        .await
+        .unwrap();
+
+    // slow queries are explained before the next statement
+    assert!(rx.try_recv().is_err());
+    conn.batch_execute("SELECT 1").await.unwrap();
+
+    // only the slow query is explained
+    let (sql, duration, plan) = rx.recv().await.unwrap();
+    assert!(sql.contains("pg_sleep"), "{sql}");
+    assert!(duration >= Duration::from_millis(200));
+    assert!(plan.contains("Function Scan on pg_sleep"), "{plan}");
+    conn.clear_interceptors();
+    assert!(rx.recv().await.is_none());
+
+    // the explainer must not keep the client of a closed connection alive
+    let mut conn = connection().await;
+    conn.explain_slow_queries(Duration::ZERO, |_| {});
+    tokio::time::timeout(Duration::from_secs(5), conn.close())
+        .await
+        .unwrap()
+        .unwrap();
+}
+
+#[tokio::test]
+async fn slow_queries_are_not_explained_inside_of_transactions() {
+    use diesel::dsl::sql;
+    use diesel::sql_types::Bool;
+    use std::time::Duration;
+
+    let db_url = std::env::var("DATABASE_URL").unwrap();
+    let conn = &mut AsyncPgConnection::establish(&db_url).await.unwrap();
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    conn.explain_slow_queries(Duration::from_millis(200), move |query| {
+        let _ = tx.send(query.plan.is_ok());
+    });
+
+    conn.transaction(|conn| {
+        async move {
+            diesel::sql_query("CREATE TEMPORARY TABLE slow_query_explained (id INTEGER)")
+                .execute(conn)
+                .await?;
+            diesel::select(sql::<Bool>(
+                "(SELECT true FROM slow_query_explained, pg_sleep(0.3) UNION SELECT true)",
+            ))
+            .get_result::<bool>(conn)
+            .await?;
+            // explaining the slow query fails once the table is gone,
+            // which must not abort this transaction
+            diesel::sql_query("DROP TABLE slow_query_explained")
+                .execute(conn)
+                .await?;
+            conn.batch_execute("SELECT 1").await?;
+            QueryResult::Ok(())
+        }
+        .scope_boxed()
+    })
+    .await
+    .unwrap();
+    assert!(rx.try_recv().is_err());
+
+    conn.batch_execute("SELECT 1").await.unwrap();
+    assert!(!rx.recv().await.unwrap());
+}
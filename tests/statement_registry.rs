@@ -0,0 +1,69 @@
+use crate::{connection, users};
+use diesel::prelude::*;
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl, SimpleAsyncConnection};
+
+#[tokio::test]
+async fn statement_registry_prepares_recorded_statements() {
+    use diesel_async::pg::StatementRegistry;
+
+    let registry = StatementRegistry::default();
+    let conn = &mut connection().await;
+    registry.attach(conn).await.unwrap();
+    diesel::insert_into(users::table)
+        .values(users::name.eq("Sean"))
+        .execute(conn)
+        .await
+        .unwrap();
+    // not safe to cache, so it's not recorded
+    diesel::sql_query("SELECT 1").execute(conn).await.unwrap();
+    assert_eq!(1, registry.len());
+
+    let other = &mut connection().await;
+    registry.attach(other).await.unwrap();
+    let prepared = diesel::dsl::sql::<diesel::sql_types::BigInt>(
+        "(SELECT count(*) FROM pg_prepared_statements WHERE statement LIKE 'INSERT INTO \"users\"%')",
+    );
+    let count = diesel::select(prepared)
+        .get_result::<i64>(other)
+        .await
+        .unwrap();
+    assert_eq!(1, count);
+}
+
+#[tokio::test]
+async fn statement_registry_is_bounded() {
+    use diesel_async::pg::StatementRegistry;
+
+    diesel::table! {
+        statement_registry_test {
+            id -> Integer,
+        }
+    }
+
+    let registry = StatementRegistry::with_capacity(1);
+    let conn = &mut connection().await;
+    conn.batch_execute("CREATE TABLE statement_registry_test (id INTEGER)")
+        .await
+        .unwrap();
+    registry.attach(conn).await.unwrap();
+    diesel::insert_into(statement_registry_test::table)
+        .values(statement_registry_test::id.eq(1))
+        .execute(conn)
+        .await
+        .unwrap();
+    // evicts the least recently recorded insert
+    let ids = statement_registry_test::table
+        .select(statement_registry_test::id)
+        .load::<i32>(conn)
+        .await
+        .unwrap();
+    assert_eq!(vec![1], ids);
+    assert_eq!(1, registry.len());
+
+    // the table only exists inside of the test transaction of `conn`,
+    // so the statement fails to prepare and is removed
+    let db_url = std::env::var("DATABASE_URL").unwrap();
+    let other = &mut AsyncPgConnection::establish(&db_url).await.unwrap();
+    registry.attach(other).await.unwrap();
+    assert!(registry.is_empty());
+}
@@ -0,0 +1,105 @@
+use crate::{connection, users};
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+
+#[tokio::test]
+async fn get_results_chunked_yields_batches() {
+    use futures_util::TryStreamExt;
+
+    let conn = &mut connection().await;
+    let names = ["A", "B", "C", "D", "E"];
+    for name in names {
+        diesel::insert_into(users::table)
+            .values(users::name.eq(name))
+            .execute(conn)
+            .await
+            .unwrap();
+    }
+
+    let chunks = users::table
+        .select(users::name)
+        .order(users::id)
+        .get_results_chunked::<String>(conn, 2)
+        .await
+        .unwrap()
+        .try_collect::<Vec<_>>()
+        .await
+        .unwrap();
+    assert_eq!(vec![vec!["A", "B"], vec!["C", "D"], vec!["E"]], chunks);
+
+    // no empty chunk is yielded if the results divide evenly
+    let chunks = users::table
+        .select(users::name)
+        .order(users::id)
+        .get_results_chunked::<String>(conn, 5)
+        .await
+        .unwrap()
+        .try_collect::<Vec<_>>()
+        .await
+        .unwrap();
+    assert_eq!(vec![names.to_vec()], chunks);
+
+    let chunks = users::table
+        .select(users::name)
+        .filter(users::name.eq("F"))
+        .get_results_chunked::<String>(conn, 2)
+        .await
+        .unwrap()
+        .try_collect::<Vec<_>>()
+        .await
+        .unwrap();
+    assert!(chunks.is_empty());
+}
+
+#[tokio::test]
+async fn sql_query_results_can_be_streamed() {
+    use diesel::sql_types::{BigInt, Integer, Text};
+    use futures_util::TryStreamExt;
+
+    #[derive(diesel::QueryableByName, Debug, PartialEq)]
+    struct NamedUser {
+        #[diesel(sql_type = Text)]
+        name: String,
+        #[diesel(sql_type = BigInt)]
+        name_length: i64,
+    }
+
+    let conn = &mut connection().await;
+    for name in ["John Doe", "Jane Doe", "Jim"] {
+        diesel::insert_into(users::table)
+            .values(users::name.eq(name))
+            .execute(conn)
+            .await
+            .unwrap();
+    }
+
+    #[cfg(feature = "postgres")]
+    let sql = "SELECT name, length(name)::bigint AS name_length FROM users \
+               WHERE length(name) > $1 ORDER BY id";
+    #[cfg(not(feature = "postgres"))]
+    let sql = "SELECT name, length(name) AS name_length FROM users \
+               WHERE length(name) > ? ORDER BY id";
+    let stream = diesel::sql_query(sql)
+        .bind::<Integer, _>(3)
+        .load_stream::<NamedUser>(conn)
+        .await
+        .unwrap();
+    let mut stream = std::pin::pin!(stream);
+
+    let first = stream.try_next().await.unwrap();
+    assert_eq!(
+        Some(NamedUser {
+            name: "John Doe".into(),
+            name_length: 8,
+        }),
+        first
+    );
+    let rest = stream.try_collect::<Vec<_>>().await.unwrap();
+    assert_eq!(
+        vec![NamedUser {
+            name: "Jane Doe".into(),
+            name_length: 8,
+        }],
+        rest
+    );
+}
@@ -37,3 +37,70 @@ fn check_run_migration() {
     // just use `run_migrations` here because that's the easiest one without additional setup
     conn.run_migrations(&migrations).unwrap();
 }
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_sync_wrapper_inside_multi_threaded_runtime() {
+    let db_url = std::env::var("DATABASE_URL").unwrap();
+    let mut conn = AsyncConnectionWrapper::<crate::TestConnection>::establish(&db_url).unwrap();
+
+    let res =
+        diesel::select(1.into_sql::<diesel::sql_types::Integer>()).get_result::<i32>(&mut conn);
+    assert_eq!(Ok(1), res);
+}
+
+#[test]
+fn test_sync_wrapper_with_runtime_handle() {
+    use diesel_async::async_connection_wrapper::Tokio;
+    use diesel_async::AsyncConnection;
+
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let db_url = std::env::var("DATABASE_URL").unwrap();
+    let inner = runtime
+        .block_on(crate::TestConnection::establish(&db_url))
+        .unwrap();
+    let mut conn = AsyncConnectionWrapper::<crate::TestConnection>::with_runtime(
+        inner,
+        Tokio::from(runtime.handle().clone()),
+    );
+
+    let res =
+        diesel::select(1.into_sql::<diesel::sql_types::Integer>()).get_result::<i32>(&mut conn);
+    assert_eq!(Ok(1), res);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_sync_wrapper_into_inner() {
+    let db_url = std::env::var("DATABASE_URL").unwrap();
+    let mut conn = AsyncConnectionWrapper::<crate::TestConnection>::establish(&db_url).unwrap();
+    let res =
+        diesel::select(1.into_sql::<diesel::sql_types::Integer>()).get_result::<i32>(&mut conn);
+    assert_eq!(Ok(1), res);
+
+    let mut conn = conn.into_inner();
+    let res = diesel_async::RunQueryDsl::get_result::<i32>(
+        diesel::select(2.into_sql::<diesel::sql_types::Integer>()),
+        &mut conn,
+    )
+    .await;
+    assert_eq!(Ok(2), res);
+}
+
+#[cfg(feature = "postgres")]
+#[test]
+fn test_sync_wrapper_load_iter() {
+    use diesel::connection::DefaultLoadingMode;
+    use diesel::sql_types::Integer;
+
+    let db_url = std::env::var("DATABASE_URL").unwrap();
+    let mut conn = AsyncConnectionWrapper::<crate::TestConnection>::establish(&db_url).unwrap();
+
+    let rows = diesel::select(diesel::dsl::sql::<Integer>("generate_series(1, 1000)"))
+        .load_iter::<i32, DefaultLoadingMode>(&mut conn)
+        .unwrap();
+    let mut expected = 1;
+    for row in rows {
+        assert_eq!(row.unwrap(), expected);
+        expected += 1;
+    }
+    assert_eq!(expected, 1001);
+}
@@ -0,0 +1,18 @@
+use diesel::sql_types::Integer;
+use diesel::IntoSql;
+use diesel_async::test_containers::TestDatabase;
+use diesel_async::RunQueryDsl;
+
+#[tokio::test]
+#[ignore = "requires a running Docker daemon"]
+async fn postgres_test_database_accepts_connections() {
+    let db = TestDatabase::postgres().await.unwrap();
+    let pool = db.pool().unwrap();
+    let mut conn = pool.get().await.unwrap();
+
+    let one = diesel::select(1.into_sql::<Integer>())
+        .get_result::<i32>(&mut conn)
+        .await
+        .unwrap();
+    assert_eq!(1, one);
+}
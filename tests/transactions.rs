@@ -0,0 +1,396 @@
+use crate::{connection, users, TestConnection};
+use diesel::prelude::*;
+use diesel::QueryResult;
+#[cfg(any(feature = "postgres", feature = "mysql"))]
+use diesel_async::AnsiTransactionManager;
+#[cfg(feature = "postgres")]
+use diesel_async::AsyncPgConnection;
+use diesel_async::{
+    transaction, AsyncConnection, InTransaction, RunQueryDsl, TransactionManager,
+    WithTransactionManager,
+};
+use scoped_futures::ScopedFutureExt;
+
+#[tokio::test]
+async fn transaction_macro_boxes_the_transaction_body() {
+    let conn = &mut connection().await;
+
+    let res = transaction!(conn, |conn| async move {
+        diesel::insert_into(users::table)
+            .values(users::name.eq("John Doe"))
+            .execute(conn)
+            .await?;
+        // nested transactions use savepoints
+        let nested = transaction!(conn, |conn| async move {
+            diesel::insert_into(users::table)
+                .values(users::name.eq("Jane Doe"))
+                .execute(conn)
+                .await?;
+            Err::<(), _>(diesel::result::Error::RollbackTransaction)
+        })
+        .await;
+        assert!(matches!(
+            nested,
+            Err(diesel::result::Error::RollbackTransaction)
+        ));
+        users::table.select(users::name).load::<String>(conn).await
+    })
+    .await
+    .unwrap();
+    assert_eq!(vec!["John Doe"], res);
+}
+
+async fn insert_user_in_transaction(
+    conn: &mut impl InTransaction<Connection = TestConnection>,
+    name: &str,
+) -> QueryResult<usize> {
+    diesel::insert_into(users::table)
+        .values(users::name.eq(name))
+        .execute(conn.connection())
+        .await
+}
+
+#[tokio::test]
+async fn typed_transaction_passes_a_transaction_connection() {
+    let conn = &mut connection().await;
+
+    conn.typed_transaction::<_, diesel::result::Error, _>(|mut conn| {
+        async move {
+            insert_user_in_transaction(&mut conn, "John Doe").await?;
+            // the wrapper dereferences to the connection
+            let names = users::table
+                .select(users::name)
+                .load::<String>(&mut *conn)
+                .await?;
+            assert_eq!(vec!["John Doe"], names);
+            Ok(())
+        }
+        .scope_boxed()
+    })
+    .await
+    .unwrap();
+
+    let res = conn
+        .typed_transaction::<(), _, _>(|mut conn| {
+            async move {
+                insert_user_in_transaction(&mut conn, "Jane Doe").await?;
+                Err(diesel::result::Error::RollbackTransaction)
+            }
+            .scope_boxed()
+        })
+        .await;
+    assert!(matches!(
+        res,
+        Err(diesel::result::Error::RollbackTransaction)
+    ));
+
+    let names = users::table
+        .select(users::name)
+        .load::<String>(conn)
+        .await
+        .unwrap();
+    assert_eq!(vec!["John Doe"], names);
+}
+
+#[tokio::test]
+async fn custom_transaction_manager_is_used_for_transactions() {
+    use diesel::connection::TransactionManagerStatus;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    type Inner = <TestConnection as AsyncConnection>::TransactionManager;
+    type Conn = WithTransactionManager<TestConnection, CountingTransactionManager>;
+
+    static BEGUN: AtomicUsize = AtomicUsize::new(0);
+    static COMMITTED: AtomicUsize = AtomicUsize::new(0);
+    static ROLLED_BACK: AtomicUsize = AtomicUsize::new(0);
+
+    struct CountingTransactionManager;
+
+    #[async_trait::async_trait]
+    impl TransactionManager<Conn> for CountingTransactionManager {
+        type TransactionStateData =
+            <Inner as TransactionManager<TestConnection>>::TransactionStateData;
+
+        async fn begin_transaction(conn: &mut Conn) -> QueryResult<()> {
+            BEGUN.fetch_add(1, Ordering::Relaxed);
+            Inner::begin_transaction(conn.inner_mut()).await
+        }
+
+        async fn rollback_transaction(conn: &mut Conn) -> QueryResult<()> {
+            ROLLED_BACK.fetch_add(1, Ordering::Relaxed);
+            Inner::rollback_transaction(conn.inner_mut()).await
+        }
+
+        async fn commit_transaction(conn: &mut Conn) -> QueryResult<()> {
+            COMMITTED.fetch_add(1, Ordering::Relaxed);
+            Inner::commit_transaction(conn.inner_mut()).await
+        }
+
+        fn transaction_manager_status_mut(conn: &mut Conn) -> &mut TransactionManagerStatus {
+            Inner::transaction_manager_status_mut(conn.inner_mut())
+        }
+    }
+
+    let conn = &mut Conn::new(connection().await);
+    let names = conn
+        .transaction::<_, diesel::result::Error, _>(|conn| {
+            async move {
+                diesel::insert_into(users::table)
+                    .values(users::name.eq("John Doe"))
+                    .execute(conn)
+                    .await?;
+                let nested = conn
+                    .transaction::<(), _, _>(|conn| {
+                        async move {
+                            diesel::insert_into(users::table)
+                                .values(users::name.eq("Jane Doe"))
+                                .execute(conn)
+                                .await?;
+                            Err(diesel::result::Error::RollbackTransaction)
+                        }
+                        .scope_boxed()
+                    })
+                    .await;
+                assert!(nested.is_err());
+                users::table.select(users::name).load::<String>(conn).await
+            }
+            .scope_boxed()
+        })
+        .await
+        .unwrap();
+    assert_eq!(vec!["John Doe"], names);
+
+    // `RunQueryDsl::load` would shadow `AtomicUsize::load`
+    assert_eq!(2, AtomicUsize::load(&BEGUN, Ordering::Relaxed));
+    assert_eq!(1, AtomicUsize::load(&COMMITTED, Ordering::Relaxed));
+    assert_eq!(1, AtomicUsize::load(&ROLLED_BACK, Ordering::Relaxed));
+}
+
+#[cfg(all(feature = "macros", feature = "postgres"))]
+#[diesel_async::test]
+async fn test_attribute_runs_the_test_in_a_transaction(
+    conn: &mut AsyncPgConnection,
+) -> QueryResult<()> {
+    let depth = AnsiTransactionManager::transaction_manager_status_mut(conn)
+        .transaction_depth()?
+        .map(|depth| depth.get());
+    assert_eq!(Some(1), depth);
+
+    crate::setup(conn).await;
+    diesel::insert_into(users::table)
+        .values(users::name.eq("John Doe"))
+        .execute(conn)
+        .await?;
+    assert_eq!(1, users::table.count().get_result::<i64>(conn).await?);
+    Ok(())
+}
+
+#[cfg(any(feature = "postgres", feature = "mysql"))]
+#[tokio::test]
+async fn named_savepoints_roll_back_parts_of_a_transaction() {
+    let conn = &mut connection().await;
+    let insert = |name| diesel::insert_into(users::table).values(users::name.eq(name));
+
+    AnsiTransactionManager::create_savepoint(conn, "first")
+        .await
+        .unwrap();
+    insert("Sean").execute(conn).await.unwrap();
+    AnsiTransactionManager::create_savepoint(conn, "second")
+        .await
+        .unwrap();
+    insert("Tess").execute(conn).await.unwrap();
+    AnsiTransactionManager::rollback_to_savepoint(conn, "second")
+        .await
+        .unwrap();
+    AnsiTransactionManager::release_savepoint(conn, "second")
+        .await
+        .unwrap();
+    let names = users::table.select(users::name).load::<String>(conn).await;
+    assert_eq!(vec!["Sean"], names.unwrap());
+
+    AnsiTransactionManager::rollback_to_savepoint(conn, "first")
+        .await
+        .unwrap();
+    let count = users::table.count().get_result::<i64>(conn).await.unwrap();
+    assert_eq!(0, count);
+    // the savepoint was released
+    assert!(
+        AnsiTransactionManager::rollback_to_savepoint(conn, "second")
+            .await
+            .is_err()
+    );
+    assert!(
+        AnsiTransactionManager::create_savepoint(conn, "second; DROP TABLE users")
+            .await
+            .is_err()
+    );
+
+    let db_url = std::env::var("DATABASE_URL").unwrap();
+    let conn = &mut TestConnection::establish(&db_url).await.unwrap();
+    let outside = AnsiTransactionManager::create_savepoint(conn, "first").await;
+    assert!(matches!(
+        outside,
+        Err(diesel::result::Error::NotInTransaction)
+    ));
+}
+
+#[cfg(feature = "postgres")]
+#[tokio::test]
+async fn postgres_rollback_cancelled_transaction() {
+    use std::time::Duration;
+
+    let db_url = std::env::var("DATABASE_URL").unwrap();
+    let conn = &mut AsyncPgConnection::establish(&db_url).await.unwrap();
+
+    let cancelled = tokio::time::timeout(
+        Duration::from_millis(100),
+        conn.transaction::<(), diesel::result::Error, _>(|conn| {
+            async move {
+                diesel::sql_query("CREATE TEMPORARY TABLE abandoned (id INTEGER)")
+                    .execute(conn)
+                    .await?;
+                futures_util::future::pending().await
+            }
+            .scope_boxed()
+        }),
+    )
+    .await;
+    assert!(cancelled.is_err());
+    assert!(AnsiTransactionManager::is_broken_transaction_manager(conn));
+
+    // the next transaction rolls back the abandoned one first
+    let tables = conn
+        .transaction::<_, diesel::result::Error, _>(|conn| {
+            async move {
+                diesel::select(diesel::dsl::sql::<diesel::sql_types::BigInt>(
+                    "count(*) FROM pg_tables WHERE tablename = 'abandoned'",
+                ))
+                .get_result::<i64>(conn)
+                .await
+            }
+            .scope_boxed()
+        })
+        .await
+        .unwrap();
+    assert_eq!(tables, 0);
+    assert!(!AnsiTransactionManager::is_broken_transaction_manager(conn));
+}
+
+#[cfg(feature = "postgres")]
+#[tokio::test]
+async fn postgres_rollback_cancelled_transaction_before_next_statement() {
+    use diesel_async::SimpleAsyncConnection;
+    use std::time::Duration;
+
+    let db_url = std::env::var("DATABASE_URL").unwrap();
+    let conn = &mut AsyncPgConnection::establish(&db_url).await.unwrap();
+
+    for _ in 0..2 {
+        let cancelled = tokio::time::timeout(
+            Duration::from_millis(100),
+            conn.transaction::<(), diesel::result::Error, _>(|conn| {
+                async move {
+                    diesel::sql_query("CREATE TEMPORARY TABLE abandoned_stmt (id INTEGER)")
+                        .execute(conn)
+                        .await?;
+                    futures_util::future::pending().await
+                }
+                .scope_boxed()
+            }),
+        )
+        .await;
+        assert!(cancelled.is_err());
+        assert!(AnsiTransactionManager::is_broken_transaction_manager(conn));
+
+        // statements outside of a transaction must not run
+        // inside of the abandoned one
+        let tables = diesel::select(diesel::dsl::sql::<diesel::sql_types::BigInt>(
+            "count(*) FROM pg_tables WHERE tablename = 'abandoned_stmt'",
+        ))
+        .get_result::<i64>(conn)
+        .await
+        .unwrap();
+        assert_eq!(tables, 0);
+        assert!(!AnsiTransactionManager::is_broken_transaction_manager(conn));
+        assert_eq!(
+            None,
+            AnsiTransactionManager::transaction_manager_status_mut(conn)
+                .transaction_depth()
+                .unwrap()
+        );
+
+        // the same applies to `batch_execute`
+        let cancelled = tokio::time::timeout(
+            Duration::from_millis(100),
+            conn.transaction::<(), diesel::result::Error, _>(|conn| {
+                async move {
+                    diesel::sql_query("CREATE TEMPORARY TABLE abandoned_batch (id INTEGER)")
+                        .execute(conn)
+                        .await?;
+                    futures_util::future::pending().await
+                }
+                .scope_boxed()
+            }),
+        )
+        .await;
+        assert!(cancelled.is_err());
+        conn.batch_execute("CREATE TEMPORARY TABLE abandoned_batch (id INTEGER)")
+            .await
+            .unwrap();
+        conn.batch_execute("DROP TABLE abandoned_batch")
+            .await
+            .unwrap();
+    }
+}
+
+#[cfg(feature = "postgres")]
+#[tokio::test]
+async fn postgres_rollback_to_clean_state() {
+    let db_url = std::env::var("DATABASE_URL").unwrap();
+    let conn = &mut AsyncPgConnection::establish(&db_url).await.unwrap();
+
+    async fn txid(conn: &mut AsyncPgConnection) -> i64 {
+        diesel::select(diesel::dsl::sql::<diesel::sql_types::BigInt>(
+            "txid_current()",
+        ))
+        .get_result(conn)
+        .await
+        .unwrap()
+    }
+
+    AnsiTransactionManager::begin_transaction(conn)
+        .await
+        .unwrap();
+    AnsiTransactionManager::begin_transaction(conn)
+        .await
+        .unwrap();
+    assert!(AnsiTransactionManager::is_broken_transaction_manager(conn));
+    conn.rollback_to_clean_state().await.unwrap();
+    assert!(!AnsiTransactionManager::is_broken_transaction_manager(conn));
+    assert_ne!(txid(conn).await, txid(conn).await);
+
+    AnsiTransactionManager::begin_transaction(conn)
+        .await
+        .unwrap();
+    AnsiTransactionManager::transaction_manager_status_mut(conn).set_in_error();
+    assert!(AnsiTransactionManager::is_broken_transaction_manager(conn));
+    conn.rollback_to_clean_state().await.unwrap();
+    assert!(!AnsiTransactionManager::is_broken_transaction_manager(conn));
+    assert_ne!(txid(conn).await, txid(conn).await);
+
+    // savepoints inside of a test transaction are rolled back,
+    // the test transaction itself is kept
+    conn.begin_test_transaction().await.unwrap();
+    AnsiTransactionManager::begin_transaction(conn)
+        .await
+        .unwrap();
+    conn.rollback_to_clean_state().await.unwrap();
+    assert_eq!(txid(conn).await, txid(conn).await);
+    assert_eq!(
+        AnsiTransactionManager::transaction_manager_status_mut(conn)
+            .transaction_depth()
+            .unwrap()
+            .map(|depth| depth.get()),
+        Some(1)
+    );
+}
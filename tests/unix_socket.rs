@@ -0,0 +1,98 @@
+use diesel::sql_types::{Integer, Text};
+use diesel::{IntoSql, QueryResult};
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
+use std::path::Path;
+
+// Builds a key-value connection string for the unix domain socket of the
+// server at `DATABASE_URL`, or returns `None` if the socket is not reachable
+// from this host, for example as the server runs in a container
+async fn unix_socket_url() -> Option<String> {
+    let db_url = std::env::var("DATABASE_URL").unwrap();
+    let config = db_url.parse::<tokio_postgres::Config>().unwrap();
+    let mut conn = AsyncPgConnection::establish(&db_url).await.unwrap();
+    let (directories, port) = diesel::select((
+        diesel::dsl::sql::<Text>("current_setting('unix_socket_directories')"),
+        diesel::dsl::sql::<Text>("current_setting('port')"),
+    ))
+    .get_result::<(String, String)>(&mut conn)
+    .await
+    .unwrap();
+    let directory = directories.split(',').map(str::trim).find(|directory| {
+        Path::new(directory)
+            .join(format!(".s.PGSQL.{port}"))
+            .exists()
+    })?;
+
+    let mut url = format!("host={directory} port={port}");
+    if let Some(user) = config.get_user() {
+        url.push_str(&format!(" user={user}"));
+    }
+    if let Some(password) = config.get_password() {
+        url.push_str(&format!(" password={}", String::from_utf8_lossy(password)));
+    }
+    if let Some(dbname) = config.get_dbname() {
+        url.push_str(&format!(" dbname={dbname}"));
+    }
+    Some(url)
+}
+
+async fn is_unix_socket(conn: &mut AsyncPgConnection) -> QueryResult<bool> {
+    // the client address is `NULL` for unix domain sockets
+    diesel::select(diesel::dsl::sql::<diesel::sql_types::Bool>(
+        "inet_client_addr() IS NULL",
+    ))
+    .get_result(conn)
+    .await
+}
+
+#[tokio::test]
+async fn establish_connects_via_unix_socket() {
+    let Some(url) = unix_socket_url().await else {
+        return;
+    };
+
+    let mut conn = AsyncPgConnection::establish(&url).await.unwrap();
+    assert_eq!(Ok(true), is_unix_socket(&mut conn).await);
+    let one = diesel::select(1.into_sql::<Integer>())
+        .get_result::<i32>(&mut conn)
+        .await;
+    assert_eq!(Ok(1), one);
+}
+
+#[tokio::test]
+async fn unix_socket_connections_ignore_sslmode() {
+    let Some(url) = unix_socket_url().await else {
+        return;
+    };
+
+    let mut conn = AsyncPgConnection::establish(&format!("{url} sslmode=require"))
+        .await
+        .unwrap();
+    assert_eq!(Ok(true), is_unix_socket(&mut conn).await);
+}
+
+#[tokio::test]
+#[cfg(feature = "deadpool")]
+async fn pooled_connections_connect_via_unix_socket() {
+    use diesel_async::pooled_connection::deadpool::Pool;
+    use diesel_async::pooled_connection::{AsyncDieselConnectionManager, ManagerConfig, TlsConfig};
+
+    let Some(url) = unix_socket_url().await else {
+        return;
+    };
+
+    let manager = AsyncDieselConnectionManager::<AsyncPgConnection>::new(url.clone());
+    let pool = Pool::builder(manager).max_size(1).build().unwrap();
+    let mut conn = pool.get().await.unwrap();
+    assert_eq!(Ok(true), is_unix_socket(&mut conn).await);
+
+    let mut config = ManagerConfig::default();
+    config.tls = Some(TlsConfig::new(tokio_postgres::NoTls));
+    let manager = AsyncDieselConnectionManager::<AsyncPgConnection>::new_with_config(
+        format!("{url} sslmode=require"),
+        config,
+    );
+    let pool = Pool::builder(manager).max_size(1).build().unwrap();
+    let mut conn = pool.get().await.unwrap();
+    assert_eq!(Ok(true), is_unix_socket(&mut conn).await);
+}
@@ -0,0 +1,74 @@
+use diesel::prelude::*;
+use diesel::QueryResult;
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl, SimpleAsyncConnection};
+use scoped_futures::ScopedFutureExt;
+
+diesel::table! {
+    work_queue_jobs {
+        id -> Integer,
+    }
+}
+
+#[tokio::test]
+async fn concurrent_workers_claim_distinct_rows() {
+    let db_url = std::env::var("DATABASE_URL").unwrap();
+    let mut conn1 = AsyncPgConnection::establish(&db_url).await.unwrap();
+    let mut conn2 = AsyncPgConnection::establish(&db_url).await.unwrap();
+    conn1
+        .batch_execute(
+            "DROP TABLE IF EXISTS work_queue_jobs; \
+             CREATE TABLE work_queue_jobs (id INTEGER PRIMARY KEY); \
+             INSERT INTO work_queue_jobs (id) VALUES (1), (2), (3);",
+        )
+        .await
+        .unwrap();
+
+    let conn2 = &mut conn2;
+    let (first, second) = conn1
+        .process_claimed(
+            work_queue_jobs::table
+                .select(work_queue_jobs::id)
+                .order_by(work_queue_jobs::id),
+            2,
+            |conn, claimed: Vec<i32>| {
+                async move {
+                    // another worker only sees the row not claimed by this worker
+                    let other = conn2
+                        .process_claimed(
+                            work_queue_jobs::table
+                                .select(work_queue_jobs::id)
+                                .order_by(work_queue_jobs::id),
+                            2,
+                            |_, claimed: Vec<i32>| {
+                                async move { QueryResult::Ok(claimed) }.scope_boxed()
+                            },
+                        )
+                        .await?;
+                    diesel::delete(
+                        work_queue_jobs::table.filter(work_queue_jobs::id.eq_any(&claimed)),
+                    )
+                    .execute(conn)
+                    .await?;
+                    QueryResult::Ok((claimed, other))
+                }
+                .scope_boxed()
+            },
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(first, vec![1, 2]);
+    assert_eq!(second, vec![3]);
+
+    let remaining = work_queue_jobs::table
+        .select(work_queue_jobs::id)
+        .load::<i32>(&mut conn1)
+        .await
+        .unwrap();
+    assert_eq!(remaining, vec![3]);
+
+    conn1
+        .batch_execute("DROP TABLE work_queue_jobs")
+        .await
+        .unwrap();
+}